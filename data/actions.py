@@ -0,0 +1,7 @@
+from outpost_data.core.builder2 import *
+
+# Action 0 means "no action", like item 0.
+ACTION.new('none').display_name('Nothing')
+
+ACTION.new('sit').display_name('Sit')
+ACTION.new('sleep').display_name('Sleep')
@@ -12,7 +12,9 @@ def gen_editor_anim(maps):
     return pony(maps).get_anim('stand-2').id
 
 def gen_physics_anim_table(maps):
-    SPEED_NAMES = ('stand', 'walk', None, 'run')
+    # NB: the client uses row 4 when the pony is in water.  Keep this in sync
+    # with libclient/predict.rs  SWIM_ANIM_ROW
+    SPEED_NAMES = ('stand', 'walk', None, 'run', 'swim')
     table = []
     for speed in SPEED_NAMES:
         if speed is None:
@@ -32,7 +34,7 @@ def gen_anim_dir_table(maps):
     # TODO: There's no good reason to restrict this to physics anims, except
     # that's how the current hack in client/js/physics.js distinguishes physics
     # anims from special ones.
-    for motion in ('stand', 'walk', 'run'):
+    for motion in ('stand', 'walk', 'run', 'swim'):
         for dir_ in range(4):
             anim = pony(maps).get_anim('%s-%d' % (motion, dir_))
             dct[anim.id] = dir_ * 2
@@ -39,6 +39,8 @@ MOTIONS = [
         Motion('walk',  1, 0, 6,  8),
         Motion('run',   3, 0, 6, 12),
         Motion('sit',   0, 5, 1,  1),
+        # TODO: placeholder - reuses the walk frames until there's real swimming art
+        Motion('swim',  1, 0, 6,  4),
         ]
 
 SPRITE_SIZE = (96, 96)
@@ -224,6 +224,13 @@ if __name__ == '__main__':
                     'server_types', 'server_util', 'server_world_types',),
                 dyn_deps=('syntax_exts',),
                 src_file='$root/src/server/main.rs'),
+            native.rust('save_tool', 'bin',
+                ('physics', 'server_bundle', 'server_config', 'server_extra',
+                    'server_types', 'server_util', 'server_world_types'),
+                src_file='$root/src/save_tool/main.rs'),
+            native.rust('convert_storage', 'bin',
+                ('physics', 'server_config', 'server_types'),
+                src_file='$root/src/migrations/convert_storage.rs'),
             native.cxx('wrapper', 'bin',
                 ('$root/src/wrapper/%s' % f
                     for f in os.listdir(os.path.join(i.root_dir, 'src', 'wrapper'))
@@ -312,6 +319,7 @@ if __name__ == '__main__':
         data.font_stack('$b_data/fonts', ('name', 'hotbar', 'title')),
         data.day_night('$b_data/day_night.json', '$root/assets/misc/day_night_pixels.png'),
         data.server_json('$b_data/server.json'),
+        data.protocol('$builddir/protocol.stamp', '$root/src/gen/protocol.json'),
         data.ui_atlas('$b_data', '$root/assets/ui_gl/png'),
         data.process(),
         data.binary_defs('$b_data/client_data.bin'),
@@ -329,7 +337,7 @@ if __name__ == '__main__':
                 exclude_names=i.use_prebuilt,
                 extra=dist_extra),
 
-        'default $builddir/dist.stamp',
+        'default $builddir/dist.stamp $builddir/protocol.stamp',
         '', # ensure there's a newline after the last command
         ))
 
@@ -32,6 +32,12 @@ def rules(i):
             command = $python3 $root/src/gen/gen_server_json.py >$out
             description = GEN $out
 
+        rule check_protocol
+            command = $python3 $root/src/gen/gen_protocol.py $in --check $
+                --rust-out $out_rust --js-out $out_js --cxx-out $out_cxx $
+                && touch $out
+            description = CHECK $in
+
         rule gen_credits
             command = $python3 $root/src/gen/gen_credits.py $root $out $dep_files
             description = GEN $out
@@ -71,6 +77,21 @@ def server_json(out_json):
         build %out_json: gen_server_json | $root/src/gen/gen_server_json.py
     ''', **locals())
 
+def protocol(stamp, src_json):
+    # The generated files are checked in, so the build never writes to the
+    # source tree.  It only fails if they're out of date.
+    out_rust = '$root/src/server/msg_gen.rs'
+    out_js = '$root/src/client/js/protocol.js'
+    out_cxx = '$root/src/wrapper/opcode.hpp'
+
+    return template('''
+        build %stamp: check_protocol %src_json $
+            | $root/src/gen/gen_protocol.py %out_rust %out_js %out_cxx
+            out_rust = %out_rust
+            out_js = %out_js
+            out_cxx = %out_cxx
+    ''', **locals())
+
 def day_night(out_json, src_img):
     return template('''
         build %out_json: process_day_night %src_img $
@@ -91,7 +112,7 @@ def process():
     data_files = ['%s_%s.json' % (f,s)
             for s in ('server', 'client')
             for f in ('structures', 'blocks', 'items', 'recipes',
-                'animations', 'sprite_layers')]
+                'animations', 'sprite_layers', 'actions')]
     data_files.append('structure_parts_client.json')
     data_files.append('structure_verts_client.json')
     data_files.append('structure_shapes_client.json')
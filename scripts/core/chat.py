@@ -1,32 +1,41 @@
-from outpost_server.core.engine import ClientProxy
+from outpost_server.core.engine import ClientProxy, ROLE_PLAYER, ROLE_ADMIN
 
 class Handler:
     """Chat command handler.  This is a wrapped function that also contains
     some metadata about the command."""
 
-    def __init__(self, func, doc=None, name=None, need_su=False):
+    def __init__(self, func, doc=None, name=None, need_su=False, role=None):
         self.func = func
         self.doc = doc
         self.name = name or func.__name__
-        self.need_su = need_su
+        if role is None:
+            role = ROLE_ADMIN if need_su else ROLE_PLAYER
+        self.role = role
+
+    @property
+    def need_su(self):
+        return self.role > ROLE_PLAYER
 
     def __call__(self, client, args):
         self.func(client, args)
 
+    def allowed(self, client):
+        if self.role == ROLE_PLAYER:
+            return True
+        # The legacy `superuser` flag grants access to everything.
+        return client.has_role(self.role) or client.is_superuser()
+
 
 _HANDLERS = {}
-_SU_HANDLERS = {}
 
 def get_handler(cmd, client):
     h = _HANDLERS.get(cmd)
-    if h is None and client.is_superuser():
-        h = _SU_HANDLERS.get(cmd)
+    if h is None or not h.allowed(client):
+        return None
     return h
 
 def list_commands(client):
-    cmds = list(_HANDLERS.keys())
-    if client.is_superuser():
-        cmds.extend(_SU_HANDLERS.keys())
+    cmds = [cmd for cmd, h in _HANDLERS.items() if h.allowed(client)]
     cmds.sort()
     return cmds
 
@@ -47,12 +56,9 @@ def client_chat_command(eng, cid, line):
 def register_command(handler):
     """Add a Handler to _HANDLERS, checking for duplicate entries."""
     cmd = handler.name
-    assert cmd not in _HANDLERS and cmd not in _SU_HANDLERS, \
+    assert cmd not in _HANDLERS, \
             'duplicate registration for chat command %r' % cmd
-    if handler.need_su:
-        _SU_HANDLERS[cmd] = handler
-    else:
-        _HANDLERS[cmd] = handler
+    _HANDLERS[cmd] = handler
 
 def command(*args, **kwargs):
     """Decorator for registering chat command handlers.  Arguments are passed
@@ -61,6 +67,7 @@ def command(*args, **kwargs):
     Usage:
         @command                            # Default options
         @command(doc='/cmd: Blah blah')     # Set documentation for /help
+        @command(role=ROLE_MODERATOR)       # Restrict to moderators and above
     """
 
     # Support no-argument usage
@@ -76,6 +83,10 @@ def command(*args, **kwargs):
 def su_command(*args, **kwargs):
     return command(*args, need_su=True, **kwargs)
 
+def mod_command(*args, **kwargs):
+    from outpost_server.core.engine import ROLE_MODERATOR
+    return command(*args, role=ROLE_MODERATOR, **kwargs)
+
 @command('/help <command>: Show detailed info about <command>')
 def help(client, args):
     cmd = args.strip()
@@ -101,3 +112,10 @@ def help(client, args):
 
 def init(hooks):
     hooks.client_chat_command(client_chat_command)
+
+def register_roles(hooks):
+    """Tell the server which commands are restricted, so it can reject them
+    before running any script code.  Must run after all modules have
+    registered their commands."""
+    for cmd, h in _HANDLERS.items():
+        hooks.set_chat_command_role(cmd, h.role)
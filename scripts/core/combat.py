@@ -0,0 +1,39 @@
+"""Death and respawn callbacks.
+
+The server handles health and damage itself.  When an entity dies, every
+function registered with `on_death` is called.  Afterward, pawns wait a few
+seconds and then respawn with full health, calling the `on_respawn` functions,
+while other entities are destroyed unless a death callback healed them."""
+from outpost_server.core.engine import EntityProxy
+
+DEATH_CALLBACKS = []
+RESPAWN_CALLBACKS = []
+
+def on_death(f):
+    """Decorator for registering a death callback.  It's called as
+    `f(e, attacker)`, where `attacker` is None if nothing killed the entity
+    directly."""
+    DEATH_CALLBACKS.append(f)
+    return f
+
+def on_respawn(f):
+    """Decorator for registering a respawn callback, called as `f(e)`.  Use
+    this to move respawning pawns somewhere safe."""
+    RESPAWN_CALLBACKS.append(f)
+    return f
+
+
+def entity_death(eng, eid, attacker_id):
+    e = EntityProxy(eng, eid)
+    attacker = EntityProxy(eng, attacker_id) if attacker_id is not None else None
+    for f in DEATH_CALLBACKS:
+        f(e, attacker)
+
+def entity_respawn(eng, eid):
+    e = EntityProxy(eng, eid)
+    for f in RESPAWN_CALLBACKS:
+        f(e)
+
+def init(hooks):
+    hooks.entity_death(entity_death)
+    hooks.entity_respawn(entity_respawn)
@@ -76,6 +76,7 @@ class DataProxy:
         _define_methods(cls, TemplateProxy, 'template')
         _define_methods(cls, AnimationProxy, 'animation')
         _define_methods(cls, SpriteLayerProxy, 'sprite_layer')
+        _define_methods(cls, ActionProxy, 'action')
 
 DATA = DataProxy()
 
@@ -164,6 +165,11 @@ class SpriteLayerProxy(DefProxy):
     def name(self):
         return _DATA.sprite_layer_name(self.id)
 
+class ActionProxy(DefProxy):
+    @property
+    def name(self):
+        return _DATA.action_name(self.id)
+
 
 def init(data):
     global _DATA
@@ -175,5 +181,6 @@ def init(data):
     TemplateProxy.INSTANCES = [None] * data.template_count()
     AnimationProxy.INSTANCES = [None] * data.animation_count()
     SpriteLayerProxy.INSTANCES = [None] * data.sprite_layer_count()
+    ActionProxy.INSTANCES = [None] * data.action_count()
 
     DataProxy._init()
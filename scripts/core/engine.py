@@ -2,6 +2,17 @@ from outpost_server.core.data import DATA, BlockProxy, TemplateProxy
 from outpost_server.core.extra import ExtraHashProxy
 from outpost_server.core.types import *
 
+# Account roles.  These must match `auth::Role` on the Rust side.
+ROLE_PLAYER = 0
+ROLE_MODERATOR = 1
+ROLE_ADMIN = 2
+
+ROLE_NAMES = {
+        'player': ROLE_PLAYER,
+        'moderator': ROLE_MODERATOR,
+        'admin': ROLE_ADMIN,
+        }
+
 def check_type(obj, ty):
     if not isinstance(obj, ty):
         raise ValueError('expected %r, but got %r' % (ty, type(obj)))
@@ -40,6 +51,9 @@ class EngineProxy(object):
     def num_clients(self):
         return self._eng.messages_clients_len()
 
+    def send_queue_stats(self):
+        return self._eng.messages_queue_stats()
+
     def world_extra(self):
         return ExtraHashProxy(self._eng.world_extra())
 
@@ -70,6 +84,74 @@ class EngineProxy(object):
         return StructureProxy(self._eng, sid) if sid is not None else None
 
 
+    def ban(self, name, reason, duration=None):
+        """Ban account `name`.  `duration` is in seconds, or `None` for a
+        permanent ban.  Returns `False` if there is no such account."""
+        if duration is not None:
+            duration = int(duration * 1000)
+        return self._eng.auth_ban(name, reason, duration)
+
+    def unban(self, name):
+        return self._eng.auth_unban(name)
+
+    def ban_info(self, name):
+        """Returns a `(reason, expires)` pair, or `None` if `name` is not
+        banned.  `expires` is a Unix timestamp in milliseconds, or `None`."""
+        return self._eng.auth_ban_info(name)
+
+    def set_role(self, name, role):
+        """Set the role of account `name`.  Returns `False` if there is no
+        such account."""
+        return self._eng.auth_set_role(name, role)
+
+    def delete_account(self, name):
+        return self._eng.auth_delete_account(name)
+
+    def change_secret(self, client, old_secret, new_secret):
+        """Change the login secret of `client`'s account.  Secrets are
+        4-tuples of 32-bit integers.  The old secret is checked in the
+        background, and `client` gets a chat message with the result.
+        Returns `False` if `client` already has a change in progress."""
+        return self._eng.auth_change_secret(client.id, tuple(old_secret), tuple(new_secret))
+
+
+    def mute(self, name, duration=None):
+        """Prevent `name` from chatting.  `duration` is in seconds, or
+        `None` to mute until explicitly unmuted."""
+        if duration is not None:
+            duration = int(duration * 1000)
+        self._eng.chat_mute(name, duration)
+
+    def unmute(self, name):
+        return self._eng.chat_unmute(name)
+
+    def is_muted(self, name):
+        return self._eng.chat_mute_status(name) is not None
+
+    def add_chat_filter(self, f):
+        """Run every chat message through `f(sender, channel, text)`, after
+        the built-in filters.  `channel` is 'global', 'local', or 'whisper'.
+        `f` returns `(True, new_text)` to send the message, or `(False,
+        reason)` to drop it and show `reason` to the sender."""
+        self._eng.chat_add_filter(f)
+
+
+    def snapshot(self):
+        """Save everything and copy the saved world into a new snapshot.
+        Returns the snapshot's name.  The snapshot is written in the
+        background, and appears in `list_snapshots` once it's complete."""
+        return self._eng.snapshot_create()
+
+    def list_snapshots(self):
+        """Returns the names of all snapshots, oldest first."""
+        return self._eng.snapshot_list()
+
+    def restore_snapshot(self, name):
+        """Restart the server and replace the world with snapshot `name`.
+        Connected players are logged back in afterward."""
+        self._eng.snapshot_restore(name)
+
+
     def schedule_timer(self, when, userdata):
         return self._eng.timer_schedule(when, userdata)
 
@@ -143,11 +225,21 @@ class ClientProxy(ObjectProxy):
         ability = DATA.item_id(ability)
         self._eng.messages_send_get_use_ability_args(self.id, ability, dialog_id, args)
 
+    def get_use_action_args(self, action, dialog_id, args):
+        action = DATA.action_id(action)
+        self._eng.messages_send_get_use_action_args(self.id, action, dialog_id, args)
+
     def extra(self):
         return ExtraHashProxy(self._eng.world_client_extra(self.id))
 
+    def role(self):
+        return self._eng.world_client_role(self.id)
+
+    def has_role(self, role):
+        return self.role() >= role
+
     def is_superuser(self):
-        return bool(self.extra().get('superuser'))
+        return bool(self.extra().get('superuser')) or self.has_role(ROLE_ADMIN)
 
     def set_main_inventories(self, i_item, i_ability):
         self._eng.logic_set_main_inventories(self.id, i_item.id, i_ability.id)
@@ -199,6 +291,61 @@ class EntityProxy(ObjectProxy):
     def clear_anim(self, anim_id):
         self._eng.world_entity_set_activity_move(self.id)
 
+    def walk_to(self, pos, callback=None, run=False):
+        from outpost_server.core import walk
+        return walk.walk_to(self, pos, callback, run)
+
+    def cancel_walk(self):
+        from outpost_server.core import walk
+        walk.cancel(self)
+
+    def destroy(self):
+        self._eng.world_entity_destroy(self.id)
+
+    def make_npc(self, temperament, spawned=False):
+        """Put this entity under NPC control.  `temperament` is one of the
+        constants in `core.npc`.  Entities created with `spawned` set are
+        destroyed after no player has seen them for a while."""
+        self._eng.world_entity_npc_add(self.id, temperament, spawned)
+
+    def remove_npc(self):
+        self._eng.world_entity_npc_remove(self.id)
+
+    def is_npc(self):
+        return self._eng.world_entity_npc_behavior(self.id) is not None
+
+    def behavior(self):
+        """Get the name of the NPC's current behavior, or None if this entity
+        is not an NPC."""
+        return self._eng.world_entity_npc_behavior(self.id)
+
+    def set_behavior(self, name, target=None):
+        """Switch the NPC to behavior `name`.  The 'follow', 'flee', and
+        'attack' behaviors require a `target` entity."""
+        target_id = target.id if target is not None else None
+        self._eng.world_entity_npc_set_behavior(self.id, name, target_id)
+
+    def health(self):
+        return self._eng.world_entity_health(self.id)[0]
+
+    def max_health(self):
+        return self._eng.world_entity_health(self.id)[1]
+
+    def is_dead(self):
+        return self.health() == 0
+
+    def damage(self, amount, attacker=None):
+        """Deal `amount` damage to this entity.  If this kills it, the
+        `entity_death` hook runs with `attacker` as the killer."""
+        attacker_id = attacker.id if attacker is not None else None
+        self._eng.world_entity_damage(self.id, amount, attacker_id)
+
+    def heal(self, amount):
+        self._eng.world_entity_heal(self.id, amount)
+
+    def set_max_health(self, max_health):
+        self._eng.world_entity_set_max_health(self.id, max_health)
+
     def extra(self):
         return ExtraHashProxy(self._eng.world_entity_extra(self.id))
 
@@ -259,6 +406,10 @@ class PlaneProxy(ObjectProxy):
         else:
             return None
 
+    def create_entity(self, pos, appearance):
+        eid = self._eng.world_entity_create(self.stable_id(), pos, appearance)
+        return EntityProxy(self._eng, eid)
+
     def create_structure(self, pos, template):
         template = DATA.template_id(template)
         sid = self._eng.world_structure_create(self.id, pos, template)
@@ -6,10 +6,13 @@ import sys
 from outpost_server import core
 import outpost_server.core.data
 import outpost_server.core.chat
+import outpost_server.core.combat
 import outpost_server.core.eval
+import outpost_server.core.npc
 import outpost_server.core.state_machine
 import outpost_server.core.timer
 import outpost_server.core.use
+import outpost_server.core.walk
 
 
 old_print = builtins.print
@@ -56,10 +59,13 @@ def init(storage, data, hooks):
     core.data.init(data)    # Must be first
 
     core.chat.init(hooks)
+    core.combat.init(hooks)
     core.eval.init(hooks)
+    core.npc.init(hooks)
     core.state_machine.init(hooks)
     core.timer.init(hooks)
     core.use.init(hooks)
+    core.walk.init(hooks)
 
     hooks.server_startup(startup)
     hooks.client_login(client_login)
@@ -73,3 +79,5 @@ def init(storage, data, hooks):
                 continue
             print('Loading %s...' % m)
             importlib.import_module('outpost_server.%s' % m)
+
+    core.chat.register_roles(hooks)
@@ -0,0 +1,122 @@
+"""NPC kinds, script-defined behaviors, and spawn rules.
+
+The server runs the built-in behaviors ('idle', 'wander', 'follow', 'flee',
+'attack') itself.  Any other behavior name is looked up in `BEHAVIORS` and
+called once per think step.  Periodically, the server picks a random open spot
+near each player and calls the `npc_spawn` hook, which chooses a kind to spawn
+there based on the plane and biome."""
+import random
+
+from outpost_server.core.engine import EntityProxy, PlaneProxy
+
+# Temperaments.  NB: keep these in sync with logic/npc.rs  Temperament
+PASSIVE = 0
+TIMID = 1
+HOSTILE = 2
+
+# Key in the entity's extra for the name of its kind.
+KIND_KEY = 'npc_kind'
+
+
+class Kind(object):
+    def __init__(self, name, appearance, temperament, on_attack=None):
+        self.name = name
+        self.appearance = appearance
+        self.temperament = temperament
+        self.on_attack = on_attack
+
+KINDS = {}
+
+def kind(name, appearance, temperament, on_attack=None):
+    """Define a kind of NPC.  `on_attack(e, target)` is called each time an
+    NPC of this kind lands an attack."""
+    k = Kind(name, appearance, temperament, on_attack)
+    KINDS[name] = k
+    return k
+
+
+BEHAVIORS = {}
+
+def behavior(name):
+    """Decorator for defining a behavior.  The function is called with the NPC's
+    EntityProxy on every think step while the behavior is active."""
+    def register(f):
+        BEHAVIORS[name] = f
+        return f
+    return register
+
+
+# List of (name, test) pairs, checked in order.
+BIOMES = []
+
+def biome(name, test):
+    """Define a biome.  `test(block_name)` checks the name of the floor block an
+    NPC would be standing on."""
+    BIOMES.append((name, test))
+
+def biome_at(plane, pos):
+    """Get the name of the biome at pixel position `pos`, or None."""
+    block_name = plane.get_block(pos.px_to_tile()).name
+    for name, test in BIOMES:
+        if test(block_name):
+            return name
+    return None
+
+
+# List of (kind, stable plane ID, biome, weight).
+SPAWN_RULES = []
+
+def spawn_rule(kind, plane=None, biome=None, weight=1):
+    """Allow `kind` to spawn naturally.  If `plane` (a StablePlaneId) or `biome`
+    is given, the rule applies only there."""
+    SPAWN_RULES.append((kind, plane, biome, weight))
+
+def spawn(plane, pos, kind, spawned=False):
+    """Create an NPC of `kind` at `pos`."""
+    e = plane.create_entity(pos, kind.appearance)
+    e.extra()[KIND_KEY] = kind.name
+    e.make_npc(kind.temperament, spawned)
+    return e
+
+def kind_of(e):
+    return KINDS.get(e.extra().get(KIND_KEY))
+
+
+def choose_kind(plane, pos):
+    stable_pid = plane.stable_id()
+    b = biome_at(plane, pos)
+    rules = [(k, w) for k, p, bb, w in SPAWN_RULES
+            if (p is None or p == stable_pid) and (bb is None or bb == b)]
+    total = sum(w for k, w in rules)
+    if total == 0:
+        return None
+    x = random.randrange(total)
+    for k, w in rules:
+        if x < w:
+            return k
+        x -= w
+
+
+def npc_spawn(eng, pid, pos):
+    plane = PlaneProxy(eng, pid)
+    k = choose_kind(plane, pos)
+    if k is not None:
+        spawn(plane, pos, k, spawned=True)
+
+def npc_think(eng, eid, name):
+    f = BEHAVIORS.get(name)
+    if f is None:
+        print('NPC %s has unknown behavior %r' % (eid, name))
+        return
+    f(EntityProxy(eng, eid))
+
+def npc_attack(eng, eid, target_id):
+    e = EntityProxy(eng, eid)
+    k = kind_of(e)
+    if k is not None and k.on_attack is not None:
+        k.on_attack(e, EntityProxy(eng, target_id))
+
+def init(hooks):
+    hooks.npc_spawn(npc_spawn)
+    hooks.npc_think(npc_think)
+    hooks.npc_attack(npc_attack)
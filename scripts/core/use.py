@@ -1,11 +1,12 @@
 from outpost_server.core import alias, util
 from outpost_server.core.data import DATA
-from outpost_server.core.data import ItemProxy
+from outpost_server.core.data import ActionProxy, ItemProxy
 from outpost_server.core.engine import ClientProxy
 
 _USE_STRUCTURE = {}
 _USE_ITEM = {}
 _USE_ABILITY = {}
+_USE_ACTION = {}
 
 
 def client_interact(eng, cid, args):
@@ -38,6 +39,13 @@ def client_use_ability(eng, cid, ability_id, args):
 
         handler(e, args)
 
+def client_use_action(eng, cid, action_id, args):
+    action = ActionProxy.by_id(action_id)
+    handler = _USE_ACTION.get(action)
+    if handler is not None:
+        e = ClientProxy(eng, cid).pawn()
+        handler(e, args)
+
 
 # Provide a way to call handlers from outside this module, so that one handler
 # can easily dispatch to another.
@@ -56,6 +64,11 @@ def call_ability_handler(ability, e, args):
     if handler is not None:
         handler(e, args)
 
+def call_action_handler(action, e, args):
+    handler = _USE_ACTION.get(DATA.action(action))
+    if handler is not None:
+        handler(e, args)
+
 
 def structure(name):
     """Decorator for registering structure use handlers.
@@ -93,8 +106,22 @@ def ability(name):
         return f
     return register
 
+def action(name):
+    """Decorator for registering handlers for actions declared in the data
+    scripts.  The handler is called as `f(e, args)`, where `e` is the pawn of
+    the client that used the action."""
+    action = DATA.action(name)
+    def register(f):
+        assert action not in _USE_ACTION, \
+                'duplicate registration for %s (original was %s)' % \
+                (action, _USE_ACTION[action].__qualname__)
+        _USE_ACTION[action] = f
+        return f
+    return register
+
 
 def init(hooks):
     hooks.client_interact(client_interact)
     hooks.client_use_item(client_use_item)
     hooks.client_use_ability(client_use_ability)
+    hooks.client_use_action(client_use_action)
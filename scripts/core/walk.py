@@ -0,0 +1,30 @@
+from outpost_server.core.engine import EntityProxy
+
+# Maps raw entity IDs to the callback for the walk currently in progress.
+CALLBACKS = {}
+
+def walk_to(e, pos, callback=None, run=False):
+    """Make entity `e` walk to `pos`, planning a path around obstacles.  When
+    the walk ends, `callback(e, arrived)` is called, with `arrived` set to
+    False if the entity got stuck or something interrupted it.  Starting a new
+    walk replaces the old one without calling its callback.  Returns False if
+    there is no path to `pos`."""
+    CALLBACKS.pop(e.id.raw, None)
+    if not e._eng.world_entity_walk_to(e.id, pos, run):
+        return False
+    if callback is not None:
+        CALLBACKS[e.id.raw] = callback
+    return True
+
+def cancel(e):
+    """Stop the walk in progress for `e`, without calling its callback."""
+    CALLBACKS.pop(e.id.raw, None)
+    e._eng.world_entity_walk_cancel(e.id)
+
+def walk_done(eng, eid, arrived):
+    callback = CALLBACKS.pop(eid.raw, None)
+    if callback is not None:
+        callback(EntityProxy(eng, eid), arrived)
+
+def init(hooks):
+    hooks.entity_walk_done(walk_done)
@@ -8,6 +8,7 @@ from . import crops
 from . import emote
 from . import hat
 from . import misc
+from . import mobs
 from . import sign
 from . import socks
 from . import structure_items
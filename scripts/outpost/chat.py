@@ -1,3 +1,4 @@
+import json
 import shlex
 
 from outpost_server.core import chat, engine, types, util
@@ -49,6 +50,35 @@ def home(client, args):
     pos = extra.get('home_pos', SPAWN_POINT)
     pawn.teleport(pos)
 
+def parse_secret(s):
+    try:
+        secret = json.loads(s)
+    except ValueError:
+        secret = None
+    if not isinstance(secret, list) or len(secret) != 4 or \
+            not all(isinstance(x, int) and 0 <= x < 1 << 32 for x in secret):
+        raise ValueError('Expected a secret like [1,2,3,4]; got %r' % s)
+    return tuple(secret)
+
+@chat.command('''
+    /passwd <old> <new>: Change your login secret
+    Secrets are written as in the config editor's login_secret, like [1,2,3,4].
+    Update login_secret to match afterward, or you won't be able to log in.
+''')
+def passwd(client, args):
+    try:
+        args = args.split()
+        if len(args) != 2:
+            raise ValueError('Expected 2 arguments, got %d' % len(args))
+        old_secret = parse_secret(args[0])
+        new_secret = parse_secret(args[1])
+
+        if not client.engine.change_secret(client, old_secret, new_secret):
+            raise ValueError('A secret change is already in progress')
+
+    except Exception as e:
+        client.send_message('Error: %r' % e)
+
 # Client-side commands (included here for /help purposes only)
 
 @chat.command('/ignore <name>: Hide chat messages from named player')
@@ -210,6 +240,133 @@ def tribe(client, args):
     except Exception as e:
         client.send_message('Error: %r' % e)
 
+def parse_duration(s):
+    units = {'m': 60, 'h': 60 * 60, 'd': 24 * 60 * 60}
+    if len(s) < 2 or s[-1] not in units or not s[:-1].isdigit():
+        raise ValueError('Expected a duration like 30m, 12h, or 7d; got %r' % s)
+    return int(s[:-1]) * units[s[-1]]
+
+@chat.mod_command('''
+        /ban <who> [<duration>] <reason>: Ban a player's account
+        <duration> is a number followed by m, h, or d.  Omit it for a permanent ban.
+        Player names containing spaces should be quoted.
+''')
+def ban(client, args):
+    try:
+        args = shlex.split(args)
+        if len(args) < 2:
+            raise ValueError('Expected at least 2 arguments, got %d' % len(args))
+        name = args[0]
+        duration = None
+        if len(args) >= 3 and args[1][-1:] in 'mhd' and args[1][:-1].isdigit():
+            duration = parse_duration(args[1])
+            reason = ' '.join(args[2:])
+        else:
+            reason = ' '.join(args[1:])
+
+        if not client.engine.ban(name, reason, duration):
+            raise ValueError('No such account: %r' % name)
+        client.send_message('Banned %r' % name)
+
+    except Exception as e:
+        client.send_message('Error: %r' % e)
+
+@chat.mod_command('/unban <who>: Lift the ban on a player\'s account')
+def unban(client, args):
+    try:
+        name = ' '.join(shlex.split(args))
+        if not client.engine.unban(name):
+            raise ValueError('%r is not banned' % name)
+        client.send_message('Unbanned %r' % name)
+
+    except Exception as e:
+        client.send_message('Error: %r' % e)
+
+@chat.mod_command('''
+        /mute <who> [<duration>]: Prevent a player from chatting
+        <duration> is a number followed by m, h, or d.  Omit it to mute until /unmute.
+        Player names containing spaces should be quoted.
+''')
+def mute(client, args):
+    try:
+        args = shlex.split(args)
+        if len(args) == 1:
+            name, duration = args[0], None
+        elif len(args) == 2:
+            name, duration = args[0], parse_duration(args[1])
+        else:
+            raise ValueError('Expected 1 or 2 arguments, got %d' % len(args))
+
+        client.engine.mute(name, duration)
+        client.send_message('Muted %r' % name)
+
+    except Exception as e:
+        client.send_message('Error: %r' % e)
+
+@chat.mod_command('/unmute <who>: Allow a muted player to chat again')
+def unmute(client, args):
+    try:
+        name = ' '.join(shlex.split(args))
+        if not client.engine.unmute(name):
+            raise ValueError('%r is not muted' % name)
+        client.send_message('Unmuted %r' % name)
+
+    except Exception as e:
+        client.send_message('Error: %r' % e)
+
+@chat.command('''
+        /l <message>: Send a message only to nearby players
+        /w <who> <message>: Send a private message to another player
+''')
+def l(client, args):
+    raise RuntimeError('/l should be handled by the server')
+
+@chat.command(l.doc)
+def w(client, args):
+    raise RuntimeError('/w should be handled by the server')
+
+@chat.su_command('/role <who> <player|moderator|admin>: Set the role of a player\'s account')
+def role(client, args):
+    try:
+        args = shlex.split(args)
+        if len(args) != 2:
+            raise ValueError('Expected 2 arguments, got %d' % len(args))
+        name, role_str = args
+        role = engine.ROLE_NAMES.get(role_str)
+        if role is None:
+            raise ValueError('No such role: %r' % role_str)
+
+        if not client.engine.set_role(name, role):
+            raise ValueError('No such account: %r' % name)
+        client.send_message('Set role of %r to %s' % (name, role_str))
+
+    except Exception as e:
+        client.send_message('Error: %r' % e)
+
+@chat.su_command('''
+        /snapshot: Save a snapshot of the whole world
+        /snapshot list: List saved snapshots
+''')
+def snapshot(client, args):
+    try:
+        if args.strip() == 'list':
+            names = client.engine.list_snapshots()
+            client.send_message('Snapshots: %s' % (', '.join(names) or '(none)'))
+        elif args.strip() == '':
+            name = client.engine.snapshot()
+            client.send_message('Saving snapshot %s' % name)
+        else:
+            raise ValueError('Unknown argument: %r' % args)
+    except Exception as e:
+        client.send_message('Error: %r' % e)
+
+@chat.su_command('/restore <name>: Restart the server and restore a snapshot')
+def restore(client, args):
+    try:
+        client.engine.restore_snapshot(args.strip())
+    except Exception as e:
+        client.send_message('Error: %r' % e)
+
 class FunctionObject:
     def __init__(self, obj, f):
         self.obj = obj
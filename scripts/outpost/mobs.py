@@ -0,0 +1,31 @@
+from outpost_server.core import combat, npc
+from outpost_server.outpost.lib.consts import *
+
+# TODO: these use pony appearances until NPCs get their own sprites
+APPEARANCE_CRITTER = (1 << 6)
+APPEARANCE_SHADE = (3 << 6) | (1 << 8)
+
+npc.biome('grass', lambda name: name.startswith('terrain/gggg'))
+npc.biome('cave', lambda name: name.startswith('terrain/cccc'))
+
+
+def shade_attack(e, target):
+    target.damage(10, e)
+
+CRITTER = npc.kind('critter', APPEARANCE_CRITTER, npc.TIMID)
+SHADE = npc.kind('shade', APPEARANCE_SHADE, npc.HOSTILE, on_attack=shade_attack)
+
+npc.spawn_rule(CRITTER, plane=STABLE_PLANE_FOREST, biome='grass')
+npc.spawn_rule(SHADE, biome='cave', weight=2)
+
+
+@combat.on_death
+def pawn_death(e, attacker):
+    c = e.controller()
+    if c is not None:
+        c.send_message('You have been knocked out!')
+
+@combat.on_respawn
+def pawn_respawn(e):
+    if e.controller() is not None:
+        e.teleport_plane(STABLE_PLANE_FOREST, SPAWN_POINT)
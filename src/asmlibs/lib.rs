@@ -93,6 +93,16 @@ pub unsafe extern fn load_terrain_chunk(client: &mut Client,
     client.load_terrain_chunk(V2::new(cx, cy), data);
 }
 
+#[no_mangle]
+pub unsafe extern fn update_terrain_blocks(client: &mut Client,
+                                           cx: i32,
+                                           cy: i32,
+                                           data_ptr: *const u16,
+                                           data_byte_len: usize) {
+    let data = make_slice(data_ptr, data_byte_len);
+    client.update_terrain_blocks(V2::new(cx, cy), data);
+}
+
 // Structures
 
 #[no_mangle]
@@ -130,7 +140,8 @@ pub unsafe extern fn entity_appear(client: &mut Client,
                                    id: u32,
                                    appearance: u32,
                                    name_ptr: *mut u8,
-                                   name_len: usize) {
+                                   name_len: usize,
+                                   collision_groups: u8) {
     let name =
         if name_ptr.is_null() {
             None
@@ -138,7 +149,7 @@ pub unsafe extern fn entity_appear(client: &mut Client,
             let name_bytes = make_boxed_slice(name_ptr, name_len).into_vec();
             Some(String::from_utf8(name_bytes).unwrap())
         };
-    client.entity_appear(id, appearance, name);
+    client.entity_appear(id, appearance, name, collision_groups);
 }
 
 #[no_mangle]
@@ -147,6 +158,22 @@ pub unsafe extern fn entity_gone(client: &mut Client,
     client.entity_gone(id);
 }
 
+#[no_mangle]
+pub unsafe extern fn entity_health(client: &mut Client,
+                                   id: u32,
+                                   hp: u16,
+                                   max: u16) {
+    client.entity_health(id, hp, max);
+}
+
+#[no_mangle]
+pub unsafe extern fn entity_damage(client: &mut Client,
+                                   id: u32,
+                                   amount: u16,
+                                   now: i32) {
+    client.entity_damage(id, amount, now);
+}
+
 #[no_mangle]
 pub unsafe extern fn entity_update(client: &mut Client,
                                    id: u32,
@@ -204,8 +231,16 @@ pub unsafe extern fn inventory_ability_id(client: &mut Client,
 }
 
 #[no_mangle]
-pub unsafe extern fn input_key(client: &mut Client, code: u8) -> u8 {
-    client.input_key(code) as u8
+pub unsafe extern fn input_key(client: &mut Client, code: u8, shift: u8) -> u8 {
+    client.input_key(code, shift != 0) as u8
+}
+
+#[no_mangle]
+pub unsafe extern fn input_gamepad(client: &mut Client,
+                                   buttons: u32,
+                                   x: i32,
+                                   y: i32) {
+    client.input_gamepad(buttons, x, y);
 }
 
 #[no_mangle]
@@ -239,6 +274,11 @@ pub unsafe extern fn open_ability_dialog(client: &mut Client) {
     client.open_ability_dialog();
 }
 
+#[no_mangle]
+pub unsafe extern fn open_keybindings_dialog(client: &mut Client) {
+    client.open_keybindings_dialog();
+}
+
 #[no_mangle]
 pub unsafe extern fn open_container_dialog(client: &mut Client,
                                            inv_id0: u32,
@@ -256,6 +296,16 @@ pub unsafe extern fn get_active_ability(client: &mut Client) -> u16 {
     client.get_active_ability()
 }
 
+#[no_mangle]
+pub unsafe extern fn set_hotbar_action(client: &mut Client,
+                                       idx: u8,
+                                       name_ptr: *mut u8,
+                                       name_len: usize) -> u8 {
+    let name_bytes = make_boxed_slice(name_ptr, name_len).into_vec();
+    let name = String::from_utf8(name_bytes).unwrap();
+    client.set_hotbar_action(idx, &name) as u8
+}
+
 
 
 // Physics
@@ -31,6 +31,12 @@ mod ffi {
                                  dest_inv: u32,
                                  dest_slot: usize,
                                  amount: u8);
+
+        pub fn ap_send_use_action(action_id: u16);
+
+        pub fn ap_run_command(name_ptr: *const u8,
+                              name_len: usize,
+                              down: u8);
     }
 }
 
@@ -72,6 +78,17 @@ impl platform::Platform for Platform {
             ffi::ap_send_move_item(src_inv, src_slot, dest_inv, dest_slot, amount);
         }
     }
+
+    fn send_use_action(&mut self, action_id: u16) {
+        unsafe { ffi::ap_send_use_action(action_id) };
+    }
+
+    fn run_command(&mut self, name: &str, down: bool) {
+        let name_bytes = name.as_bytes();
+        unsafe {
+            ffi::ap_run_command(name_bytes.as_ptr(), name_bytes.len(), down as u8);
+        }
+    }
 }
 
 
@@ -78,6 +78,8 @@ var asmlibs_code_raw = function(global, env, buffer) {
     var _ap_config_clear = env.ap_config_clear;
     var _ap_set_cursor = env.ap_set_cursor;
     var _ap_send_move_item = env.ap_send_move_item;
+    var _ap_send_use_action = env.ap_send_use_action;
+    var _ap_run_command = env.ap_run_command;
 
     var tempRet0 = 0;
 
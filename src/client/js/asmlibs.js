@@ -262,6 +262,17 @@ var module_env = function(asm) {
             asm.conn.sendMoveItem(src_inv, src_slot, dest_inv, dest_slot, amount);
         },
 
+        'ap_send_use_action': function(action_id) {
+            var time = asm.timing.encodeSend(asm.timing.nextArrival());
+            asm.conn.sendUseAction(time, action_id);
+        },
+
+        'ap_run_command': function(name_ptr, name_len, down) {
+            if (asm.onCommand != null) {
+                asm.onCommand(asm._loadString(name_ptr, name_len), down != 0);
+            }
+        },
+
 
         'STACK_START': STACK_START,
         'STACK_END': STACK_END,
@@ -308,6 +319,8 @@ var HEAP_PADDING = 256 * 1024;
 function DynAsm() {
     this.asmgl = new AsmGl();
     this.conn = null;   // Will be set later, in main.js
+    this.timing = null; // Same
+    this.onCommand = null; // Same
 
     this.buffer = new ArrayBuffer(next_heap_size(INIT_HEAP_SIZE));
     this._memcpy(STATIC_START, static_data);
@@ -460,7 +473,7 @@ DynAsm.prototype.structureReplace = function(id, template_id, oneshot_start) {
     this._raw['structure_replace'](this.client, id, template_id, oneshot_start);
 };
 
-DynAsm.prototype.entityAppear = function(id, appearance, name) {
+DynAsm.prototype.entityAppear = function(id, appearance, name, collision_groups) {
     var name_ptr = 0;
     var name_len = 0;
     if (name != null && name.length > 0) {
@@ -470,13 +483,22 @@ DynAsm.prototype.entityAppear = function(id, appearance, name) {
     }
 
     // Library takes ownership of the name allocation.
-    this._raw['entity_appear'](this.client, id, appearance, name_ptr, name_len);
+    this._raw['entity_appear'](this.client, id, appearance, name_ptr, name_len,
+            collision_groups);
 };
 
 DynAsm.prototype.entityGone = function(id) {
     this._raw['entity_gone'](this.client, id);
 };
 
+DynAsm.prototype.entityHealth = function(id, hp, max) {
+    this._raw['entity_health'](this.client, id, hp, max);
+};
+
+DynAsm.prototype.entityDamage = function(id, amount, now) {
+    this._raw['entity_damage'](this.client, id, amount, now);
+};
+
 DynAsm.prototype.entityUpdate = function(id, motion, anim) {
     var arr = this._stackAlloc(Int32Array, 9);
 
@@ -531,8 +553,12 @@ DynAsm.prototype.inventoryAbilityId = function(id) {
     this._raw['inventory_ability_id'](this.client, id);
 };
 
-DynAsm.prototype.inputKey = function(code) {
-    return this._raw['input_key'](this.client, code);
+DynAsm.prototype.inputKey = function(code, shift) {
+    return this._raw['input_key'](this.client, code, shift ? 1 : 0);
+};
+
+DynAsm.prototype.inputGamepad = function(buttons, x, y) {
+    this._raw['input_gamepad'](this.client, buttons, x, y);
 };
 
 DynAsm.prototype.inputMouseMove = function(x, y) {
@@ -551,6 +577,10 @@ DynAsm.prototype.openInventoryDialog = function() {
     return this._raw['open_inventory_dialog'](this.client);
 };
 
+DynAsm.prototype.openKeybindingsDialog = function() {
+    return this._raw['open_keybindings_dialog'](this.client);
+};
+
 DynAsm.prototype.openAbilityDialog = function() {
     return this._raw['open_ability_dialog'](this.client);
 };
@@ -567,6 +597,13 @@ DynAsm.prototype.getActiveAbility = function() {
     return this._raw['get_active_ability'](this.client);
 };
 
+DynAsm.prototype.setHotbarAction = function(idx, name) {
+    var name_view = this._allocString(name);
+    // Library takes ownership of the name allocation.
+    return this._raw['set_hotbar_action'](this.client, idx,
+            name_view.byteOffset, name_view.byteLength) != 0;
+};
+
 DynAsm.prototype.setRegionShape = function(base, size, layer, shape) {
     var region = this._stackAlloc(Int32Array, 6);
     store_vec(region, 0, base);
@@ -630,6 +667,17 @@ DynAsm.prototype.loadTerrainChunk = function(cx, cy, data) {
     this._heapFree(buf);
 };
 
+DynAsm.prototype.updateTerrainBlocks = function(cx, cy, blocks) {
+    var buf = this._heapAlloc(Uint16Array, blocks.length * 2);
+    for (var i = 0; i < blocks.length; ++i) {
+        buf[i * 2] = blocks[i].offset;
+        buf[i * 2 + 1] = blocks[i].block_id;
+    }
+    this._raw['update_terrain_blocks'](this.client,
+            cx, cy, buf.byteOffset, buf.byteLength);
+    this._heapFree(buf);
+};
+
 DynAsm.prototype.renderFrame = function(now, future) {
     this._raw['render_frame'](this.client, now, future);
 };
@@ -20,6 +20,7 @@ var DEFAULT_CONFIG = {
         // 9 slots in each array
         'names': [-1, -1, -1, -1, -1, -1, -1, -1, -1],
         'is_item': [false, false, false, false, false, false, false, false, false],
+        'is_action': [false, false, false, false, false, false, false, false, false],
         'active_item': -1,
         'active_ability': -1,
     },
@@ -35,6 +36,7 @@ var DEFAULT_CONFIG = {
         38: 'move_up',      // ArrowUp
         40: 'move_down',    // ArrowDown
         16: 'run',          // Shift
+        88: 'jump',         // X
 
         65: 'interact',     // A
         83: 'use_ability',  // S
@@ -64,6 +66,25 @@ var DEFAULT_CONFIG = {
         57: 'hotbar_9',
     },
 
+    // Indexed by button number in the standard gamepad layout.
+    'gamepad_bindings': {
+        0: 'interact',      // A
+        1: 'cancel',        // B
+        2: 'use_item',      // X
+        3: 'use_ability',   // Y
+        4: 'hotbar_prev',   // Left bumper
+        5: 'hotbar_next',   // Right bumper
+        6: 'run',           // Left trigger
+        7: 'jump',          // Right trigger
+        8: 'show_menu',     // Back
+        9: 'inventory',     // Start
+        10: 'abilities',    // Left stick
+        12: 'move_up',      // D-pad
+        13: 'move_down',
+        14: 'move_left',
+        15: 'move_right',
+    },
+
     'chat_keybindings': {
         13: 'send',         // Enter
         27: 'cancel',       // Esc
@@ -128,6 +149,7 @@ exports.Config = {
     last_appearance: new ConfigItem('last_appearance'),
 
     keybindings: new ConfigItem('keybindings'),
+    gamepad_bindings: new ConfigItem('gamepad_bindings'),
     chat_keybindings: new ConfigItem('chat_keybindings'),
     ui_keybindings: new ConfigItem('ui_keybindings'),
 
@@ -187,6 +209,15 @@ ConfigItem.prototype.save = function() {
 };
 
 
+// The `raw*` functions bypass the `ConfigItem` caches, so the cached value must
+// be dropped after any change.
+function invalidate(base_key) {
+    var item = exports.Config[base_key];
+    if (item != null) {
+        item.value = null;
+    }
+}
+
 function rawGet(key) {
     var parts = key.split('.');
 
@@ -240,6 +271,7 @@ function rawSet(key, val) {
         }
         localStorage.setItem(base_key, val_str);
     }
+    invalidate(base_key);
 };
 exports.rawSet = rawSet;
 
@@ -261,5 +293,6 @@ function rawClear(key, val) {
     } else {
         localStorage.removeItem(base_key);
     }
+    invalidate(base_key);
 };
 exports.rawClear = rawClear;
@@ -0,0 +1,47 @@
+// Number of buttons in the standard gamepad layout.  NB: keep in sync with
+// libclient/gamepad.rs  NUM_BUTTONS
+var NUM_BUTTONS = 16;
+// Stick axes are scaled to this range before being passed to the client
+// library.  NB: keep in sync with libclient/gamepad.rs  AXIS_MAX
+var AXIS_MAX = 1000;
+
+
+function findGamepad() {
+    if (navigator.getGamepads == null) {
+        return null;
+    }
+
+    var pads = navigator.getGamepads();
+    for (var i = 0; i < pads.length; ++i) {
+        var pad = pads[i];
+        if (pad != null && pad.connected && pad.mapping == 'standard') {
+            return pad;
+        }
+    }
+    return null;
+}
+
+function scaleAxis(x) {
+    return Math.max(-AXIS_MAX, Math.min(AXIS_MAX, Math.round(x * AXIS_MAX)))|0;
+}
+
+// Forward the state of the first gamepad to the client library.  The Gamepad
+// API has no events for button presses, so this must be called every frame.
+function pollGamepad(asm_client) {
+    var pad = findGamepad();
+    if (pad == null) {
+        asm_client.inputGamepad(0, 0, 0);
+        return;
+    }
+
+    var buttons = 0;
+    var count = Math.min(pad.buttons.length, NUM_BUTTONS);
+    for (var i = 0; i < count; ++i) {
+        if (pad.buttons[i].pressed) {
+            buttons |= 1 << i;
+        }
+    }
+
+    asm_client.inputGamepad(buttons, scaleAxis(pad.axes[0]), scaleAxis(pad.axes[1]));
+}
+exports.pollGamepad = pollGamepad;
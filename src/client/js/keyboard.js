@@ -2,40 +2,10 @@ var Config = require('config').Config;
 
 
 
-function asmEncodeKey(code, shift) {
-    var adj = shift ? 10 : 0;
-    switch (Config.keybindings.get()[code]) {
-        case 'move_left':   return 0 + adj;
-        case 'move_right':  return 1 + adj;
-        case 'move_up':     return 2 + adj;
-        case 'move_down':   return 3 + adj;
-
-        // TODO: enter key handling is a hack
-        case 'chat':        return 20;
-        case 'cancel':      return 21;
-
-        case 'hotbar_1':    return 31;
-        case 'hotbar_2':    return 32;
-        case 'hotbar_3':    return 33;
-        case 'hotbar_4':    return 34;
-        case 'hotbar_5':    return 35;
-        case 'hotbar_6':    return 36;
-        case 'hotbar_7':    return 37;
-        case 'hotbar_8':    return 38;
-        case 'hotbar_9':    return 39;
-
-        case 'debug_show_panel': return 114;
-
-        default:            return null;
-    }
-}
-
 function asmDispatchKey(asm_client, code, shift) {
-    var asm_code = asmEncodeKey(code, shift);
-    if (asm_code == null) {
-        return false;
-    }
-    return asm_client.inputKey(asm_code);
+    // The client library maps the key code to a command using its own copy of
+    // `Config.keybindings`.
+    return asm_client.inputKey(code, shift);
 }
 
 
@@ -14,6 +14,7 @@ var Motion = require('entity').Motion;
 var InventoryTracker = require('inventory').InventoryTracker;
 
 var Keyboard = require('keyboard').Keyboard;
+var pollGamepad = require('gamepad').pollGamepad;
 var Dialog = require('ui/dialog').Dialog;
 var Banner = require('ui/banner').Banner;
 var ChatWindow = require('ui/chat').ChatWindow;
@@ -24,7 +25,6 @@ var Menu = require('ui/menu').Menu;
 var ConfigEditor = require('ui/configedit').ConfigEditor;
 var MusicTest = require('ui/musictest').MusicTest;
 var PonyEditor = require('ui/ponyedit').PonyEditor;
-var KeybindingEditor = require('ui/keybinding').KeybindingEditor;
 var widget = require('ui/widget');
 var ErrorList = require('ui/errorlist').ErrorList;
 var InventoryUpdateList = require('ui/invupdate').InventoryUpdateList;
@@ -101,6 +101,12 @@ function init() {
     dnd = new DNDState(keyboard);
     dialog = new Dialog(keyboard);
     chat = new ChatWindow();
+    chat.onBind = function(idx, name) {
+        if (!(idx >= 0 && idx < 9) || !name ||
+                !asm_client.setHotbarAction(idx, name)) {
+            chat.addMessage('***\tUsage: /bind <1-9> <action>');
+        }
+    };
     inv_update_list = new InventoryUpdateList();
     music_test = new MusicTest();
 
@@ -146,6 +152,7 @@ function init() {
                 timing.scheduleUpdates(5, 30);
                 inv_tracker = new InventoryTracker(conn, asm_client);
                 asm_client.conn = conn;
+                asm_client.timing = timing;
 
                 maybeRegister(info, function() {
                     conn.sendLogin(Config.login_name.get(), Config.login_secret.get());
@@ -211,6 +218,7 @@ function openConn(info, next) {
     conn.onClose = handleClose;
     conn.onInit = handleInit;
     conn.onTerrainChunk = handleTerrainChunk;
+    conn.onTerrainBlocksUpdate = handleTerrainBlocksUpdate;
     conn.onEntityUpdate = handleEntityUpdate;
     conn.onUnloadChunk = handleUnloadChunk;
     conn.onOpenDialog = handleOpenDialog;
@@ -218,6 +226,8 @@ function openConn(info, next) {
     conn.onChatUpdate = handleChatUpdate;
     conn.onEntityAppear = handleEntityAppear;
     conn.onEntityGone = handleEntityGone;
+    conn.onEntityHealth = handleEntityHealth;
+    conn.onEntityDamage = handleEntityDamage;
     conn.onStructureAppear = handleStructureAppear;
     conn.onStructureGone = handleStructureGone;
     conn.onMainInventory = handleMainInventory;
@@ -226,6 +236,7 @@ function openConn(info, next) {
     conn.onGetInteractArgs = handleGetInteractArgs;
     conn.onGetUseItemArgs = handleGetUseItemArgs;
     conn.onGetUseAbilityArgs = handleGetUseAbilityArgs;
+    conn.onGetUseActionArgs = handleGetUseActionArgs;
     conn.onSyncStatus = handleSyncStatus;
     conn.onStructureReplace = handleStructureReplace;
 }
@@ -320,7 +331,8 @@ function initMenus() {
                 dialog.show(new widget.Form(new Iframe('instructions.html', keyboard)));
             }],
             ['&Keyboard Controls', function() {
-                dialog.show(new KeybindingEditor(keyboard));
+                dialog.hide();
+                asm_client.openKeybindingsDialog();
             }],
             ['&Debug Menu', function() { dialog.show(debug_menu); }],
             ['&Credits', function() {
@@ -408,6 +420,7 @@ var INPUT_RIGHT =   0x0002;
 var INPUT_UP =      0x0004;
 var INPUT_DOWN =    0x0008;
 var INPUT_RUN =     0x0010;
+var INPUT_JUMP =    0x0020;
 
 var ACTION_USE =        1;
 var ACTION_INVENTORY =  2;
@@ -420,6 +433,7 @@ function setupKeyHandler() {
         'move_left': false,
         'move_right': false,
         'run': false,
+        'jump': false,
     };
 
     keyboard.pushHandler(function(down, evt) {
@@ -434,6 +448,15 @@ function setupKeyHandler() {
             return shouldStop;
         }
 
+        return handleBinding(binding, down, evt.shiftKey) || shouldStop;
+    });
+
+    // Gamepad buttons and sticks are mapped to bindings by the client library.
+    asm_client.onCommand = function(binding, down) {
+        handleBinding(binding, down, false);
+    };
+
+    function handleBinding(binding, down, shift) {
         if (dirs_held.hasOwnProperty(binding)) {
             dirs_held[binding] = down;
             updateWalkDir();
@@ -448,7 +471,7 @@ function setupKeyHandler() {
                     $('key-list').classList.toggle('hidden', !show);
                     break;
                 case 'debug_test':
-                    if (!evt.shiftKey) {
+                    if (!shift) {
                         asm_client.debugExport();
                     } else {
                         asm_client.debugImport();
@@ -493,14 +516,14 @@ function setupKeyHandler() {
                     break;
 
                 default:
-                    return shouldStop;
+                    return false;
             }
 
             return true;
         } else {
-            return shouldStop;
+            return false;
         }
-    });
+    }
 
     function updateWalkDir() {
         var bits = 0;
@@ -531,6 +554,11 @@ function setupKeyHandler() {
             target_velocity = target_velocity.mulScalar(50);
         }
 
+        if (dirs_held['jump']) {
+            bits |= INPUT_JUMP;
+            target_velocity.z = 1;
+        }
+
         var arrival = timing.nextArrival() + Config.input_delay.get();
         conn.sendInput(timing.encodeSend(arrival), bits);
 
@@ -579,6 +607,12 @@ function handleTerrainChunk(i, data) {
     asm_client.loadTerrainChunk(cx, cy, data);
 }
 
+function handleTerrainBlocksUpdate(i, blocks) {
+    var cx = (i % LOCAL_SIZE)|0;
+    var cy = (i / LOCAL_SIZE)|0;
+    asm_client.updateTerrainBlocks(cx, cy, blocks);
+}
+
 function handleEntityUpdate(id, motion, anim) {
     var m = new Motion(motion.start_pos);
     m.end_pos = motion.end_pos;
@@ -630,8 +664,8 @@ function handleChatUpdate(msg) {
     chat.addMessage(msg);
 }
 
-function handleEntityAppear(id, appearance_bits, name) {
-    asm_client.entityAppear(id, appearance_bits, name);
+function handleEntityAppear(id, appearance_bits, name, collision_groups) {
+    asm_client.entityAppear(id, appearance_bits, name, collision_groups);
 }
 
 function handleEntityGone(id, time) {
@@ -639,9 +673,18 @@ function handleEntityGone(id, time) {
     asm_client.entityGone(id);
 }
 
-function handleStructureAppear(id, template_id, x, y, z) {
+function handleEntityHealth(id, hp, max) {
+    asm_client.entityHealth(id, hp, max);
+}
+
+function handleEntityDamage(id, amount) {
     var now = timing.visibleNow();
-    asm_client.structureAppear(id, x, y, z, template_id, now);
+    asm_client.entityDamage(id, amount, now);
+}
+
+function handleStructureAppear(id, template_id, pos) {
+    var now = timing.visibleNow();
+    asm_client.structureAppear(id, pos.x, pos.y, pos.z, template_id, now);
 }
 
 function handleStructureGone(id, time) {
@@ -697,6 +740,12 @@ function handleGetUseAbilityArgs(item_id, dialog_id, parts) {
     });
 }
 
+function handleGetUseActionArgs(action_id, dialog_id, parts) {
+    handleGenericGetArgs(dialog_id, parts, function(time, args) {
+        conn.sendUseActionWithArgs(time, action_id, args);
+    });
+}
+
 function handleGenericGetArgs(dialog_id, parts, cb) {
     var d = new (DIALOG_TYPES[dialog_id])(parts);
     d.onsubmit = function(args) {
@@ -745,6 +794,15 @@ function frame(ac, client_now) {
         return;
     }
 
+    // Gamepad input is ignored while a menu or other dialog has the keyboard.
+    // Report a neutral gamepad instead, so that anything held when the dialog
+    // opened gets released.
+    if (dialog.isVisible()) {
+        asm_client.inputGamepad(0, 0, 0);
+    } else {
+        pollGamepad(asm_client);
+    }
+
     var now = timing.visibleNow();
     var future = now + timing.ping;
     asm_client.renderFrame(now, future);
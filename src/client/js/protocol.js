@@ -0,0 +1,572 @@
+// Generated by src/gen/gen_protocol.py from src/gen/protocol.json.  Do not edit.
+
+var Vec = require('util/vec').Vec;
+var Reader = require('util/wire').Reader;
+var MessageBuilder = require('util/wire').MessageBuilder;
+
+
+exports.PROTOCOL_VERSION = 1;
+
+// EntityHealth and EntityDamage
+exports.CAP_ENTITY_HEALTH = 0x0001;
+// GetUseActionArgs
+exports.CAP_USE_ACTION = 0x0002;
+// TerrainBlocksUpdate
+exports.CAP_TERRAIN_DELTA = 0x0004;
+
+// DEPRECATED                   0x0001;
+// DEPRECATED                   0x0002;
+var OP_PING =                   0x0003;
+var OP_INPUT =                  0x0004;
+var OP_LOGIN =                  0x0005;
+// DEPRECATED                   0x0006;
+var OP_UNSUBSCRIBE_INVENTORY =  0x0007;
+// DEPRECATED                   0x0008;
+var OP_CRAFT_RECIPE =           0x0009;
+var OP_CHAT =                   0x000a;
+var OP_REGISTER =               0x000b;
+var OP_INTERACT =               0x000c;
+var OP_USE_ITEM =               0x000d;
+var OP_USE_ABILITY =            0x000e;
+// DEPRECATED                   0x000f;
+var OP_INTERACT_WITH_ARGS =     0x0010;
+var OP_USE_ITEM_WITH_ARGS =     0x0011;
+var OP_USE_ABILITY_WITH_ARGS =  0x0012;
+var OP_MOVE_ITEM =              0x0013;
+var OP_USE_ACTION =             0x0014;
+var OP_USE_ACTION_WITH_ARGS =   0x0015;
+var OP_HELLO =                  0x0016;
+
+var OP_TERRAIN_CHUNK =          0x8001;
+// DEPRECATED                   0x8002;
+var OP_PONG =                   0x8003;
+var OP_ENTITY_UPDATE =          0x8004;
+var OP_INIT =                   0x8005;
+var OP_KICK_REASON =            0x8006;
+var OP_UNLOAD_CHUNK =           0x8007;
+var OP_OPEN_DIALOG =            0x8008;
+// DEPRECATED                   0x8009;
+var OP_OPEN_CRAFTING =          0x800a;
+var OP_CHAT_UPDATE =            0x800b;
+var OP_ENTITY_APPEAR =          0x800c;
+var OP_ENTITY_GONE =            0x800d;
+var OP_REGISTER_RESULT =        0x800e;
+var OP_STRUCTURE_APPEAR =       0x800f;
+var OP_STRUCTURE_GONE =         0x8010;
+var OP_MAIN_INVENTORY =         0x8011;
+var OP_ABILITY_INVENTORY =      0x8012;
+var OP_PLANE_FLAGS =            0x8013;
+var OP_GET_INTERACT_ARGS =      0x8014;
+var OP_GET_USE_ITEM_ARGS =      0x8015;
+var OP_GET_USE_ABILITY_ARGS =   0x8016;
+var OP_SYNC_STATUS =            0x8017;
+var OP_STRUCTURE_REPLACE =      0x8018;
+var OP_INVENTORY_UPDATE =       0x8019;
+var OP_INVENTORY_APPEAR =       0x801a;
+var OP_INVENTORY_GONE =         0x801b;
+var OP_ENTITY_HEALTH =          0x801c;
+var OP_ENTITY_DAMAGE =          0x801d;
+var OP_GET_USE_ACTION_ARGS =    0x801e;
+var OP_HELLO_RESULT =           0x801f;
+var OP_TERRAIN_BLOCKS_UPDATE =  0x8020;
+
+
+function getLocalPos(r) {
+    return new Vec(r.get16(), r.get16(), r.get16());
+}
+
+function getMotion(r) {
+    return {
+        start_pos: getLocalPos(r),
+        start_time: r.get16(),
+        end_pos: getLocalPos(r),
+        end_time: r.get16(),
+    };
+}
+
+function getExtraArg(r) {
+    var tag = r.get8();
+    switch (tag) {
+        case 0: return r.getI32();
+        case 1: return r.getString();
+        case 2: return r.getList(function() { return getExtraArg(r); });
+        case 3: return r.getMap(function() { return getExtraArg(r); }, function() { return getExtraArg(r); });
+        default:
+            console.assert(false, 'bad ExtraArg tag:', tag);
+            return null;
+    }
+}
+
+function getSlotData(r) {
+    return {
+        tag: r.get8(),
+        count: r.get8(),
+        item_id: r.get16(),
+    };
+}
+
+function getBlockUpdate(r) {
+    return {
+        offset: r.get16(),
+        block_id: r.get16(),
+    };
+}
+
+function putSecret(msg, x) {
+    for (var i = 0; i < 4; ++i) {
+        msg.put32(x[i]);
+    }
+}
+
+function putExtraArg(msg, x) {
+    if (typeof x == 'number' || typeof x == 'boolean') {
+        msg.put8(0);
+        msg.put32(x);
+    } else if (typeof x == 'string') {
+        msg.put8(1);
+        msg.putString(x);
+    } else if (x.constructor == Array) {
+        msg.put8(2);
+        msg.putList(x, function(y) { putExtraArg(msg, y); });
+    } else {
+        msg.put8(3);
+        msg.putMap(x, function(y) { putExtraArg(msg, y); }, function(y) { putExtraArg(msg, y); });
+    }
+}
+
+
+/** Set all message handlers on `conn` to `null`. */
+exports.initHandlers = function(conn) {
+    conn.onTerrainChunk = null;
+    conn.onPong = null;
+    conn.onEntityUpdate = null;
+    conn.onInit = null;
+    conn.onUnloadChunk = null;
+    conn.onOpenDialog = null;
+    conn.onOpenCrafting = null;
+    conn.onChatUpdate = null;
+    conn.onEntityAppear = null;
+    conn.onEntityGone = null;
+    conn.onRegisterResult = null;
+    conn.onStructureAppear = null;
+    conn.onStructureGone = null;
+    conn.onMainInventory = null;
+    conn.onAbilityInventory = null;
+    conn.onPlaneFlags = null;
+    conn.onGetInteractArgs = null;
+    conn.onGetUseItemArgs = null;
+    conn.onGetUseAbilityArgs = null;
+    conn.onSyncStatus = null;
+    conn.onStructureReplace = null;
+    conn.onInventoryUpdate = null;
+    conn.onInventoryAppear = null;
+    conn.onInventoryGone = null;
+    conn.onEntityHealth = null;
+    conn.onEntityDamage = null;
+    conn.onGetUseActionArgs = null;
+    conn.onTerrainBlocksUpdate = null;
+};
+
+/**
+ * Decode the message in `evt` and pass it to the matching handler on `conn`:
+ * `conn.onFoo` for most messages, or `conn._handleFoo` for ones the
+ * `Connection` handles itself.
+ */
+exports.dispatch = function(conn, evt) {
+    var r = new Reader(new DataView(evt.data));
+    var opcode = r.get16();
+
+    switch (opcode) {
+        case OP_TERRAIN_CHUNK:
+            var chunk_idx = r.get16();
+            var data = r.getU16Array();
+            if (conn.onTerrainChunk != null) {
+                conn.onTerrainChunk(chunk_idx, data);
+            }
+            break;
+
+        case OP_PONG:
+            var cookie = r.get16();
+            var server_time = r.get16();
+            if (conn.onPong != null) {
+                conn.onPong(cookie, server_time, evt.timeStamp);
+            }
+            break;
+
+        case OP_ENTITY_UPDATE:
+            var entity_id = r.get32();
+            var motion = getMotion(r);
+            var anim = r.get16();
+            if (conn.onEntityUpdate != null) {
+                conn.onEntityUpdate(entity_id, motion, anim);
+            }
+            break;
+
+        case OP_INIT:
+            var entity_id = r.get32();
+            var now = r.get16();
+            var cycle_base = r.get32();
+            var cycle_ms = r.get32();
+            if (conn.onInit != null) {
+                conn.onInit(entity_id, now, cycle_base, cycle_ms);
+            }
+            break;
+
+        case OP_KICK_REASON:
+            var msg = r.getString();
+            conn._handleKickReason(msg);
+            break;
+
+        case OP_UNLOAD_CHUNK:
+            var chunk_idx = r.get16();
+            if (conn.onUnloadChunk != null) {
+                conn.onUnloadChunk(chunk_idx);
+            }
+            break;
+
+        case OP_OPEN_DIALOG:
+            var dialog_id = r.get32();
+            var params = r.getList(function() { return r.get32(); });
+            if (conn.onOpenDialog != null) {
+                conn.onOpenDialog(dialog_id, params);
+            }
+            break;
+
+        case OP_OPEN_CRAFTING:
+            var station_type = r.get32();
+            var station_id = r.get32();
+            var inventory_id = r.get32();
+            if (conn.onOpenCrafting != null) {
+                conn.onOpenCrafting(station_type, station_id, inventory_id);
+            }
+            break;
+
+        case OP_CHAT_UPDATE:
+            var msg = r.getString();
+            if (conn.onChatUpdate != null) {
+                conn.onChatUpdate(msg);
+            }
+            break;
+
+        case OP_ENTITY_APPEAR:
+            var entity_id = r.get32();
+            var appearance = r.get32();
+            var collision_groups = r.get8();
+            var name = r.getString();
+            if (conn.onEntityAppear != null) {
+                conn.onEntityAppear(entity_id, appearance, name, collision_groups);
+            }
+            break;
+
+        case OP_ENTITY_GONE:
+            var entity_id = r.get32();
+            var time = r.get16();
+            if (conn.onEntityGone != null) {
+                conn.onEntityGone(entity_id, time);
+            }
+            break;
+
+        case OP_REGISTER_RESULT:
+            var code = r.get32();
+            var msg = r.getString();
+            if (conn.onRegisterResult != null) {
+                conn.onRegisterResult(code, msg);
+            }
+            break;
+
+        case OP_STRUCTURE_APPEAR:
+            var structure_id = r.get32();
+            var template_id = r.get32();
+            var pos = getLocalPos(r);
+            if (conn.onStructureAppear != null) {
+                conn.onStructureAppear(structure_id, template_id, pos);
+            }
+            break;
+
+        case OP_STRUCTURE_GONE:
+            var structure_id = r.get32();
+            if (conn.onStructureGone != null) {
+                conn.onStructureGone(structure_id);
+            }
+            break;
+
+        case OP_MAIN_INVENTORY:
+            var inventory_id = r.get32();
+            if (conn.onMainInventory != null) {
+                conn.onMainInventory(inventory_id);
+            }
+            break;
+
+        case OP_ABILITY_INVENTORY:
+            var inventory_id = r.get32();
+            if (conn.onAbilityInventory != null) {
+                conn.onAbilityInventory(inventory_id);
+            }
+            break;
+
+        case OP_PLANE_FLAGS:
+            var flags = r.get32();
+            if (conn.onPlaneFlags != null) {
+                conn.onPlaneFlags(flags);
+            }
+            break;
+
+        case OP_GET_INTERACT_ARGS:
+            var dialog_id = r.get32();
+            var args = getExtraArg(r);
+            if (conn.onGetInteractArgs != null) {
+                conn.onGetInteractArgs(dialog_id, args);
+            }
+            break;
+
+        case OP_GET_USE_ITEM_ARGS:
+            var item_id = r.get16();
+            var dialog_id = r.get32();
+            var args = getExtraArg(r);
+            if (conn.onGetUseItemArgs != null) {
+                conn.onGetUseItemArgs(item_id, dialog_id, args);
+            }
+            break;
+
+        case OP_GET_USE_ABILITY_ARGS:
+            var item_id = r.get16();
+            var dialog_id = r.get32();
+            var args = getExtraArg(r);
+            if (conn.onGetUseAbilityArgs != null) {
+                conn.onGetUseAbilityArgs(item_id, dialog_id, args);
+            }
+            break;
+
+        case OP_SYNC_STATUS:
+            var kind = r.get8();
+            if (conn.onSyncStatus != null) {
+                conn.onSyncStatus(kind);
+            }
+            break;
+
+        case OP_STRUCTURE_REPLACE:
+            var structure_id = r.get32();
+            var template_id = r.get32();
+            if (conn.onStructureReplace != null) {
+                conn.onStructureReplace(structure_id, template_id);
+            }
+            break;
+
+        case OP_INVENTORY_UPDATE:
+            var inventory_id = r.get32();
+            var slot_idx = r.get8();
+            var slot = getSlotData(r);
+            if (conn.onInventoryUpdate != null) {
+                conn.onInventoryUpdate(inventory_id, slot_idx, slot);
+            }
+            break;
+
+        case OP_INVENTORY_APPEAR:
+            var inventory_id = r.get32();
+            var slots = r.getList(function() { return getSlotData(r); });
+            if (conn.onInventoryAppear != null) {
+                conn.onInventoryAppear(inventory_id, slots);
+            }
+            break;
+
+        case OP_INVENTORY_GONE:
+            var inventory_id = r.get32();
+            if (conn.onInventoryGone != null) {
+                conn.onInventoryGone(inventory_id);
+            }
+            break;
+
+        case OP_ENTITY_HEALTH:
+            var entity_id = r.get32();
+            var hp = r.get16();
+            var max = r.get16();
+            if (conn.onEntityHealth != null) {
+                conn.onEntityHealth(entity_id, hp, max);
+            }
+            break;
+
+        case OP_ENTITY_DAMAGE:
+            var entity_id = r.get32();
+            var amount = r.get16();
+            if (conn.onEntityDamage != null) {
+                conn.onEntityDamage(entity_id, amount);
+            }
+            break;
+
+        case OP_GET_USE_ACTION_ARGS:
+            var action_id = r.get16();
+            var dialog_id = r.get32();
+            var args = getExtraArg(r);
+            if (conn.onGetUseActionArgs != null) {
+                conn.onGetUseActionArgs(action_id, dialog_id, args);
+            }
+            break;
+
+        case OP_HELLO_RESULT:
+            var version = r.get32();
+            var caps = r.get32();
+            conn._handleHelloResult(version, caps);
+            break;
+
+        case OP_TERRAIN_BLOCKS_UPDATE:
+            var chunk_idx = r.get16();
+            var blocks = r.getList(function() { return getBlockUpdate(r); });
+            if (conn.onTerrainBlocksUpdate != null) {
+                conn.onTerrainBlocksUpdate(chunk_idx, blocks);
+            }
+            break;
+
+        default:
+            console.assert(false, 'received invalid opcode:', opcode.toString(16));
+            return;
+    }
+
+    console.assert(r.done(), 'received message with bad length');
+};
+
+
+var MESSAGE_BUILDER = new MessageBuilder(8192);
+
+/** Add a `sendFoo` method for each request to `proto`, which must have a `socket`. */
+exports.installSenders = function(proto) {
+    proto.sendPing = function(cookie) {
+        var msg = MESSAGE_BUILDER.reset();
+        msg.put16(OP_PING);
+        msg.put16(cookie);
+        this.socket.send(msg.done());
+    };
+
+    proto.sendInput = function(time, input) {
+        var msg = MESSAGE_BUILDER.reset();
+        msg.put16(OP_INPUT);
+        msg.put16(time);
+        msg.put16(input);
+        this.socket.send(msg.done());
+    };
+
+    proto.sendLogin = function(name, secret) {
+        var msg = MESSAGE_BUILDER.reset();
+        msg.put16(OP_LOGIN);
+        putSecret(msg, secret);
+        msg.putString(name);
+        this.socket.send(msg.done());
+    };
+
+    proto.sendUnsubscribeInventory = function(inventory_id) {
+        var msg = MESSAGE_BUILDER.reset();
+        msg.put16(OP_UNSUBSCRIBE_INVENTORY);
+        msg.put32(inventory_id);
+        this.socket.send(msg.done());
+    };
+
+    proto.sendCraftRecipe = function(station_id, inventory_id, recipe_id, count) {
+        var msg = MESSAGE_BUILDER.reset();
+        msg.put16(OP_CRAFT_RECIPE);
+        msg.put32(station_id);
+        msg.put32(inventory_id);
+        msg.put16(recipe_id);
+        msg.put16(count);
+        this.socket.send(msg.done());
+    };
+
+    proto.sendChat = function(text) {
+        var msg = MESSAGE_BUILDER.reset();
+        msg.put16(OP_CHAT);
+        msg.putString(text);
+        this.socket.send(msg.done());
+    };
+
+    proto.sendRegister = function(name, secret, appearance) {
+        var msg = MESSAGE_BUILDER.reset();
+        msg.put16(OP_REGISTER);
+        putSecret(msg, secret);
+        msg.put32(appearance);
+        msg.putString(name);
+        this.socket.send(msg.done());
+    };
+
+    proto.sendInteract = function(time) {
+        var msg = MESSAGE_BUILDER.reset();
+        msg.put16(OP_INTERACT);
+        msg.put16(time);
+        this.socket.send(msg.done());
+    };
+
+    proto.sendUseItem = function(time, item_id) {
+        var msg = MESSAGE_BUILDER.reset();
+        msg.put16(OP_USE_ITEM);
+        msg.put16(time);
+        msg.put16(item_id);
+        this.socket.send(msg.done());
+    };
+
+    proto.sendUseAbility = function(time, item_id) {
+        var msg = MESSAGE_BUILDER.reset();
+        msg.put16(OP_USE_ABILITY);
+        msg.put16(time);
+        msg.put16(item_id);
+        this.socket.send(msg.done());
+    };
+
+    proto.sendInteractWithArgs = function(time, args) {
+        var msg = MESSAGE_BUILDER.reset();
+        msg.put16(OP_INTERACT_WITH_ARGS);
+        msg.put16(time);
+        putExtraArg(msg, args);
+        this.socket.send(msg.done());
+    };
+
+    proto.sendUseItemWithArgs = function(time, item_id, args) {
+        var msg = MESSAGE_BUILDER.reset();
+        msg.put16(OP_USE_ITEM_WITH_ARGS);
+        msg.put16(time);
+        msg.put16(item_id);
+        putExtraArg(msg, args);
+        this.socket.send(msg.done());
+    };
+
+    proto.sendUseAbilityWithArgs = function(time, item_id, args) {
+        var msg = MESSAGE_BUILDER.reset();
+        msg.put16(OP_USE_ABILITY_WITH_ARGS);
+        msg.put16(time);
+        msg.put16(item_id);
+        putExtraArg(msg, args);
+        this.socket.send(msg.done());
+    };
+
+    proto.sendMoveItem = function(from_inventory, from_slot, to_inventory, to_slot, count) {
+        var msg = MESSAGE_BUILDER.reset();
+        msg.put16(OP_MOVE_ITEM);
+        msg.put32(from_inventory);
+        msg.put8(from_slot);
+        msg.put32(to_inventory);
+        msg.put8(to_slot);
+        msg.put8(count);
+        this.socket.send(msg.done());
+    };
+
+    proto.sendUseAction = function(time, action_id) {
+        var msg = MESSAGE_BUILDER.reset();
+        msg.put16(OP_USE_ACTION);
+        msg.put16(time);
+        msg.put16(action_id);
+        this.socket.send(msg.done());
+    };
+
+    proto.sendUseActionWithArgs = function(time, action_id, args) {
+        var msg = MESSAGE_BUILDER.reset();
+        msg.put16(OP_USE_ACTION_WITH_ARGS);
+        msg.put16(time);
+        msg.put16(action_id);
+        putExtraArg(msg, args);
+        this.socket.send(msg.done());
+    };
+
+    proto.sendHello = function(version, caps) {
+        var msg = MESSAGE_BUILDER.reset();
+        msg.put16(OP_HELLO);
+        msg.put32(version);
+        msg.put32(caps);
+        this.socket.send(msg.done());
+    };
+};
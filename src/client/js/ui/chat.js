@@ -18,6 +18,9 @@ function ChatWindow() {
     }
 
     this.count = 0;
+
+    // Called with the slot number and action name for `/bind`.
+    this.onBind = null;
 }
 exports.ChatWindow = ChatWindow;
 
@@ -132,6 +135,10 @@ ChatWindow.prototype.finishTyping = function(keyboard, conn, send) {
             } else if (cmd == 'unignore') {
                 this.removeIgnore(arg);
                 handled = true;
+            } else if (cmd == 'bind' && this.onBind != null) {
+                var parts = arg.split(' ');
+                this.onBind(parseInt(parts[0]) - 1, parts[1]);
+                handled = true;
             }
         }
     }
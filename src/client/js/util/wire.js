@@ -0,0 +1,132 @@
+var decodeUtf8 = require('util/misc').decodeUtf8;
+
+// Low-level readers and writers for the wire format.  The per-message codecs
+// built on these are generated into protocol.js.
+
+
+/** @constructor */
+function Reader(view) {
+    this._view = view;
+    this._offset = 0;
+}
+exports.Reader = Reader;
+
+Reader.prototype.get8 = function() {
+    var result = this._view.getUint8(this._offset);
+    this._offset += 1;
+    return result;
+};
+
+Reader.prototype.get16 = function() {
+    var result = this._view.getUint16(this._offset, true);
+    this._offset += 2;
+    return result;
+};
+
+Reader.prototype.get32 = function() {
+    var result = this._view.getUint32(this._offset, true);
+    this._offset += 4;
+    return result;
+};
+
+Reader.prototype.getI32 = function() {
+    var result = this._view.getInt32(this._offset, true);
+    this._offset += 4;
+    return result;
+};
+
+Reader.prototype.getString = function() {
+    var len = this.get16();
+    var result = decodeUtf8(new Uint8Array(this._view.buffer, this._offset, len));
+    this._offset += len;
+    return result;
+};
+
+Reader.prototype.getList = function(f) {
+    var len = this.get16();
+    var result = new Array(len);
+    for (var i = 0; i < len; ++i) {
+        result[i] = f();
+    }
+    return result;
+};
+
+Reader.prototype.getMap = function(key_f, value_f) {
+    var len = this.get16();
+    var result = new Object();
+    for (var i = 0; i < len; ++i) {
+        var k = key_f();
+        result[k] = value_f();
+    }
+    return result;
+};
+
+// Returns a view into the message buffer instead of copying.
+// TODO: byte order in the Uint16Array will be wrong on big-endian systems.
+Reader.prototype.getU16Array = function() {
+    var len = this.get16();
+    var result = new Uint16Array(this._view.buffer, this._offset, len);
+    this._offset += 2 * len;
+    return result;
+};
+
+Reader.prototype.done = function() {
+    return this._offset == this._view.buffer.byteLength;
+};
+
+
+/** @constructor */
+function MessageBuilder(length) {
+    this._buf = new DataView(new ArrayBuffer(length));
+    this._offset = 0;
+}
+exports.MessageBuilder = MessageBuilder;
+
+MessageBuilder.prototype.put8 = function(n) {
+    this._buf.setUint8(this._offset, n);
+    this._offset += 1;
+};
+
+MessageBuilder.prototype.put16 = function(n) {
+    this._buf.setUint16(this._offset, n, true);
+    this._offset += 2;
+};
+
+MessageBuilder.prototype.put32 = function(n) {
+    this._buf.setUint32(this._offset, n, true);
+    this._offset += 4;
+};
+
+MessageBuilder.prototype.putString = function(s) {
+    var utf8 = unescape(encodeURIComponent(s));
+    this.put16(utf8.length);
+    for (var i = 0; i < utf8.length; ++i) {
+        this.put8(utf8.charCodeAt(i));
+    }
+};
+
+MessageBuilder.prototype.putList = function(a, f) {
+    this.put16(a.length);
+    for (var i = 0; i < a.length; ++i) {
+        f(a[i]);
+    }
+};
+
+MessageBuilder.prototype.putMap = function(m, key_f, value_f) {
+    var props = Object.getOwnPropertyNames(m);
+    this.put16(props.length);
+    for (var i = 0; i < props.length; ++i) {
+        key_f(props[i]);
+        value_f(m[props[i]]);
+    }
+};
+
+MessageBuilder.prototype.done = function() {
+    var buf = new Uint8Array(this._buf.buffer, 0, this._offset);
+    return buf;
+};
+
+MessageBuilder.prototype.reset = function() {
+    this._offset = 0;
+    return this;
+};
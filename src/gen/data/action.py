@@ -0,0 +1,22 @@
+class ActionDef(object):
+    def __init__(self, name, ui_name):
+        self.name = name
+        self.ui_name = ui_name
+
+        self.id = None
+
+
+def build_client_json(actions):
+    def convert(a):
+        return {
+                'name': a.name,
+                'ui_name': a.ui_name,
+                }
+    return list(convert(a) for a in actions)
+
+def build_server_json(actions):
+    def convert(a):
+        return {
+                'name': a.name,
+                }
+    return list(convert(a) for a in actions)
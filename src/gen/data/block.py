@@ -18,6 +18,11 @@ class BlockDef(object):
         self.light_color = None
         self.light_radius = None
 
+        # Movement properties.  `speed` is a percentage of normal walking speed.
+        self.speed = 100
+        self.liquid = False
+        self.slippery = False
+
         self.id = None
         self.tile_ids = None
 
@@ -25,6 +30,11 @@ class BlockDef(object):
         self.light_color = color
         self.light_radius = radius
 
+    def set_surface(self, speed=100, liquid=False, slippery=False):
+        self.speed = speed
+        self.liquid = liquid
+        self.slippery = slippery
+
 
 def build_sheet(blocks):
     """Build a sprite sheet containing all the tile images for the provided
@@ -63,15 +73,28 @@ def build_client_json(blocks):
                 light_g=green,
                 light_b=blue,
                 light_radius=b.light_radius)
+        if b.speed != 100:
+            dct['speed'] = b.speed
+        # NB: keep these flag values in sync with libphysics surface_flags
+        flags = (1 if b.liquid else 0) | (2 if b.slippery else 0)
+        if flags != 0:
+            dct['surface_flags'] = flags
         return dct
 
     return list(convert(b) for b in blocks)
 
 def build_server_json(blocks):
     def convert(b):
-        return {
+        dct = {
                 'name': b.name,
                 'shape': b.shape,
                 }
+        if b.speed != 100:
+            dct['speed'] = b.speed
+        if b.liquid:
+            dct['liquid'] = True
+        if b.slippery:
+            dct['slippery'] = True
+        return dct
 
     return list(convert(b) for b in blocks)
@@ -5,11 +5,12 @@ from .recipe import RecipeBuilder
 from .sprite import SpriteBuilder
 from .loot_table import LootTableBuilder
 from .extra import ExtraBuilder
+from .action import ActionBuilder
 
 __all__ = (
         'INSTANCES',
         'BLOCK', 'STRUCTURE', 'ITEM', 'RECIPE', 'SPRITE', 'LOOT_TABLE',
-        'EXTRA',
+        'EXTRA', 'ACTION',
         )
 
 
@@ -21,6 +22,7 @@ INSTANCES = dict(
         sprite = SpriteBuilder(),
         loot_table = LootTableBuilder(),
         extra = ExtraBuilder(),
+        action = ActionBuilder(),
         )
 
 BLOCK = INSTANCES['block']
@@ -30,3 +32,4 @@ RECIPE = INSTANCES['recipe']
 SPRITE = INSTANCES['sprite']
 LOOT_TABLE = INSTANCES['loot_table']
 EXTRA = INSTANCES['extra']
+ACTION = INSTANCES['action']
@@ -0,0 +1,17 @@
+from outpost_data.core.builder2.base import *
+from outpost_data.core.action import ActionDef
+
+
+class ActionPrototype(PrototypeBase):
+    KIND = 'action'
+    FIELDS = ('display_name',)
+
+    def instantiate(self):
+        self.name = self.require('name') or '_%x' % id(self)
+        display_name = self.require('display_name', default=self.name)
+        return ActionDef(self.name, display_name)
+
+class ActionBuilder(BuilderBase):
+    PROTO_CLASS = ActionPrototype
+
+    display_name = dict_modifier('display_name')
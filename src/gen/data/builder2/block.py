@@ -2,12 +2,13 @@ from PIL import Image
 
 from outpost_data.core.builder2.base import *
 from outpost_data.core.consts import *
+from outpost_data.core import util
 from outpost_data.core.block import BlockDef
 
 
 class BlockPrototype(PrototypeBase):
     KIND = 'block'
-    FIELDS = ('shape', 'top', 'bottom', 'front', 'back')
+    FIELDS = ('shape', 'top', 'bottom', 'front', 'back', 'speed', 'liquid', 'slippery')
 
     def instantiate(self):
         name = self.require('name') or '_%x' % id(self)
@@ -18,7 +19,18 @@ class BlockPrototype(PrototypeBase):
             if x is not None:
                 tiles[side] = raw_image(x)
 
-        return BlockDef(name, shape, tiles)
+        speed = self.speed if self.speed is not None else 100
+        if not 1 <= speed <= 255:
+            util.err('%s %r: speed %r out of range (1-255)' %
+                    (self.KIND, self.name, speed))
+            speed = 100
+
+        b = BlockDef(name, shape, tiles)
+        b.set_surface(
+                speed,
+                bool(self.liquid),
+                bool(self.slippery))
+        return b
 
 class BlockBuilder(BuilderBase):
     PROTO_CLASS = BlockPrototype
@@ -28,3 +40,6 @@ class BlockBuilder(BuilderBase):
     bottom = dict_modifier('bottom')
     front = dict_modifier('front')
     back = dict_modifier('back')
+    speed = dict_modifier('speed')
+    liquid = dict_modifier('liquid')
+    slippery = dict_modifier('slippery')
@@ -2,9 +2,11 @@ from collections import namedtuple
 import json
 import os
 
+import PIL.Image
 
 from outpost_data.core import boxpack, builder2, files, image2, loader, util
 from outpost_data.core import structure, block, item, recipe, sprite, loot_table, extra
+from outpost_data.core import action
 from outpost_data.core.loader import TimeIt
 
 
@@ -16,6 +18,7 @@ Defs = namedtuple('Defs', (
     'sprites',
     'loot_tables',
     'extras',
+    'actions',
 ))
 
 IdMaps = namedtuple('IdMaps', (
@@ -24,6 +27,7 @@ IdMaps = namedtuple('IdMaps', (
     'items',
     'recipes',
     'sprites',
+    'actions',
 ))
 
 def collect_defs():
@@ -35,6 +39,7 @@ def collect_defs():
             builder2.SPRITE.all(),
             builder2.LOOT_TABLE.all(),
             builder2.EXTRA.all(),
+            builder2.ACTION.all(),
             )
 
 def postprocess(defs):
@@ -44,6 +49,7 @@ def postprocess(defs):
         util.assign_ids(defs.items, ['none']),
         util.assign_ids(defs.recipes),
         util.assign_ids(defs.sprites),
+        util.assign_ids(defs.actions, ['none']),
     )
 
     recipe.resolve_item_ids(defs.recipes, id_maps.items)
@@ -124,9 +130,16 @@ def emit_recipes(output_dir, recipes):
 def emit_sprites(output_dir, sprites):
     # Build sheets first, to populate the offset fields
     packer = boxpack.ImagePacker((2048, 2048), res=16)
-    # Place the name font first so it always sits at (0,0) on sheet 0
+    # Place the name font first so it always sits at (0,0) on sheet 0.  A
+    # small block of solid white goes just below it, for drawing health bars.
+    # NB: keep this in sync with libclient/graphics/entity.rs  emit_solid
     # TODO: bit of a hack
-    font_off = packer.place([image2.Image.open(os.path.join(output_dir, 'fonts/name.png'))])
+    font_img = PIL.Image.open(os.path.join(output_dir, 'fonts/name.png')).convert('RGBA')
+    fw, fh = font_img.size
+    font_sheet = PIL.Image.new('RGBA', (max(fw, 4), fh + 4))
+    font_sheet.paste(font_img, (0, 0))
+    font_sheet.paste((255, 255, 255, 255), (0, fh, 4, fh + 4))
+    font_off = packer.place([image2.Image.from_raw(font_sheet)])
     assert font_off == [(0, (0, 0))]
     sprite.pack_images(sprites, packer)
     anims, layers, graphics = sprite.collect_defs(sprites)
@@ -164,6 +177,13 @@ def emit_loot_tables(output_dir, loot_tables):
     write_json(output_dir, 'loot_tables_server.json',
             loot_table.build_server_json(loot_tables))
 
+def emit_actions(output_dir, actions):
+    write_json(output_dir, 'actions_server.json',
+            action.build_server_json(actions))
+
+    write_json(output_dir, 'actions_client.json',
+            action.build_client_json(actions))
+
 def emit_extras(output_dir, extras):
     write_json(output_dir, 'extras_client.json',
             extra.build_client_json(extras))
@@ -184,11 +204,12 @@ def generate(output_dir):
     time('sprites', emit_sprites, output_dir, defs.sprites)
     time('loot_tables', emit_loot_tables, output_dir, defs.loot_tables)
     time('extras', emit_extras, output_dir, defs.extras)
+    time('actions', emit_actions, output_dir, defs.actions)
 
     print('%d structures, %d blocks, %d items, %d recipes' %
             (len(defs.structures), len(defs.blocks), len(defs.items), len(defs.recipes)))
-    print('%d sprites, %d loot tables, %d extras' %
-            (len(defs.sprites), len(defs.loot_tables), len(defs.extras)))
+    print('%d sprites, %d loot tables, %d extras, %d actions' %
+            (len(defs.sprites), len(defs.loot_tables), len(defs.extras), len(defs.actions)))
 
     with open(os.path.join(output_dir, 'stamp'), 'w') as f:
         pass
@@ -185,6 +185,8 @@ class BinaryDefs:
             Field('light_b',    'B', 10,  0),
             Field('shape',      'B', 11,  0),
             Field('light_radius', 'H', 12,  0),
+            Field('speed',      'B', 14, 100),
+            Field('surface_flags', 'B', 15, 0),
             ))
 
         self.convert_file(b'Blocks\0\0', 'blocks_client.json', c)
@@ -200,6 +202,17 @@ class BinaryDefs:
         self.convert_file(b'Items\0\0\0', 'items_client.json', c,
                 adjust=self.intern_strings)
 
+    def convert_actions(self):
+        c = Converter(16, (
+            Field('name_off',       'I',  0,  0),
+            Field('name_len',       'I',  4,  0),
+            Field('ui_name_off',    'I',  8,  0),
+            Field('ui_name_len',    'I', 12,  0),
+            ))
+
+        self.convert_file(b'Actions\0', 'actions_client.json', c,
+                adjust=self.intern_strings)
+
     def convert_structures(self):
         c = Converter(20, (
             Field('size',           'BBB',  0),
@@ -320,6 +333,7 @@ def main():
 
     bd.convert_blocks()
     bd.convert_items()
+    bd.convert_actions()
     bd.convert_structures()
     bd.convert_sprites()
     bd.convert_extras()
@@ -0,0 +1,921 @@
+'''Generate the client/server protocol code from `protocol.json`.
+
+The schema lists every message with its opcode and fields, plus the named
+types those fields use.  From it this script produces:
+
+ * Rust: the `Request` and `Response` enums, their `read_from`/`write_to`
+   methods, the `op` opcode constants, the named struct/union types and their
+   `wire::ReadFrom`/`WriteTo` impls, and sample values of every message for
+   the round-trip tests.  The output is `include!`d by `src/server/msg.rs`.
+ * Javascript: a module with the opcode and capability constants, a decoder
+   that dispatches server messages to a `Connection`'s `on*` handlers, and
+   the `send*` methods for client requests.
+ * C++: the control opcodes used by the wrapper.
+
+Fields are listed in the order used by the Rust enum variants.  On the wire,
+`String` fields always come after all the other fields of a message.
+
+Named types are one of:
+
+ * `"u16"` (a string): a Rust type alias for a primitive type.
+ * `{"newtype": "u32"}`: a Rust newtype around a primitive type.
+ * `{"array": "u32", "len": 4}`: a fixed-size array.
+ * `{"tuple": [[name, type], ...]}`: a Rust tuple.  Javascript sees an object
+   with the given field names, or a `Vec` if `"js": "Vec"` is set.
+ * `{"struct": [[name, type], ...]}`: a Rust struct, which is generated here.
+ * `{"union": tag_name, "variants": [...]}`: a Rust enum with a `u8` tag,
+   which is generated here along with the enum for the tag.  `"js"` on each
+   variant says what kind of Javascript value encodes as that variant.
+ * `{"extern": other}`: a Rust type defined by hand in `msg.rs`, which is
+   encoded the same way as the type `other`.
+
+The generated files are checked in.  After editing the schema, regenerate them
+with:
+
+    python3 src/gen/gen_protocol.py src/gen/protocol.json \\
+        --rust-out src/server/msg_gen.rs --js-out src/client/js/protocol.js \\
+        --cxx-out src/wrapper/opcode.hpp
+
+The build runs the same command with `--check`, which fails if any of them
+are out of date instead of writing them.
+
+Messages may set `"rust"` to give the Rust value to use in place of the usual
+variant (for opcodes that share a variant), `"js_internal"` to deliver the
+message to `Connection._handleFoo` instead of `onFoo`, and `"js_extra_args"`
+to pass extra expressions to the Javascript handler.
+'''
+import argparse
+import json
+import re
+import sys
+
+
+HEADER = 'Generated by src/gen/gen_protocol.py from src/gen/protocol.json.  Do not edit.'
+
+PRIMS = {
+        # name: (js getter, js putter)
+        'u8': ('get8', 'put8'),
+        'u16': ('get16', 'put16'),
+        'u32': ('get32', 'put32'),
+        'i32': ('getI32', 'put32'),
+        'bool': ('get8', 'put8'),
+        }
+
+
+def snake_upper(name):
+    return re.sub('([a-z0-9])([A-Z])', r'\1_\2', name).upper()
+
+
+# Types
+
+class Type:
+    def __init__(self, name, args=()):
+        self.name = name
+        self.args = tuple(args)
+
+    def __eq__(self, other):
+        return self.name == other.name and self.args == other.args
+
+    def __hash__(self):
+        return hash((self.name, self.args))
+
+def split_args(s):
+    parts = []
+    depth = 0
+    cur = ''
+    for c in s:
+        if c == ',' and depth == 0:
+            parts.append(cur)
+            cur = ''
+            continue
+        if c == '<':
+            depth += 1
+        elif c == '>':
+            depth -= 1
+        cur += c
+    parts.append(cur)
+    return parts
+
+def parse_type(s):
+    s = s.strip()
+    if '<' not in s:
+        return Type(s)
+    assert s.endswith('>'), 'bad type: %r' % s
+    base, _, inner = s.partition('<')
+    return Type(base.strip(), (parse_type(a) for a in split_args(inner[:-1])))
+
+
+class Schema:
+    def __init__(self, j):
+        self.j = j
+        self.types = j['types']
+
+        def msgs(key):
+            return [Message(m) for m in j.get(key, ())]
+        self.requests = msgs('requests')
+        self.responses = msgs('responses')
+        self.control_requests = msgs('control_requests')
+        self.control_responses = msgs('control_responses')
+
+        self.extra_request_variants = j.get('control_request_variants', {})
+
+    def kind(self, t):
+        '''Return the kind of type `t` and its definition.'''
+        if t.name in PRIMS:
+            return 'prim', None
+        if t.name == 'String':
+            return 'string', None
+        if t.name in ('Vec', 'HashMap'):
+            return t.name.lower(), None
+
+        d = self.types[t.name]
+        if isinstance(d, str):
+            return 'alias', parse_type(d)
+        for k in ('newtype', 'array', 'tuple', 'struct', 'union', 'extern'):
+            if k in d:
+                return k, d
+        raise ValueError('bad definition for type %s' % t.name)
+
+    def resolve(self, t):
+        '''Strip off aliases and newtypes, leaving the type used for encoding.'''
+        while True:
+            k, d = self.kind(t)
+            if k == 'alias':
+                t = d
+            elif k == 'newtype':
+                t = parse_type(d['newtype'])
+            elif k == 'extern':
+                t = parse_type(d['extern'])
+            else:
+                return t
+
+    def fields(self, t):
+        k, d = self.kind(t)
+        return [(n, parse_type(ft)) for n, ft in d[k]]
+
+    def variants(self, t):
+        _, d = self.kind(t)
+        return [(v['name'], v['tag'], parse_type(v['type']), v['js']) for v in d['variants']]
+
+    def walk(self, types):
+        '''Yield every named compound type reachable from `types`, each only once.'''
+        seen = set()
+        def go(t):
+            if t in seen:
+                return
+            seen.add(t)
+            for a in t.args:
+                yield from go(a)
+            if t.name in PRIMS or t.name in ('String', 'Vec', 'HashMap'):
+                return
+            k, d = self.kind(t)
+            if k in ('alias', 'newtype'):
+                return
+            if k == 'extern':
+                yield from go(parse_type(d['extern']))
+                return
+            if k == 'array':
+                yield from go(parse_type(d['array']))
+            elif k in ('tuple', 'struct'):
+                for _, ft in self.fields(t):
+                    yield from go(ft)
+            elif k == 'union':
+                for _, _, vt, _ in self.variants(t):
+                    yield from go(vt)
+            yield t
+        for t in types:
+            yield from go(t)
+
+
+class Message:
+    def __init__(self, j):
+        self.name = j['name']
+        self.opcode = int(j['opcode'], 16)
+        self.fields = [(n, parse_type(t)) for n, t in j.get('fields', ())]
+        self.rust = j.get('rust')
+        self.js_internal = j.get('js_internal', False)
+        self.js_extra_args = j.get('js_extra_args', [])
+
+        assert len(self.fields) <= 5, \
+                '%s: too many fields for wire::ReadFrom tuple impls' % self.name
+
+    def wire_fields(self):
+        return [f for f in self.fields if f[1].name != 'String'] + \
+                [f for f in self.fields if f[1].name == 'String']
+
+    def field_types(self):
+        return [t for _, t in self.fields]
+
+
+# Rust
+
+def rust_type(s, t):
+    if t.name == 'Vec':
+        return 'Vec<%s>' % rust_type(s, t.args[0])
+    if t.name == 'HashMap':
+        return 'HashMap<%s, %s>' % (rust_type(s, t.args[0]), rust_type(s, t.args[1]))
+    if t.name in PRIMS or t.name == 'String':
+        return t.name
+
+    k, d = s.kind(t)
+    if k == 'array':
+        return '[%s; %d]' % (rust_type(s, parse_type(d['array'])), d['len'])
+    if k == 'tuple':
+        return '(%s)' % ', '.join(rust_type(s, ft) for _, ft in s.fields(t))
+    return t.name
+
+def rust_tuple_type(s, ts):
+    if len(ts) == 1:
+        return rust_type(s, ts[0])
+    return '(%s)' % ', '.join(rust_type(s, t) for t in ts)
+
+def rust_pattern(names):
+    if len(names) == 1:
+        return names[0]
+    return '(%s)' % ', '.join(names)
+
+
+class Counter:
+    def __init__(self):
+        self.n = 0
+
+    def next(self):
+        self.n += 1
+        return self.n
+
+def rust_sample(s, t, ctr, depth=0):
+    if t.name in ('u8', 'u16', 'u32'):
+        return str(ctr.next())
+    if t.name == 'i32':
+        return '-%d' % ctr.next()
+    if t.name == 'bool':
+        return 'true'
+    if t.name == 'String':
+        return '"str%d".to_owned()' % ctr.next()
+    if t.name == 'Vec':
+        a = t.args[0]
+        return 'vec![%s, %s]' % (rust_sample(s, a, ctr, depth), rust_sample(s, a, ctr, depth))
+    if t.name == 'HashMap':
+        k, v = t.args
+        return '{ let mut m = HashMap::new(); m.insert(%s, %s); m }' % (
+                rust_sample(s, k, ctr, depth), rust_sample(s, v, ctr, depth))
+
+    k, d = s.kind(t)
+    if k == 'alias':
+        return rust_sample(s, d, ctr, depth)
+    if k == 'newtype':
+        return '%s(%d)' % (t.name, ctr.next())
+    if k == 'array':
+        elem = parse_type(d['array'])
+        return '[%s]' % ', '.join(rust_sample(s, elem, ctr, depth) for _ in range(d['len']))
+    if k == 'tuple':
+        return '(%s)' % ', '.join(rust_sample(s, ft, ctr, depth) for _, ft in s.fields(t))
+    if k == 'struct':
+        return '%s { %s }' % (t.name, ', '.join('%s: %s' % (n, rust_sample(s, ft, ctr, depth))
+                                                for n, ft in s.fields(t)))
+    if k == 'extern':
+        return d['sample']
+    if k == 'union':
+        variants = s.variants(t)
+        def variant_sample(v):
+            name, _, vt, _ = v
+            return '%s::%s(%s)' % (t.name, name, rust_sample(s, vt, ctr, depth + 1))
+        # At the top level, build a list holding one of each other variant, so every variant
+        # gets exercised.  Nested values are just the first variant.
+        list_type = Type('Vec', (t,))
+        lists = [v for v in variants if v[2] == list_type]
+        if depth > 0 or len(lists) == 0:
+            return variant_sample(variants[0])
+        items = [variant_sample(v) for v in variants if v[2] != list_type]
+        return '%s::%s(vec![%s])' % (t.name, lists[0][0], ', '.join(items))
+    raise ValueError('no sample for %s' % t.name)
+
+def rust_message_sample(s, enum, m):
+    if m.rust is not None:
+        return '%s::%s' % (enum, m.rust)
+    if len(m.fields) == 0:
+        return '%s::%s' % (enum, m.name)
+    ctr = Counter()
+    return '%s::%s(%s)' % (enum, m.name,
+                           ', '.join(rust_sample(s, t, ctr) for t in m.field_types()))
+
+
+def rust_read_arm(s, m, out):
+    value = m.rust or m.name
+    if len(m.fields) == 0:
+        out.append('            op::%s => {' % m.name)
+        out.append('                %s' % value)
+        out.append('            },')
+        return
+
+    wire = m.wire_fields()
+    out.append('            op::%s => {' % m.name)
+    out.append('                let %s: %s = try!(wr.read());' % (
+        rust_pattern([n for n, _ in wire]), rust_tuple_type(s, [t for _, t in wire])))
+    out.append('                %s(%s)' % (value, ', '.join(n for n, _ in m.fields)))
+    out.append('            },')
+
+def rust_write_arm(s, m, out):
+    if m.rust is not None:
+        pat = m.rust
+    elif len(m.fields) == 0:
+        pat = m.name
+    else:
+        pat = '%s(%s)' % (m.name, ', '.join('ref %s' % n for n, _ in m.fields))
+
+    if len(m.fields) == 0:
+        out.append('            %s =>' % pat)
+        out.append('                ww.write_msg(id, op::%s),' % m.name)
+    else:
+        out.append('            %s =>' % pat)
+        out.append('                ww.write_msg(id, (op::%s, %s)),' % (
+            m.name, ', '.join(n for n, _ in m.wire_fields())))
+
+def rust_size_arm(s, m, out):
+    if len(m.fields) == 0:
+        out.append('            %s => wire::WriteTo::size(&op::%s),' % (m.rust or m.name, m.name))
+    else:
+        pat = m.rust or '%s(%s)' % (m.name, ', '.join('ref %s' % n for n, _ in m.fields))
+        out.append('            %s =>' % pat)
+        out.append('                wire::WriteTo::size(&(op::%s, %s)),' % (
+            m.name, ', '.join(n for n, _ in m.wire_fields())))
+
+def rust_variant(s, m):
+    if len(m.fields) == 0:
+        return '    %s,' % m.name
+    return '    %s(%s),' % (m.name, ', '.join(rust_type(s, t) for t in m.field_types()))
+
+
+def gen_rust_struct(s, t, out):
+    fields = s.fields(t)
+    name = t.name
+    out.append('#[derive(Debug, Clone)]')
+    out.append('pub struct %s {' % name)
+    for n, ft in fields:
+        out.append('    pub %s: %s,' % (n, rust_type(s, ft)))
+    out.append('}')
+    out.append('')
+    out.append('impl wire::ReadFrom for %s {' % name)
+    out.append('    fn read_from<R: Read>(r: &mut WireReader<R>) -> io::Result<%s> {' % name)
+    out.append('        Ok(%s {' % name)
+    for n, _ in fields:
+        out.append('            %s: try!(r.read()),' % n)
+    out.append('        })')
+    out.append('    }')
+    out.append('}')
+    out.append('')
+    out.append('impl wire::WriteTo for %s {' % name)
+    out.append('    fn write_to<W: Write>(&self, w: &mut WireWriter<W>) -> io::Result<()> {')
+    for n, _ in fields:
+        out.append('        try!(w.write(&self.%s));' % n)
+    out.append('        Ok(())')
+    out.append('    }')
+    out.append('')
+    out.append('    fn size(&self) -> usize {')
+    out.append(' +\n'.join('        wire::WriteTo::size(&self.%s)' % n for n, _ in fields))
+    out.append('    }')
+    out.append('')
+    out.append('    fn size_is_fixed() -> bool {')
+    out.append(' &&\n'.join('        <%s as wire::WriteTo>::size_is_fixed()' % rust_type(s, ft)
+                            for _, ft in fields))
+    out.append('    }')
+    out.append('}')
+
+def gen_rust_union(s, t, out):
+    _, d = s.kind(t)
+    name = t.name
+    tag = d['union']
+    variants = s.variants(t)
+
+    out.append('#[derive(Debug, Clone)]')
+    out.append('pub enum %s {' % name)
+    for vn, _, vt, _ in variants:
+        out.append('    %s(%s),' % (vn, rust_type(s, vt)))
+    out.append('}')
+    out.append('')
+    out.append('#[derive(Clone, Copy, PartialEq, Eq, Debug)]')
+    out.append('enum %s {' % tag)
+    for vn, vtag, _, _ in variants:
+        out.append('    %s = %d,' % (vn, vtag))
+    out.append('}')
+    out.append('')
+    out.append('impl wire::ReadFrom for %s {' % tag)
+    out.append('    fn read_from<R: Read>(r: &mut WireReader<R>) -> io::Result<%s> {' % tag)
+    out.append('        let tag = match try!(r.read::<u8>()) {')
+    for vn, vtag, _, _ in variants:
+        out.append('            %d => %s::%s,' % (vtag, tag, vn))
+    out.append('            x => return Err(io::Error::new(io::ErrorKind::Other,')
+    out.append('                                           format!("bad %s variant: {}", x))),'
+               % tag)
+    out.append('        };')
+    out.append('        Ok(tag)')
+    out.append('    }')
+    out.append('}')
+    out.append('')
+    out.append('impl wire::WriteTo for %s {' % tag)
+    out.append('    fn write_to<W: Write>(&self, w: &mut WireWriter<W>) -> io::Result<()> {')
+    out.append('        w.write(*self as u8)')
+    out.append('    }')
+    out.append('')
+    out.append('    fn size(&self) -> usize { 1 }')
+    out.append('')
+    out.append('    fn size_is_fixed() -> bool { true }')
+    out.append('}')
+    out.append('')
+    out.append('impl wire::ReadFrom for %s {' % name)
+    out.append('    fn read_from<R: Read>(r: &mut WireReader<R>) -> io::Result<%s> {' % name)
+    out.append('        let x = match try!(r.read()) {')
+    for vn, _, _, _ in variants:
+        out.append('            %s::%s => %s::%s(try!(r.read())),' % (tag, vn, name, vn))
+    out.append('        };')
+    out.append('        Ok(x)')
+    out.append('    }')
+    out.append('}')
+    out.append('')
+    out.append('impl wire::WriteTo for %s {' % name)
+    out.append('    fn write_to<W: Write>(&self, w: &mut WireWriter<W>) -> io::Result<()> {')
+    out.append('        match *self {')
+    for vn, _, _, _ in variants:
+        out.append('            %s::%s(ref x) => w.write((%s::%s, x)),' % (name, vn, tag, vn))
+    out.append('        }')
+    out.append('    }')
+    out.append('')
+    out.append('    fn size(&self) -> usize {')
+    out.append('        let inner_size = match *self {')
+    for vn, _, _, _ in variants:
+        out.append('            %s::%s(ref x) => wire::WriteTo::size(x),' % (name, vn))
+    out.append('        };')
+    out.append('        1 + inner_size')
+    out.append('    }')
+    out.append('')
+    out.append('    fn size_is_fixed() -> bool { false }')
+    out.append('}')
+
+
+def gen_rust(s):
+    out = []
+    out.append('// %s' % HEADER)
+    out.append('//')
+    out.append('// Included by msg.rs.')
+    out.append('')
+    out.append('')
+    out.append('/// The protocol version spoken by this server.  Clients must send a matching version in '
+               'their')
+    out.append('/// `Hello` before they can log in.  Bump this whenever an existing message changes '
+               'format.')
+    out.append('pub const PROTOCOL_VERSION: u32 = %d;' % s.j['version'])
+    out.append('')
+    out.append('// Optional protocol features.  A client lists the ones it understands in its `Hello`, '
+               'and the')
+    out.append('// server only sends the corresponding messages to clients that asked for them.  New '
+               'capabilities')
+    out.append('// can be added without bumping `PROTOCOL_VERSION`.')
+    out.append('bitflags! {')
+    out.append('    pub flags Capabilities: u32 {')
+    for c in s.j['capabilities']:
+        out.append('        // %s' % c['doc'])
+        out.append('        const CAP_%s = %s,' % (c['name'], c['bit']))
+    out.append('    }')
+    out.append('}')
+    out.append('')
+    out.append('')
+
+    out.append('#[allow(non_upper_case_globals, dead_code)]')
+    out.append('mod op {')
+    out.append('    use super::Opcode;')
+    def consts(title, msgs):
+        out.append('')
+        out.append('    // %s' % title)
+        for name, code in msgs:
+            out.append('    pub const %s: Opcode = Opcode(0x%04x);' % (name, code))
+    def deprecated(key):
+        return sorted(((n, int(c, 16)) for n, c in s.j.get(key, {}).items()),
+                      key=lambda x: x[1])
+    consts('Requests', [(m.name, m.opcode) for m in s.requests])
+    consts('Deprecated requests', deprecated('deprecated_requests'))
+    consts('Responses', [(m.name, m.opcode) for m in s.responses])
+    consts('Deprecated responses', deprecated('deprecated_responses'))
+    consts('Control messages', sorted(((m.name, m.opcode)
+                                       for m in s.control_requests + s.control_responses),
+                                      key=lambda x: x[1]))
+    out.append('}')
+    out.append('')
+    out.append('')
+
+    all_types = []
+    for m in s.requests + s.responses + s.control_requests + s.control_responses:
+        all_types.extend(m.field_types())
+    for t in s.walk(all_types):
+        k, _ = s.kind(t)
+        if k == 'struct':
+            gen_rust_struct(s, t, out)
+        elif k == 'union':
+            gen_rust_union(s, t, out)
+        else:
+            continue
+        out.append('')
+        out.append('')
+
+    # Request
+    out.append('#[allow(dead_code)]')
+    out.append('#[derive(Debug)]')
+    out.append('pub enum Request {')
+    out.append('    // Ordinary requests')
+    for m in s.requests:
+        out.append(rust_variant(s, m))
+    out.append('')
+    out.append('    // Control messages')
+    for m in s.control_requests:
+        if m.rust is None:
+            out.append(rust_variant(s, m))
+    for name, tys in sorted(s.extra_request_variants.items()):
+        out.append('    %s(%s),' % (name, ', '.join(tys)))
+    out.append('')
+    out.append('    // Server-internal messages')
+    out.append('    BadMessage(Opcode),')
+    out.append('}')
+    out.append('')
+    out.append('impl Request {')
+    out.append('    pub fn read_from<R: Read>(wr: &mut WireReader<R>) -> '
+               'io::Result<(WireId, Request)> {')
+    out.append('        let id = try!(wr.read_header());')
+    out.append('        let opcode = Opcode(try!(wr.read()));')
+    out.append('')
+    out.append('        let req = match opcode {')
+    for m in s.requests:
+        rust_read_arm(s, m, out)
+    out.append('')
+    for m in s.control_requests:
+        rust_read_arm(s, m, out)
+    out.append('            _ => BadMessage(opcode),')
+    out.append('        };')
+    out.append('')
+    out.append('        if !wr.done() {')
+    out.append('            Ok((id, BadMessage(opcode)))')
+    out.append('        } else {')
+    out.append('            Ok((id, req))')
+    out.append('        }')
+    out.append('    }')
+    out.append('')
+    out.append('    #[allow(dead_code)]')
+    out.append('    pub fn write_to<W: Write>(&self, id: WireId, ww: &mut WireWriter<W>) -> '
+               'io::Result<()> {')
+    out.append('        try!(match *self {')
+    for m in s.requests + s.control_requests:
+        rust_write_arm(s, m, out)
+    out.append('            _ => Err(io::Error::new(io::ErrorKind::Other,')
+    out.append('                                    format!("can\'t write {:?} to the wire", self))),')
+    out.append('        });')
+    out.append('        ww.flush()')
+    out.append('    }')
+    out.append('}')
+    out.append('')
+    out.append('')
+
+    # Response
+    out.append('#[allow(dead_code)]')
+    out.append('#[derive(Debug)]')
+    out.append('pub enum Response {')
+    for m in s.responses:
+        out.append(rust_variant(s, m))
+    out.append('')
+    for m in s.control_responses:
+        out.append(rust_variant(s, m))
+    out.append('}')
+    out.append('')
+    out.append('impl Response {')
+    out.append('    #[allow(dead_code)]')
+    out.append('    pub fn read_from<R: Read>(wr: &mut WireReader<R>) -> '
+               'io::Result<(WireId, Response)> {')
+    out.append('        let id = try!(wr.read_header());')
+    out.append('        let opcode = Opcode(try!(wr.read()));')
+    out.append('')
+    out.append('        let resp = match opcode {')
+    for m in s.responses:
+        rust_read_arm(s, m, out)
+    out.append('')
+    for m in s.control_responses:
+        rust_read_arm(s, m, out)
+    out.append('            _ => return Err(io::Error::new(io::ErrorKind::Other,')
+    out.append('                                           format!("bad opcode: {:?}", opcode))),')
+    out.append('        };')
+    out.append('')
+    out.append('        if !wr.done() {')
+    out.append('            Err(io::Error::new(io::ErrorKind::Other,')
+    out.append('                               format!("extra bytes after message: {:?}", opcode)))')
+    out.append('        } else {')
+    out.append('            Ok((id, resp))')
+    out.append('        }')
+    out.append('    }')
+    out.append('')
+    out.append('    pub fn write_to<W: Write>(&self, id: WireId, ww: &mut WireWriter<W>) -> '
+               'io::Result<()> {')
+    out.append('        try!(match *self {')
+    for m in s.responses + s.control_responses:
+        rust_write_arm(s, m, out)
+    out.append('        });')
+    out.append('        ww.flush()')
+    out.append('    }')
+    out.append('')
+    out.append('    /// The number of bytes `write_to` will produce, including the message header.')
+    out.append('    pub fn wire_size(&self) -> usize {')
+    out.append('        4 + match *self {')
+    for m in s.responses + s.control_responses:
+        rust_size_arm(s, m, out)
+    out.append('        }')
+    out.append('    }')
+    out.append('}')
+    out.append('')
+    out.append('')
+
+    # Samples
+    def samples(fn_name, enum, msgs):
+        out.append('#[cfg(test)]')
+        out.append('pub fn %s() -> Vec<%s> {' % (fn_name, enum))
+        out.append('    vec![')
+        for m in msgs:
+            out.append('        %s,' % rust_message_sample(s, enum, m))
+        out.append('    ]')
+        out.append('}')
+    out.append('/// One value of each request, for round-trip tests.')
+    samples('sample_requests', 'Request', s.requests + s.control_requests)
+    out.append('')
+    out.append('/// One value of each response, for round-trip tests.')
+    samples('sample_responses', 'Response', s.responses + s.control_responses)
+
+    return '\n'.join(out) + '\n'
+
+
+# Javascript
+
+def js_get(s, t):
+    '''Expression that reads a value of type `t` from the reader `r`.'''
+    if t.name in PRIMS:
+        return 'r.%s()' % PRIMS[t.name][0]
+    if t.name == 'String':
+        return 'r.getString()'
+    if t.name == 'Vec':
+        a = s.resolve(t.args[0])
+        if a.name == 'u16':
+            # Zero-copy view of the message buffer.
+            return 'r.getU16Array()'
+        return 'r.getList(function() { return %s; })' % js_get(s, a)
+    if t.name == 'HashMap':
+        k, v = t.args
+        return 'r.getMap(function() { return %s; }, function() { return %s; })' % (
+                js_get(s, k), js_get(s, v))
+
+    k, d = s.kind(t)
+    if k in ('alias', 'newtype', 'extern'):
+        return js_get(s, s.resolve(t))
+    return 'get%s(r)' % t.name
+
+def js_put(s, t, x):
+    '''Statement that writes `x`, of type `t`, to the builder `msg`.'''
+    if t.name in PRIMS:
+        return 'msg.%s(%s);' % (PRIMS[t.name][1], x)
+    if t.name == 'String':
+        return 'msg.putString(%s);' % x
+    if t.name == 'Vec':
+        return 'msg.putList(%s, function(y) { %s });' % (x, js_put(s, t.args[0], 'y'))
+    if t.name == 'HashMap':
+        k, v = t.args
+        return 'msg.putMap(%s, function(y) { %s }, function(y) { %s });' % (
+                x, js_put(s, k, 'y'), js_put(s, v, 'y'))
+
+    k, d = s.kind(t)
+    if k in ('alias', 'newtype', 'extern'):
+        return js_put(s, s.resolve(t), x)
+    return 'put%s(msg, %s);' % (t.name, x)
+
+def gen_js_get_fn(s, t, out):
+    k, d = s.kind(t)
+    out.append('function get%s(r) {' % t.name)
+    if k == 'array':
+        elem = parse_type(d['array'])
+        out.append('    var result = new Array(%d);' % d['len'])
+        out.append('    for (var i = 0; i < %d; ++i) {' % d['len'])
+        out.append('        result[i] = %s;' % js_get(s, elem))
+        out.append('    }')
+        out.append('    return result;')
+    elif k == 'tuple' and d.get('js') == 'Vec':
+        out.append('    return new Vec(%s);' % ', '.join(js_get(s, ft) for _, ft in s.fields(t)))
+    elif k in ('tuple', 'struct'):
+        out.append('    return {')
+        for n, ft in s.fields(t):
+            out.append('        %s: %s,' % (n, js_get(s, ft)))
+        out.append('    };')
+    elif k == 'union':
+        out.append('    var tag = r.get8();')
+        out.append('    switch (tag) {')
+        for vn, vtag, vt, _ in s.variants(t):
+            out.append('        case %d: return %s;' % (vtag, js_get(s, vt)))
+        out.append('        default:')
+        out.append("            console.assert(false, 'bad %s tag:', tag);" % t.name)
+        out.append('            return null;')
+        out.append('    }')
+    out.append('}')
+
+JS_KIND_TEST = {
+        'number': "typeof x == 'number' || typeof x == 'boolean'",
+        'string': "typeof x == 'string'",
+        'array': 'x.constructor == Array',
+        }
+
+def gen_js_put_fn(s, t, out):
+    k, d = s.kind(t)
+    out.append('function put%s(msg, x) {' % t.name)
+    if k == 'array':
+        elem = parse_type(d['array'])
+        out.append('    for (var i = 0; i < %d; ++i) {' % d['len'])
+        out.append('        %s' % js_put(s, elem, 'x[i]'))
+        out.append('    }')
+    elif k in ('tuple', 'struct'):
+        for n, ft in s.fields(t):
+            out.append('    %s' % js_put(s, ft, 'x.%s' % n))
+    elif k == 'union':
+        variants = s.variants(t)
+        for i, (vn, vtag, vt, js) in enumerate(variants):
+            if js == 'object':
+                assert i == len(variants) - 1, 'the "object" variant must come last'
+                out.append('    } else {' if i > 0 else '    {')
+            else:
+                out.append('    %sif (%s) {' % ('} else ' if i > 0 else '', JS_KIND_TEST[js]))
+            out.append('        msg.put8(%d);' % vtag)
+            out.append('        %s' % js_put(s, vt, 'x'))
+        out.append('    }')
+    out.append('}')
+
+def gen_js(s):
+    out = []
+    out.append('// %s' % HEADER)
+    out.append('')
+    out.append("var Vec = require('util/vec').Vec;")
+    out.append("var Reader = require('util/wire').Reader;")
+    out.append("var MessageBuilder = require('util/wire').MessageBuilder;")
+    out.append('')
+    out.append('')
+    out.append('exports.PROTOCOL_VERSION = %d;' % s.j['version'])
+    out.append('')
+    for c in s.j['capabilities']:
+        out.append('// %s' % c['doc'])
+        out.append('exports.CAP_%s = %s;' % (c['name'], c['bit']))
+    out.append('')
+
+    def consts(msgs, deprecated_key):
+        items = [('OP_%s' % snake_upper(m.name), m.opcode) for m in msgs]
+        for n, c in s.j.get(deprecated_key, {}).items():
+            items.append((None, int(c, 16)))
+        for name, code in sorted(items, key=lambda x: x[1]):
+            if name is None:
+                out.append('// DEPRECATED                   0x%04x;' % code)
+            else:
+                out.append('%s0x%04x;' % (('var %s =' % name).ljust(32), code))
+    consts(s.requests, 'deprecated_requests')
+    out.append('')
+    consts(s.responses, 'deprecated_responses')
+    out.append('')
+    out.append('')
+
+    resp_types = []
+    for m in s.responses:
+        resp_types.extend(m.field_types())
+    req_types = []
+    for m in s.requests:
+        req_types.extend(m.field_types())
+
+    for t in s.walk(resp_types):
+        if s.kind(t)[0] in ('array', 'tuple', 'struct', 'union'):
+            gen_js_get_fn(s, t, out)
+            out.append('')
+    for t in s.walk(req_types):
+        if s.kind(t)[0] in ('array', 'tuple', 'struct', 'union'):
+            gen_js_put_fn(s, t, out)
+            out.append('')
+    out.append('')
+
+    out.append('/** Set all message handlers on `conn` to `null`. */')
+    out.append('exports.initHandlers = function(conn) {')
+    for m in s.responses:
+        if not m.js_internal:
+            out.append('    conn.on%s = null;' % m.name)
+    out.append('};')
+    out.append('')
+
+    out.append('/**')
+    out.append(' * Decode the message in `evt` and pass it to the matching handler on `conn`:')
+    out.append(' * `conn.onFoo` for most messages, or `conn._handleFoo` for ones the')
+    out.append(' * `Connection` handles itself.')
+    out.append(' */')
+    out.append('exports.dispatch = function(conn, evt) {')
+    out.append('    var r = new Reader(new DataView(evt.data));')
+    out.append('    var opcode = r.get16();')
+    out.append('')
+    out.append('    switch (opcode) {')
+    for m in s.responses:
+        out.append('        case OP_%s:' % snake_upper(m.name))
+        for n, t in m.wire_fields():
+            out.append('            var %s = %s;' % (n, js_get(s, t)))
+        args = ', '.join([n for n, _ in m.fields] + m.js_extra_args)
+        if m.js_internal:
+            out.append('            conn._handle%s(%s);' % (m.name, args))
+        else:
+            out.append('            if (conn.on%s != null) {' % m.name)
+            out.append('                conn.on%s(%s);' % (m.name, args))
+            out.append('            }')
+        out.append('            break;')
+        out.append('')
+    out.append('        default:')
+    out.append("            console.assert(false, 'received invalid opcode:', opcode.toString(16));")
+    out.append('            return;')
+    out.append('    }')
+    out.append('')
+    out.append("    console.assert(r.done(), 'received message with bad length');")
+    out.append('};')
+    out.append('')
+    out.append('')
+
+    out.append('var MESSAGE_BUILDER = new MessageBuilder(8192);')
+    out.append('')
+    out.append('/** Add a `sendFoo` method for each request to `proto`, which must have a `socket`. */')
+    out.append('exports.installSenders = function(proto) {')
+    for i, m in enumerate(s.requests):
+        if i > 0:
+            out.append('')
+        out.append('    proto.send%s = function(%s) {' % (m.name, ', '.join(n for n, _ in m.fields)))
+        out.append('        var msg = MESSAGE_BUILDER.reset();')
+        out.append('        msg.put16(OP_%s);' % snake_upper(m.name))
+        for n, t in m.wire_fields():
+            out.append('        %s' % js_put(s, t, n))
+        out.append('        this.socket.send(msg.done());')
+        out.append('    };')
+    out.append('};')
+
+    return '\n'.join(out) + '\n'
+
+
+# C++
+
+def gen_cxx(s):
+    out = []
+    out.append('#ifndef OUTPOST_WRAPPER_OPCODES_HPP')
+    out.append('#define OUTPOST_WRAPPER_OPCODES_HPP')
+    out.append('')
+    out.append('// %s' % HEADER)
+    out.append('')
+    out.append('enum opcode {')
+    msgs = sorted(s.control_requests + s.control_responses, key=lambda m: m.opcode)
+    for m in msgs:
+        out.append('    %s0x%04x,' % (('OP_%s =' % snake_upper(m.name)).ljust(24), m.opcode))
+    out.append('};')
+    out.append('')
+    out.append('#endif // OUTPOST_WRAPPER_OPCODES_HPP')
+    return '\n'.join(out) + '\n'
+
+
+def build_parser():
+    parser = argparse.ArgumentParser(
+            description='Generate protocol code from the schema.')
+    parser.add_argument('schema', metavar='SCHEMA',
+            help='path to protocol.json')
+    parser.add_argument('--rust-out', metavar='FILE',
+            help='where to write the generated Rust code')
+    parser.add_argument('--js-out', metavar='FILE',
+            help='where to write the generated Javascript module')
+    parser.add_argument('--cxx-out', metavar='FILE',
+            help='where to write the generated C++ opcode header')
+    parser.add_argument('--check', action='store_true', default=False,
+            help="don't write anything; fail if the output files are out of date")
+    return parser
+
+def main(args):
+    with open(args.schema) as f:
+        s = Schema(json.load(f))
+
+    stale = False
+    for path, gen in ((args.rust_out, gen_rust),
+                      (args.js_out, gen_js),
+                      (args.cxx_out, gen_cxx)):
+        if path is None:
+            continue
+        code = gen(s)
+        if args.check:
+            try:
+                with open(path) as f:
+                    ok = f.read() == code
+            except FileNotFoundError:
+                ok = False
+            if not ok:
+                sys.stderr.write('%s is out of date with %s; regenerate it with '
+                        'src/gen/gen_protocol.py\n' % (path, args.schema))
+                stale = True
+            continue
+        with open(path, 'w') as f:
+            f.write(code)
+
+    if stale:
+        sys.exit(1)
+
+if __name__ == '__main__':
+    parser = build_parser()
+    main(parser.parse_args(sys.argv[1:]))
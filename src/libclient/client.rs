@@ -15,7 +15,8 @@ use graphics::renderer::Renderer;
 
 use physics;
 use physics::{CHUNK_SIZE, CHUNK_BITS, TILE_SIZE};
-use physics::Shape;
+use physics::{Shape, EntityBox};
+use physics::collision_groups;
 use physics::v3::{V3, V2, Vn, scalar, Region};
 
 use Time;
@@ -26,6 +27,7 @@ use graphics::renderer::Scene;
 use graphics::renderer::ONESHOT_MODULUS;
 use graphics::types::StructureTemplate;
 use inventory::{Inventories, Item, InventoryId};
+use keys::Command;
 use misc::Misc;
 use predict::Predictor;
 use structures::Structures;
@@ -89,6 +91,8 @@ impl<'d, P: Platform> Client<'d, P> {
         };
 
         c.misc.hotbar.init(c.platform.config(), &c.data);
+        c.misc.keys.init(c.platform.config());
+        c.misc.gamepad.init(c.platform.config());
         c.ui.root.init(c.platform.config());
 
         c
@@ -140,11 +144,44 @@ impl<'d, P: Platform> Client<'d, P> {
             let b = blocks[chunk_bounds.index(pos)];
             block_data[b as usize].shape
         });
+        self.terrain_shape.set_surface_in_region_by(chunk_bounds, |pos| {
+            let b = blocks[chunk_bounds.index(pos)];
+            block_data[b as usize].surface()
+        });
 
         // Invalidate cached geometry
         self.renderer.invalidate_terrain_geometry();
     }
 
+    /// Apply a partial update to a loaded chunk.  `changes` holds `(index, block_id)` pairs,
+    /// flattened.
+    pub fn update_terrain_blocks(&mut self, cpos: V2, changes: &[u16]) {
+        let bounds = Region::new(scalar(0), scalar(LOCAL_SIZE));
+        let blocks = &mut self.chunks[bounds.index(cpos)];
+
+        let chunk_bounds = Region::new(scalar(0), scalar(CHUNK_SIZE)) +
+                           (cpos * scalar(CHUNK_SIZE)).extend(0);
+        let block_data = self.data.blocks();
+        for change in changes.chunks(2) {
+            if change.len() < 2 || change[0] as usize >= blocks.len() {
+                continue;
+            }
+            let (idx, b) = (change[0] as usize, change[1]);
+            blocks[idx] = b;
+
+            let pos = chunk_bounds.from_index(idx);
+            let block_bounds = Region::new(pos, pos + scalar(1));
+            self.terrain_shape.set_shape_in_region_by(block_bounds, 0, |_| {
+                block_data[b as usize].shape
+            });
+            self.terrain_shape.set_surface_in_region_by(block_bounds, |_| {
+                block_data[b as usize].surface()
+            });
+        }
+
+        self.renderer.invalidate_terrain_geometry_chunk(cpos);
+    }
+
     // Structure tracking
 
     pub fn add_structure_shape(&mut self,
@@ -239,8 +276,10 @@ impl<'d, P: Platform> Client<'d, P> {
     pub fn entity_appear(&mut self,
                          id: EntityId,
                          appearance: u32,
-                         name: Option<String>) {
-        self.entities.insert(id, appearance, name);
+                         name: Option<String>,
+                         collision_groups: u8) {
+        let groups = collision_groups::Groups::from_bits_truncate(collision_groups);
+        self.entities.insert(id, appearance, name, groups);
     }
 
     pub fn entity_gone(&mut self,
@@ -248,6 +287,20 @@ impl<'d, P: Platform> Client<'d, P> {
         self.entities.remove(id);
     }
 
+    pub fn entity_health(&mut self,
+                         id: EntityId,
+                         hp: u16,
+                         max: u16) {
+        self.entities.set_health(id, hp, max);
+    }
+
+    pub fn entity_damage(&mut self,
+                         id: EntityId,
+                         amount: u16,
+                         now: Time) {
+        self.entities.add_damage(id, amount, now);
+    }
+
     pub fn entity_update(&mut self,
                          id: EntityId,
                          when: Time,
@@ -315,13 +368,20 @@ impl<'d, P: Platform> Client<'d, P> {
         let dyn = Dyn::new(self.view_size,
                            &self.inventories,
                            &self.misc.hotbar,
+                           &self.misc.keys,
                            &self.debug);
         f(&mut self.ui, dyn)
     }
 
-    pub fn input_key(&mut self, code: u8) -> bool {
+    pub fn input_key(&mut self, code: u8, shift: bool) -> bool {
+        let key =
+            if self.ui.root.dialog.inner.wants_raw_keys() {
+                Some(KeyAction::RawKey(code))
+            } else {
+                self.misc.keys.get(code).and_then(|cmd| KeyAction::from_command(cmd, shift))
+            };
         let status =
-            if let Some(key) = KeyAction::from_code(code) {
+            if let Some(key) = key {
                 self.with_ui_dyn(|ui, dyn| ui.handle_key(key, dyn))
             } else {
                 EventStatus::Unhandled
@@ -329,6 +389,39 @@ impl<'d, P: Platform> Client<'d, P> {
         self.process_event_status(status)
     }
 
+    /// Update the gamepad state.  See `gamepad::Gamepad::update` for the meaning of the
+    /// arguments.  Commands that the UI doesn't handle are passed back to the platform.
+    pub fn input_gamepad(&mut self, buttons: u32, x: i32, y: i32) {
+        use ui::dialogs::AnyDialog;
+
+        let (pressed, released) = self.misc.gamepad.update(buttons, x, y);
+
+        for cmd in released {
+            self.platform.run_command(cmd.name(), false);
+        }
+
+        for cmd in pressed {
+            let dialog_open = match self.ui.root.dialog.inner {
+                AnyDialog::None => false,
+                _ => true,
+            };
+            // Gamepads have no Enter key, so the interact button confirms in dialogs instead.
+            let key =
+                if dialog_open && cmd == Command::Interact { Some(KeyAction::Select) }
+                else { KeyAction::from_command(cmd, false) };
+            let handled = match key {
+                Some(key) => {
+                    let status = self.with_ui_dyn(|ui, dyn| ui.handle_key(key, dyn));
+                    self.process_event_status(status)
+                },
+                None => false,
+            };
+            if !handled {
+                self.platform.run_command(cmd.name(), true);
+            }
+        }
+    }
+
     pub fn input_mouse_move(&mut self, pos: V2) -> bool {
         let status = self.with_ui_dyn(|ui, dyn| ui.handle_mouse_move(pos, dyn));
         self.process_event_status(status)
@@ -349,6 +442,11 @@ impl<'d, P: Platform> Client<'d, P> {
         self.ui.root.dialog.inner = AnyDialog::inventory();
     }
 
+    pub fn open_keybindings_dialog(&mut self) {
+        use ui::dialogs::AnyDialog;
+        self.ui.root.dialog.inner = AnyDialog::keybindings();
+    }
+
     pub fn open_ability_dialog(&mut self) {
         use ui::dialogs::AnyDialog;
         self.ui.root.dialog.inner = AnyDialog::ability();
@@ -367,11 +465,40 @@ impl<'d, P: Platform> Client<'d, P> {
         self.misc.hotbar.active_ability().unwrap_or(0)
     }
 
+    /// Bind the action named `name` to hotbar slot `idx`.  Returns `false` if there is no such
+    /// action.
+    pub fn set_hotbar_action(&mut self, idx: u8, name: &str) -> bool {
+        let action_id = match self.data.find_action_id(name) {
+            Some(x) => x,
+            None => return false,
+        };
+        self.misc.hotbar.set_action_slot(&self.data,
+                                         self.platform.config_mut(),
+                                         idx,
+                                         action_id);
+        true
+    }
+
 
     // Physics
 
     pub fn feed_input(&mut self, time: Time, dir: V3) {
-        self.predictor.input(time, dir, &*self.terrain_shape, &self.data);
+        let entities = self.entity_boxes(time);
+        self.predictor.input(time, dir, &*self.terrain_shape, &entities, &self.data);
+    }
+
+    /// Collect the bounding boxes of all entities other than the player's pawn, for collision
+    /// checks during prediction.  `predict` drops the ones out of reach at each step, as the
+    /// server does.
+    // NB: keep this in sync with server/physics.rs  entity_boxes()
+    fn entity_boxes(&self, now: Time) -> Vec<EntityBox> {
+        // TODO: hardcoded constant based on entity size
+        let size = V3::new(32, 32, 64);
+        let ahead = now + physics::ENTITY_LOOKAHEAD as Time;
+        self.entities.iter()
+            .filter(|&(&id, _)| Some(id) != self.pawn_id)
+            .map(|(_, e)| EntityBox::swept(e.pos(now), e.pos(ahead), size, e.collision_groups))
+            .collect()
     }
 
 
@@ -446,7 +573,8 @@ impl<'d, P: Platform> Client<'d, P> {
         self.debug.day_time = day_time;
         self.debug.day_phase = self.misc.day_night.phase_delta(&self.data, day_time).0;
 
-        self.predictor.update(future, &*self.terrain_shape, &self.data);
+        let entities = self.entity_boxes(now);
+        self.predictor.update(future, &*self.terrain_shape, &entities, &self.data);
 
         let pos =
             if self.pawn_id.is_some() {
@@ -474,6 +602,7 @@ impl<'d, P: Platform> Client<'d, P> {
                                cursor_pos);
 
         self.entities.apply_updates(scene.now);
+        self.entities.expire_damage(scene.now);
         self.prepare(&scene, future);
 
         self.renderer.render(&scene);
@@ -522,7 +651,7 @@ impl<'d, P: Platform> Client<'d, P> {
     }
 
     pub fn ponyedit_render(&mut self, appearance: u32) {
-        self.entities.insert(0, appearance, None);
+        self.entities.insert(0, appearance, None, collision_groups::Groups::empty());
         let anim = self.data().editor_anim();
         self.entities.ponyedit_hack(0, anim);
         println!("created entity #0 with app {:x}", appearance);
@@ -585,6 +714,10 @@ pub trait ClientObj {
                           src_slot: usize,
                           dest_slot: u8);
     fn handle_hotbar_select(&mut self, idx: u8);
+
+    fn handle_hotbar_cycle(&mut self, delta: i8);
+
+    fn handle_bind_key(&mut self, cmd: Command, code: u8);
 }
 
 impl<'d, P: Platform> ClientObj for Client<'d, P> {
@@ -615,7 +748,17 @@ impl<'d, P: Platform> ClientObj for Client<'d, P> {
     }
 
     fn handle_hotbar_select(&mut self, idx: u8) {
-        self.misc.hotbar.select(self.platform.config_mut(), idx);
+        if let Some(action_id) = self.misc.hotbar.select(self.platform.config_mut(), idx) {
+            self.platform.send_use_action(action_id);
+        }
+    }
+
+    fn handle_hotbar_cycle(&mut self, delta: i8) {
+        self.misc.hotbar.cycle(self.platform.config_mut(), delta);
+    }
+
+    fn handle_bind_key(&mut self, cmd: Command, code: u8) {
+        self.misc.keys.bind(self.platform.config_mut(), cmd, code);
     }
 }
 
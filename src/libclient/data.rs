@@ -36,6 +36,10 @@ impl<'a> ItemDef<'a> {
     }
 }
 
+// Action definitions have the same layout as items: just a name and a display name.
+pub type RawActionDef = RawItemDef;
+pub type ActionDef<'a> = ItemDef<'a>;
+
 
 pub struct Animation {
     pub local_id: u16,
@@ -161,6 +165,7 @@ macro_rules! gen_data {
 gen_data! {
     blocks (b"Blocks\0\0"): BlockData,
     raw_items (b"Items\0\0\0"): RawItemDef,
+    raw_actions (b"Actions\0"): RawActionDef,
 
     templates (b"StrcDefs"): StructureTemplate,
     template_parts (b"StrcPart"): TemplatePart,
@@ -215,6 +220,20 @@ impl Data {
         None
     }
 
+    pub fn action_def(&self, id: u16) -> ActionDef {
+        self.make_item_def(&self.raw_actions()[id as usize])
+    }
+
+    pub fn find_action_id(&self, name: &str) -> Option<u16> {
+        for (i, raw) in self.raw_actions().iter().enumerate() {
+            let def = self.make_item_def(raw);
+            if name == def.name() {
+                return Some(i as u16);
+            }
+        }
+        None
+    }
+
 
     pub fn template(&self, id: u32) -> &StructureTemplate {
         &self.templates()[id as usize]
@@ -5,6 +5,7 @@ use std::collections::Bound;
 use std::cmp::Ordering;
 use std::ops::Index;
 
+use physics::collision_groups::Groups;
 use physics::v3::{V3, scalar};
 
 use Time;
@@ -37,10 +38,19 @@ impl Motion {
     }
 }
 
+/// How long a damage number stays visible above an entity.
+pub const DAMAGE_DISPLAY_TIME: Time = 1000;
+
 pub struct Entity {
     pub motion: Motion,
     pub appearance: u32,
     pub name: Option<String>,
+    /// Collision groups, as decided by the server.  Used to predict collisions with this entity.
+    pub collision_groups: Groups,
+    /// Current and maximum health, if the server has sent them.
+    pub health: Option<(u16, u16)>,
+    /// Recent damage, as (time, amount) pairs, oldest first.
+    pub damage: Vec<(Time, u16)>,
     serial: u32,
 }
 
@@ -111,7 +121,8 @@ impl Entities {
     pub fn insert(&mut self,
                   id: EntityId,
                   appearance: u32,
-                  name: Option<String>) {
+                  name: Option<String>,
+                  collision_groups: Groups) {
         let serial = self.next_serial();
         self.map.insert(id, Entity {
             motion: Motion {
@@ -123,6 +134,9 @@ impl Entities {
             },
             appearance: appearance,
             name: name,
+            collision_groups: collision_groups,
+            health: None,
+            damage: Vec::new(),
             serial: serial,
         });
     }
@@ -142,6 +156,27 @@ impl Entities {
         }
     }
 
+    pub fn set_health(&mut self, id: EntityId, hp: u16, max: u16) {
+        if let Some(e) = self.map.get_mut(&id) {
+            e.health = Some((hp, max));
+        }
+    }
+
+    pub fn add_damage(&mut self, id: EntityId, amount: u16, now: Time) {
+        if let Some(e) = self.map.get_mut(&id) {
+            e.damage.push((now, amount));
+        }
+    }
+
+    pub fn expire_damage(&mut self, now: Time) {
+        for e in self.map.values_mut() {
+            if e.damage.len() == 0 {
+                continue;
+            }
+            e.damage.retain(|&(when, _)| now - when < DAMAGE_DISPLAY_TIME);
+        }
+    }
+
     pub fn apply_updates(&mut self, now: Time) {
         while self.updates.len() > 0 && self.updates.peek().unwrap().when <= now {
             let update = self.updates.pop().unwrap();
@@ -0,0 +1,84 @@
+//! Gamepad support.  The platform reports the state of the gamepad once per frame, and `Gamepad`
+//! turns it into presses and releases of `keys::Command`s, which then go through the same paths
+//! as keyboard input.
+use std::prelude::v1::*;
+
+use keys::Command;
+use platform::{Config, ConfigKey};
+
+
+/// Number of buttons in the standard gamepad layout.
+pub const NUM_BUTTONS: usize = 16;
+
+/// Stick axes are reported in the range `-AXIS_MAX .. AXIS_MAX`.
+pub const AXIS_MAX: i32 = 1000;
+/// Stick deflections smaller than this are ignored.
+pub const DEADZONE: i32 = 250;
+/// Stick deflections larger than this make the pawn run.
+pub const RUN_THRESHOLD: i32 = 850;
+
+/// sin(22.5 degrees), scaled by `AXIS_MAX`.  An axis counts as pressed if the stick is within 67.5
+/// degrees of it, which splits the stick into eight equal directions.
+const DIAGONAL_LIMIT: i64 = 383;
+
+pub struct Gamepad {
+    bindings: [Option<Command>; NUM_BUTTONS],
+    held: Vec<Command>,
+}
+
+impl Gamepad {
+    pub fn new() -> Gamepad {
+        Gamepad {
+            bindings: [None; NUM_BUTTONS],
+            held: Vec::new(),
+        }
+    }
+
+    pub fn init<C: Config>(&mut self, cfg: &C) {
+        for i in 0 .. NUM_BUTTONS {
+            let name = cfg.get_str(ConfigKey::GamepadBinding(i as u8));
+            self.bindings[i] = Command::from_name(&name);
+        }
+    }
+
+    /// Update the gamepad state.  `buttons` has one bit per button, and `x` and `y` are the
+    /// position of the left stick.  Returns the commands that were pressed and released since the
+    /// last update.
+    pub fn update(&mut self, buttons: u32, x: i32, y: i32) -> (Vec<Command>, Vec<Command>) {
+        let mut held = Vec::new();
+        for i in 0 .. NUM_BUTTONS {
+            if buttons & (1 << i) != 0 {
+                if let Some(cmd) = self.bindings[i] {
+                    held.push(cmd);
+                }
+            }
+        }
+        stick_commands(x, y, &mut held);
+
+        let pressed = held.iter().cloned().filter(|c| !self.held.contains(c)).collect();
+        let released = self.held.iter().cloned().filter(|c| !held.contains(c)).collect();
+        self.held = held;
+        (pressed, released)
+    }
+}
+
+/// Convert a stick position to movement commands.
+fn stick_commands(x: i32, y: i32, out: &mut Vec<Command>) {
+    let mag_sq = (x * x + y * y) as i64;
+    if mag_sq < (DEADZONE * DEADZONE) as i64 {
+        return;
+    }
+
+    let limit_sq = mag_sq * DIAGONAL_LIMIT * DIAGONAL_LIMIT;
+    let scale_sq = (AXIS_MAX * AXIS_MAX) as i64;
+    if (x * x) as i64 * scale_sq > limit_sq {
+        out.push(if x < 0 { Command::MoveLeft } else { Command::MoveRight });
+    }
+    if (y * y) as i64 * scale_sq > limit_sq {
+        out.push(if y < 0 { Command::MoveUp } else { Command::MoveDown });
+    }
+
+    if mag_sq > (RUN_THRESHOLD * RUN_THRESHOLD) as i64 {
+        out.push(Command::Run);
+    }
+}
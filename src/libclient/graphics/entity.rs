@@ -1,10 +1,11 @@
 use std::prelude::v1::*;
+use std::cmp;
 
 use physics::v3::{V3, V2, scalar, Region};
 use physics::{CHUNK_SIZE, CHUNK_BITS, TILE_SIZE, TILE_BITS};
 
 use data::Data;
-use entity::{Entities, Entity, EntityId, Motion};
+use entity::{Entities, Entity, EntityId, Motion, DAMAGE_DISPLAY_TIME};
 use fonts::{self, FontMetricsExt};
 use platform::gl;
 use predict::Predictor;
@@ -85,6 +86,16 @@ pub struct GeomGen<'a> {
 
 const LOCAL_PX_MASK: i32 = (1 << (TILE_BITS + CHUNK_BITS + LOCAL_BITS)) - 1;
 
+const HACKY_ADJUSTMENT: u16 = 24;
+
+const HEALTH_BAR_WIDTH: u16 = 32;
+const HEALTH_BAR_HEIGHT: u16 = 3;
+const HEALTH_BAR_BG: (u8, u8, u8) = (0x44, 0x00, 0x00);
+const HEALTH_BAR_FG: (u8, u8, u8) = (0x22, 0xcc, 0x22);
+const DAMAGE_COLOR: (u8, u8, u8) = (0xff, 0x44, 0x22);
+/// How far damage numbers float upward before disappearing.
+const DAMAGE_RISE: i32 = 16;
+
 impl<'a> GeomGen<'a> {
     pub fn new(entities: &'a Entities,
                predictor: &'a Predictor,
@@ -126,7 +137,7 @@ impl<'a> GeomGen<'a> {
                 continue;
             }
 
-            let num_quads = count_layers(e.appearance) + name_len(&e.name);
+            let num_quads = count_layers(e.appearance) + name_len(&e.name) + count_status(e);
             count += 6 * num_quads;
         }
         count
@@ -149,7 +160,7 @@ impl<'a> GeometryGenerator for GeomGen<'a> {
                 continue;
             }
 
-            let num_quads = count_layers(e.appearance) + name_len(&e.name);
+            let num_quads = count_layers(e.appearance) + name_len(&e.name) + count_status(e);
             if idx + 6 * num_quads >= buf.len() {
                 return (idx, true);
             }
@@ -163,8 +174,6 @@ impl<'a> GeometryGenerator for GeomGen<'a> {
             let dest_x = (pos.x - 32) as u16;
             let dest_y = (pos.y - pos.z - 64) as u16;
 
-            const HACKY_ADJUSTMENT: u16 = 24;
-
             for_each_layer(e.appearance, |layer_table_idx, color| {
                 let layer_idx = self.data.pony_layer_table()[layer_table_idx];
                 let l = self.data.sprite_layer(layer_idx);
@@ -206,45 +215,27 @@ impl<'a> GeometryGenerator for GeomGen<'a> {
             });
 
             if let (false, &Some(ref name)) = (is_pawn, &e.name) {
-                let name_center_x = dest_x + 48;
-                let name_y = dest_y + 12;
-                let name_x = name_center_x - fonts::NAME.measure_width(name) as u16 / 2;
-                for (char_idx, offset) in fonts::NAME.iter_str(name) {
-                    if let Some(char_idx) = char_idx {
-                        let dx = name_x + offset as u16;
-                        let dy = name_y;
-                        let sx = fonts::NAME.xs[char_idx] as u16;
-                        let sy = 0;
-                        let w = fonts::NAME.widths[char_idx] as u16;
-                        let h = fonts::NAME.height as u16;
-
-                        for &(cx, cy) in &[(0, 0), (1, 0), (1, 1), (0, 0), (1, 1), (0, 1)] {
-                            buf[idx] = Vertex {
-                                dest_pos: (dx + cx * w,
-                                           dy + cy * h),
-                                src_pos: (sx + cx * w,
-                                          sy + cy * h),
-                                sheet: 0,
-                                color: (255, 255, 255),
-
-                                ref_pos: (pos.x as u16,
-                                          // TODO: hardcoded size
-                                          // TODO: arbitrary adjustment
-                                          pos.y as u16 + HACKY_ADJUSTMENT,
-                                          pos.z as u16),
-                                // TODO: hardcoded size
-                                ref_size_z: 64,
-
-                                anim_length: 1,
-                                anim_rate: 1,
-                                anim_start: 0,
-                                anim_step: 0,
-                            };
-                            idx += 1;
-                        }
-                    }
+                emit_text(buf, &mut idx, pos, name, dest_x + 48, dest_y + 12, (255, 255, 255));
+            }
+
+            if let Some((hp, max)) = e.health {
+                if hp < max {
+                    let bar_x = dest_x + 48 - HEALTH_BAR_WIDTH / 2;
+                    let bar_y = dest_y + 6;
+                    let fill = (HEALTH_BAR_WIDTH as u32 * hp as u32 / max as u32) as u16;
+                    emit_solid(buf, &mut idx, pos, (bar_x, bar_y),
+                               (HEALTH_BAR_WIDTH, HEALTH_BAR_HEIGHT), HEALTH_BAR_BG);
+                    emit_solid(buf, &mut idx, pos, (bar_x, bar_y),
+                               (fill, HEALTH_BAR_HEIGHT), HEALTH_BAR_FG);
                 }
             }
+
+            for &(when, amount) in &e.damage {
+                let age = cmp::max(0, self.now - when);
+                let rise = (age * DAMAGE_RISE / DAMAGE_DISPLAY_TIME) as u16;
+                emit_text(buf, &mut idx, pos, &amount.to_string(),
+                          dest_x + 48, dest_y.wrapping_sub(rise), DAMAGE_COLOR);
+            }
         }
 
         // Ran out of entites - we're done.
@@ -286,6 +277,88 @@ fn name_len(name: &Option<String>) -> usize {
     }
 }
 
+/// Count the quads used for the health bar and damage numbers.
+fn count_status(e: &Entity) -> usize {
+    let mut count = 0;
+    if let Some((hp, max)) = e.health {
+        if hp < max {
+            count += 2;
+        }
+    }
+    for &(_, amount) in &e.damage {
+        count += amount.to_string().len();
+    }
+    count
+}
+
+/// Emit a quad that copies the `src_size` rect at `src` on sheet 0 to the `dest_size` rect at
+/// `dest`, tinted by `color`.
+fn emit_quad(buf: &mut [Vertex],
+             idx: &mut usize,
+             pos: V3,
+             dest: (u16, u16),
+             dest_size: (u16, u16),
+             src: (u16, u16),
+             src_size: (u16, u16),
+             color: (u8, u8, u8)) {
+    for &(cx, cy) in &[(0, 0), (1, 0), (1, 1), (0, 0), (1, 1), (0, 1)] {
+        buf[*idx] = Vertex {
+            dest_pos: (dest.0 + cx * dest_size.0,
+                       dest.1 + cy * dest_size.1),
+            src_pos: (src.0 + cx * src_size.0,
+                      src.1 + cy * src_size.1),
+            sheet: 0,
+            color: color,
+
+            ref_pos: (pos.x as u16,
+                      // TODO: hardcoded size
+                      // TODO: arbitrary adjustment
+                      pos.y as u16 + HACKY_ADJUSTMENT,
+                      pos.z as u16),
+            // TODO: hardcoded size
+            ref_size_z: 64,
+
+            anim_length: 1,
+            anim_rate: 1,
+            anim_start: 0,
+            anim_step: 0,
+        };
+        *idx += 1;
+    }
+}
+
+/// Draw a solid rectangle, by stretching a single texel from the block of solid white just below
+/// the name font on sheet 0.
+fn emit_solid(buf: &mut [Vertex],
+              idx: &mut usize,
+              pos: V3,
+              dest: (u16, u16),
+              size: (u16, u16),
+              color: (u8, u8, u8)) {
+    // NB: keep this in sync with gen/data/gen.py  emit_sprites
+    let src = (1, fonts::NAME.height as u16 + 1);
+    emit_quad(buf, idx, pos, dest, size, src, (1, 1), color);
+}
+
+/// Draw `text` in the name font, centered horizontally on `center_x`.
+fn emit_text(buf: &mut [Vertex],
+             idx: &mut usize,
+             pos: V3,
+             text: &str,
+             center_x: u16,
+             y: u16,
+             color: (u8, u8, u8)) {
+    let x = center_x - fonts::NAME.measure_width(text) as u16 / 2;
+    for (char_idx, offset) in fonts::NAME.iter_str(text) {
+        if let Some(char_idx) = char_idx {
+            let src = (fonts::NAME.xs[char_idx] as u16, 0);
+            let size = (fonts::NAME.widths[char_idx] as u16,
+                        fonts::NAME.height as u16);
+            emit_quad(buf, idx, pos, (x + offset as u16, y), size, src, size, color);
+        }
+    }
+}
+
 fn for_each_layer<F: FnMut(usize, (u8, u8, u8))>(appearance: u32, mut f: F) {
     let red = (appearance as usize >> 4) & 3;
     let green = (appearance as usize >> 2) & 3;
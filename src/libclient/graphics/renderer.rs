@@ -371,6 +371,13 @@ impl<GL: Context> Renderer<GL> {
         self.terrain_geom.invalidate();
     }
 
+    /// Invalidate the terrain geometry only if it includes the chunk at local position `cpos`.
+    pub fn invalidate_terrain_geometry_chunk(&mut self, cpos: V2) {
+        self.terrain_geom.invalidate_if(|bounds| {
+            bounds.points().any(|p| p & scalar(LOCAL_MASK) == cpos)
+        });
+    }
+
 
     pub fn update_structure_geometry(&mut self,
                                      data: &Data,
@@ -815,6 +822,17 @@ impl<GL: Context, K: Eq> GeomCache<GL, K> {
         self.last_key = None;
     }
 
+    pub fn invalidate_if<F>(&mut self, f: F)
+            where F: FnOnce(&K) -> bool {
+        let hit = match self.last_key {
+            Some(ref k) => f(k),
+            None => false,
+        };
+        if hit {
+            self.last_key = None;
+        }
+    }
+
     pub fn is_valid(&self, k: &K) -> bool {
         if let Some(ref last_key) = self.last_key {
             last_key == k
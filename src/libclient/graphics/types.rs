@@ -1,4 +1,5 @@
-use physics::{CHUNK_BITS, Shape};
+use physics::{CHUNK_BITS, Shape, Surface};
+use physics::surface_flags;
 use graphics::LOCAL_BITS;
 
 
@@ -15,7 +16,8 @@ pub struct BlockData {
     pub light_color: (u8, u8, u8),
     pub shape: Shape,
     pub light_radius: u16,
-    pub _pad2: u16,
+    pub speed: u8,
+    pub surface_flags: u8,
 
     // 16
 }
@@ -30,6 +32,13 @@ impl BlockData {
             _ => panic!("invalid side number"),
         }
     }
+
+    pub fn surface(&self) -> Surface {
+        Surface {
+            speed: self.speed,
+            flags: surface_flags::Flags::from_bits_truncate(self.surface_flags),
+        }
+    }
 }
 
 
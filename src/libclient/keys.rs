@@ -0,0 +1,229 @@
+use std::prelude::v1::*;
+
+use platform::{Config, ConfigKey};
+
+
+/// A logical command that can be bound to a key.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Command {
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Run,
+    Jump,
+
+    Interact,
+    UseAbility,
+    UseItem,
+    Abilities,
+    Inventory,
+
+    Hotbar(u8),
+    HotbarNext,
+    HotbarPrev,
+
+    ShowControls,
+    ShowMenu,
+    ToggleCursor,
+    Chat,
+    ChatCommand,
+    Cancel,
+    DebugShowPanel,
+    DebugTest,
+}
+
+/// Every command, in the order they appear in the keybindings dialog.
+pub const COMMANDS: [Command; 30] = [
+    Command::MoveUp,
+    Command::MoveDown,
+    Command::MoveLeft,
+    Command::MoveRight,
+    Command::Run,
+    Command::Jump,
+
+    Command::Interact,
+    Command::UseAbility,
+    Command::UseItem,
+    Command::Abilities,
+    Command::Inventory,
+
+    Command::Hotbar(0),
+    Command::Hotbar(1),
+    Command::Hotbar(2),
+    Command::Hotbar(3),
+    Command::Hotbar(4),
+    Command::Hotbar(5),
+    Command::Hotbar(6),
+    Command::Hotbar(7),
+    Command::Hotbar(8),
+    Command::HotbarNext,
+    Command::HotbarPrev,
+
+    Command::ShowControls,
+    Command::ShowMenu,
+    Command::ToggleCursor,
+    Command::Chat,
+    Command::ChatCommand,
+    Command::Cancel,
+    Command::DebugShowPanel,
+    Command::DebugTest,
+];
+
+const HOTBAR_NAMES: [&'static str; 9] = [
+    "hotbar_1", "hotbar_2", "hotbar_3", "hotbar_4", "hotbar_5",
+    "hotbar_6", "hotbar_7", "hotbar_8", "hotbar_9",
+];
+
+const HOTBAR_UI_NAMES: [&'static str; 9] = [
+    "Slot 1", "Slot 2", "Slot 3", "Slot 4", "Slot 5",
+    "Slot 6", "Slot 7", "Slot 8", "Slot 9",
+];
+
+impl Command {
+    /// The name used for this command in the `keybindings` config.  NB: keep these in sync with
+    /// client/js/config.js and the key handler in client/js/main.js
+    pub fn name(self) -> &'static str {
+        use self::Command::*;
+        match self {
+            MoveLeft => "move_left",
+            MoveRight => "move_right",
+            MoveUp => "move_up",
+            MoveDown => "move_down",
+            Run => "run",
+            Jump => "jump",
+
+            Interact => "interact",
+            UseAbility => "use_ability",
+            UseItem => "use_item",
+            Abilities => "abilities",
+            Inventory => "inventory",
+
+            Hotbar(idx) => HOTBAR_NAMES[idx as usize],
+            HotbarNext => "hotbar_next",
+            HotbarPrev => "hotbar_prev",
+
+            ShowControls => "show_controls",
+            ShowMenu => "show_menu",
+            ToggleCursor => "toggle_cursor",
+            Chat => "chat",
+            ChatCommand => "chat_command",
+            Cancel => "cancel",
+            DebugShowPanel => "debug_show_panel",
+            DebugTest => "debug_test",
+        }
+    }
+
+    pub fn ui_name(self) -> &'static str {
+        use self::Command::*;
+        match self {
+            MoveLeft => "Left",
+            MoveRight => "Right",
+            MoveUp => "Up",
+            MoveDown => "Down",
+            Run => "Run",
+            Jump => "Jump",
+
+            Interact => "Interact",
+            UseAbility => "Use Ability",
+            UseItem => "Use Item",
+            Abilities => "Open Abilities",
+            Inventory => "Open Inventory",
+
+            Hotbar(idx) => HOTBAR_UI_NAMES[idx as usize],
+            HotbarNext => "Next Slot",
+            HotbarPrev => "Previous Slot",
+
+            ShowControls => "Show Controls",
+            ShowMenu => "Open Menu",
+            ToggleCursor => "Placement Cursor",
+            Chat => "Open Chat",
+            ChatCommand => "Start Chat Command",
+            Cancel => "Cancel",
+            DebugShowPanel => "Debug Panel",
+            DebugTest => "Debug Test",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Command> {
+        COMMANDS.iter().cloned().find(|c| c.name() == name)
+    }
+}
+
+
+/// Maps physical key codes to commands.  Each key has at most one command, but a command can
+/// have several keys.
+pub struct Keybindings {
+    map: [Option<Command>; 256],
+}
+
+impl Keybindings {
+    pub fn new() -> Keybindings {
+        Keybindings {
+            map: [None; 256],
+        }
+    }
+
+    pub fn init<C: Config>(&mut self, cfg: &C) {
+        for code in 0 .. 256 {
+            let name = cfg.get_str(ConfigKey::Keybinding(code as u8));
+            self.map[code] = Command::from_name(&name);
+        }
+    }
+
+    pub fn get(&self, code: u8) -> Option<Command> {
+        self.map[code as usize]
+    }
+
+    /// Get the first key bound to `cmd`, if any.
+    pub fn key_for(&self, cmd: Command) -> Option<u8> {
+        self.map.iter().position(|&c| c == Some(cmd)).map(|code| code as u8)
+    }
+
+    /// Bind `code` to `cmd`, replacing any other keys bound to `cmd`.  If `code` was bound to a
+    /// different command, that binding is removed, and the old command is returned.
+    pub fn bind<C: Config>(&mut self, cfg: &mut C, cmd: Command, code: u8) -> Option<Command> {
+        let old = self.map[code as usize];
+
+        for i in 0 .. 256 {
+            if self.map[i] == Some(cmd) && i != code as usize {
+                self.map[i] = None;
+                cfg.set_str(ConfigKey::Keybinding(i as u8), "");
+            }
+        }
+
+        self.map[code as usize] = Some(cmd);
+        cfg.set_str(ConfigKey::Keybinding(code), cmd.name());
+
+        if old != Some(cmd) { old } else { None }
+    }
+}
+
+
+/// Get a short display name for a key code.  These follow client/js/util/keynames.js, except
+/// that arrow keys are spelled out.
+pub fn key_name(code: u8) -> String {
+    match code {
+        37 => "Left".into(),
+        39 => "Right".into(),
+        38 => "Up".into(),
+        40 => "Down".into(),
+
+        16 => "Shift".into(),
+        17 => "Ctrl".into(),
+        18 => "Alt".into(),
+
+        13 => "Enter".into(),
+        32 => "Space".into(),
+        9 => "Tab".into(),
+        8 => "Bksp".into(),
+        27 => "Esc".into(),
+
+        191 => "/".into(),
+
+        112 ... 123 => format!("F{}", code - 111),
+        b'A' ... b'Z' | b'0' ... b'9' => (code as char).to_string(),
+
+        _ => format!("#{}", code),
+    }
+}
@@ -37,6 +37,8 @@ mod terrain;
 mod structures;
 pub mod entity;
 pub mod inventory;
+mod gamepad;
+mod keys;
 mod misc;
 mod predict;
 mod debug;
@@ -44,6 +46,8 @@ mod debug;
 pub mod graphics;
 pub mod ui;
 
+#[cfg(test)] mod tests;
+
 // TODO: change this to u32 (requires adjustment of server timing so that `now` is not negative
 // just after startup)
 pub type Time = i32;
@@ -2,11 +2,15 @@ use std::prelude::v1::*;
 
 use Time;
 use data::Data;
+use gamepad::Gamepad;
+use keys::Keybindings;
 use platform::{Config, ConfigKey};
 
 /// Miscellaneous client state
 pub struct Misc {
     pub hotbar: Hotbar,
+    pub keys: Keybindings,
+    pub gamepad: Gamepad,
     pub day_night: DayNight,
     pub plane_is_dark: bool,
     pub show_cursor: bool,
@@ -16,6 +20,8 @@ impl Misc {
     pub fn new() -> Misc {
         Misc {
             hotbar: Hotbar::new(),
+            keys: Keybindings::new(),
+            gamepad: Gamepad::new(),
             day_night: DayNight::new(),
             plane_is_dark: false,
             show_cursor: false,
@@ -26,17 +32,23 @@ impl Misc {
 
 #[derive(Clone, Copy)]
 struct HotbarSlot {
+    /// Item ID, or action ID if `is_action` is set.
     item_id: u16,
     is_ability: bool,
+    is_action: bool,
 }
 
 impl HotbarSlot {
     fn is_item(&self) -> bool {
-        self.item_id != 0 && !self.is_ability
+        self.item_id != 0 && !self.is_ability && !self.is_action
     }
 
     fn is_ability(&self) -> bool {
-        self.item_id != 0 && self.is_ability
+        self.item_id != 0 && self.is_ability && !self.is_action
+    }
+
+    fn is_action(&self) -> bool {
+        self.item_id != 0 && self.is_action
     }
 
     fn is_empty(&self) -> bool {
@@ -48,25 +60,32 @@ pub struct Hotbar {
     slots: [HotbarSlot; 9],
     cur_item: i8,
     cur_ability: i8,
+    /// The most recently selected slot, used as the starting point for `cycle`.
+    last_selected: u8,
 }
 
 impl Hotbar {
     pub fn new() -> Hotbar {
         Hotbar {
-            slots: [HotbarSlot { item_id: 0, is_ability: false }; 9],
+            slots: [HotbarSlot { item_id: 0, is_ability: false, is_action: false }; 9],
             cur_item: -1,
             cur_ability: -1,
+            last_selected: 0,
         }
     }
 
     pub fn init<C: Config>(&mut self, cfg: &C, data: &Data) {
         for i in 0 .. 9 {
             let name = cfg.get_str(ConfigKey::HotbarItemName(i as u8));
-            let item_id = data.find_item_id(&name).unwrap_or(0);
             let is_item = cfg.get_int(ConfigKey::HotbarIsItem(i as u8)) != 0;
+            let is_action = cfg.get_int(ConfigKey::HotbarIsAction(i as u8)) != 0;
+            let item_id =
+                if is_action { data.find_action_id(&name).unwrap_or(0) }
+                else { data.find_item_id(&name).unwrap_or(0) };
 
             self.slots[i].item_id = item_id;
             self.slots[i].is_ability = !is_item;
+            self.slots[i].is_action = is_action;
         }
 
         let idx = cfg.get_int(ConfigKey::HotbarActiveItem);
@@ -94,6 +113,10 @@ impl Hotbar {
         self.slots[idx as usize].is_ability()
     }
 
+    pub fn is_action(&self, idx: u8) -> bool {
+        self.slots[idx as usize].is_action()
+    }
+
     pub fn active_item_index(&self) -> Option<u8> {
         if self.cur_item >= 0 && self.cur_item < 9 {
             Some(self.cur_item as u8)
@@ -142,11 +165,38 @@ impl Hotbar {
 
         self.slots[idx as usize].item_id = item_id;
         self.slots[idx as usize].is_ability = is_ability;
+        self.slots[idx as usize].is_action = false;
 
         let name = data.item_def(item_id).name();
         cfg.set_str(ConfigKey::HotbarItemName(idx), name);
         cfg.set_int(ConfigKey::HotbarIsItem(idx), (!is_ability) as i32);
+        cfg.set_int(ConfigKey::HotbarIsAction(idx), 0);
+
+        self.check_active(cfg, idx);
+    }
+
+    pub fn set_action_slot<C: Config>(&mut self,
+                                      data: &Data,
+                                      cfg: &mut C,
+                                      idx: u8,
+                                      action_id: u16) {
+        if idx >= 9 {
+            return;
+        }
+
+        self.slots[idx as usize].item_id = action_id;
+        self.slots[idx as usize].is_ability = false;
+        self.slots[idx as usize].is_action = true;
 
+        let name = data.action_def(action_id).name();
+        cfg.set_str(ConfigKey::HotbarItemName(idx), name);
+        cfg.set_int(ConfigKey::HotbarIsItem(idx), 0);
+        cfg.set_int(ConfigKey::HotbarIsAction(idx), 1);
+
+        self.check_active(cfg, idx);
+    }
+
+    fn check_active<C: Config>(&mut self, cfg: &mut C, idx: u8) {
         // Ensure cur_item and cur_ability are still valid
         if self.cur_item == idx as i8 && !self.slots[idx as usize].is_item() {
             self.cur_item = -1;
@@ -159,12 +209,15 @@ impl Hotbar {
         }
     }
 
+    /// Select hotbar slot `idx`.  Item and ability slots become active; for an action slot,
+    /// this returns the action ID so the caller can send it to the server.
     pub fn select<C: Config>(&mut self,
                              cfg: &mut C,
-                             idx: u8) {
+                             idx: u8) -> Option<u16> {
         if idx >= 9 {
-            return;
+            return None;
         }
+        self.last_selected = idx;
 
         let slot = &self.slots[idx as usize];
         if slot.is_item() {
@@ -173,8 +226,28 @@ impl Hotbar {
         } else if slot.is_ability() {
             self.cur_ability = idx as i8;
             cfg.set_int(ConfigKey::HotbarActiveAbility, idx as i32);
+        } else if slot.is_action() {
+            return Some(slot.item_id);
         }
         // Otherwise, it's an empty slot, so do nothing.
+        None
+    }
+
+    /// Select the next item or ability slot after the most recently selected one, or the
+    /// previous one if `delta` is negative.  Empty slots and actions are skipped.
+    pub fn cycle<C: Config>(&mut self,
+                            cfg: &mut C,
+                            delta: i8) {
+        let step = if delta < 0 { 8 } else { 1 };
+        let mut idx = self.last_selected;
+        for _ in 0 .. 9 {
+            idx = (idx + step) % 9;
+            let slot = self.slots[idx as usize];
+            if slot.is_item() || slot.is_ability() {
+                self.select(cfg, idx);
+                return;
+            }
+        }
     }
 
 }
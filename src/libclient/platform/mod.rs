@@ -21,6 +21,12 @@ pub trait Platform {
                       dest_inv: InventoryId,
                       dest_slot: usize,
                       amount: u8);
+
+    fn send_use_action(&mut self, action_id: u16);
+
+    /// Run a command that the client library doesn't handle itself, such as a gamepad button
+    /// bound to `interact`.  `name` is the command name used in the `keybindings` config.
+    fn run_command(&mut self, name: &str, down: bool);
 }
 
 
@@ -49,6 +55,12 @@ pub trait PlatformObj {
                       dest_inv: InventoryId,
                       dest_slot: usize,
                       amount: u8);
+
+    fn send_use_action(&mut self, action_id: u16);
+
+    /// Run a command that the client library doesn't handle itself, such as a gamepad button
+    /// bound to `interact`.  `name` is the command name used in the `keybindings` config.
+    fn run_command(&mut self, name: &str, down: bool);
 }
 
 impl<P: Platform> PlatformObj for P {
@@ -73,6 +85,14 @@ impl<P: Platform> PlatformObj for P {
                       amount: u8) {
         Platform::send_move_item(self, src_inv, src_slot, dest_inv, dest_slot, amount);
     }
+
+    fn send_use_action(&mut self, action_id: u16) {
+        Platform::send_use_action(self, action_id);
+    }
+
+    fn run_command(&mut self, name: &str, down: bool) {
+        Platform::run_command(self, name, down);
+    }
 }
 
 
@@ -80,8 +100,11 @@ pub enum ConfigKey {
     DebugShowPanel,
     HotbarItemName(u8),
     HotbarIsItem(u8),
+    HotbarIsAction(u8),
     HotbarActiveItem,
     HotbarActiveAbility,
+    Keybinding(u8),
+    GamepadBinding(u8),
     ScaleWorld,
 }
 
@@ -92,8 +115,11 @@ impl ConfigKey {
             DebugShowPanel => "debug_show_panel".into(),
             HotbarItemName(idx) => format!("hotbar.names.{}", idx),
             HotbarIsItem(idx) => format!("hotbar.is_item.{}", idx),
+            HotbarIsAction(idx) => format!("hotbar.is_action.{}", idx),
             HotbarActiveItem => "hotbar.active_item".into(),
             HotbarActiveAbility => "hotbar.active_ability".into(),
+            Keybinding(code) => format!("keybindings.{}", code),
+            GamepadBinding(button) => format!("gamepad_bindings.{}", button),
             ScaleWorld => "scale_world".into(),
         }
     }
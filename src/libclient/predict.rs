@@ -3,7 +3,8 @@ use std::collections::VecDeque;
 use std::u16;
 
 use physics::v3::{V3, Vn, scalar};
-use physics::{self, ShapeSource};
+use physics::{self, ShapeSource, EntityBox};
+use physics::collision_groups::{self, PAWN};
 
 use Time;
 use data::Data;
@@ -75,18 +76,24 @@ impl Predictor {
         self.stale = true;
     }
 
-    pub fn input<S>(&mut self, time: Time, dir: V3, shape: &S, data: &Data)
+    pub fn input<S>(&mut self,
+                    time: Time,
+                    dir: V3,
+                    shape: &S,
+                    entities: &[EntityBox],
+                    data: &Data)
             where S: ShapeSource {
         let input = Input { time: time, dir: dir };
         play_input(&mut self.motion,
                    &mut self.cur_dir,
                    &input,
                    shape,
+                   entities,
                    data);
         self.inputs.push_back(input);
     }
 
-    pub fn update<S>(&mut self, now: Time, shape: &S, data: &Data)
+    pub fn update<S>(&mut self, now: Time, shape: &S, entities: &[EntityBox], data: &Data)
             where S: ShapeSource {
         if self.stale {
             // Replay all inputs.
@@ -96,6 +103,7 @@ impl Predictor {
                            &mut self.cur_dir,
                            input,
                            shape,
+                           entities,
                            data);
             }
             self.stale = false;
@@ -103,10 +111,11 @@ impl Predictor {
 
         while self.motion.end_time < now {
             self.motion = predict(shape,
+                                  entities,
                                   data,
+                                  &self.motion,
                                   self.motion.end_pos,
                                   self.motion.end_time,
-                                  self.motion.anim_id,
                                   self.cur_dir);
         }
     }
@@ -120,37 +129,58 @@ fn play_input<S>(motion: &mut Motion,
                  dir: &mut V3,
                  input: &Input,
                  shape: &S,
+                 entities: &[EntityBox],
                  data: &Data)
         where S: ShapeSource {
     // Play forward until the time of the input event.
     while motion.end_time < input.time {
         *motion = predict(shape,
+                          entities,
                           data,
+                          motion,
                           motion.end_pos,
                           motion.end_time,
-                          motion.anim_id,
                           *dir);
     }
 
     // Play the input event.
     *dir = input.dir;
     *motion = predict(shape,
+                      entities,
                       data,
+                      motion,
                       motion.pos(input.time),
                       input.time,
-                      motion.anim_id,
                       *dir);
 }
 
+/// Predict the motion that follows `old_motion`, starting at `start_pos` and `start_time`.
 fn predict<S: ShapeSource>(shape: &S,
+                           entities: &[EntityBox],
                            data: &Data,
+                           old_motion: &Motion,
                            start_pos: V3,
                            start_time: Time,
-                           old_anim: u16,
                            target_velocity: V3) -> Motion {
     // TODO: hardcoded constant
     let size = V3::new(32, 32, 64);
-    let (mut end_pos, mut dur) = physics::collide(shape, start_pos, size, target_velocity);
+    // The predicted entity is always the player's own pawn.
+    let mask = collision_groups::mask(PAWN);
+    let old_anim = old_motion.anim_id;
+    let momentum = physics::Momentum::from_motion(old_motion.start_pos,
+                                                  old_motion.end_pos,
+                                                  (old_motion.end_time -
+                                                   old_motion.start_time) as i32);
+    let swimming = physics::surface_at(shape, start_pos, size).is_liquid();
+    // NB: keep this in sync with server/physics.rs  entity_boxes()
+    let reach = physics::entity_reach(start_pos, size);
+    let entities = entities.iter()
+        .filter(|b| reach.overlaps(b.bounds))
+        .cloned()
+        .collect::<Vec<_>>();
+    let (mut end_pos, mut dur) = physics::collide_with_entities(shape, &entities, mask,
+                                                                start_pos, size, target_velocity,
+                                                                momentum);
 
 
     // NB: keep this in sync with server/physics.rs
@@ -164,7 +194,11 @@ fn predict<S: ShapeSource>(shape: &S,
     }
 
     // TODO: hardcoded constant
-    let speed = target_velocity.abs().max() / 50;
+    // NB: keep this in sync with data/extras.py  gen_physics_anim_table()
+    const SWIM_ANIM_ROW: i32 = 4;
+    let speed =
+        if swimming { SWIM_ANIM_ROW }
+        else { target_velocity.reduce().abs().max() / 50 };
     let old_dir = data.anim_dir(old_anim);
     let new_anim =
         if old_dir.is_none() && end_pos == start_pos {
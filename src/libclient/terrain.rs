@@ -1,16 +1,19 @@
 use std::mem;
 
 use physics::v3::{V3, V2, Vn, scalar, Region};
-use physics::{Shape, ShapeSource};
+use physics::{Shape, ShapeSource, Surface};
 use physics::{CHUNK_SIZE, CHUNK_BITS, CHUNK_MASK};
 
 pub const NUM_LAYERS: usize = 4;
 
 pub type ShapeChunk = [Shape; 1 << (3 * CHUNK_BITS)];
+pub type SurfaceChunk = [Surface; 1 << (3 * CHUNK_BITS)];
 
 pub struct ChunkShape {
     layers: [ShapeChunk; NUM_LAYERS],
     merged: ShapeChunk,
+    /// Surfaces of the terrain blocks (layer 0).  Structures have no surfaces of their own.
+    surfaces: SurfaceChunk,
 }
 
 impl ChunkShape {
@@ -24,6 +27,10 @@ impl ChunkShape {
         for shape in self.merged.iter_mut() {
             *shape = Shape::Empty;
         }
+
+        for surface in self.surfaces.iter_mut() {
+            *surface = Surface::normal();
+        }
     }
 
     fn refresh(&mut self, bounds: Region) {
@@ -51,6 +58,32 @@ impl ChunkShape {
         self.refresh(inner_bounds);
     }
 
+    fn set_surface_in_region_by<F>(&mut self, bounds: Region, f: F)
+            where F: Fn(V3) -> Surface {
+        let chunk_bounds = Region::new(scalar(0), scalar(CHUNK_SIZE));
+        for pos in bounds.intersect(chunk_bounds).points() {
+            self.surfaces[chunk_bounds.index(pos)] = f(pos);
+        }
+    }
+
+    // NB: keep this in sync with server/cache.rs  compute_shape()
+    fn get_surface(&self, idx: usize) -> Surface {
+        let mut shape = self.layers[0][idx];
+        let mut covered = false;
+        for layer in self.layers.iter().skip(1) {
+            if shape_overrides(shape, layer[idx]) {
+                shape = layer[idx];
+                covered |= !shape.is_empty();
+            }
+        }
+
+        if covered {
+            Surface::normal()
+        } else {
+            self.surfaces[idx]
+        }
+    }
+
     fn find_ceiling(&self, pos: V3) -> i32 {
         let chunk_bounds = Region::new(scalar(0), scalar(CHUNK_SIZE));
         for z in pos.z + 1 .. CHUNK_SIZE {
@@ -90,20 +123,36 @@ impl ShapeSource for TerrainShape {
             return Shape::Solid;
         }
 
-        let tile = pos & scalar(CHUNK_MASK);
-        let chunk = (pos.reduce() >> CHUNK_BITS) & scalar(LOCAL_MASK);
+        let (chunk, idx) = self.index(pos);
+        chunk.merged[idx]
+    }
 
-        let local_bounds = Region::<V2>::new(scalar(0), scalar(LOCAL_SIZE));
-        let chunk_bounds = Region::new(scalar(0), scalar(CHUNK_SIZE));
+    fn get_surface(&self, pos: V3) -> Surface {
+        if pos.z < 0 || pos.z >= CHUNK_SIZE {
+            return Surface::normal();
+        }
 
-        self.chunks[local_bounds.index(chunk)].merged[chunk_bounds.index(tile)]
+        let (chunk, idx) = self.index(pos);
+        chunk.get_surface(idx)
     }
 }
 
 impl TerrainShape {
     pub fn new() -> TerrainShape {
-        // 0 == Shape::Empty
-        unsafe { mem::zeroed() }
+        // 0 == Shape::Empty, but surfaces must be set explicitly.
+        let mut shape: TerrainShape = unsafe { mem::zeroed() };
+        shape.clear();
+        shape
+    }
+
+    fn index(&self, pos: V3) -> (&ChunkShape, usize) {
+        let tile = pos & scalar(CHUNK_MASK);
+        let chunk = (pos.reduce() >> CHUNK_BITS) & scalar(LOCAL_MASK);
+
+        let local_bounds = Region::<V2>::new(scalar(0), scalar(LOCAL_SIZE));
+        let chunk_bounds = Region::new(scalar(0), scalar(CHUNK_SIZE));
+
+        (&self.chunks[local_bounds.index(chunk)], chunk_bounds.index(tile))
     }
 
     pub fn clear(&mut self) {
@@ -133,6 +182,20 @@ impl TerrainShape {
         self.set_shape_in_region_by(bounds, layer, |_pos| shape);
     }
 
+    /// Set the surfaces of the terrain blocks in `bounds`.
+    pub fn set_surface_in_region_by<F>(&mut self, bounds: Region, f: F)
+            where F: Fn(V3) -> Surface {
+        let cpos_bounds = bounds.reduce().div_round_signed(CHUNK_SIZE);
+        let local_bounds = Region::new(scalar(0), scalar(LOCAL_SIZE));
+        for cpos in cpos_bounds.points() {
+            let adj = (cpos * scalar(CHUNK_SIZE)).extend(0);
+            let adj_bounds = bounds - adj;
+            let cpos = cpos & scalar(LOCAL_MASK);
+            self.chunks[local_bounds.index(cpos)].set_surface_in_region_by(
+                adj_bounds, |pos| f(pos + adj));
+        }
+    }
+
     pub fn find_ceiling(&self, pos: V3) -> i32 {
         let cpos = pos.reduce().div_floor(scalar(CHUNK_SIZE));
         let offset = pos - (cpos * scalar(CHUNK_SIZE)).extend(0);
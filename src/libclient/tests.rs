@@ -0,0 +1,54 @@
+use std::prelude::v1::*;
+
+use gamepad::Gamepad;
+use keys::Command;
+use keys::Command::*;
+
+
+/// Get the commands held with the stick at `x, y` and no buttons pressed.
+fn stick(x: i32, y: i32) -> Vec<Command> {
+    let mut pad = Gamepad::new();
+    let (pressed, released) = pad.update(0, x, y);
+    assert_eq!(released, vec![]);
+    pressed
+}
+
+#[test]
+fn stick_deadzone() {
+    assert_eq!(stick(0, 0), vec![]);
+    assert_eq!(stick(200, 0), vec![]);
+    assert_eq!(stick(0, -249), vec![]);
+    // Small deflections on both axes add up.
+    assert_eq!(stick(150, 150), vec![]);
+    assert_eq!(stick(200, 200), vec![MoveRight, MoveDown]);
+
+    assert_eq!(stick(260, 0), vec![MoveRight]);
+    assert_eq!(stick(0, -300), vec![MoveUp]);
+}
+
+#[test]
+fn stick_directions() {
+    // Within 22.5 degrees of an axis, only that axis counts.
+    assert_eq!(stick(600, 200), vec![MoveRight]);
+    assert_eq!(stick(-200, 600), vec![MoveDown]);
+    // Beyond that, it's a diagonal.
+    assert_eq!(stick(600, 300), vec![MoveRight, MoveDown]);
+    assert_eq!(stick(-400, -400), vec![MoveLeft, MoveUp]);
+}
+
+#[test]
+fn stick_run() {
+    assert_eq!(stick(840, 0), vec![MoveRight]);
+    assert_eq!(stick(860, 0), vec![MoveRight, Run]);
+    assert_eq!(stick(-700, 700), vec![MoveLeft, MoveDown, Run]);
+}
+
+#[test]
+fn stick_release() {
+    let mut pad = Gamepad::new();
+    assert_eq!(pad.update(0, 500, 0), (vec![MoveRight], vec![]));
+    assert_eq!(pad.update(0, 500, 0), (vec![], vec![]));
+    assert_eq!(pad.update(0, 500, 500), (vec![MoveDown], vec![]));
+    // A neutral update, like the one sent while a dialog is open, releases everything.
+    assert_eq!(pad.update(0, 0, 0), (vec![], vec![MoveRight, MoveDown]));
+}
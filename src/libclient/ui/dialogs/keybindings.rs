@@ -0,0 +1,147 @@
+use std::prelude::v1::*;
+use std::cmp;
+
+use physics::v3::{V2, Region};
+
+use client::ClientObj;
+use fonts::{self, FontMetricsExt};
+use keys::{self, Command, COMMANDS};
+use ui::geom::Geom;
+use ui::input::{KeyAction, EventStatus};
+use ui::widget::*;
+
+
+const ROWS: usize = 15;
+const NAME_WIDTH: i32 = 80;
+const KEY_WIDTH: i32 = 40;
+const COLUMN_WIDTH: i32 = NAME_WIDTH + KEY_WIDTH + 8;
+const ROW_HEIGHT: i32 = 10;
+
+pub struct Keybindings {
+    focus: usize,
+    /// Set while waiting for the user to press the new key for the focused command.
+    capturing: bool,
+    /// The key and command that blocked the most recent rebinding, if any.  Keys that are already
+    /// in use must be unbound (by giving their command a different key) before they can be
+    /// reused, so no command ever loses its key by accident.
+    conflict: Option<(u8, Command)>,
+}
+
+impl Keybindings {
+    pub fn new() -> Keybindings {
+        Keybindings {
+            focus: 0,
+            capturing: false,
+            conflict: None,
+        }
+    }
+
+    pub fn capturing(&self) -> bool {
+        self.capturing
+    }
+
+    fn move_focus(&mut self, delta: i32) {
+        let focus = self.focus as i32 + delta;
+        self.focus = cmp::max(0, cmp::min(COMMANDS.len() as i32 - 1, focus)) as usize;
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct KeybindingsDyn<'a> {
+    keys: &'a keys::Keybindings,
+}
+
+impl<'a> KeybindingsDyn<'a> {
+    pub fn new(keys: &'a keys::Keybindings) -> KeybindingsDyn<'a> {
+        KeybindingsDyn {
+            keys: keys,
+        }
+    }
+}
+
+impl<'a, 'b> Widget for WidgetPack<'a, Keybindings, KeybindingsDyn<'b>> {
+    fn size(&mut self) -> V2 {
+        let cols = (COMMANDS.len() + ROWS - 1) / ROWS;
+        V2::new(cols as i32 * COLUMN_WIDTH, (ROWS + 1) as i32 * ROW_HEIGHT)
+    }
+
+    fn walk_layout<V: Visitor>(&mut self, _v: &mut V, _pos: V2) {
+        // No children
+    }
+
+    fn render(&mut self, geom: &mut Geom, rect: Region<V2>) {
+        let font = &fonts::NAME;
+
+        for (i, &cmd) in COMMANDS.iter().enumerate() {
+            let base = rect.min + V2::new((i / ROWS) as i32 * COLUMN_WIDTH,
+                                          (i % ROWS) as i32 * ROW_HEIGHT);
+
+            let name =
+                if i == self.state.focus { format!("> {}", cmd.ui_name()) }
+                else { format!("  {}", cmd.ui_name()) };
+            geom.draw_str(font, &name, base);
+
+            let key =
+                if i == self.state.focus && self.state.capturing {
+                    "...".to_owned()
+                } else {
+                    match self.dyn.keys.key_for(cmd) {
+                        Some(code) => keys::key_name(code),
+                        None => "---".to_owned(),
+                    }
+                };
+            let key_width = font.measure_width(&key) as i32;
+            geom.draw_str(font, &key, base + V2::new(NAME_WIDTH + KEY_WIDTH - key_width, 0));
+        }
+
+        let status =
+            if self.state.capturing {
+                format!("Press a key for {}", COMMANDS[self.state.focus].ui_name())
+            } else if let Some((code, cmd)) = self.state.conflict {
+                format!("{} is already used for {}", keys::key_name(code), cmd.ui_name())
+            } else {
+                "Enter: change key".to_owned()
+            };
+        geom.draw_str(font, &status, rect.min + V2::new(0, ROWS as i32 * ROW_HEIGHT));
+    }
+
+    fn on_key(&mut self, key: KeyAction) -> EventStatus {
+        use ui::input::KeyAction::*;
+
+        if self.state.capturing {
+            let code = match key {
+                RawKey(code) => code,
+                _ => return EventStatus::Handled,
+            };
+
+            let cmd = COMMANDS[self.state.focus];
+            self.state.capturing = false;
+            match self.dyn.keys.get(code) {
+                // The Cancel key aborts.  This also means Cancel itself can only be moved to a
+                // different key, never left unbound.
+                Some(Command::Cancel) => return EventStatus::Handled,
+                Some(old) if old != cmd => {
+                    self.state.conflict = Some((code, old));
+                    return EventStatus::Handled;
+                },
+                _ => {},
+            }
+            return EventStatus::Action(box move |c: &mut ClientObj| {
+                c.handle_bind_key(cmd, code);
+            });
+        }
+
+        match key {
+            MoveUp(amt) => self.state.move_focus(-(amt as i32)),
+            MoveDown(amt) => self.state.move_focus(amt as i32),
+            MoveLeft(_) => self.state.move_focus(-(ROWS as i32)),
+            MoveRight(_) => self.state.move_focus(ROWS as i32),
+            Select => {
+                self.state.capturing = true;
+                self.state.conflict = None;
+            },
+            _ => return EventStatus::Unhandled,
+        }
+        EventStatus::Handled
+    }
+}
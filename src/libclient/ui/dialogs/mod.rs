@@ -2,6 +2,7 @@ use std::prelude::v1::*;
 use physics::v3::{V2, scalar, Region};
 
 use inventory::{Inventories, InventoryId};
+use keys;
 use ui::dialog;
 use ui::geom::Geom;
 use ui::input::{KeyAction, EventStatus};
@@ -9,9 +10,11 @@ use ui::widget::*;
 
 
 mod inventory;
+mod keybindings;
 
 pub use self::inventory::{Inventory, InventoryDyn};
 pub use self::inventory::{Container, ContainerDyn};
+pub use self::keybindings::{Keybindings, KeybindingsDyn};
 
 
 pub enum AnyDialog {
@@ -19,6 +22,7 @@ pub enum AnyDialog {
     Inventory(Inventory),
     Ability(Inventory),
     Container(Container),
+    Keybindings(Keybindings),
 }
 
 impl AnyDialog {
@@ -38,6 +42,18 @@ impl AnyDialog {
                      inv_id2: InventoryId) -> AnyDialog {
         AnyDialog::Container(Container::new(inv_id1, inv_id2))
     }
+
+    pub fn keybindings() -> AnyDialog {
+        AnyDialog::Keybindings(Keybindings::new())
+    }
+
+    /// Check if the dialog wants raw key codes instead of the usual `KeyAction`s.
+    pub fn wants_raw_keys(&self) -> bool {
+        match *self {
+            AnyDialog::Keybindings(ref k) => k.capturing(),
+            _ => false,
+        }
+    }
 }
 
 impl dialog::Inner for AnyDialog {
@@ -47,6 +63,7 @@ impl dialog::Inner for AnyDialog {
             AnyDialog::Inventory(_) => "Inventory",
             AnyDialog::Ability(_) => "Abilities",
             AnyDialog::Container(_) => "Container",
+            AnyDialog::Keybindings(_) => "Controls",
         }
     }
 
@@ -61,12 +78,15 @@ impl dialog::Inner for AnyDialog {
 #[derive(Clone, Copy)]
 pub struct AnyDialogDyn<'a> {
     inventories: &'a Inventories,
+    keys: &'a keys::Keybindings,
 }
 
 impl<'a> AnyDialogDyn<'a> {
-    pub fn new(inventories: &'a Inventories) -> AnyDialogDyn<'a> {
+    pub fn new(inventories: &'a Inventories,
+               keys: &'a keys::Keybindings) -> AnyDialogDyn<'a> {
         AnyDialogDyn {
             inventories: inventories,
+            keys: keys,
         }
     }
 }
@@ -102,6 +122,13 @@ impl<'a, 'b> Widget for WidgetPack<'a, AnyDialog, AnyDialogDyn<'b>> {
                 let rect = Region::sized(child.size()) + pos;
                 v.visit(&mut child, rect);
             },
+
+            AnyDialog::Keybindings(ref mut state) => {
+                let dyn = KeybindingsDyn::new(self.dyn.keys);
+                let mut child = WidgetPack::new(state, dyn);
+                let rect = Region::sized(child.size()) + pos;
+                v.visit(&mut child, rect);
+            },
         }
     }
 
@@ -2,6 +2,7 @@ use std::prelude::v1::*;
 use std::boxed::FnBox;
 
 use client::ClientObj;
+use keys::Command;
 
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -15,30 +16,34 @@ pub enum KeyAction {
     Cancel,
 
     SetHotbar(i8),
+    CycleHotbar(i8),
 
     ToggleDebugPanel,
+
+    /// An unmapped key code.  Sent in place of the usual action while a dialog is waiting for a
+    /// key to bind.
+    RawKey(u8),
 }
 
 impl KeyAction {
-    pub fn from_code(code: u8) -> Option<KeyAction> {
+    pub fn from_command(cmd: Command, shift: bool) -> Option<KeyAction> {
         use self::KeyAction::*;
-        match code {
-            0 => Some(MoveLeft(1)),
-            1 => Some(MoveRight(1)),
-            2 => Some(MoveUp(1)),
-            3 => Some(MoveDown(1)),
-
-            10 => Some(MoveLeft(10)),
-            11 => Some(MoveRight(10)),
-            12 => Some(MoveUp(10)),
-            13 => Some(MoveDown(10)),
-
-            20 => Some(Select),
-            21 => Some(Cancel),
-
-            31 ... 39 => Some(SetHotbar(code as i8 - 31)),
-
-            114 => Some(ToggleDebugPanel),
+        let dist = if shift { 10 } else { 1 };
+        match cmd {
+            Command::MoveLeft => Some(MoveLeft(dist)),
+            Command::MoveRight => Some(MoveRight(dist)),
+            Command::MoveUp => Some(MoveUp(dist)),
+            Command::MoveDown => Some(MoveDown(dist)),
+
+            // TODO: enter key handling is a hack
+            Command::Chat => Some(Select),
+            Command::Cancel => Some(Cancel),
+
+            Command::Hotbar(idx) => Some(SetHotbar(idx as i8)),
+            Command::HotbarNext => Some(CycleHotbar(1)),
+            Command::HotbarPrev => Some(CycleHotbar(-1)),
+
+            Command::DebugShowPanel => Some(ToggleDebugPanel),
 
             _ => None,
         }
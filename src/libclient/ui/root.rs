@@ -7,6 +7,7 @@ use data::Data;
 use debug::Debug as DebugDyn;
 use fonts::{self, FontMetricsExt};
 use inventory::{Inventory, Inventories};
+use keys::Keybindings;
 use misc;
 use platform::{Config, ConfigKey};
 use ui::atlas;
@@ -39,6 +40,7 @@ pub struct RootDyn<'a> {
     pub screen_size: V2,
     pub inventories: &'a Inventories,
     pub hotbar: &'a misc::Hotbar,
+    pub keys: &'a Keybindings,
     pub debug: &'a DebugDyn,
 }
 
@@ -46,12 +48,14 @@ impl<'a> RootDyn<'a> {
     pub fn new(screen_size: (u16, u16),
                inventories: &'a Inventories,
                hotbar: &'a misc::Hotbar,
+               keys: &'a Keybindings,
                debug: &'a DebugDyn) -> RootDyn<'a> {
         RootDyn {
             screen_size: V2::new(screen_size.0 as i32,
                                  screen_size.1 as i32),
             inventories: inventories,
             hotbar: hotbar,
+            keys: keys,
             debug: debug,
         }
     }
@@ -75,7 +79,7 @@ impl<'a, 'b> Widget for WidgetPack<'a, Root, RootDyn<'b>> {
         {
             // Dialog
             let self_rect = Region::sized(self.size()) + pos;
-            let dyn = dialogs::AnyDialogDyn::new(self.dyn.inventories);
+            let dyn = dialogs::AnyDialogDyn::new(self.dyn.inventories, self.dyn.keys);
             let mut child = WidgetPack::new(&mut self.state.dialog, dyn);
             let child_rect = Region::sized(child.size());
             let rect = child_rect.align(self_rect, Align::Center, Align::Center);
@@ -139,6 +143,12 @@ impl<'a, 'b> Widget for WidgetPack<'a, Root, RootDyn<'b>> {
             }
         }
 
+        if let KeyAction::CycleHotbar(delta) = key {
+            return EventStatus::Action(box move |c: &mut ClientObj| {
+                c.handle_hotbar_cycle(delta);
+            });
+        }
+
         match self.state.dialog.inner {
             AnyDialog::None => EventStatus::Unhandled,
             _ => EventStatus::Handled,
@@ -174,7 +184,10 @@ impl<'a> hotbar::HotbarDyn for HotbarDyn<'a> {
                 is_active_ability: false,
             },
         };
-        let item_id = self.state.item_id(idx);
+        // Actions have no icon of their own.
+        let item_id =
+            if self.state.is_action(idx) { 0 }
+            else { self.state.item_id(idx) };
         let quantity =
             if self.state.is_item(idx) { Some(inv.count(item_id)) }
             else { None };
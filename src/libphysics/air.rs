@@ -0,0 +1,150 @@
+//! Vertical movement: jumping and falling.  Unlike walking, movement through the air ignores the
+//! floor-continuity rules and only checks for solid obstacles.  Entities also pass over each other
+//! in the air, so `EntityStep` is not involved here.
+//!
+//! A jump is split into two motions.  First the entity rises straight up to `JUMP_HEIGHT` above
+//! where it started.  Then, since nothing is holding it up, it falls at `FALL_SPEED`, drifting in
+//! the direction it is trying to move, until it lands on a floor, a ramp, or the top of a solid
+//! block.  Falling depends only on the current position, but rising needs to remember the height of
+//! the jump, which is recovered from the previous motion by `jump_peak`.
+use v3::{V3, Vn, Axis, Region, scalar};
+
+use super::{Shape, ShapeSource};
+use super::{TILE_SIZE, CHUNK_SIZE};
+use super::motion_duration;
+use super::walk::collide_tile;
+
+
+/// Height of a jump, in pixels.  High enough to get on top of a one-tile step with some room to
+/// spare.
+pub const JUMP_HEIGHT: i32 = 64;
+
+/// Vertical speed while rising during a jump, in pixels per second.
+pub const JUMP_SPEED: i32 = 200;
+
+/// Vertical speed while falling, in pixels per second.
+pub const FALL_SPEED: i32 = 100;
+
+/// Maximum length of a single air motion, counted in pixels along its fastest axis.  Matches the
+/// limit in `walk_path`.
+const MAX_STEPS: i32 = 500;
+
+
+/// Check if the motion from `start_pos` to `end_pos` is the rising part of a jump, and if so, get
+/// the height it is rising toward.  Only jumps move straight up; climbing a ramp always involves
+/// some horizontal movement too.
+pub fn jump_peak(start_pos: V3, end_pos: V3) -> Option<i32> {
+    if end_pos.reduce() == start_pos.reduce() && end_pos.z > start_pos.z {
+        Some(end_pos.z)
+    } else {
+        None
+    }
+}
+
+/// Compute the next motion for an entity that is in the air or is starting a jump.  Returns `None`
+/// if the entity is standing on something and should walk normally instead.
+pub fn movement<S: ShapeSource>(chunk: &S,
+                                pos: V3,
+                                size: V3,
+                                velocity: V3,
+                                jump: bool,
+                                rise_to: Option<i32>) -> Option<(V3, i32)> {
+    if let Some(top) = rise_to {
+        if pos.z < top {
+            if let Some(result) = rise(chunk, pos, size, top) {
+                return Some(result);
+            }
+        }
+    }
+
+    if !supported(chunk, pos, size) {
+        return Some(fall(chunk, pos, size, velocity));
+    }
+
+    if jump {
+        // If there's no headroom, just walk instead.
+        return rise(chunk, pos, size, pos.z + JUMP_HEIGHT);
+    }
+
+    None
+}
+
+fn rise<S: ShapeSource>(chunk: &S, pos: V3, size: V3, top: i32) -> Option<(V3, i32)> {
+    let v = V3::new(0, 0, JUMP_SPEED);
+    let end_pos = line(chunk, pos, size, v, top - pos.z, false);
+    if end_pos == pos {
+        None
+    } else {
+        Some((end_pos, motion_duration(pos, end_pos, v)))
+    }
+}
+
+fn fall<S: ShapeSource>(chunk: &S, pos: V3, size: V3, velocity: V3) -> (V3, i32) {
+    let down = V3::new(0, 0, -FALL_SPEED);
+    // If something is in the way, try dropping each horizontal component of the motion, and
+    // finally falling straight down.
+    let candidates = [velocity + down,
+                      velocity.with(Axis::X, 0) + down,
+                      velocity.with(Axis::Y, 0) + down,
+                      down];
+    for &v in &candidates {
+        let end_pos = line(chunk, pos, size, v, MAX_STEPS, true);
+        if end_pos != pos {
+            return (end_pos, motion_duration(pos, end_pos, v));
+        }
+    }
+    (pos, 0)
+}
+
+/// Move from `start` in a straight line with velocity `v`, one pixel at a time along the fastest
+/// axis, until the path is blocked or `max_steps` steps have been taken.  If `land` is set, also
+/// stop upon reaching a floor.
+fn line<S: ShapeSource>(chunk: &S, start: V3, size: V3, v: V3, max_steps: i32, land: bool) -> V3 {
+    let steps = v.abs().max();
+    let mut pos = start;
+    for i in 1 .. max_steps + 1 {
+        if land && on_floor(chunk, pos, size) {
+            break;
+        }
+        let next = start + v * scalar(i) / scalar(steps);
+        if blocked(chunk, next, size) {
+            break;
+        }
+        pos = next;
+    }
+    pos
+}
+
+
+/// Check if an entity at `pos` is standing on something, so that it won't fall.
+pub fn supported<S: ShapeSource>(chunk: &S, pos: V3, size: V3) -> bool {
+    on_floor(chunk, pos, size) || blocked(chunk, pos - V3::new(0, 0, 1), size)
+}
+
+/// Check if an entity at `pos` is standing on the surface of a `Floor` tile.  Floors have no
+/// thickness, so `blocked` never reports them.  Any part of the entity's outline resting on the
+/// floor is enough to hold it up.
+fn on_floor<S: ShapeSource>(chunk: &S, pos: V3, size: V3) -> bool {
+    if pos.z % TILE_SIZE != 0 {
+        return false;
+    }
+    let z = pos.z / TILE_SIZE;
+    let tiles = Region::new(pos, pos + size).reduce().div_round(TILE_SIZE);
+    tiles.points().any(|p| chunk.get_shape(p.extend(z)) == Shape::Floor)
+}
+
+/// Check if an entity at `pos` would overlap any solid terrain or leave the bottom or top of the
+/// world.
+fn blocked<S: ShapeSource>(chunk: &S, pos: V3, size: V3) -> bool {
+    let bounds = Region::new(pos, pos + size);
+    if bounds.min.z < 0 || bounds.max.z > CHUNK_SIZE * TILE_SIZE {
+        return true;
+    }
+
+    bounds.div_round(TILE_SIZE).points().any(|tile_pos| {
+        let tile_base_px = tile_pos * scalar(TILE_SIZE);
+        let tile_bounds = Region::new(tile_base_px, tile_base_px + scalar(TILE_SIZE));
+        let overlap = bounds.intersect(tile_bounds);
+        collide_tile(chunk.get_shape(tile_pos), overlap - tile_base_px)
+    })
+}
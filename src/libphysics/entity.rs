@@ -0,0 +1,92 @@
+//! Optional entity-versus-entity collision layer.  This wraps another `StepCallback` (normally
+//! `GroundStep`) and additionally refuses any step that would move the entity into the bounding
+//! box of another entity in a blocking collision group.
+use v3::{V3, Vn, Axis, Region, scalar};
+
+use super::{ShapeSource, StepCallback};
+use super::collision_groups::Groups;
+
+
+/// The bounding box of some other entity, in the same coordinate system as the `ShapeSource`.
+#[derive(Clone, Copy, Debug)]
+pub struct EntityBox {
+    pub bounds: Region,
+    pub groups: Groups,
+}
+
+impl EntityBox {
+    pub fn new(pos: V3, size: V3, groups: Groups) -> EntityBox {
+        EntityBox {
+            bounds: Region::new(pos, pos + size),
+            groups: groups,
+        }
+    }
+
+    /// The space covered by an entity moving in a straight line from `start` to `end`.  Blocking
+    /// this whole area keeps others from walking into the entity's path.
+    pub fn swept(start: V3, end: V3, size: V3, groups: Groups) -> EntityBox {
+        EntityBox {
+            bounds: Region::new(start, start + size).join(Region::new(end, end + size)),
+            groups: groups,
+        }
+    }
+}
+
+
+pub struct EntityStep<'a, CB> {
+    inner: CB,
+    size: V3,
+    entities: &'a [EntityBox],
+    mask: Groups,
+}
+
+impl<'a, CB: StepCallback> EntityStep<'a, CB> {
+    pub fn new(inner: CB, size: V3, entities: &'a [EntityBox], mask: Groups) -> EntityStep<'a, CB> {
+        EntityStep {
+            inner: inner,
+            size: size,
+            entities: entities,
+            mask: mask,
+        }
+    }
+
+    /// Check if moving from `pos` by `dir` would enter the box of a blocking entity.  Boxes that
+    /// already overlap the entity at `pos` are ignored, so that two entities that somehow ended
+    /// up inside each other can still walk apart.
+    fn blocked(&self, pos: V3, dir: V3) -> bool {
+        let cur = Region::new(pos, pos + self.size);
+        let next = cur + dir;
+        self.entities.iter().any(|e| {
+            e.groups.intersects(self.mask) &&
+            next.overlaps(e.bounds) &&
+            !cur.overlaps(e.bounds)
+        })
+    }
+}
+
+impl<'a, CB: StepCallback> StepCallback for EntityStep<'a, CB> {
+    fn adjust_offset<S: ShapeSource>(&self, chunk: &S, pos: V3, dir: V3) -> V3 {
+        let adj_dir = self.inner.adjust_offset(chunk, pos, dir);
+        if adj_dir == scalar(0) || !self.blocked(pos, adj_dir) {
+            return adj_dir;
+        }
+
+        // Try sliding along the other entity by dropping one horizontal component of the motion.
+        for &axis in &[Axis::X, Axis::Y] {
+            if dir.get(axis) == 0 {
+                continue;
+            }
+            let slide_dir = dir.with(axis, 0);
+            if slide_dir.reduce() == scalar(0) {
+                continue;
+            }
+
+            let adj_dir = self.inner.adjust_offset(chunk, pos, slide_dir);
+            if adj_dir != scalar(0) && !self.blocked(pos, adj_dir) {
+                return adj_dir;
+            }
+        }
+
+        scalar(0)
+    }
+}
@@ -12,12 +12,19 @@ use std::prelude::v1::*;
 #[macro_use] extern crate bitflags;
 
 use std::collections::VecDeque;
-use v3::{Vn, V3, V2, Region, scalar};
+use v3::{Vn, V3, V2, Axis, Region, scalar};
 
 
 pub mod v3;
+mod air;
+mod entity;
 mod walk;
 
+pub use air::{JUMP_HEIGHT, JUMP_SPEED, FALL_SPEED, jump_peak};
+pub use entity::EntityBox;
+
+#[cfg(test)] mod tests;
+
 
 pub const TILE_BITS: usize = 5;
 pub const TILE_SIZE: i32 = 1 << TILE_BITS;      // 32
@@ -27,6 +34,24 @@ pub const CHUNK_BITS: usize = 4;
 pub const CHUNK_SIZE: i32 = 1 << CHUNK_BITS;    // 16
 pub const CHUNK_MASK: i32 = CHUNK_SIZE - 1;
 
+/// Duration of the motion of an entity that can't move only because other entities are in the
+/// way.  Those entities might leave without the blocked entity finding out (by teleporting, for
+/// example), so it checks again after this long instead of standing still indefinitely.
+pub const ENTITY_BLOCKED_RETRY: i32 = 500;
+
+/// Entities avoid the space another entity will pass through in the next this many
+/// milliseconds.  See `EntityBox::swept`.
+pub const ENTITY_LOOKAHEAD: i32 = 500;
+
+/// A single `collide` call never moves an entity more than this many pixels along each axis.
+pub const ENTITY_REACH: i32 = 500;
+
+/// The area an entity at `pos` can cover during one `collide_with_entities` call.  Callers leave
+/// out entities whose boxes don't overlap it, so the server and client must use the same area.
+pub fn entity_reach(pos: V3, size: V3) -> Region {
+    Region::new(pos, pos + size).expand(scalar(ENTITY_REACH))
+}
+
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 #[repr(u8)]
@@ -47,8 +72,10 @@ impl Shape {
             0 => Empty,
             1 => Floor,
             2 => Solid,
+            3 => RampE,
+            4 => RampW,
+            5 => RampS,
             6 => RampN,
-            // TODO: add ramp variants once they are actually supported
             _ => return None,
         };
         Some(s)
@@ -71,9 +98,41 @@ impl Shape {
 }
 
 
+/// Movement properties of a block, which affect entities standing on it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Surface {
+    /// Walking speed, as a percentage of normal.
+    pub speed: u8,
+    pub flags: surface_flags::Flags,
+}
+
+impl Surface {
+    pub fn normal() -> Surface {
+        Surface {
+            speed: 100,
+            flags: surface_flags::Flags::empty(),
+        }
+    }
+
+    pub fn is_liquid(&self) -> bool {
+        self.flags.contains(surface_flags::LIQUID)
+    }
+
+    pub fn is_slippery(&self) -> bool {
+        self.flags.contains(surface_flags::SLIPPERY)
+    }
+}
+
+
 pub trait ShapeSource {
     fn get_shape(&self, pos: V3) -> Shape;
 
+    /// Get the movement properties of the block at `pos`.  The default treats every block as
+    /// normal ground.
+    fn get_surface(&self, _pos: V3) -> Surface {
+        Surface::normal()
+    }
+
     fn get_shape_below(&self, mut pos: V3) -> (Shape, i32) {
         while pos.z >= 0 {
             let s = self.get_shape(pos);
@@ -87,26 +146,133 @@ pub trait ShapeSource {
 }
 
 
-pub fn collide<S: ShapeSource>(chunk: &S, pos: V3, size: V3, velocity: V3) -> (V3, i32) {
+/// Get the surface an entity at `pos` is standing on.  This is the block under the middle of its
+/// outline at the level of its feet, or the block below that if that one is empty.
+pub fn surface_at<S: ShapeSource>(chunk: &S, pos: V3, size: V3) -> Surface {
+    let center = pos + size.with(Axis::Z, 0) / scalar(2);
+    let tile = center.div_floor(scalar(TILE_SIZE));
+    if chunk.get_shape(tile).is_empty() && tile.z > 0 {
+        chunk.get_surface(tile - V3::new(0, 0, 1))
+    } else {
+        chunk.get_surface(tile)
+    }
+}
+
+
+/// The parts of an entity's previous motion that carry over into its next one.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Momentum {
+    /// The height that a jump in progress is rising toward.  A jump interrupted by a change of
+    /// direction keeps rising to the same height.
+    pub rise_to: Option<i32>,
+    /// The velocity of the previous motion.  An entity on slippery ground keeps sliding at this
+    /// velocity when it stops walking.
+    pub velocity: V3,
+}
+
+impl Momentum {
+    pub fn none() -> Momentum {
+        Momentum {
+            rise_to: None,
+            velocity: scalar(0),
+        }
+    }
+
+    /// Get the momentum of an entity that moved from `start_pos` to `end_pos` over `duration`
+    /// milliseconds.
+    pub fn from_motion(start_pos: V3, end_pos: V3, duration: i32) -> Momentum {
+        let velocity =
+            if duration > 0 {
+                (end_pos - start_pos) * scalar(1000) / scalar(duration)
+            } else {
+                scalar(0)
+            };
+        Momentum {
+            rise_to: jump_peak(start_pos, end_pos),
+            velocity: velocity,
+        }
+    }
+}
+
+
+/// Compute the motion of an entity at `pos` that is trying to move with `velocity`.  Returns the
+/// end position and the duration of the motion, in milliseconds.
+///
+/// Only the sign of `velocity.z` matters: if it's positive, the entity wants to jump.  `momentum`
+/// should be computed from the entity's previous motion.
+pub fn collide<S: ShapeSource>(chunk: &S,
+                               pos: V3,
+                               size: V3,
+                               velocity: V3,
+                               momentum: Momentum) -> (V3, i32) {
     use walk::GroundStep;
 
-    if velocity == scalar(0) {
-        return (pos, core::i32::MAX);
+    collide_inner(chunk, pos, size, velocity, momentum, GroundStep::new(size))
+}
+
+/// Like `collide`, but also stop when running into any of `entities` whose groups intersect
+/// `mask`.  Entities only block walking, not jumping or falling.  If only entities keep it from
+/// moving at all, the duration is `ENTITY_BLOCKED_RETRY`.
+pub fn collide_with_entities<S: ShapeSource>(chunk: &S,
+                                             entities: &[EntityBox],
+                                             mask: collision_groups::Groups,
+                                             pos: V3,
+                                             size: V3,
+                                             velocity: V3,
+                                             momentum: Momentum) -> (V3, i32) {
+    use entity::EntityStep;
+    use walk::GroundStep;
+
+    let step = EntityStep::new(GroundStep::new(size), size, entities, mask);
+    let (end_pos, dur) = collide_inner(chunk, pos, size, velocity, momentum, step);
+    if end_pos == pos && dur == 0 && collide(chunk, pos, size, velocity, momentum).0 != pos {
+        return (pos, ENTITY_BLOCKED_RETRY);
     }
+    (end_pos, dur)
+}
 
-    let end_pos = walk_path(chunk, pos, size, velocity, GroundStep::new(size));
+fn collide_inner<S, CB>(chunk: &S,
+                        pos: V3,
+                        size: V3,
+                        velocity: V3,
+                        momentum: Momentum,
+                        cb: CB) -> (V3, i32)
+        where S: ShapeSource,
+              CB: StepCallback {
+    let surface = surface_at(chunk, pos, size);
+    let planar = velocity.with(Axis::Z, 0);
+    let jump = velocity.z > 0 && !surface.is_liquid();
+    if let Some(result) = air::movement(chunk, pos, size, planar, jump, momentum.rise_to) {
+        return result;
+    }
 
-    // Find the actual velocity after adjustment
-    let velocity_mag = velocity.abs().max();
-    let offset_mag = (end_pos - pos).abs().max();
-    let t =
-        if velocity_mag == 0 {
-            0
+    let planar =
+        if planar == scalar(0) && surface.is_slippery() {
+            // Keep sliding.  The momentum already reflects the surface speed, so don't scale it
+            // again.
+            momentum.velocity.with(Axis::Z, 0)
         } else {
-            offset_mag * 1000 / velocity_mag
+            planar * scalar(surface.speed as i32) / scalar(100)
         };
 
-    (end_pos, t)
+    if planar == scalar(0) {
+        return (pos, core::i32::MAX);
+    }
+
+    let end_pos = walk_path(chunk, pos, size, planar, cb);
+    (end_pos, motion_duration(pos, end_pos, planar))
+}
+
+/// Find the actual velocity after adjustment, and return the time it takes to get from `pos` to
+/// `end_pos`.
+fn motion_duration(pos: V3, end_pos: V3, velocity: V3) -> i32 {
+    let velocity_mag = velocity.abs().max();
+    let offset_mag = (end_pos - pos).abs().max();
+    if velocity_mag == 0 {
+        0
+    } else {
+        offset_mag * 1000 / velocity_mag
+    }
 }
 
 
@@ -114,16 +280,17 @@ trait StepCallback {
     fn adjust_offset<S: ShapeSource>(&self, chunk: &S, pos: V3, dir: V3) -> V3;
 }
 
-fn walk_path<S, CB>(chunk: &S, start_pos: V3, _size: V3, velocity: V3,
+fn walk_path<S, CB>(chunk: &S, start_pos: V3, size: V3, velocity: V3,
                     cb: CB) -> V3
         where S: ShapeSource,
               CB: StepCallback {
     let dir = velocity.signum();
     let mut pos = start_pos;
+    let surface = surface_at(chunk, start_pos, size);
 
     let mut last_adj_dir = dir;
 
-    for i in 0..500 {
+    for i in 0..ENTITY_REACH {
         // Try up to 4 times to find a direction we can move in.
         let adj_dir = cb.adjust_offset(chunk, pos, dir);
 
@@ -135,6 +302,11 @@ fn walk_path<S, CB>(chunk: &S, start_pos: V3, _size: V3, velocity: V3,
 
         last_adj_dir = adj_dir;
         pos = pos + adj_dir;
+
+        // Stop on reaching a different kind of ground, since the speed may need to change.
+        if surface_at(chunk, pos, size) != surface {
+            break;
+        }
     }
 
     pos
@@ -164,6 +336,38 @@ fn stop_fill<S: ShapeSource>(chunk: &S, pos: V3) -> bool {
     check_floor(chunk, pos) || !check_ceiling(chunk, pos)
 }
 
+pub mod surface_flags {
+    bitflags! {
+        pub flags Flags: u8 {
+            /// Entities swim instead of walking, and can't jump.
+            const LIQUID =      1 << 0,
+            /// Entities keep sliding after they stop walking.
+            const SLIPPERY =    1 << 1,
+        }
+    }
+}
+
+pub mod collision_groups {
+    bitflags! {
+        pub flags Groups: u8 {
+            /// Entities controlled by a client.
+            const PAWN =    1 << 0,
+            /// All other entities.
+            const NPC =     1 << 1,
+        }
+    }
+
+    /// Get the set of groups that block the movement of an entity in `groups`.  Pawns don't block
+    /// each other, so players can't wall off doorways.
+    pub fn mask(groups: Groups) -> Groups {
+        if groups.contains(PAWN) {
+            NPC
+        } else {
+            PAWN | NPC
+        }
+    }
+}
+
 pub mod fill_flags {
     bitflags! {
         pub flags Flags: u8 {
@@ -0,0 +1,466 @@
+use std::prelude::v1::*;
+
+use super::{Shape, ShapeSource, TILE_SIZE, EntityBox, collide, collide_with_entities};
+use super::{ENTITY_BLOCKED_RETRY, ENTITY_REACH, entity_reach};
+use super::{JUMP_HEIGHT, Momentum, Surface, jump_peak};
+use super::surface_flags::{Flags, LIQUID, SLIPPERY};
+use super::collision_groups::{self, PAWN, NPC};
+use v3::{V3, V2, Vn, Axis, scalar};
+
+
+/// Tile coordinates of the corner of the test map.  Physics assumes nothing has negative
+/// coordinates, so the map is shifted away from the origin to leave a margin of empty tiles.
+const ORIGIN: i32 = 1;
+
+/// An 8x8 test map with a single ramp crossing it.  Along the `uphill` direction, tiles 0-2 are
+/// floor at z = 0, tile 3 is the ramp, and tiles 4-7 are a plateau (solid at z = 0, floor at
+/// z = 1).  Everything outside the map is empty.
+struct RampScene {
+    uphill: V2,
+    ramp: Shape,
+}
+
+impl RampScene {
+    fn new(ramp: Shape) -> RampScene {
+        let uphill = match ramp {
+            Shape::RampE => V2::new(1, 0),
+            Shape::RampW => V2::new(-1, 0),
+            Shape::RampS => V2::new(0, 1),
+            Shape::RampN => V2::new(0, -1),
+            _ => panic!("not a ramp: {:?}", ramp),
+        };
+        RampScene {
+            uphill: uphill,
+            ramp: ramp,
+        }
+    }
+
+    /// Distance of tile `pos` from the low edge of the map, measured along `uphill`.
+    fn dist(&self, pos: V2) -> i32 {
+        match (self.uphill.x, self.uphill.y) {
+            (1, 0) => pos.x,
+            (-1, 0) => 7 - pos.x,
+            (0, 1) => pos.y,
+            (0, -1) => 7 - pos.y,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Pixel position of the tile at distance `dist` along `uphill`, in the middle of the map.
+    fn pos(&self, dist: i32, z: i32) -> V3 {
+        let tile = match (self.uphill.x, self.uphill.y) {
+            (1, 0) => V2::new(dist, 3),
+            (-1, 0) => V2::new(7 - dist, 3),
+            (0, 1) => V2::new(3, dist),
+            (0, -1) => V2::new(3, 7 - dist),
+            _ => unreachable!(),
+        };
+        (tile + scalar(ORIGIN)).extend(z) * scalar(TILE_SIZE)
+    }
+}
+
+impl ShapeSource for RampScene {
+    fn get_shape(&self, pos: V3) -> Shape {
+        let pos = pos - V3::new(ORIGIN, ORIGIN, 0);
+        if pos.x < 0 || pos.x >= 8 || pos.y < 0 || pos.y >= 8 {
+            return Shape::Empty;
+        }
+
+        match (self.dist(pos.reduce()), pos.z) {
+            (0 ... 2, 0) => Shape::Floor,
+            (3, 0) => self.ramp,
+            (4 ... 7, 0) => Shape::Solid,
+            (4 ... 7, 1) => Shape::Floor,
+            _ => Shape::Empty,
+        }
+    }
+}
+
+
+/// Keep walking with velocity `v` until the path is blocked.  `collide` stops each time the
+/// direction of motion changes (for example, at the top or bottom of a ramp), so this calls it
+/// repeatedly, the same way the server does when a motion ends.
+fn walk<S: ShapeSource>(s: &S, start: V3, v: V3) -> V3 {
+    let size = V3::new(32, 32, 64);
+    let mut pos = start;
+    for _ in 0 .. 20 {
+        let (end, _) = collide(s, pos, size, v, Momentum::none());
+        if end == pos {
+            break;
+        }
+        pos = end;
+    }
+    pos
+}
+
+fn check_up_and_down(ramp: Shape) {
+    let s = RampScene::new(ramp);
+    let v = s.uphill.extend(0) * scalar(50);
+
+    let top = walk(&s, s.pos(1, 0), v);
+    assert_eq!(top, s.pos(7, 1));
+
+    let bottom = walk(&s, s.pos(6, 1), -v);
+    assert_eq!(bottom, s.pos(0, 0));
+}
+
+#[test]
+fn walk_ramp_e() {
+    check_up_and_down(Shape::RampE);
+}
+
+#[test]
+fn walk_ramp_w() {
+    check_up_and_down(Shape::RampW);
+}
+
+#[test]
+fn walk_ramp_s() {
+    check_up_and_down(Shape::RampS);
+}
+
+#[test]
+fn walk_ramp_n() {
+    check_up_and_down(Shape::RampN);
+}
+
+#[test]
+fn walk_across_ramp() {
+    // A pawn standing on a ramp can walk along it sideways without changing height.
+    for &ramp in &[Shape::RampE, Shape::RampW, Shape::RampS, Shape::RampN] {
+        let s = RampScene::new(ramp);
+        let side = V3::new(s.uphill.y, s.uphill.x, 0);
+        // The pawn covers the whole ramp tile, so it stands at the ramp's highest point.
+        let start = s.pos(3, 1);
+
+        for &v in &[side * scalar(50), -side * scalar(50)] {
+            let end = walk(&s, start, v);
+            assert!(end != start, "{:?}: couldn't move along the ramp", ramp);
+            assert_eq!((end - start) * s.uphill.extend(1), scalar(0));
+        }
+    }
+}
+
+/// An 8x8 map of flat floor, offset by `ORIGIN` like `RampScene`.
+struct FlatScene;
+
+impl ShapeSource for FlatScene {
+    fn get_shape(&self, pos: V3) -> Shape {
+        let pos = pos - V3::new(ORIGIN, ORIGIN, 0);
+        if pos.x < 0 || pos.x >= 8 || pos.y < 0 || pos.y >= 8 || pos.z != 0 {
+            return Shape::Empty;
+        }
+        Shape::Floor
+    }
+}
+
+#[test]
+fn entity_blocks_movement() {
+    let size = V3::new(32, 32, 64);
+    let start = V3::new(ORIGIN, 3 + ORIGIN, 0) * scalar(TILE_SIZE);
+    let other = EntityBox::new(start + V3::new(128, 0, 0), size, NPC);
+    let v = V3::new(50, 0, 0);
+
+    let (end, _) = collide_with_entities(&FlatScene, &[other], collision_groups::mask(PAWN),
+                                         start, size, v, Momentum::none());
+    assert_eq!(end, start + V3::new(96, 0, 0));
+
+    // With no entities, the result matches plain `collide`.
+    assert_eq!(collide_with_entities(&FlatScene, &[], collision_groups::mask(PAWN),
+                                     start, size, v, Momentum::none()),
+               collide(&FlatScene, start, size, v, Momentum::none()));
+}
+
+#[test]
+fn entity_mask_filters_groups() {
+    let size = V3::new(32, 32, 64);
+    let start = V3::new(ORIGIN, 3 + ORIGIN, 0) * scalar(TILE_SIZE);
+    let other = EntityBox::new(start + V3::new(128, 0, 0), size, PAWN);
+    let v = V3::new(50, 0, 0);
+
+    // Pawns walk through other pawns, but NPCs don't.
+    let (end, _) = collide_with_entities(&FlatScene, &[other], collision_groups::mask(PAWN),
+                                         start, size, v, Momentum::none());
+    assert_eq!(end, collide(&FlatScene, start, size, v, Momentum::none()).0);
+
+    let (end, _) = collide_with_entities(&FlatScene, &[other], collision_groups::mask(NPC),
+                                         start, size, v, Momentum::none());
+    assert_eq!(end, start + V3::new(96, 0, 0));
+}
+
+#[test]
+fn entity_blocked_motion_is_retried() {
+    let size = V3::new(32, 32, 64);
+    let start = V3::new(ORIGIN, 3 + ORIGIN, 0) * scalar(TILE_SIZE);
+    let other = EntityBox::new(start + V3::new(32, 0, 0), size, NPC);
+
+    // Blocked by an entity: stay put, but only for a short time.
+    let (end, dur) = collide_with_entities(&FlatScene, &[other], collision_groups::mask(PAWN),
+                                           start, size, V3::new(50, 0, 0), Momentum::none());
+    assert_eq!((end, dur), (start, ENTITY_BLOCKED_RETRY));
+
+    // Blocked by the edge of the map: same as plain `collide`.
+    let (end, dur) = collide_with_entities(&FlatScene, &[other], collision_groups::mask(PAWN),
+                                           start, size, V3::new(-50, 0, 0), Momentum::none());
+    assert_eq!((end, dur), collide(&FlatScene, start, size, V3::new(-50, 0, 0),
+                                   Momentum::none()));
+}
+
+#[test]
+fn entity_path_blocks_movement() {
+    // Another entity is about to cross in front, so stop short of its path.
+    let size = V3::new(32, 32, 64);
+    let start = V3::new(ORIGIN, 3 + ORIGIN, 0) * scalar(TILE_SIZE);
+    let other = EntityBox::swept(start + V3::new(128, -64, 0),
+                                 start + V3::new(128, 64, 0),
+                                 size, NPC);
+    let v = V3::new(50, 0, 0);
+
+    let (end, _) = collide_with_entities(&FlatScene, &[other], collision_groups::mask(PAWN),
+                                         start, size, v, Momentum::none());
+    assert_eq!(end, start + V3::new(96, 0, 0));
+}
+
+/// A floor one tile wide and 32 tiles long, running east from the corner of the map.
+struct CorridorScene;
+
+impl ShapeSource for CorridorScene {
+    fn get_shape(&self, pos: V3) -> Shape {
+        let pos = pos - V3::new(ORIGIN, ORIGIN, 0);
+        if pos.x < 0 || pos.x >= 32 || pos.y != 0 || pos.z != 0 {
+            return Shape::Empty;
+        }
+        Shape::Floor
+    }
+}
+
+#[test]
+fn entity_reach_covers_one_move() {
+    // Entities outside `entity_reach` get left out of the list, so they must never make a
+    // difference.
+    let size = V3::new(32, 32, 64);
+    let start = V3::new(ORIGIN, ORIGIN, 0) * scalar(TILE_SIZE);
+    let v = V3::new(50, 0, 0);
+    let reach = entity_reach(start, size);
+
+    let (end, _) = collide(&CorridorScene, start, size, v, Momentum::none());
+    assert_eq!(end, start + V3::new(ENTITY_REACH, 0, 0));
+
+    // Just past the edge of the reach.
+    let other = EntityBox::new(V3::new(reach.max.x, start.y, 0), size, NPC);
+    assert!(!reach.overlaps(other.bounds));
+    let (end2, _) = collide_with_entities(&CorridorScene, &[other], collision_groups::mask(PAWN),
+                                          start, size, v, Momentum::none());
+    assert_eq!(end2, end);
+}
+
+#[test]
+fn entity_slide() {
+    // Moving diagonally into an entity slides along its side instead of stopping dead.
+    let size = V3::new(32, 32, 64);
+    let start = V3::new(ORIGIN, 3 + ORIGIN, 0) * scalar(TILE_SIZE);
+    let other = EntityBox::new(start + V3::new(64, 0, 0), size, NPC);
+    let v = V3::new(50, 50, 0);
+
+    let (end, _) = collide_with_entities(&FlatScene, &[other], collision_groups::mask(PAWN),
+                                         start, size, v, Momentum::none());
+    assert!(end.y > start.y);
+    assert_eq!(end.z, 0);
+}
+
+#[test]
+fn entity_overlap_can_escape() {
+    // Entities that already overlap can still move apart.
+    let size = V3::new(32, 32, 64);
+    let start = V3::new(3 + ORIGIN, 3 + ORIGIN, 0) * scalar(TILE_SIZE);
+    let other = EntityBox::new(start + V3::new(16, 0, 0), size, NPC);
+    let v = V3::new(-50, 0, 0);
+
+    let (end, _) = collide_with_entities(&FlatScene, &[other], collision_groups::mask(PAWN),
+                                         start, size, v, Momentum::none());
+    assert!(end.x < start.x);
+}
+
+
+/// An 8x8 test map with a one-tile step: floor at z = 0 for x < 4, and solid at z = 0 with floor
+/// at z = 1 for x >= 4.
+struct StepScene;
+
+impl ShapeSource for StepScene {
+    fn get_shape(&self, pos: V3) -> Shape {
+        let pos = pos - V3::new(ORIGIN, ORIGIN, 0);
+        if pos.x < 0 || pos.x >= 8 || pos.y < 0 || pos.y >= 8 {
+            return Shape::Empty;
+        }
+        match (pos.x, pos.z) {
+            (0 ... 3, 0) => Shape::Floor,
+            (4 ... 7, 0) => Shape::Solid,
+            (4 ... 7, 1) => Shape::Floor,
+            _ => Shape::Empty,
+        }
+    }
+}
+
+fn tile_pos(x: i32, y: i32, z: i32) -> V3 {
+    (V3::new(x, y, z) + V3::new(ORIGIN, ORIGIN, 0)) * scalar(TILE_SIZE)
+}
+
+/// Follow an entity through a series of motions until it comes to rest.  The first motion uses
+/// velocity `v`, and the rest use `v` without the jump bit, as if the jump key was tapped.
+fn jump<S: ShapeSource>(s: &S, start: V3, v: V3) -> V3 {
+    let size = V3::new(32, 32, 64);
+    let mut pos = start;
+    let mut vel = v;
+    let mut momentum = Momentum::none();
+    for _ in 0 .. 20 {
+        let (end, dur) = collide(s, pos, size, vel, momentum);
+        if end == pos {
+            break;
+        }
+        momentum = Momentum::from_motion(pos, end, dur);
+        vel = v.with(Axis::Z, 0);
+        pos = end;
+    }
+    pos
+}
+
+fn rising(z: i32) -> Momentum {
+    Momentum {
+        rise_to: Some(z),
+        velocity: scalar(0),
+    }
+}
+
+#[test]
+fn fall_to_floor() {
+    let size = V3::new(32, 32, 64);
+    let start = tile_pos(5, 3, 1) + V3::new(0, 0, 40);
+    assert_eq!(collide(&StepScene, start, size, scalar(0), Momentum::none()),
+               (tile_pos(5, 3, 1), 400));
+}
+
+#[test]
+fn jump_in_place() {
+    let size = V3::new(32, 32, 64);
+    let start = tile_pos(1, 3, 0);
+    let peak = start + V3::new(0, 0, JUMP_HEIGHT);
+
+    let (end, _) = collide(&StepScene, start, size, V3::new(0, 0, 1), Momentum::none());
+    assert_eq!(end, peak);
+    assert_eq!(jump_peak(start, end), Some(peak.z));
+
+    // At the peak, the entity starts falling.
+    let (end, _) = collide(&StepScene, peak, size, scalar(0), rising(peak.z));
+    assert_eq!(end, start);
+    assert_eq!(jump_peak(peak, end), None);
+}
+
+#[test]
+fn jump_continues_after_input_change() {
+    let size = V3::new(32, 32, 64);
+    let start = tile_pos(1, 3, 0);
+    let peak = start + V3::new(0, 0, JUMP_HEIGHT);
+
+    // Halfway up, the player starts moving.  The entity keeps rising to the original peak.
+    let mid = start + V3::new(0, 0, JUMP_HEIGHT / 2);
+    let (end, _) = collide(&StepScene, mid, size, V3::new(50, 0, 0), rising(peak.z));
+    assert_eq!(end, peak);
+}
+
+#[test]
+fn jump_onto_step() {
+    // Without jumping, the step blocks movement.
+    assert_eq!(walk(&StepScene, tile_pos(1, 3, 0), V3::new(50, 0, 0)), tile_pos(3, 3, 0));
+    assert_eq!(jump(&StepScene, tile_pos(3, 3, 0), V3::new(50, 0, 1)), tile_pos(7, 3, 1));
+}
+
+#[test]
+fn jump_off_step() {
+    assert_eq!(walk(&StepScene, tile_pos(6, 3, 1), V3::new(-50, 0, 0)), tile_pos(4, 3, 1));
+    assert_eq!(jump(&StepScene, tile_pos(4, 3, 1), V3::new(-50, 0, 1)), tile_pos(0, 3, 0));
+}
+
+#[test]
+fn jump_peak_ignores_ramps() {
+    let s = RampScene::new(Shape::RampE);
+    let size = V3::new(32, 32, 64);
+    let mut pos = s.pos(1, 0);
+    while pos.z == 0 {
+        let (end, _) = collide(&s, pos, size, V3::new(50, 0, 0), Momentum::none());
+        assert!(end != pos);
+        assert_eq!(jump_peak(pos, end), None);
+        pos = end;
+    }
+}
+
+
+/// A flat 8x8 map where column x = 3 is mud (half speed), x = 4 is ice, and x = 5 is water.
+struct SurfaceScene;
+
+impl ShapeSource for SurfaceScene {
+    fn get_shape(&self, pos: V3) -> Shape {
+        FlatScene.get_shape(pos)
+    }
+
+    fn get_surface(&self, pos: V3) -> Surface {
+        let (speed, flags) = match pos.x - ORIGIN {
+            3 => (50, Flags::empty()),
+            4 => (100, SLIPPERY),
+            5 => (50, LIQUID),
+            _ => return Surface::normal(),
+        };
+        Surface {
+            speed: speed,
+            flags: flags,
+        }
+    }
+}
+
+#[test]
+fn surface_speed() {
+    let size = V3::new(32, 32, 64);
+    let v = V3::new(50, 0, 0);
+
+    // The motion stops where the ground changes, and continues at the new speed.
+    let start = tile_pos(1, 3, 0);
+    let (end, dur) = collide(&SurfaceScene, start, size, v, Momentum::none());
+    assert_eq!(end, tile_pos(2, 3, 0) + V3::new(16, 0, 0));
+    assert_eq!(dur, 48 * 1000 / 50);
+
+    let (end2, dur2) = collide(&SurfaceScene, end, size, v, Momentum::from_motion(start, end, dur));
+    assert_eq!(end2, tile_pos(3, 3, 0) + V3::new(16, 0, 0));
+    assert_eq!(dur2, 32 * 1000 / 25);
+}
+
+#[test]
+fn surface_slippery() {
+    let size = V3::new(32, 32, 64);
+    let start = tile_pos(4, 3, 0);
+
+    // Sliding continues at the previous velocity when the player stops walking.
+    let momentum = Momentum::from_motion(start - V3::new(50, 0, 0), start, 1000);
+    let (end, _) = collide(&SurfaceScene, start, size, scalar(0), momentum);
+    assert!(end.x > start.x);
+
+    // Normal ground stops immediately.
+    let start = tile_pos(1, 3, 0);
+    let momentum = Momentum::from_motion(start - V3::new(50, 0, 0), start, 1000);
+    assert_eq!(collide(&SurfaceScene, start, size, scalar(0), momentum).0, start);
+}
+
+#[test]
+fn surface_liquid_no_jump() {
+    let size = V3::new(32, 32, 64);
+    let start = tile_pos(5, 3, 0);
+    let (end, _) = collide(&SurfaceScene, start, size, V3::new(0, 0, 1), Momentum::none());
+    assert_eq!(end, start);
+}
+
+#[test]
+fn shape_from_primitive() {
+    for &shape in &[Shape::Empty, Shape::Floor, Shape::Solid,
+                    Shape::RampE, Shape::RampW, Shape::RampS, Shape::RampN] {
+        assert_eq!(Shape::from_primitive(shape as usize), Some(shape));
+    }
+    assert_eq!(Shape::from_primitive(7), None);
+}
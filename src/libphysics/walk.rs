@@ -346,7 +346,7 @@ fn adjust_blocked(blocked: u8, axis_mask: u8) -> u8 {
     blocked
 }
 
-fn collide_tile(shape: Shape, overlap: Region) -> bool {
+pub fn collide_tile(shape: Shape, overlap: Region) -> bool {
     use super::Shape::*;
     match shape {
         Empty => false,
@@ -6,7 +6,7 @@ use physics::CHUNK_SIZE;
 use server_config::Data;
 use server_extra::Extra;
 use server_types::*;
-use server_world_types::{Motion, Item};
+use server_world_types::{Motion, Item, Health, DEFAULT_MAX_HEALTH};
 use server_world_types::{EntityAttachment, InventoryAttachment, StructureAttachment};
 use server_world_types::flags::{TerrainChunkFlags, StructureFlags};
 
@@ -268,6 +268,8 @@ impl<'d> Builder<'d> {
         self.cleanup();
 
         Bundle {
+            version: CURRENT_SAVE_VERSION,
+
             anims: convert_vec(self.anims.vals, |s| s.to_owned().into_boxed_str()),
             items: convert_vec(self.items.vals, |s| s.to_owned().into_boxed_str()),
             blocks: convert_vec(self.blocks.vals, |s| s.to_owned().into_boxed_str()),
@@ -575,6 +577,7 @@ struct EntityBits {
     facing: V3,
     target_velocity: V3,
     appearance: u32,
+    health: Health,
 
     extra: Extra,
     stable_id: StableId,
@@ -592,6 +595,7 @@ impl EntityBits {
             facing: V3::new(1, 0, 0),
             target_velocity: scalar(0),
             appearance: 0,
+            health: Health::new(DEFAULT_MAX_HEALTH),
 
             extra: Extra::new(),
             stable_id: NO_STABLE_ID,
@@ -609,6 +613,7 @@ impl EntityBits {
             facing: self.facing,
             target_velocity: self.target_velocity,
             appearance: self.appearance,
+            health: self.health,
 
             extra: self.extra,
             stable_id: self.stable_id,
@@ -668,6 +673,11 @@ impl<'a, 'd> EntityBuilder<'a, 'd> {
         self
     }
 
+    pub fn health(&mut self, hp: u16, max: u16) -> &mut Self {
+        self.get().health = Health { hp: hp, max: max };
+        self
+    }
+
     pub fn stable_id(&mut self, id: StableId) -> &mut Self {
         self.get().stable_id = id;
         self
@@ -0,0 +1,21 @@
+//! CRC-32 checksums (the IEEE polynomial, as used by zlib and PNG), for detecting corrupted save
+//! files.
+
+const POLY: u32 = 0xedb88320;
+
+pub fn crc32(buf: &[u8]) -> u32 {
+    let mut table = [0_u32; 256];
+    for (i, t) in table.iter_mut().enumerate() {
+        let mut c = i as u32;
+        for _ in 0 .. 8 {
+            c = if c & 1 != 0 { POLY ^ (c >> 1) } else { c >> 1 };
+        }
+        *t = c;
+    }
+
+    let mut crc = !0_u32;
+    for &b in buf {
+        crc = table[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
@@ -9,6 +9,8 @@ use server_util::{StrError, StringError};
 pub enum Error {
     Io(io::Error),
     Str(StrError),
+    /// The file's contents don't match the checksum in its header.
+    Checksum { expected: u32, actual: u32 },
 }
 
 impl fmt::Display for Error {
@@ -16,6 +18,9 @@ impl fmt::Display for Error {
         match *self {
             Error::Io(ref e) => e.fmt(f),
             Error::Str(ref e) => e.fmt(f),
+            Error::Checksum { expected, actual } =>
+                write!(f, "bundle is corrupt: checksum is {:08x}, but header says {:08x}",
+                       actual, expected),
         }
     }
 }
@@ -25,6 +30,7 @@ impl error::Error for Error {
         match *self {
             Error::Io(ref e) => e.description(),
             Error::Str(ref e) => e.description(),
+            Error::Checksum { .. } => "bundle is corrupt: checksum mismatch",
         }
     }
 
@@ -32,6 +38,7 @@ impl error::Error for Error {
         match *self {
             Error::Io(ref e) => Some(e as &error::Error),
             Error::Str(ref e) => Some(e as &error::Error),
+            Error::Checksum { .. } => None,
         }
     }
 }
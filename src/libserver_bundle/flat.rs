@@ -19,7 +19,8 @@
 //!
 //! We use the normal `Result`/`try!` error reporting when unflattening a bundle.  There is no
 //! recovery mechanism.  If the data is corrupt, it needs to be fixed manually before reading it
-//! in.
+//! in.  Files from version 1.1 onward carry a checksum of their contents, so accidental corruption
+//! (such as a truncated write) is reported as `Error::Checksum` instead of producing garbage.
 
 // TODO: error handling!
 
@@ -31,18 +32,16 @@ use std::str;
 
 use server_types::*;
 use server_util::Convert;
-use server_util::{StrError, StrResult};
 
-use server_world_types::{Motion, Item};
+use server_world_types::{Motion, Item, Health, DEFAULT_MAX_HEALTH};
 use server_world_types::flags::{TerrainChunkFlags, StructureFlags};
 use server_world_types::{EntityAttachment, InventoryAttachment, StructureAttachment};
 use server_extra::{self as extra, Extra};
 
+use checksum;
 use super::types::*;
 
-
-pub type Error = StrError;
-pub type Result<T> = StrResult<T>;
+pub use error::{Error, Result};
 
 
 /// Convert usize to u32, truncating.
@@ -73,7 +72,9 @@ pub struct FileHeader {
     header_offset: u32,
     /// Number of `SectionHeader`s in this file.
     header_count: u32,
-    _reserved0: u32,
+    /// CRC-32 of everything following the `FileHeader`.  Only present in version 1.1 and later;
+    /// older files have zero here.
+    checksum: u32,
 }
 
 // NB: size_of::<SectionHeader> is 16, a multiple of ALIGNMENT
@@ -244,7 +245,10 @@ fn align(x: usize) -> usize {
 
 /// The maximum alignment of any field of any member of Flat.
 const ALIGNMENT: usize = 8;
-const CURRENT_VERSION: (u16, u16) = (1, 0);
+/// Version 1.2 adds the `DVer` section, and 1.3 adds `CHlt`.
+const CURRENT_VERSION: (u16, u16) = (1, 3);
+/// The first version with a valid `FileHeader.checksum`.
+const CHECKSUM_VERSION: (u16, u16) = (1, 1);
 
 macro_rules! filter_sections {
     (file_header, $e:expr) => { () };
@@ -252,6 +256,11 @@ macro_rules! filter_sections {
     ($name:ident, $e:expr) => { $e };
 }
 
+macro_rules! filter_file_header {
+    (file_header, $e:expr) => { () };
+    ($name:ident, $e:expr) => { $e };
+}
+
 unsafe fn extract_section<'a, S: Section<'a>>(buf: &'a [u8],
                                               offset: u32,
                                               len: u32) -> Option<S::Ref> {
@@ -348,7 +357,8 @@ macro_rules! flat {
                     minor: CURRENT_VERSION.1,
                     header_offset: trunc32!(mem::size_of::<FileHeader>()),
                     header_count: sat32!(num_sections),
-                    _reserved0: 0,
+                    // Filled in by `write`, once the rest of the file has been laid out.
+                    checksum: 0,
                 }));
 
                 // We set up offsets to place the sections headers at the beginning, followed by
@@ -374,19 +384,31 @@ macro_rules! flat {
             pub fn write<W: io::Write>(&mut self, w: &mut W) -> io::Result<()> {
                 self.build_headers();
 
+                // The checksum in the file header covers everything after it, so lay out the rest
+                // of the file in memory first.
                 // NB: relies on file_header and section_headers being first in declaration order.
-                $(
+                let mut body = Vec::new();
+                $(filter_file_header!($name,
                     {
                         let r = <$ty as Section>::borrow(&self.$name);
                         let buf = unsafe { <$ty as Section>::as_bytes(r) };
                         assert!(buf.len() == <$ty as Section>::byte_len(&self.$name));
-                        try!(w.write_all(buf));
+                        body.extend_from_slice(buf);
                         if buf.len() % ALIGNMENT != 0 {
                             // Write enough zeros to get to the next multiple of ALIGNMENT
-                            try!(w.write_all(&[0; ALIGNMENT][buf.len() % ALIGNMENT ..]));
+                            body.extend_from_slice(&[0; ALIGNMENT][buf.len() % ALIGNMENT ..]);
                         }
                     }
-                )*
+                );)*
+
+                self.file_header.as_mut().unwrap().checksum = checksum::crc32(&body);
+                {
+                    let r = <Option<Box<FileHeader>> as Section>::borrow(&self.file_header);
+                    let buf = unsafe { <Option<Box<FileHeader>> as Section>::as_bytes(r) };
+                    assert!(buf.len() % ALIGNMENT == 0);
+                    try!(w.write_all(buf));
+                }
+                try!(w.write_all(&body));
 
                 Ok(())
             }
@@ -417,6 +439,16 @@ macro_rules! flat {
                 };
                 v.file_header = Some(file_header);
 
+                if (file_header.major, file_header.minor) >= CHECKSUM_VERSION {
+                    let actual = checksum::crc32(&buf[mem::size_of::<FileHeader>() ..]);
+                    if actual != file_header.checksum {
+                        return Err(Error::Checksum {
+                            expected: file_header.checksum,
+                            actual: actual,
+                        });
+                    }
+                }
+
                 if file_header.header_offset as usize % ALIGNMENT != 0 {
                     fail!("FlatView::from_bytes: misaligned section header offset");
                 }
@@ -506,6 +538,7 @@ flat! {
     b"HSec",  section_headers: Vec<SectionHeader>,
 
     // Data definitions
+    b"DVer",  save_version: Option<Box<u32>>,
     b"DAni",  anims: Vec<FlatStr>,
     b"DItm",  items: Vec<FlatStr>,
     b"DBlk",  blocks: Vec<FlatStr>,
@@ -531,6 +564,9 @@ flat! {
     b"CItm",  inv_items: Vec<CItem>,
     b"CLdC",  loaded_chunks: Vec<CLoadedChunk>,
     b"CBlC",  block_chunks: Vec<CBlockChunk>,
+    // Health of each entity, in the same order as `entities`.  Files from before version 1.3 have
+    // no `CHlt` section, and their entities start out at full health.
+    b"CHlt",  entity_health: Vec<CHealth>,
 }
 
 
@@ -564,6 +600,12 @@ pub struct CLoadedChunk {
     pub tcid: u64,
 }
 
+#[repr(C)] #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CHealth {
+    pub hp: u16,
+    pub max: u16,
+}
+
 #[repr(C)] #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct CItem {
     pub tag: u8,
@@ -1136,6 +1178,19 @@ impl Conv for Item {
     }
 }
 
+impl Conv for Health {
+    type C = CHealth;
+
+    fn conv(self) -> CHealth {
+        CHealth { hp: self.hp, max: self.max }
+    }
+
+    fn unconv(obj: CHealth) -> Health {
+        Health { hp: obj.hp, max: obj.max }
+    }
+}
+
+
 impl Flatten for Item {
     fn flatten_idx(&self, f: &mut Flat) -> usize {
         let off = f.inv_items.len();
@@ -1696,6 +1751,7 @@ impl Flatten for Entity {
             child_inventories: f.flatten_part(&self.child_inventories),
         };
         f.entities.push(fe);
+        f.entity_health.push(conv(self.health));
         off
     }
 
@@ -1715,6 +1771,8 @@ impl Flatten for Entity {
             facing: unconv(fe.facing),
             target_velocity: unconv(fe.target_velocity),
             appearance: fe.appearance,
+            health: f.entity_health.get(off).map_or(Health::new(DEFAULT_MAX_HEALTH),
+                                                    |&h| unconv(h)),
 
             extra: f.unflatten_part(&fe.extra),
             stable_id: fe.stable_id,
@@ -1848,6 +1906,8 @@ impl Flatten for Structure {
 
 impl Flatten for Bundle {
     fn flatten_idx(&self, f: &mut Flat) -> usize {
+        f.save_version = Some(Box::new(self.version));
+
         for s in &*self.anims {
             let fs = f.flatten_part(s);
             f.anims.push(fs);
@@ -1894,6 +1954,9 @@ impl Flatten for Bundle {
         assert!(off == 0);
 
         Bundle {
+            // Files written before save versions existed have no `DVer` section.
+            version: f.save_version.map_or(0, |&v| v),
+
             anims: unflatten_strs(f, &f.anims),
             items: unflatten_strs(f, &f.items),
             blocks: unflatten_strs(f, &f.blocks),
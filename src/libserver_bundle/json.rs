@@ -0,0 +1,689 @@
+//! Conversion between `Bundle`s and JSON, for inspecting and hand-editing save files.
+//!
+//! The JSON form mirrors the `Bundle` struct: each field becomes a key of the same name.  Vectors
+//! are written as arrays (`[x, y, z]`), and the enum-like fields use short strings or single-key
+//! objects (`"world"`, `{"client": 3}`).  Inventory slots are `null` when empty, otherwise
+//! `{"count": n, "item": id}` for bulk items or `{"special": n, "item": id}` for special items.
+//!
+//! `Extra` values that have a natural JSON equivalent (null, bools, numbers, strings, arrays, and
+//! hashes) are written as-is.  Everything else is tagged with a single-key object whose key starts
+//! with `!`, using the same names as `migrations/dump_extra.rs`: `{"!stable_client": 17}`,
+//! `{"!v3": [1, 2, 3]}`, `{"!region2": [[0, 0], [4, 4]]}`.  This means a hash whose only key
+//! starts with `!` can't be represented.
+
+use std::collections::BTreeMap;
+
+use rustc_serialize::json::{Json, Object};
+
+use server_extra::{Extra, Value, View, ArrayViewMut, HashViewMut};
+use server_types::*;
+use server_util::StringResult;
+use server_world_types::flags::{TerrainChunkFlags, StructureFlags};
+use server_world_types::{Motion, Item, Health, DEFAULT_MAX_HEALTH};
+use server_world_types::{EntityAttachment, InventoryAttachment, StructureAttachment};
+
+use types::*;
+
+
+// Encoding
+
+macro_rules! object {
+    ($($k:expr => $v:expr,)*) => {{
+        let mut o = BTreeMap::new();
+        $( o.insert($k.to_owned(), $v); )*
+        Json::Object(o)
+    }};
+}
+
+fn tagged(tag: &str, v: Json) -> Json {
+    let mut o = BTreeMap::new();
+    o.insert(format!("!{}", tag), v);
+    Json::Object(o)
+}
+
+fn enc_strs(v: &[Box<str>]) -> Json {
+    Json::Array(v.iter().map(|s| Json::String((**s).to_owned())).collect())
+}
+
+fn enc_ids<T: Copy, F: Fn(T) -> u32>(v: &[T], f: F) -> Json {
+    Json::Array(v.iter().map(|&x| Json::U64(f(x) as u64)).collect())
+}
+
+fn enc_v2(v: V2) -> Json {
+    Json::Array(vec![Json::I64(v.x as i64), Json::I64(v.y as i64)])
+}
+
+fn enc_v3(v: V3) -> Json {
+    Json::Array(vec![Json::I64(v.x as i64), Json::I64(v.y as i64), Json::I64(v.z as i64)])
+}
+
+fn enc_stable<T>(id: Stable<T>) -> Json {
+    Json::U64(id.unwrap())
+}
+
+fn enc_motion(m: &Motion) -> Json {
+    object! {
+        "start_time" => Json::I64(m.start_time),
+        "duration" => Json::U64(m.duration as u64),
+        "start_pos" => enc_v3(m.start_pos),
+        "end_pos" => enc_v3(m.end_pos),
+    }
+}
+
+fn enc_health(h: Health) -> Json {
+    object! {
+        "hp" => Json::U64(h.hp as u64),
+        "max" => Json::U64(h.max as u64),
+    }
+}
+
+fn enc_item(i: Item) -> Json {
+    match i {
+        Item::Empty => Json::Null,
+        Item::Bulk(count, id) => object! {
+            "count" => Json::U64(count as u64),
+            "item" => Json::U64(id as u64),
+        },
+        Item::Special(tag, id) => object! {
+            "special" => Json::U64(tag as u64),
+            "item" => Json::U64(id as u64),
+        },
+    }
+}
+
+fn enc_entity_attachment(a: EntityAttachment) -> Json {
+    match a {
+        EntityAttachment::World => Json::String("world".to_owned()),
+        EntityAttachment::Chunk => Json::String("chunk".to_owned()),
+        EntityAttachment::Client(id) => object! { "client" => Json::U64(id.unwrap() as u64), },
+    }
+}
+
+fn enc_inventory_attachment(a: InventoryAttachment) -> Json {
+    match a {
+        InventoryAttachment::World => Json::String("world".to_owned()),
+        InventoryAttachment::Client(id) =>
+            object! { "client" => Json::U64(id.unwrap() as u64), },
+        InventoryAttachment::Entity(id) =>
+            object! { "entity" => Json::U64(id.unwrap() as u64), },
+        InventoryAttachment::Structure(id) =>
+            object! { "structure" => Json::U64(id.unwrap() as u64), },
+    }
+}
+
+fn enc_structure_attachment(a: StructureAttachment) -> Json {
+    match a {
+        StructureAttachment::Plane => Json::String("plane".to_owned()),
+        StructureAttachment::Chunk => Json::String("chunk".to_owned()),
+    }
+}
+
+pub fn encode_value(v: Value) -> Json {
+    match v {
+        Value::Null => Json::Null,
+        Value::Bool(b) => Json::Boolean(b),
+        Value::Int(i) => Json::I64(i),
+        Value::Float(f) => Json::F64(f),
+        Value::Str(s) => Json::String(s),
+
+        Value::ClientId(id) => tagged("client", Json::U64(id.unwrap() as u64)),
+        Value::EntityId(id) => tagged("entity", Json::U64(id.unwrap() as u64)),
+        Value::InventoryId(id) => tagged("inventory", Json::U64(id.unwrap() as u64)),
+        Value::PlaneId(id) => tagged("plane", Json::U64(id.unwrap() as u64)),
+        Value::TerrainChunkId(id) => tagged("terrain_chunk", Json::U64(id.unwrap() as u64)),
+        Value::StructureId(id) => tagged("structure", Json::U64(id.unwrap() as u64)),
+
+        Value::StableClientId(id) => tagged("stable_client", enc_stable(id)),
+        Value::StableEntityId(id) => tagged("stable_entity", enc_stable(id)),
+        Value::StableInventoryId(id) => tagged("stable_inventory", enc_stable(id)),
+        Value::StablePlaneId(id) => tagged("stable_plane", enc_stable(id)),
+        Value::StableTerrainChunkId(id) => tagged("stable_terrain_chunk", enc_stable(id)),
+        Value::StableStructureId(id) => tagged("stable_structure", enc_stable(id)),
+
+        Value::V2(v) => tagged("v2", enc_v2(v)),
+        Value::V3(v) => tagged("v3", enc_v3(v)),
+        Value::Region2(r) =>
+            tagged("region2", Json::Array(vec![enc_v2(r.min), enc_v2(r.max)])),
+        Value::Region3(r) =>
+            tagged("region3", Json::Array(vec![enc_v3(r.min), enc_v3(r.max)])),
+    }
+}
+
+fn encode_view(v: View) -> Json {
+    match v {
+        View::Value(v) => encode_value(v),
+        View::Array(a) => Json::Array(a.iter().map(encode_view).collect()),
+        View::Hash(h) => Json::Object(h.iter()
+                                       .map(|(k, v)| (k.to_owned(), encode_view(v)))
+                                       .collect()),
+    }
+}
+
+pub fn encode_extra(e: &Extra) -> Json {
+    Json::Object(e.iter().map(|(k, v)| (k.to_owned(), encode_view(v))).collect())
+}
+
+fn enc_world(w: &World) -> Json {
+    object! {
+        "now" => Json::I64(w.now),
+        "next_client" => Json::U64(w.next_client),
+        "next_entity" => Json::U64(w.next_entity),
+        "next_inventory" => Json::U64(w.next_inventory),
+        "next_plane" => Json::U64(w.next_plane),
+        "next_terrain_chunk" => Json::U64(w.next_terrain_chunk),
+        "next_structure" => Json::U64(w.next_structure),
+        "extra" => encode_extra(&w.extra),
+        "child_entities" => enc_ids(&w.child_entities, EntityId::unwrap),
+        "child_inventories" => enc_ids(&w.child_inventories, InventoryId::unwrap),
+    }
+}
+
+fn enc_client(c: &Client) -> Json {
+    object! {
+        "name" => Json::String((*c.name).to_owned()),
+        "pawn" => c.pawn.map_or(Json::Null, |id| Json::U64(id.unwrap() as u64)),
+        "extra" => encode_extra(&c.extra),
+        "stable_id" => Json::U64(c.stable_id),
+        "child_entities" => enc_ids(&c.child_entities, EntityId::unwrap),
+        "child_inventories" => enc_ids(&c.child_inventories, InventoryId::unwrap),
+    }
+}
+
+fn enc_entity(e: &Entity) -> Json {
+    object! {
+        "stable_plane" => enc_stable(e.stable_plane),
+        "motion" => enc_motion(&e.motion),
+        "anim" => Json::U64(e.anim as u64),
+        "facing" => enc_v3(e.facing),
+        "target_velocity" => enc_v3(e.target_velocity),
+        "appearance" => Json::U64(e.appearance as u64),
+        "health" => enc_health(e.health),
+        "extra" => encode_extra(&e.extra),
+        "stable_id" => Json::U64(e.stable_id),
+        "attachment" => enc_entity_attachment(e.attachment),
+        "child_inventories" => enc_ids(&e.child_inventories, InventoryId::unwrap),
+    }
+}
+
+fn enc_inventory(i: &Inventory) -> Json {
+    object! {
+        "contents" => Json::Array(i.contents.iter().map(|&x| enc_item(x)).collect()),
+        "extra" => encode_extra(&i.extra),
+        "stable_id" => Json::U64(i.stable_id),
+        "attachment" => enc_inventory_attachment(i.attachment),
+    }
+}
+
+fn enc_plane(p: &Plane) -> Json {
+    object! {
+        "name" => Json::String((*p.name).to_owned()),
+        "saved_chunks" => Json::Array(p.saved_chunks.iter().map(|&(cpos, id)| {
+            Json::Array(vec![enc_v2(cpos), enc_stable(id)])
+        }).collect()),
+        "extra" => encode_extra(&p.extra),
+        "stable_id" => Json::U64(p.stable_id),
+    }
+}
+
+fn enc_terrain_chunk(tc: &TerrainChunk) -> Json {
+    object! {
+        "stable_plane" => enc_stable(tc.stable_plane),
+        "cpos" => enc_v2(tc.cpos),
+        "blocks" => Json::Array(tc.blocks.iter().map(|&b| Json::U64(b as u64)).collect()),
+        "extra" => encode_extra(&tc.extra),
+        "stable_id" => Json::U64(tc.stable_id),
+        "flags" => Json::U64(tc.flags.bits() as u64),
+        "child_structures" => enc_ids(&tc.child_structures, StructureId::unwrap),
+    }
+}
+
+fn enc_structure(s: &Structure) -> Json {
+    object! {
+        "stable_plane" => enc_stable(s.stable_plane),
+        "pos" => enc_v3(s.pos),
+        "template" => Json::U64(s.template as u64),
+        "extra" => encode_extra(&s.extra),
+        "stable_id" => Json::U64(s.stable_id),
+        "flags" => Json::U64(s.flags.bits() as u64),
+        "attachment" => enc_structure_attachment(s.attachment),
+        "child_inventories" => enc_ids(&s.child_inventories, InventoryId::unwrap),
+    }
+}
+
+pub fn encode_bundle(b: &Bundle) -> Json {
+    object! {
+        "version" => Json::U64(b.version as u64),
+
+        "anims" => enc_strs(&b.anims),
+        "items" => enc_strs(&b.items),
+        "blocks" => enc_strs(&b.blocks),
+        "templates" => enc_strs(&b.templates),
+
+        "world" => b.world.as_ref().map_or(Json::Null, |w| enc_world(w)),
+        "clients" => Json::Array(b.clients.iter().map(enc_client).collect()),
+        "entities" => Json::Array(b.entities.iter().map(enc_entity).collect()),
+        "inventories" => Json::Array(b.inventories.iter().map(enc_inventory).collect()),
+        "planes" => Json::Array(b.planes.iter().map(enc_plane).collect()),
+        "terrain_chunks" => Json::Array(b.terrain_chunks.iter().map(enc_terrain_chunk).collect()),
+        "structures" => Json::Array(b.structures.iter().map(enc_structure).collect()),
+    }
+}
+
+
+// Decoding
+
+fn field<'a>(j: &'a Json, key: &str) -> StringResult<&'a Json> {
+    let o = unwrap!(j.as_object(), "expected an object");
+    match o.get(key) {
+        Some(x) => Ok(x),
+        None => fail!("missing field {:?}", key),
+    }
+}
+
+fn dec_u64(j: &Json) -> StringResult<u64> {
+    Ok(unwrap!(j.as_u64(), "expected a non-negative integer"))
+}
+
+fn dec_i64(j: &Json) -> StringResult<i64> {
+    Ok(unwrap!(j.as_i64(), "expected an integer"))
+}
+
+macro_rules! dec_int {
+    ($name:ident, $ty:ty, $wide:ty, $dec:ident) => {
+        fn $name(j: &Json) -> StringResult<$ty> {
+            let x: $wide = try!($dec(j));
+            if x as $ty as $wide != x {
+                fail!("integer {} is out of range for {}", x, stringify!($ty));
+            }
+            Ok(x as $ty)
+        }
+    };
+}
+
+dec_int!(dec_u8, u8, u64, dec_u64);
+dec_int!(dec_u16, u16, u64, dec_u64);
+dec_int!(dec_u32, u32, u64, dec_u64);
+dec_int!(dec_i32, i32, i64, dec_i64);
+
+fn dec_array(j: &Json) -> StringResult<&[Json]> {
+    Ok(unwrap!(j.as_array(), "expected an array"))
+}
+
+fn dec_str(j: &Json) -> StringResult<&str> {
+    Ok(unwrap!(j.as_string(), "expected a string"))
+}
+
+fn dec_strs(j: &Json) -> StringResult<Box<[Box<str>]>> {
+    let mut v = Vec::new();
+    for x in try!(dec_array(j)) {
+        v.push(try!(dec_str(x)).to_owned().into_boxed_str());
+    }
+    Ok(v.into_boxed_slice())
+}
+
+fn dec_ids<T, F: Fn(u32) -> T>(j: &Json, f: F) -> StringResult<Box<[T]>> {
+    let mut v = Vec::new();
+    for x in try!(dec_array(j)) {
+        v.push(f(try!(dec_u32(x))));
+    }
+    Ok(v.into_boxed_slice())
+}
+
+fn dec_v2(j: &Json) -> StringResult<V2> {
+    let a = try!(dec_array(j));
+    if a.len() != 2 {
+        fail!("expected a 2D vector");
+    }
+    Ok(V2::new(try!(dec_i32(&a[0])), try!(dec_i32(&a[1]))))
+}
+
+fn dec_v3(j: &Json) -> StringResult<V3> {
+    let a = try!(dec_array(j));
+    if a.len() != 3 {
+        fail!("expected a 3D vector");
+    }
+    Ok(V3::new(try!(dec_i32(&a[0])), try!(dec_i32(&a[1])), try!(dec_i32(&a[2]))))
+}
+
+fn dec_region<V, F: Fn(&Json) -> StringResult<V>>(j: &Json, f: F) -> StringResult<Region<V>>
+        where V: Vn {
+    let a = try!(dec_array(j));
+    if a.len() != 2 {
+        fail!("expected a [min, max] pair");
+    }
+    Ok(Region::new(try!(f(&a[0])), try!(f(&a[1]))))
+}
+
+fn dec_stable<T>(j: &Json) -> StringResult<Stable<T>> {
+    Ok(Stable::new(try!(dec_u64(j))))
+}
+
+fn dec_motion(j: &Json) -> StringResult<Motion> {
+    Ok(Motion {
+        start_time: try!(dec_i64(try!(field(j, "start_time")))),
+        duration: try!(dec_u16(try!(field(j, "duration")))),
+        start_pos: try!(dec_v3(try!(field(j, "start_pos")))),
+        end_pos: try!(dec_v3(try!(field(j, "end_pos")))),
+    })
+}
+
+fn dec_health(j: &Json) -> StringResult<Health> {
+    Ok(Health {
+        hp: try!(dec_u16(try!(field(j, "hp")))),
+        max: try!(dec_u16(try!(field(j, "max")))),
+    })
+}
+
+fn dec_item(j: &Json) -> StringResult<Item> {
+    if j.is_null() {
+        return Ok(Item::Empty);
+    }
+    let id = try!(dec_u16(try!(field(j, "item"))));
+    if let Some(count) = j.find("count") {
+        Ok(Item::Bulk(try!(dec_u8(count)), id))
+    } else if let Some(tag) = j.find("special") {
+        Ok(Item::Special(try!(dec_u8(tag)), id))
+    } else {
+        fail!("item must have either \"count\" or \"special\"");
+    }
+}
+
+/// Split a single-key object like `{"client": 3}` into its key and value.  A plain string is a
+/// variant with no value.
+fn dec_variant(j: &Json) -> StringResult<(&str, Option<&Json>)> {
+    if let Some(s) = j.as_string() {
+        return Ok((s, None));
+    }
+    let o = unwrap!(j.as_object(), "expected a string or single-key object");
+    if o.len() != 1 {
+        fail!("expected a string or single-key object");
+    }
+    let (k, v) = o.iter().next().unwrap();
+    Ok((&**k, Some(v)))
+}
+
+fn dec_entity_attachment(j: &Json) -> StringResult<EntityAttachment> {
+    match try!(dec_variant(j)) {
+        ("world", None) => Ok(EntityAttachment::World),
+        ("chunk", None) => Ok(EntityAttachment::Chunk),
+        ("client", Some(id)) => Ok(EntityAttachment::Client(ClientId(try!(dec_u16(id))))),
+        (k, _) => fail!("unknown entity attachment {:?}", k),
+    }
+}
+
+fn dec_inventory_attachment(j: &Json) -> StringResult<InventoryAttachment> {
+    match try!(dec_variant(j)) {
+        ("world", None) => Ok(InventoryAttachment::World),
+        ("client", Some(id)) => Ok(InventoryAttachment::Client(ClientId(try!(dec_u16(id))))),
+        ("entity", Some(id)) => Ok(InventoryAttachment::Entity(EntityId(try!(dec_u32(id))))),
+        ("structure", Some(id)) =>
+            Ok(InventoryAttachment::Structure(StructureId(try!(dec_u32(id))))),
+        (k, _) => fail!("unknown inventory attachment {:?}", k),
+    }
+}
+
+fn dec_structure_attachment(j: &Json) -> StringResult<StructureAttachment> {
+    match try!(dec_variant(j)) {
+        ("plane", None) => Ok(StructureAttachment::Plane),
+        ("chunk", None) => Ok(StructureAttachment::Chunk),
+        (k, _) => fail!("unknown structure attachment {:?}", k),
+    }
+}
+
+
+/// The three shapes a JSON value can take inside an `Extra`.
+enum ExtraJson<'a> {
+    Value(Value),
+    Array(&'a [Json]),
+    Hash(&'a Object),
+}
+
+fn is_tagged(o: &Object) -> bool {
+    o.len() == 1 && o.keys().next().unwrap().starts_with("!")
+}
+
+fn dec_tagged(tag: &str, j: &Json) -> StringResult<Value> {
+    Ok(match tag {
+        "client" => Value::ClientId(ClientId(try!(dec_u16(j)))),
+        "entity" => Value::EntityId(EntityId(try!(dec_u32(j)))),
+        "inventory" => Value::InventoryId(InventoryId(try!(dec_u32(j)))),
+        "plane" => Value::PlaneId(PlaneId(try!(dec_u32(j)))),
+        "terrain_chunk" => Value::TerrainChunkId(TerrainChunkId(try!(dec_u32(j)))),
+        "structure" => Value::StructureId(StructureId(try!(dec_u32(j)))),
+
+        "stable_client" => Value::StableClientId(try!(dec_stable(j))),
+        "stable_entity" => Value::StableEntityId(try!(dec_stable(j))),
+        "stable_inventory" => Value::StableInventoryId(try!(dec_stable(j))),
+        "stable_plane" => Value::StablePlaneId(try!(dec_stable(j))),
+        "stable_terrain_chunk" => Value::StableTerrainChunkId(try!(dec_stable(j))),
+        "stable_structure" => Value::StableStructureId(try!(dec_stable(j))),
+
+        "v2" => Value::V2(try!(dec_v2(j))),
+        "v3" => Value::V3(try!(dec_v3(j))),
+        "region2" => Value::Region2(try!(dec_region(j, dec_v2))),
+        "region3" => Value::Region3(try!(dec_region(j, dec_v3))),
+
+        _ => fail!("unknown extra value type {:?}", tag),
+    })
+}
+
+fn classify(j: &Json) -> StringResult<ExtraJson> {
+    Ok(match *j {
+        Json::Null => ExtraJson::Value(Value::Null),
+        Json::Boolean(b) => ExtraJson::Value(Value::Bool(b)),
+        Json::I64(i) => ExtraJson::Value(Value::Int(i)),
+        Json::U64(_) => ExtraJson::Value(Value::Int(try!(dec_i64(j)))),
+        Json::F64(f) => ExtraJson::Value(Value::Float(f)),
+        Json::String(ref s) => ExtraJson::Value(Value::Str(s.clone())),
+        Json::Array(ref a) => ExtraJson::Array(a),
+        Json::Object(ref o) => {
+            if is_tagged(o) {
+                let (k, v) = o.iter().next().unwrap();
+                ExtraJson::Value(try!(dec_tagged(&k[1..], v)))
+            } else {
+                ExtraJson::Hash(o)
+            }
+        },
+    })
+}
+
+fn dec_extra_array(mut a: ArrayViewMut, items: &[Json]) -> StringResult<()> {
+    for (i, j) in items.iter().enumerate() {
+        a.borrow().push();
+        match try!(classify(j)) {
+            ExtraJson::Value(v) => a.borrow().set(i, v),
+            ExtraJson::Array(x) => try!(dec_extra_array(a.borrow().set_array(i), x)),
+            ExtraJson::Hash(x) => try!(dec_extra_hash(a.borrow().set_hash(i), x)),
+        }
+    }
+    Ok(())
+}
+
+fn dec_extra_hash(mut h: HashViewMut, items: &Object) -> StringResult<()> {
+    for (k, j) in items {
+        match try!(classify(j)) {
+            ExtraJson::Value(v) => h.borrow().set(k, v),
+            ExtraJson::Array(x) => try!(dec_extra_array(h.borrow().set_array(k), x)),
+            ExtraJson::Hash(x) => try!(dec_extra_hash(h.borrow().set_hash(k), x)),
+        }
+    }
+    Ok(())
+}
+
+/// Store the JSON value `j` in `e` under `key`, replacing any existing value.
+pub fn set_extra(e: &mut Extra, key: &str, j: &Json) -> StringResult<()> {
+    match try!(classify(j)) {
+        ExtraJson::Value(v) => e.set(key, v),
+        ExtraJson::Array(x) => try!(dec_extra_array(e.set_array(key), x)),
+        ExtraJson::Hash(x) => try!(dec_extra_hash(e.set_hash(key), x)),
+    }
+    Ok(())
+}
+
+pub fn decode_extra(j: &Json) -> StringResult<Extra> {
+    let o = unwrap!(j.as_object(), "extra must be an object");
+    let mut e = Extra::new();
+    for (k, v) in o {
+        try!(set_extra(&mut e, k, v));
+    }
+    Ok(e)
+}
+
+fn dec_world(j: &Json) -> StringResult<World> {
+    Ok(World {
+        now: try!(dec_i64(try!(field(j, "now")))),
+        next_client: try!(dec_u64(try!(field(j, "next_client")))),
+        next_entity: try!(dec_u64(try!(field(j, "next_entity")))),
+        next_inventory: try!(dec_u64(try!(field(j, "next_inventory")))),
+        next_plane: try!(dec_u64(try!(field(j, "next_plane")))),
+        next_terrain_chunk: try!(dec_u64(try!(field(j, "next_terrain_chunk")))),
+        next_structure: try!(dec_u64(try!(field(j, "next_structure")))),
+        extra: try!(decode_extra(try!(field(j, "extra")))),
+        child_entities: try!(dec_ids(try!(field(j, "child_entities")), EntityId)),
+        child_inventories: try!(dec_ids(try!(field(j, "child_inventories")), InventoryId)),
+    })
+}
+
+fn dec_client(j: &Json) -> StringResult<Client> {
+    let pawn = try!(field(j, "pawn"));
+    Ok(Client {
+        name: try!(dec_str(try!(field(j, "name")))).to_owned().into_boxed_str(),
+        pawn: if pawn.is_null() { None } else { Some(EntityId(try!(dec_u32(pawn)))) },
+        extra: try!(decode_extra(try!(field(j, "extra")))),
+        stable_id: try!(dec_u64(try!(field(j, "stable_id")))),
+        child_entities: try!(dec_ids(try!(field(j, "child_entities")), EntityId)),
+        child_inventories: try!(dec_ids(try!(field(j, "child_inventories")), InventoryId)),
+    })
+}
+
+fn dec_entity(j: &Json) -> StringResult<Entity> {
+    Ok(Entity {
+        stable_plane: try!(dec_stable(try!(field(j, "stable_plane")))),
+        motion: try!(dec_motion(try!(field(j, "motion")))),
+        anim: try!(dec_u16(try!(field(j, "anim")))),
+        facing: try!(dec_v3(try!(field(j, "facing")))),
+        target_velocity: try!(dec_v3(try!(field(j, "target_velocity")))),
+        appearance: try!(dec_u32(try!(field(j, "appearance")))),
+        health: match j.find("health") {
+            Some(h) => try!(dec_health(h)),
+            // Bundles from before version 1.3 have no health.  Use full health, as `flat` does.
+            None => Health::new(DEFAULT_MAX_HEALTH),
+        },
+        extra: try!(decode_extra(try!(field(j, "extra")))),
+        stable_id: try!(dec_u64(try!(field(j, "stable_id")))),
+        attachment: try!(dec_entity_attachment(try!(field(j, "attachment")))),
+        child_inventories: try!(dec_ids(try!(field(j, "child_inventories")), InventoryId)),
+    })
+}
+
+fn dec_inventory(j: &Json) -> StringResult<Inventory> {
+    let mut contents = Vec::new();
+    for x in try!(dec_array(try!(field(j, "contents")))) {
+        contents.push(try!(dec_item(x)));
+    }
+    Ok(Inventory {
+        contents: contents.into_boxed_slice(),
+        extra: try!(decode_extra(try!(field(j, "extra")))),
+        stable_id: try!(dec_u64(try!(field(j, "stable_id")))),
+        attachment: try!(dec_inventory_attachment(try!(field(j, "attachment")))),
+    })
+}
+
+fn dec_plane(j: &Json) -> StringResult<Plane> {
+    let mut saved_chunks = Vec::new();
+    for x in try!(dec_array(try!(field(j, "saved_chunks")))) {
+        let pair = try!(dec_array(x));
+        if pair.len() != 2 {
+            fail!("expected a [cpos, stable_id] pair");
+        }
+        saved_chunks.push((try!(dec_v2(&pair[0])), try!(dec_stable(&pair[1]))));
+    }
+    Ok(Plane {
+        name: try!(dec_str(try!(field(j, "name")))).to_owned().into_boxed_str(),
+        saved_chunks: saved_chunks.into_boxed_slice(),
+        extra: try!(decode_extra(try!(field(j, "extra")))),
+        stable_id: try!(dec_u64(try!(field(j, "stable_id")))),
+    })
+}
+
+fn dec_terrain_chunk(j: &Json) -> StringResult<TerrainChunk> {
+    let block_json = try!(dec_array(try!(field(j, "blocks"))));
+    if block_json.len() != CHUNK_TOTAL {
+        fail!("expected {} blocks, but found {}", CHUNK_TOTAL, block_json.len());
+    }
+    let mut blocks = Box::new([0; CHUNK_TOTAL]);
+    for (i, x) in block_json.iter().enumerate() {
+        blocks[i] = try!(dec_u16(x));
+    }
+
+    let flags = try!(dec_u32(try!(field(j, "flags"))));
+    Ok(TerrainChunk {
+        stable_plane: try!(dec_stable(try!(field(j, "stable_plane")))),
+        cpos: try!(dec_v2(try!(field(j, "cpos")))),
+        blocks: blocks,
+        extra: try!(decode_extra(try!(field(j, "extra")))),
+        stable_id: try!(dec_u64(try!(field(j, "stable_id")))),
+        flags: unwrap!(TerrainChunkFlags::from_bits(flags), "unknown terrain chunk flags"),
+        child_structures: try!(dec_ids(try!(field(j, "child_structures")), StructureId)),
+    })
+}
+
+fn dec_structure(j: &Json) -> StringResult<Structure> {
+    let flags = try!(dec_u32(try!(field(j, "flags"))));
+    Ok(Structure {
+        stable_plane: try!(dec_stable(try!(field(j, "stable_plane")))),
+        pos: try!(dec_v3(try!(field(j, "pos")))),
+        template: try!(dec_u32(try!(field(j, "template")))),
+        extra: try!(decode_extra(try!(field(j, "extra")))),
+        stable_id: try!(dec_u64(try!(field(j, "stable_id")))),
+        flags: unwrap!(StructureFlags::from_bits(flags), "unknown structure flags"),
+        attachment: try!(dec_structure_attachment(try!(field(j, "attachment")))),
+        child_inventories: try!(dec_ids(try!(field(j, "child_inventories")), InventoryId)),
+    })
+}
+
+fn dec_list<T, F>(j: &Json, key: &str, f: F) -> StringResult<Box<[T]>>
+        where F: Fn(&Json) -> StringResult<T> {
+    let mut v = Vec::new();
+    for (i, x) in try!(dec_array(try!(field(j, key)))).iter().enumerate() {
+        match f(x) {
+            Ok(y) => v.push(y),
+            Err(e) => fail!("{}[{}]: {}", key, i, e.msg),
+        }
+    }
+    Ok(v.into_boxed_slice())
+}
+
+pub fn decode_bundle(j: &Json) -> StringResult<Bundle> {
+    let world = try!(field(j, "world"));
+    let world = if world.is_null() {
+        None
+    } else {
+        match dec_world(world) {
+            Ok(w) => Some(Box::new(w)),
+            Err(e) => fail!("world: {}", e.msg),
+        }
+    };
+
+    Ok(Bundle {
+        version: match j.find("version") {
+            Some(v) => try!(dec_u32(v)),
+            // Bundles dumped before save versions existed have no version.  Use 0, as `flat` does,
+            // so the migrations bring them up to date when they're loaded.
+            None => 0,
+        },
+
+        anims: try!(dec_strs(try!(field(j, "anims")))),
+        items: try!(dec_strs(try!(field(j, "items")))),
+        blocks: try!(dec_strs(try!(field(j, "blocks")))),
+        templates: try!(dec_strs(try!(field(j, "templates")))),
+
+        world: world,
+        clients: try!(dec_list(j, "clients", dec_client)),
+        entities: try!(dec_list(j, "entities", dec_entity)),
+        inventories: try!(dec_list(j, "inventories", dec_inventory)),
+        planes: try!(dec_list(j, "planes", dec_plane)),
+        terrain_chunks: try!(dec_list(j, "terrain_chunks", dec_terrain_chunk)),
+        structures: try!(dec_list(j, "structures", dec_structure)),
+    })
+}
@@ -2,6 +2,7 @@
 
 #[cfg(ffi)] extern crate libc;
 extern crate physics;
+extern crate rustc_serialize;
 extern crate server_config;
 extern crate server_extra;
 extern crate server_types;
@@ -9,8 +10,10 @@ extern crate server_types;
 extern crate server_world_types;
 
 pub mod builder;
+pub mod checksum;
 pub mod error;
 pub mod flat;
+pub mod json;
 pub mod types;
 
 #[cfg(ffi)] pub mod ffi;
@@ -1,11 +1,20 @@
 use server_extra::Extra;
 use server_types::*;
 use server_world_types::flags::{TerrainChunkFlags, StructureFlags};
-use server_world_types::{Motion, Item};
+use server_world_types::{Motion, Item, Health};
 use server_world_types::{EntityAttachment, InventoryAttachment, StructureAttachment};
 
 
+/// The save version written by this version of the code.  This is bumped whenever the meaning of
+/// saved data changes in a way that requires old bundles to be upgraded (renamed items, changed
+/// `Extra` layouts, etc.).  The server's `world::bundle::migrate` module has the upgrade for each
+/// version.  Bundles from before save versions were introduced are version 0.
+pub const CURRENT_SAVE_VERSION: u32 = 1;
+
 pub struct Bundle {
+    /// Save version of the bundle's contents.  See `CURRENT_SAVE_VERSION`.
+    pub version: u32,
+
     pub anims: Box<[Box<str>]>,
     pub items: Box<[Box<str>]>,
     pub blocks: Box<[Box<str>]>,
@@ -55,6 +64,7 @@ pub struct Entity {
     pub facing: V3,
     pub target_velocity: V3,
     pub appearance: u32,
+    pub health: Health,
 
     pub extra: Extra,
     pub stable_id: StableId,
@@ -151,6 +161,7 @@ impl Clone for Entity {
             facing: self.facing,
             target_velocity: self.target_velocity,
             appearance: self.appearance,
+            health: self.health,
 
             extra: self.extra.clone(),
             stable_id: self.stable_id,
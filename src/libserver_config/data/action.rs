@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+use rustc_serialize::json::Json;
+
+use libserver_types::*;
+
+use super::ParseError;
+
+pub struct ActionData {
+    names: Vec<String>,
+    name_to_id: HashMap<String, ActionId>,
+}
+
+impl ActionData {
+    pub fn from_json(json: Json) -> Result<ActionData, ParseError> {
+        let actions = expect!(json.as_array(),
+                              "found non-array at top level");
+
+        let mut names = Vec::with_capacity(actions.len());
+        let mut name_to_id = HashMap::new();
+
+        for (i, action) in actions.iter().enumerate() {
+            let name = get_convert!(action, "name", as_string,
+                                    "for action {}", i);
+
+            names.push(name.to_owned());
+            name_to_id.insert(name.to_owned(), i as ActionId);
+        }
+
+        Ok(ActionData {
+            names: names,
+            name_to_id: name_to_id,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn name(&self, id: ActionId) -> &str {
+        &*self.names[id as usize]
+    }
+
+    pub fn get_name(&self, id: ActionId) -> Option<&str> {
+        self.names.get(id as usize).map(|s| &**s)
+    }
+
+    pub fn get_id(&self, name: &str) -> ActionId {
+        self.find_id(name).unwrap_or_else(|| panic!("unknown action id: {}", name))
+    }
+
+    pub fn find_id(&self, name: &str) -> Option<ActionId> {
+        self.name_to_id.get(name).map(|&x| x)
+    }
+}
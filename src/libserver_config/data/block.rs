@@ -2,12 +2,15 @@ use std::collections::HashMap;
 use std::iter::repeat;
 use rustc_serialize::json::Json;
 
+use libphysics::Surface;
+use libphysics::surface_flags::{self, LIQUID, SLIPPERY};
 use libserver_types::*;
 
 use super::ParseError;
 
 pub struct BlockData {
     shapes: Vec<Shape>,
+    surfaces: Vec<Surface>,
     names: Vec<String>,
     name_to_id: HashMap<String, BlockId>,
 }
@@ -18,6 +21,7 @@ impl BlockData {
                                 "found non-array at top level");
 
         let mut shapes = repeat(Shape::Empty).take(blocks.len()).collect::<Vec<_>>();
+        let mut surfaces = repeat(Surface::normal()).take(blocks.len()).collect::<Vec<_>>();
         let mut names = Vec::with_capacity(shapes.len());
         let mut name_to_id = HashMap::new();
 
@@ -31,17 +35,43 @@ impl BlockData {
                 "empty" => Shape::Empty,
                 "floor" => Shape::Floor,
                 "solid" => Shape::Solid,
+                "ramp_e" => Shape::RampE,
+                "ramp_w" => Shape::RampW,
+                "ramp_s" => Shape::RampS,
                 "ramp_n" => Shape::RampN,
                 _ => return fail!("invalid shape \"{}\" for block {} ({})",
                                   shape_str, i, name),
             };
             shapes[i] = shape;
+
+            // Movement properties are optional.  Most blocks are normal ground.
+            let speed = find_convert!(block, "speed", as_u64,
+                                      "for block {} ({})", i, name).unwrap_or(100);
+            // A speed of zero would leave entities stuck on the block with no way off.
+            if speed == 0 || speed > 255 {
+                return fail!("speed {} out of range (1-255) for block {} ({})", speed, i, name);
+            }
+            let mut flags = surface_flags::Flags::empty();
+            if find_convert!(block, "liquid", as_boolean,
+                             "for block {} ({})", i, name).unwrap_or(false) {
+                flags.insert(LIQUID);
+            }
+            if find_convert!(block, "slippery", as_boolean,
+                             "for block {} ({})", i, name).unwrap_or(false) {
+                flags.insert(SLIPPERY);
+            }
+            surfaces[i] = Surface {
+                speed: speed as u8,
+                flags: flags,
+            };
+
             names.push(name.to_owned());
             name_to_id.insert(name.to_owned(), i as BlockId);
         }
 
         Ok(BlockData {
             shapes: shapes,
+            surfaces: surfaces,
             names: names,
             name_to_id: name_to_id,
         })
@@ -55,6 +85,10 @@ impl BlockData {
         self.shapes.get(id as usize).map(|&x| x).unwrap_or(Shape::Empty)
     }
 
+    pub fn surface(&self, id: BlockId) -> Surface {
+        self.surfaces.get(id as usize).map(|&x| x).unwrap_or(Surface::normal())
+    }
+
     pub fn name(&self, id: BlockId) -> &str {
         &*self.names[id as usize]
     }
@@ -1,5 +1,6 @@
 use rustc_serialize::json::Json;
 
+pub use self::action::ActionData;
 pub use self::block::BlockData;
 pub use self::item::ItemData;
 pub use self::recipe::{Recipe, RecipeData};
@@ -21,6 +22,7 @@ pub struct Data {
     pub animations: AnimationData,
     pub sprite_layers: SpriteLayerData,
     pub loot_tables: LootTables,
+    pub actions: ActionData,
 }
 
 impl Data {
@@ -30,7 +32,8 @@ impl Data {
                      structure_template_json: Json,
                      animation_json: Json,
                      sprite_layer_json: Json,
-                     loot_table_json: Json) -> Result<Data, ParseError> {
+                     loot_table_json: Json,
+                     action_json: Json) -> Result<Data, ParseError> {
         Ok(Data {
             block_data: try!(BlockData::from_json(block_json)),
             item_data: try!(ItemData::from_json(item_json)),
@@ -39,6 +42,7 @@ impl Data {
             animations: try!(AnimationData::from_json(animation_json)),
             sprite_layers: try!(SpriteLayerData::from_json(sprite_layer_json)),
             loot_tables: try!(LootTables::from_json(loot_table_json)),
+            actions: try!(ActionData::from_json(action_json)),
         })
     }
 }
@@ -106,6 +110,7 @@ macro_rules! convert {
 }
 
 
+pub mod action;
 pub mod block;
 pub mod item;
 pub mod recipe;
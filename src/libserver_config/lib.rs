@@ -2,6 +2,7 @@
 
 #[macro_use] extern crate log;
 extern crate rand;
+extern crate rusqlite;
 
 extern crate physics as libphysics;
 extern crate server_types as libserver_types;
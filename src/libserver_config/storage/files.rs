@@ -0,0 +1,259 @@
+//! The original storage layout: one file per world object, under `save/`.
+
+use std::borrow::Cow;
+use std::char;
+use std::fmt::Debug;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use libserver_types::{Stable, PlaneId, TerrainChunkId};
+
+use super::{Storage, SaveFile, AtomicFile, try_open_file};
+use super::SAVE_DIR;
+
+
+const CLIENT_DIR: &'static str = "clients";
+const PLANE_DIR: &'static str = "planes";
+const TERRAIN_CHUNK_DIR: &'static str = "terrain_chunks";
+const WORLD_FILE_NAME: &'static str = "world.dat";
+
+pub struct FileStorage {
+    base: PathBuf,
+}
+
+impl FileStorage {
+    pub fn new<P: AsRef<Path>>(base: &P) -> FileStorage {
+        let base = base.as_ref().to_owned();
+        fs::create_dir_all(base.join(SAVE_DIR).join(CLIENT_DIR)).unwrap();
+        fs::create_dir_all(base.join(SAVE_DIR).join(PLANE_DIR)).unwrap();
+        fs::create_dir_all(base.join(SAVE_DIR).join(TERRAIN_CHUNK_DIR)).unwrap();
+
+        FileStorage {
+            base: base,
+        }
+    }
+
+    pub fn world_path(&self) -> PathBuf {
+        self.base.join(SAVE_DIR).join(WORLD_FILE_NAME)
+    }
+
+    pub fn client_path(&self, name: &str) -> PathBuf {
+        self.base.join(SAVE_DIR).join(CLIENT_DIR)
+            .join(&*sanitize(name))
+            .with_extension("client")
+    }
+
+    pub fn plane_path(&self, stable_pid: Stable<PlaneId>) -> PathBuf {
+        self.base.join(SAVE_DIR).join(PLANE_DIR)
+            .join(format!("{:x}", stable_pid.unwrap()))
+            .with_extension("plane")
+    }
+
+    pub fn terrain_chunk_path(&self, stable_tcid: Stable<TerrainChunkId>) -> PathBuf {
+        self.base.join(SAVE_DIR).join(TERRAIN_CHUNK_DIR)
+            .join(format!("{:x}", stable_tcid.unwrap()))
+            .with_extension("terrain_chunk")
+    }
+
+    /// List the stems of all files in `dir` with extension `ext`.  Leftover temporary files from
+    /// interrupted writes have a different extension, so they are skipped.
+    fn list_dir(&self, dir: &str, ext: &str) -> Vec<String> {
+        let mut result = Vec::new();
+        for ent in fs::read_dir(self.base.join(SAVE_DIR).join(dir)).unwrap() {
+            let path = ent.unwrap().path();
+            if path.extension().and_then(|e| e.to_str()) != Some(ext) {
+                continue;
+            }
+            if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                result.push(stem.to_owned());
+            }
+        }
+        result
+    }
+}
+
+impl Storage for FileStorage {
+    fn base_path(&self) -> &Path {
+        &self.base
+    }
+
+
+    fn open_world_file(&self) -> Option<Box<Read>> {
+        try_open_file(self.world_path()).map(|f| Box::new(f) as Box<Read>)
+    }
+
+    fn open_client_file(&self, name: &str) -> Option<Box<Read>> {
+        try_open_file(self.client_path(name)).map(|f| Box::new(f) as Box<Read>)
+    }
+
+    fn open_plane_file(&self, stable_pid: Stable<PlaneId>) -> Option<Box<Read>> {
+        try_open_file(self.plane_path(stable_pid)).map(|f| Box::new(f) as Box<Read>)
+    }
+
+    fn open_terrain_chunk_file(&self, stable_tcid: Stable<TerrainChunkId>) -> Option<Box<Read>> {
+        try_open_file(self.terrain_chunk_path(stable_tcid)).map(|f| Box::new(f) as Box<Read>)
+    }
+
+
+    fn create_world_file(&self) -> Box<SaveFile> {
+        Box::new(AtomicFile::create(self.world_path()).unwrap())
+    }
+
+    fn create_client_file(&self, name: &str) -> Box<SaveFile> {
+        Box::new(AtomicFile::create(self.client_path(name)).unwrap())
+    }
+
+    fn create_plane_file(&self, stable_pid: Stable<PlaneId>) -> Box<SaveFile> {
+        Box::new(AtomicFile::create(self.plane_path(stable_pid)).unwrap())
+    }
+
+    fn create_terrain_chunk_file(&self, stable_tcid: Stable<TerrainChunkId>) -> Box<SaveFile> {
+        Box::new(AtomicFile::create(self.terrain_chunk_path(stable_tcid)).unwrap())
+    }
+
+    fn remove_client_file(&self, name: &str) {
+        try_remove_file(self.client_path(name))
+    }
+
+    fn remove_plane_file(&self, stable_pid: Stable<PlaneId>) {
+        try_remove_file(self.plane_path(stable_pid))
+    }
+
+    fn remove_terrain_chunk_file(&self, stable_tcid: Stable<TerrainChunkId>) {
+        try_remove_file(self.terrain_chunk_path(stable_tcid))
+    }
+
+
+    fn list_clients(&self) -> Vec<String> {
+        self.list_dir(CLIENT_DIR, "client").into_iter().filter_map(|s| {
+            let name = unsanitize(&s);
+            if name.is_none() {
+                warn!("skipping client file with bad name: {:?}", s);
+            }
+            name
+        }).collect()
+    }
+
+    fn list_planes(&self) -> Vec<Stable<PlaneId>> {
+        self.list_dir(PLANE_DIR, "plane").into_iter()
+            .filter_map(|s| u64::from_str_radix(&s, 16).ok())
+            .map(Stable::new)
+            .collect()
+    }
+
+    fn list_terrain_chunks(&self) -> Vec<Stable<TerrainChunkId>> {
+        self.list_dir(TERRAIN_CHUNK_DIR, "terrain_chunk").into_iter()
+            .filter_map(|s| u64::from_str_radix(&s, 16).ok())
+            .map(Stable::new)
+            .collect()
+    }
+
+    /// Hard-link every save file into `staging`.  Saves always replace a file by renaming a new
+    /// one over it (see `AtomicFile`), never by writing in place, so the links keep the old
+    /// contents.
+    fn freeze_world(&self, staging: &Path) -> io::Result<Box<Storage + Send>> {
+        let frozen = FileStorage::new(&staging);
+        if self.world_path().exists() {
+            try!(fs::hard_link(self.world_path(), frozen.world_path()));
+        }
+        for name in self.list_clients() {
+            try!(fs::hard_link(self.client_path(&name), frozen.client_path(&name)));
+        }
+        for id in self.list_planes() {
+            try!(fs::hard_link(self.plane_path(id), frozen.plane_path(id)));
+        }
+        for id in self.list_terrain_chunks() {
+            try!(fs::hard_link(self.terrain_chunk_path(id), frozen.terrain_chunk_path(id)));
+        }
+        Ok(Box::new(frozen))
+    }
+}
+
+
+fn char_legal(c: char) -> bool {
+    (c >= 'a' && c <= 'z') ||
+    (c >= 'A' && c <= 'Z') ||
+    (c >= '0' && c <= '9') ||
+    (c == '_') ||
+    (c == ',') ||
+    (c == '.')
+    // The character '-' is also legal, but we use it for encoding out-of-range characters.  '-'
+    // itself gets encoded as '-x2d'.
+}
+
+fn sanitize(s: &str) -> Cow<str> {
+    let mut last = 0;
+    let mut buf = String::new();
+
+    for (i, c) in s.chars().enumerate() {
+        if char_legal(c) {
+            continue;
+        }
+
+        buf.push_str(&s[last..i]);
+
+        if c as u32 <= 0xff {
+            buf.push_str(&*format!("-x{:02x}", c as u32));
+        } else if c as u32 <= 0xffff {
+            buf.push_str(&*format!("-u{:04x}", c as u32));
+        } else {
+            buf.push_str(&*format!("-U{:08x}", c as u32));
+        }
+
+        last = i + 1;
+    }
+
+    if last == 0 {
+        Cow::Borrowed(s)
+    } else {
+        buf.push_str(&s[last..]);
+        Cow::Owned(buf)
+    }
+}
+
+/// Reverse the encoding done by `sanitize`.  Returns `None` if `s` is not a valid encoding.
+fn unsanitize(s: &str) -> Option<String> {
+    let mut result = String::new();
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '-' {
+            result.push(c);
+            continue;
+        }
+
+        let len = match chars.next() {
+            Some('x') => 2,
+            Some('u') => 4,
+            Some('U') => 8,
+            _ => return None,
+        };
+        let hex = chars.by_ref().take(len).collect::<String>();
+        if hex.len() != len {
+            return None;
+        }
+        let code = match u32::from_str_radix(&hex, 16) {
+            Ok(x) => x,
+            Err(_) => return None,
+        };
+        match char::from_u32(code) {
+            Some(c) => result.push(c),
+            None => return None,
+        }
+    }
+
+    Some(result)
+}
+
+fn try_remove_file<P: AsRef<Path>+Debug>(path: P) {
+    match fs::remove_file(path) {
+        Ok(()) => {},
+        Err(e) => {
+            match e.kind() {
+                io::ErrorKind::NotFound => {},
+                _ => panic!("error removing file: {}", e),
+            }
+        },
+    }
+}
@@ -0,0 +1,173 @@
+//! Storage backend that keeps world objects in memory, for tests.  Nothing is written to the save
+//! directory.  Plain files (the auth database, chat log, terrain summaries) still live under the
+//! base directory, so tests should give each `MemStorage` its own scratch directory.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use libserver_types::{Stable, PlaneId, TerrainChunkId};
+
+use super::{Storage, SaveFile};
+use super::SAVE_DIR;
+
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+enum Key {
+    World,
+    Client(String),
+    Plane(u64),
+    TerrainChunk(u64),
+}
+
+type Blobs = Arc<Mutex<HashMap<Key, Vec<u8>>>>;
+
+pub struct MemStorage {
+    base: PathBuf,
+    blobs: Blobs,
+}
+
+impl MemStorage {
+    pub fn new<P: AsRef<Path>>(base: &P) -> MemStorage {
+        let base = base.as_ref().to_owned();
+        fs::create_dir_all(base.join(SAVE_DIR)).unwrap();
+
+        MemStorage {
+            base: base,
+            blobs: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn read(&self, key: Key) -> Option<Box<Read>> {
+        let blobs = self.blobs.lock().unwrap();
+        blobs.get(&key).map(|data| Box::new(Cursor::new(data.clone())) as Box<Read>)
+    }
+
+    fn write(&self, key: Key) -> Box<SaveFile> {
+        Box::new(BlobFile {
+            blobs: self.blobs.clone(),
+            key: key,
+            data: Vec::new(),
+        })
+    }
+
+    fn remove(&self, key: Key) {
+        self.blobs.lock().unwrap().remove(&key);
+    }
+
+    fn list<T, F>(&self, f: F) -> Vec<T>
+            where F: Fn(&Key) -> Option<T> {
+        let blobs = self.blobs.lock().unwrap();
+        blobs.keys().filter_map(f).collect()
+    }
+}
+
+impl Storage for MemStorage {
+    fn base_path(&self) -> &Path {
+        &self.base
+    }
+
+
+    fn open_world_file(&self) -> Option<Box<Read>> {
+        self.read(Key::World)
+    }
+
+    fn open_client_file(&self, name: &str) -> Option<Box<Read>> {
+        self.read(Key::Client(name.to_owned()))
+    }
+
+    fn open_plane_file(&self, stable_pid: Stable<PlaneId>) -> Option<Box<Read>> {
+        self.read(Key::Plane(stable_pid.unwrap()))
+    }
+
+    fn open_terrain_chunk_file(&self, stable_tcid: Stable<TerrainChunkId>) -> Option<Box<Read>> {
+        self.read(Key::TerrainChunk(stable_tcid.unwrap()))
+    }
+
+
+    fn create_world_file(&self) -> Box<SaveFile> {
+        self.write(Key::World)
+    }
+
+    fn create_client_file(&self, name: &str) -> Box<SaveFile> {
+        self.write(Key::Client(name.to_owned()))
+    }
+
+    fn create_plane_file(&self, stable_pid: Stable<PlaneId>) -> Box<SaveFile> {
+        self.write(Key::Plane(stable_pid.unwrap()))
+    }
+
+    fn create_terrain_chunk_file(&self, stable_tcid: Stable<TerrainChunkId>) -> Box<SaveFile> {
+        self.write(Key::TerrainChunk(stable_tcid.unwrap()))
+    }
+
+    fn remove_client_file(&self, name: &str) {
+        self.remove(Key::Client(name.to_owned()))
+    }
+
+    fn remove_plane_file(&self, stable_pid: Stable<PlaneId>) {
+        self.remove(Key::Plane(stable_pid.unwrap()))
+    }
+
+    fn remove_terrain_chunk_file(&self, stable_tcid: Stable<TerrainChunkId>) {
+        self.remove(Key::TerrainChunk(stable_tcid.unwrap()))
+    }
+
+
+    fn list_clients(&self) -> Vec<String> {
+        self.list(|k| match *k {
+            Key::Client(ref name) => Some(name.clone()),
+            _ => None,
+        })
+    }
+
+    fn list_planes(&self) -> Vec<Stable<PlaneId>> {
+        self.list(|k| match *k {
+            Key::Plane(id) => Some(Stable::new(id)),
+            _ => None,
+        })
+    }
+
+    fn list_terrain_chunks(&self) -> Vec<Stable<TerrainChunkId>> {
+        self.list(|k| match *k {
+            Key::TerrainChunk(id) => Some(Stable::new(id)),
+            _ => None,
+        })
+    }
+
+    fn freeze_world(&self, _staging: &Path) -> io::Result<Box<Storage + Send>> {
+        let blobs = self.blobs.lock().unwrap().clone();
+        Ok(Box::new(MemStorage {
+            base: self.base.clone(),
+            blobs: Arc::new(Mutex::new(blobs)),
+        }))
+    }
+}
+
+
+/// Buffers a bundle, then replaces the stored copy on `commit`.
+struct BlobFile {
+    blobs: Blobs,
+    key: Key,
+    data: Vec<u8>,
+}
+
+impl Write for BlobFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.data.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SaveFile for BlobFile {
+    fn commit(self: Box<Self>) -> io::Result<()> {
+        let this = *self;
+        this.blobs.lock().unwrap().insert(this.key, this.data);
+        Ok(())
+    }
+}
@@ -0,0 +1,342 @@
+//! Access to the server's on-disk state.  `Storage` covers both the read-only game data and the
+//! save files.  Most files are plain files under the base directory, but world objects (the world
+//! itself, clients, planes, and terrain chunks) go through a backend, which is either
+//! `FileStorage` (one file per object) or `SqliteStorage` (a single database).  Tests can use
+//! `MemStorage`, which keeps world objects in memory.
+
+use std::fmt::Debug;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use libserver_types::{Stable, PlaneId, TerrainChunkId};
+
+pub use self::files::FileStorage;
+pub use self::memory::MemStorage;
+pub use self::sqlite::SqliteStorage;
+
+mod files;
+mod memory;
+mod sqlite;
+
+
+const DATA_DIR: &'static str = "data";
+const BLOCK_DATA_FILE: &'static str = "blocks.json";
+const ITEM_DATA_FILE: &'static str = "items.json";
+const RECIPE_DATA_FILE: &'static str = "recipes.json";
+const OLD_TEMPLATE_DATA_FILE: &'static str = "objects.json";
+const TEMPLATE_DATA_FILE: &'static str = "structures.json";
+const ANIMATION_DATA_FILE: &'static str = "animations.json";
+const SPRITE_LAYER_DATA_FILE: &'static str = "sprite_layers.json";
+const LOOT_TABLE_DATA_FILE: &'static str = "loot_tables.json";
+const ACTION_DATA_FILE: &'static str = "actions.json";
+
+const SCRIPT_DIR: &'static str = "scripts";
+const SNAPSHOT_DIR: &'static str = "snapshots";
+
+const SAVE_DIR: &'static str = "save";
+const SUMMARY_DIR: &'static str = "summary";
+const AUTH_DB_FILE_NAME: &'static str = "auth.sqlite";
+const RESTART_FILE_NAME: &'static str = "restart.dat";
+const CHAT_LOG_FILE_NAME: &'static str = "chat.log";
+const CHAT_MUTE_FILE_NAME: &'static str = "chat_mutes.txt";
+const PENDING_RESTORE_FILE_NAME: &'static str = "restore_snapshot.txt";
+
+
+/// Open the storage at `base`, using whichever backend the save directory was set up for.  A save
+/// directory containing a world database uses `SqliteStorage`; anything else uses `FileStorage`.
+pub fn open<P: AsRef<Path>>(base: &P) -> Box<Storage> {
+    if sqlite::exists(base.as_ref()) {
+        info!("using sqlite storage backend");
+        Box::new(SqliteStorage::new(base))
+    } else {
+        Box::new(FileStorage::new(base))
+    }
+}
+
+
+/// A save file that is being written.  Nothing is visible to readers until `commit` is called.
+/// Dropping a `SaveFile` without committing discards whatever was written.
+pub trait SaveFile: Write {
+    fn commit(self: Box<Self>) -> io::Result<()>;
+}
+
+pub trait Storage: Sync {
+    /// The directory containing the `data`, `scripts`, and `save` directories.
+    fn base_path(&self) -> &Path;
+
+
+    // World objects.  These are the methods that differ between backends.
+
+    fn open_world_file(&self) -> Option<Box<Read>>;
+    fn open_client_file(&self, name: &str) -> Option<Box<Read>>;
+    fn open_plane_file(&self, stable_pid: Stable<PlaneId>) -> Option<Box<Read>>;
+    fn open_terrain_chunk_file(&self, stable_tcid: Stable<TerrainChunkId>) -> Option<Box<Read>>;
+
+    fn create_world_file(&self) -> Box<SaveFile>;
+    fn create_client_file(&self, name: &str) -> Box<SaveFile>;
+    fn create_plane_file(&self, stable_pid: Stable<PlaneId>) -> Box<SaveFile>;
+    fn create_terrain_chunk_file(&self, stable_tcid: Stable<TerrainChunkId>) -> Box<SaveFile>;
+
+    fn remove_client_file(&self, name: &str);
+    fn remove_plane_file(&self, stable_pid: Stable<PlaneId>);
+    fn remove_terrain_chunk_file(&self, stable_tcid: Stable<TerrainChunkId>);
+
+    /// Names of all saved clients.
+    fn list_clients(&self) -> Vec<String>;
+    fn list_planes(&self) -> Vec<Stable<PlaneId>>;
+    fn list_terrain_chunks(&self) -> Vec<Stable<TerrainChunkId>>;
+
+    /// Get a read-only view of the world objects as they are now, unaffected by later saves.  The
+    /// view can be read on another thread while saving continues.  This should be cheap, since
+    /// it runs on the engine thread; the expensive part is reading the view.  `staging` is an
+    /// empty scratch directory the backend may use.  The caller removes it once done with the
+    /// view.
+    fn freeze_world(&self, staging: &Path) -> io::Result<Box<Storage + Send>>;
+
+
+    // Everything else is stored as plain files, regardless of the backend.
+
+    fn data_path(&self, file: &str) -> PathBuf {
+        self.base_path().join(DATA_DIR).join(file)
+    }
+
+    fn open_block_data(&self) -> File {
+        File::open(self.data_path(BLOCK_DATA_FILE)).unwrap()
+    }
+
+    fn open_item_data(&self) -> File {
+        File::open(self.data_path(ITEM_DATA_FILE)).unwrap()
+    }
+
+    fn open_recipe_data(&self) -> File {
+        File::open(self.data_path(RECIPE_DATA_FILE)).unwrap()
+    }
+
+    fn open_old_template_data(&self) -> File {
+        File::open(self.data_path(OLD_TEMPLATE_DATA_FILE)).unwrap()
+    }
+
+    fn open_template_data(&self) -> File {
+        File::open(self.data_path(TEMPLATE_DATA_FILE)).unwrap()
+    }
+
+    fn open_animation_data(&self) -> File {
+        File::open(self.data_path(ANIMATION_DATA_FILE)).unwrap()
+    }
+
+    fn open_sprite_layer_data(&self) -> File {
+        File::open(self.data_path(SPRITE_LAYER_DATA_FILE)).unwrap()
+    }
+
+    fn open_loot_table_data(&self) -> File {
+        File::open(self.data_path(LOOT_TABLE_DATA_FILE)).unwrap()
+    }
+
+    fn open_action_data(&self) -> File {
+        File::open(self.data_path(ACTION_DATA_FILE)).unwrap()
+    }
+
+
+    fn script_dir(&self) -> PathBuf {
+        self.base_path().join(SCRIPT_DIR)
+    }
+
+    fn auth_db_path(&self) -> PathBuf {
+        self.base_path().join(SAVE_DIR).join(AUTH_DB_FILE_NAME)
+    }
+
+    fn chat_log_path(&self) -> PathBuf {
+        self.base_path().join(SAVE_DIR).join(CHAT_LOG_FILE_NAME)
+    }
+
+    fn chat_mute_path(&self) -> PathBuf {
+        self.base_path().join(SAVE_DIR).join(CHAT_MUTE_FILE_NAME)
+    }
+
+    /// Directory holding world snapshots.  Each snapshot is a subdirectory laid out like a base
+    /// directory, so it can be opened with `FileStorage::new`.
+    fn snapshot_dir(&self) -> PathBuf {
+        self.base_path().join(SNAPSHOT_DIR)
+    }
+
+    /// File naming the snapshot to restore on the next startup, if any.
+    fn pending_restore_path(&self) -> PathBuf {
+        self.base_path().join(SAVE_DIR).join(PENDING_RESTORE_FILE_NAME)
+    }
+
+    fn restart_file_path(&self) -> PathBuf {
+        self.base_path().join(SAVE_DIR).join(RESTART_FILE_NAME)
+    }
+
+    fn summary_file_path(&self,
+                         name: &str,
+                         suffix: &Path) -> PathBuf {
+        self.base_path().join(SAVE_DIR).join(SUMMARY_DIR)
+            .join(name)
+            .join(suffix)
+    }
+
+
+    fn open_restart_file(&self) -> Option<File> {
+        try_open_file(self.restart_file_path())
+    }
+
+    fn create_restart_file(&self) -> AtomicFile {
+        AtomicFile::create(self.restart_file_path()).unwrap()
+    }
+
+    fn remove_restart_file(&self) {
+        fs::remove_file(self.restart_file_path()).unwrap()
+    }
+
+    fn create_pending_restore_file(&self) -> AtomicFile {
+        AtomicFile::create(self.pending_restore_path()).unwrap()
+    }
+
+    fn open_summary_file(&self,
+                         name: &str,
+                         suffix: &Path) -> Option<File> {
+        try_open_file(self.summary_file_path(name, suffix))
+    }
+
+    fn create_summary_file(&self,
+                           name: &str,
+                           suffix: &Path) -> AtomicFile {
+        let path = self.summary_file_path(name, suffix);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        AtomicFile::create(path).unwrap()
+    }
+}
+
+
+/// Copy the world, clients, planes, and terrain chunks from `from` into `to`.  Objects already in
+/// `to` are overwritten, but objects missing from `from` are left alone (see `clear_world`).
+pub fn copy_world(from: &Storage, to: &Storage) -> io::Result<()> {
+    fn copy(src: Option<Box<Read>>, mut dest: Box<SaveFile>) -> io::Result<()> {
+        let mut src = match src {
+            Some(x) => x,
+            None => return Err(io::Error::new(io::ErrorKind::NotFound,
+                                              "file was listed but could not be opened")),
+        };
+        let mut buf = Vec::new();
+        try!(src.read_to_end(&mut buf));
+        try!(dest.write_all(&buf));
+        dest.commit()
+    }
+
+    if let Some(src) = from.open_world_file() {
+        try!(copy(Some(src), to.create_world_file()));
+    }
+    for name in from.list_clients() {
+        try!(copy(from.open_client_file(&name), to.create_client_file(&name)));
+    }
+    for id in from.list_planes() {
+        try!(copy(from.open_plane_file(id), to.create_plane_file(id)));
+    }
+    for id in from.list_terrain_chunks() {
+        try!(copy(from.open_terrain_chunk_file(id), to.create_terrain_chunk_file(id)));
+    }
+    Ok(())
+}
+
+/// Remove all clients, planes, and terrain chunks from `s`.  The world file is kept, since every
+/// save has one and `copy_world` will overwrite it.
+pub fn clear_world(s: &Storage) {
+    for name in s.list_clients() {
+        s.remove_client_file(&name);
+    }
+    for id in s.list_planes() {
+        s.remove_plane_file(id);
+    }
+    for id in s.list_terrain_chunks() {
+        s.remove_terrain_chunk_file(id);
+    }
+}
+
+
+/// A file that atomically replaces its target when committed.  Data is written to a temporary
+/// file alongside the target.  `commit` syncs it to disk and renames it over the target, so a
+/// crash at any point leaves either the old contents or the new ones, never a mix.  If the
+/// `AtomicFile` is dropped without being committed, the temporary file is removed and the target
+/// is left untouched.
+pub struct AtomicFile {
+    file: File,
+    path: PathBuf,
+    tmp_path: PathBuf,
+    committed: bool,
+}
+
+impl AtomicFile {
+    fn create(path: PathBuf) -> io::Result<AtomicFile> {
+        let mut tmp_name = path.file_name().unwrap().to_owned();
+        tmp_name.push(".tmp");
+        let tmp_path = path.with_file_name(tmp_name);
+
+        let file = try!(File::create(&tmp_path));
+        Ok(AtomicFile {
+            file: file,
+            path: path,
+            tmp_path: tmp_path,
+            committed: false,
+        })
+    }
+
+    /// Get the underlying file, for writers that require a `File` specifically.
+    pub fn file_mut(&mut self) -> &mut File {
+        &mut self.file
+    }
+
+    pub fn commit(mut self) -> io::Result<()> {
+        try!(self.file.flush());
+        try!(self.file.sync_all());
+        try!(fs::rename(&self.tmp_path, &self.path));
+        self.committed = true;
+
+        // Sync the directory too, so the rename itself survives a crash.  Not all platforms allow
+        // opening directories, so this is best-effort.
+        if let Some(dir) = self.path.parent() {
+            if let Ok(f) = File::open(dir) {
+                let _ = f.sync_all();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl SaveFile for AtomicFile {
+    fn commit(self: Box<Self>) -> io::Result<()> {
+        (*self).commit()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.tmp_path);
+        }
+    }
+}
+
+
+fn try_open_file<P: AsRef<Path>+Debug>(path: P) -> Option<File> {
+    match File::open(path) {
+        Ok(f) => Some(f),
+        Err(e) => {
+            match e.kind() {
+                io::ErrorKind::NotFound => None,
+                _ => panic!("error opening file: {}", e),
+            }
+        },
+    }
+}
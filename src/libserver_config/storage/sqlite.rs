@@ -0,0 +1,270 @@
+//! Storage backend that keeps all world objects in a single SQLite database, `save/world.sqlite`.
+//! Each object's bundle is stored as a blob.  Clients are keyed by name, planes and terrain chunks
+//! by stable ID, same as the file names used by `FileStorage`.
+
+use std::io::{self, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use rusqlite::{self, Connection};
+use rusqlite::types::ToSql;
+
+use libserver_types::{Stable, PlaneId, TerrainChunkId};
+
+use super::{Storage, SaveFile};
+use super::SAVE_DIR;
+
+
+const WORLD_DB_FILE_NAME: &'static str = "world.sqlite";
+
+const SCHEMA: &'static str = "
+    CREATE TABLE IF NOT EXISTS world (
+        id        INTEGER PRIMARY KEY CHECK (id = 0),
+        data      BLOB NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS clients (
+        name      TEXT PRIMARY KEY,
+        data      BLOB NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS planes (
+        stable_id INTEGER PRIMARY KEY,
+        data      BLOB NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS terrain_chunks (
+        stable_id INTEGER PRIMARY KEY,
+        data      BLOB NOT NULL
+    );
+";
+
+
+fn db_path(base: &Path) -> PathBuf {
+    base.join(SAVE_DIR).join(WORLD_DB_FILE_NAME)
+}
+
+/// Check whether the save directory under `base` contains a world database.
+pub fn exists(base: &Path) -> bool {
+    db_path(base).is_file()
+}
+
+
+/// Which table a blob lives in, and its key.  Stable IDs are stored as `INTEGER`, which is signed,
+/// so they get cast to `i64` (preserving the bit pattern).
+enum Key {
+    World,
+    Client(String),
+    Plane(i64),
+    TerrainChunk(i64),
+}
+
+fn select(conn: &Connection, key: &Key) -> rusqlite::Result<Option<Vec<u8>>> {
+    let (sql, param): (&str, Box<ToSql>) = match *key {
+        Key::World => ("SELECT data FROM world WHERE id = $1", Box::new(0_i64)),
+        Key::Client(ref name) =>
+            ("SELECT data FROM clients WHERE name = $1", Box::new(name.clone())),
+        Key::Plane(id) => ("SELECT data FROM planes WHERE stable_id = $1", Box::new(id)),
+        Key::TerrainChunk(id) =>
+            ("SELECT data FROM terrain_chunks WHERE stable_id = $1", Box::new(id)),
+    };
+
+    let mut stmt = try!(conn.prepare(sql));
+    for row in try!(stmt.query(&[&*param])) {
+        let row = try!(row);
+        return Ok(Some(row.get(0)));
+    }
+    Ok(None)
+}
+
+fn replace(conn: &Connection, key: &Key, data: &Vec<u8>) -> rusqlite::Result<()> {
+    let (sql, param): (&str, Box<ToSql>) = match *key {
+        Key::World => ("INSERT OR REPLACE INTO world (id, data) VALUES ($1, $2)",
+                       Box::new(0_i64)),
+        Key::Client(ref name) =>
+            ("INSERT OR REPLACE INTO clients (name, data) VALUES ($1, $2)",
+             Box::new(name.clone())),
+        Key::Plane(id) =>
+            ("INSERT OR REPLACE INTO planes (stable_id, data) VALUES ($1, $2)",
+             Box::new(id)),
+        Key::TerrainChunk(id) =>
+            ("INSERT OR REPLACE INTO terrain_chunks (stable_id, data) VALUES ($1, $2)",
+             Box::new(id)),
+    };
+
+    try!(conn.execute(sql, &[&*param, data as &ToSql]));
+    Ok(())
+}
+
+
+/// `Storage` must be `Sync`, since the terrain generation thread shares it.  A `Connection` can
+/// move between threads but not be shared, so every use of it goes through the `Mutex`.
+pub struct SqliteStorage {
+    base: PathBuf,
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStorage {
+    pub fn new<P: AsRef<Path>>(base: &P) -> SqliteStorage {
+        let base = base.as_ref().to_owned();
+        ::std::fs::create_dir_all(base.join(SAVE_DIR)).unwrap();
+
+        let conn = Connection::open(&db_path(&base)).unwrap();
+        // In WAL mode, a reader's transaction sees a fixed view of the database while writes go
+        // on.  `freeze_world` relies on this.
+        conn.execute_batch("PRAGMA journal_mode = WAL;").unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+
+        SqliteStorage {
+            base: base,
+            conn: Arc::new(Mutex::new(conn)),
+        }
+    }
+
+    fn read(&self, key: Key) -> Option<Box<Read>> {
+        let conn = self.conn.lock().unwrap();
+        match select(&conn, &key) {
+            Ok(opt) => opt.map(|data| Box::new(Cursor::new(data)) as Box<Read>),
+            Err(e) => panic!("error reading from world database: {}", e),
+        }
+    }
+
+    fn write(&self, key: Key) -> Box<SaveFile> {
+        Box::new(BlobFile {
+            conn: self.conn.clone(),
+            key: key,
+            data: Vec::new(),
+        })
+    }
+
+    fn list<T, F>(&self, sql: &str, f: F) -> Vec<T>
+            where F: Fn(&rusqlite::Row) -> T {
+        let conn = self.conn.lock().unwrap();
+        let result = conn.prepare(sql).and_then(|mut stmt| {
+            let mut result = Vec::new();
+            for row in try!(stmt.query(&[])) {
+                result.push(f(&try!(row)));
+            }
+            Ok(result)
+        });
+        match result {
+            Ok(x) => x,
+            Err(e) => panic!("error reading from world database: {}", e),
+        }
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn base_path(&self) -> &Path {
+        &self.base
+    }
+
+
+    fn open_world_file(&self) -> Option<Box<Read>> {
+        self.read(Key::World)
+    }
+
+    fn open_client_file(&self, name: &str) -> Option<Box<Read>> {
+        self.read(Key::Client(name.to_owned()))
+    }
+
+    fn open_plane_file(&self, stable_pid: Stable<PlaneId>) -> Option<Box<Read>> {
+        self.read(Key::Plane(stable_pid.unwrap() as i64))
+    }
+
+    fn open_terrain_chunk_file(&self, stable_tcid: Stable<TerrainChunkId>) -> Option<Box<Read>> {
+        self.read(Key::TerrainChunk(stable_tcid.unwrap() as i64))
+    }
+
+
+    fn create_world_file(&self) -> Box<SaveFile> {
+        self.write(Key::World)
+    }
+
+    fn create_client_file(&self, name: &str) -> Box<SaveFile> {
+        self.write(Key::Client(name.to_owned()))
+    }
+
+    fn create_plane_file(&self, stable_pid: Stable<PlaneId>) -> Box<SaveFile> {
+        self.write(Key::Plane(stable_pid.unwrap() as i64))
+    }
+
+    fn create_terrain_chunk_file(&self, stable_tcid: Stable<TerrainChunkId>) -> Box<SaveFile> {
+        self.write(Key::TerrainChunk(stable_tcid.unwrap() as i64))
+    }
+
+    fn remove_client_file(&self, name: &str) {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM clients WHERE name = $1", &[&name as &ToSql]).unwrap();
+    }
+
+    fn remove_plane_file(&self, stable_pid: Stable<PlaneId>) {
+        let conn = self.conn.lock().unwrap();
+        let id = stable_pid.unwrap() as i64;
+        conn.execute("DELETE FROM planes WHERE stable_id = $1", &[&id as &ToSql]).unwrap();
+    }
+
+    fn remove_terrain_chunk_file(&self, stable_tcid: Stable<TerrainChunkId>) {
+        let conn = self.conn.lock().unwrap();
+        let id = stable_tcid.unwrap() as i64;
+        conn.execute("DELETE FROM terrain_chunks WHERE stable_id = $1", &[&id as &ToSql]).unwrap();
+    }
+
+
+    fn list_clients(&self) -> Vec<String> {
+        self.list("SELECT name FROM clients", |row| row.get(0))
+    }
+
+    fn list_planes(&self) -> Vec<Stable<PlaneId>> {
+        self.list("SELECT stable_id FROM planes", |row| {
+            let id: i64 = row.get(0);
+            Stable::new(id as u64)
+        })
+    }
+
+    fn list_terrain_chunks(&self) -> Vec<Stable<TerrainChunkId>> {
+        self.list("SELECT stable_id FROM terrain_chunks", |row| {
+            let id: i64 = row.get(0);
+            Stable::new(id as u64)
+        })
+    }
+
+    /// Open a second connection and start a read transaction on it.  The transaction is never
+    /// committed; it ends when the view is dropped.
+    fn freeze_world(&self, _staging: &Path) -> io::Result<Box<Storage + Send>> {
+        let to_io = |e: rusqlite::Error| io::Error::new(io::ErrorKind::Other, e.to_string());
+        let conn = try!(Connection::open(&db_path(&self.base)).map_err(&to_io));
+        // `BEGIN` is deferred, so the view is fixed only once something is read.
+        try!(conn.execute_batch("BEGIN; SELECT count(*) FROM world;").map_err(&to_io));
+        Ok(Box::new(SqliteStorage {
+            base: self.base.clone(),
+            conn: Arc::new(Mutex::new(conn)),
+        }))
+    }
+}
+
+
+/// Buffers a bundle in memory, then stores it in the database on `commit`.  Each blob is
+/// replaced in a single statement, so readers see either the old blob or the new one.
+struct BlobFile {
+    conn: Arc<Mutex<Connection>>,
+    key: Key,
+    data: Vec<u8>,
+}
+
+impl Write for BlobFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.data.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SaveFile for BlobFile {
+    fn commit(self: Box<Self>) -> io::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        match replace(&conn, &self.key, &self.data) {
+            Ok(()) => Ok(()),
+            Err(e) => Err(io::Error::new(io::ErrorKind::Other, e.to_string())),
+        }
+    }
+}
@@ -51,6 +51,7 @@ impl<Id> Stable<Id> {
 
 // Typedef IDs.  These are used to identify game data elements.
 
+pub type ActionId = u16;
 pub type AnimId = u16;
 pub type BlockId = u16;
 pub type ItemId = u16;
@@ -64,6 +65,7 @@ pub const EMPTY_BLOCK: BlockId = 0;
 pub const PLACEHOLDER_BLOCK: BlockId = 1;
 pub const NO_SLOT: SlotId = u8::MAX;
 pub const NO_ITEM: ItemId = 0;
+pub const NO_ACTION: ActionId = 0;
 
 
 // Newtype IDs.  These are used to identify game objects (parts of the World).
@@ -111,3 +111,27 @@ impl Motion {
         self.start_time + self.duration as Time
     }
 }
+
+
+/// Maximum health of a newly created entity.
+pub const DEFAULT_MAX_HEALTH: u16 = 100;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Health {
+    pub hp: u16,
+    pub max: u16,
+}
+
+impl Health {
+    /// Full health, with a maximum of `max`.
+    pub fn new(max: u16) -> Health {
+        Health {
+            hp: max,
+            max: max,
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.hp == 0
+    }
+}
@@ -52,7 +52,7 @@ pub trait Summary {
     fn alloc() -> Box<Self>;
 
     /// Write the summary data to a file.
-    fn write_to(&self, f: File) -> io::Result<()>;
+    fn write_to(&self, f: &mut File) -> io::Result<()>;
 
     /// Create a new summary from the contents of a file.
     fn read_from(f: File) -> io::Result<Box<Self>>;
@@ -95,8 +95,8 @@ impl<'d, K: Key, T: Summary> Cache<'d, K, T> {
         while self.cache.len() + extra > CACHE_LIMIT {
             let (key, entry) = self.cache.pop_front().unwrap();
             if entry.dirty {
-                let file = self.storage.create_summary_file(self.name, &key.to_path());
-                match entry.data.write_to(file) {
+                let mut file = self.storage.create_summary_file(self.name, &key.to_path());
+                match entry.data.write_to(file.file_mut()).and_then(|_| file.commit()) {
                     Ok(_) => {},
                     Err(e) => {
                         warn!("error writing cache entry to disk: {}",
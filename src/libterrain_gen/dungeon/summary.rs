@@ -37,7 +37,7 @@ impl Summary for ChunkSummary {
         })
     }
 
-    fn write_to(&self, mut f: File) -> io::Result<()> {
+    fn write_to(&self, f: &mut File) -> io::Result<()> {
         try!(f.write_all(&self.cave_walls));
 
         Ok(())
@@ -80,14 +80,14 @@ impl Summary for PlaneSummary {
         })
     }
 
-    fn write_to(&self, mut f: File) -> io::Result<()> {
-        try!(unsafe { write_vec(&mut f, &self.edges) });
-        try!(unsafe { write_vec(&mut f, &self.neg_edges) });
-        try!(unsafe { write_vec(&mut f, &self.tris) });
+    fn write_to(&self, f: &mut File) -> io::Result<()> {
+        try!(unsafe { write_vec(f, &self.edges) });
+        try!(unsafe { write_vec(f, &self.neg_edges) });
+        try!(unsafe { write_vec(f, &self.tris) });
 
         try!(f.write_bytes(self.vaults.len().to_u32().unwrap()));
         for v in &self.vaults {
-            try!(v.write_to(&mut f));
+            try!(v.write_to(f));
         }
 
         Ok(())
@@ -36,7 +36,7 @@ impl Summary for CaveDetail {
         Box::new(unsafe { mem::zeroed() })
     }
 
-    fn write_to(&self, mut f: File) -> io::Result<()> {
+    fn write_to(&self, f: &mut File) -> io::Result<()> {
         try!(f.write_bytes_slice(&self.raw));
         Ok(())
     }
@@ -32,7 +32,7 @@ macro_rules! define_grid {
                 Box::new(unsafe { mem::zeroed() })
             }
 
-            fn write_to(&self, mut f: ::std::fs::File) -> ::std::io::Result<()> {
+            fn write_to(&self, f: &mut ::std::fs::File) -> ::std::io::Result<()> {
                 use libserver_util::bytes::WriteBytes;
                 f.write_bytes_slice(&self.data)
             }
@@ -94,7 +94,7 @@ macro_rules! define_points {
                 Box::new($Points { data:  Vec::new() })
             }
 
-            fn write_to(&self, mut f: ::std::fs::File) -> ::std::io::Result<()> {
+            fn write_to(&self, f: &mut ::std::fs::File) -> ::std::io::Result<()> {
                 use libserver_util::bytes::WriteBytes;
                 try!(f.write_bytes(self.data.len() as u32));
                 try!(f.write_bytes_slice(&self.data));
@@ -117,7 +117,7 @@ impl Summary for PlaneGlobals {
         })
     }
 
-    fn write_to(&self, mut f: File) -> io::Result<()> {
+    fn write_to(&self, f: &mut File) -> io::Result<()> {
         let mut rng = self.rng.clone();
         let rng_seed: (u32, u32, u32, u32) = rng.gen();
         try!(f.write_bytes(rng_seed));
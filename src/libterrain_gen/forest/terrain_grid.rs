@@ -66,7 +66,7 @@ impl Summary for TerrainGrid {
         Box::new(unsafe { mem::zeroed() })
     }
 
-    fn write_to(&self, mut f: File) -> io::Result<()> {
+    fn write_to(&self, f: &mut File) -> io::Result<()> {
         let mut buffer = [(0, 0); ((CHUNK_SIZE + 1) * (CHUNK_SIZE + 1)) as usize];
         for layer in 0 .. (CHUNK_SIZE / 2) as usize {
             for i in 0 .. buffer.len() {
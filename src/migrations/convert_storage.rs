@@ -0,0 +1,31 @@
+/// Copy the world, clients, planes, and terrain chunks from one storage backend to another.  The
+/// bundles are copied as-is.  Data, scripts, auth, and summary files are not touched.
+///
+/// Usage: ./convert_storage files|sqlite files|sqlite old_dist new_dist
+
+extern crate server_config;
+
+use std::env;
+
+use server_config::storage::{self, Storage, FileStorage, SqliteStorage};
+
+fn open_storage(kind: &str, path: &str) -> Box<Storage> {
+    match kind {
+        "files" => Box::new(FileStorage::new(&path)),
+        "sqlite" => Box::new(SqliteStorage::new(&path)),
+        _ => panic!("unknown storage backend {:?} (expected files or sqlite)", kind),
+    }
+}
+
+fn main() {
+    let args = env::args().collect::<Vec<_>>();
+    assert!(args.len() == 5,
+            "usage: {} files|sqlite files|sqlite old_dist new_dist", args[0]);
+    let old = open_storage(&args[1], &args[3]);
+    let new = open_storage(&args[2], &args[4]);
+
+    storage::copy_world(&*old, &*new).unwrap();
+
+    println!("copied world, {} clients, {} planes, {} terrain chunks",
+             old.list_clients().len(), old.list_planes().len(), old.list_terrain_chunks().len());
+}
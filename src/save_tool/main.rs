@@ -0,0 +1,328 @@
+/// Inspect and edit saved bundles (the world, clients, planes, and terrain chunks).  Works with
+/// either storage backend, picked the same way as the server does.  Stop the server before editing
+/// anything, or it will overwrite the changes on its next save.
+///
+/// Usage:
+///     ./save_tool DIST saves                           list the saved bundles
+///     ./save_tool DIST dump SAVE                       print the bundle as JSON
+///     ./save_tool DIST load JSON_FILE SAVE             replace SAVE with the bundle in JSON_FILE
+///     ./save_tool DIST list SAVE                       list objects and their stable IDs
+///     ./save_tool DIST diff SAVE1 SAVE2                show differences between two bundles
+///     ./save_tool DIST get-extra SAVE OBJECT [KEY]     print an object's extra (or one key of it)
+///     ./save_tool DIST set-extra SAVE OBJECT KEY JSON  set an extra key to a JSON value
+///     ./save_tool DIST remove-extra SAVE OBJECT KEY    remove an extra key
+///
+/// DIST is the directory containing `save`, as passed to the server.  SAVE is `world`,
+/// `client:NAME`, `plane:STABLE_ID`, or `terrain_chunk:STABLE_ID`, as printed by `saves`.
+///
+/// OBJECT is `world`, or `client`, `entity`, `inventory`, `plane`, `terrain_chunk`, or `structure`
+/// followed by `:INDEX`, using the indices printed by `list`.  See `server_bundle::json` for the
+/// JSON format, including how non-JSON `Extra` values such as `V3`s are written.
+
+extern crate rustc_serialize;
+extern crate server_bundle;
+extern crate server_config;
+extern crate server_extra;
+extern crate server_types;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process;
+
+use rustc_serialize::json::Json;
+
+use server_bundle::flat::{Flat, FlatView};
+use server_bundle::json;
+use server_bundle::types::Bundle;
+use server_config::storage::{self, Storage, SaveFile};
+use server_extra::Extra;
+use server_types::{Stable, StableId};
+
+
+fn usage() -> ! {
+    let _ = writeln!(io::stderr(), "usage: save_tool DIST saves|dump|load|list|diff|get-extra|\
+                                     set-extra|remove-extra ARGS...");
+    process::exit(2);
+}
+
+fn die(msg: &str) -> ! {
+    let _ = writeln!(io::stderr(), "save_tool: {}", msg);
+    process::exit(1);
+}
+
+
+/// A saved bundle, as named on the command line.
+enum Save {
+    World,
+    Client(String),
+    Plane(StableId),
+    TerrainChunk(StableId),
+}
+
+fn parse_save(name: &str) -> Save {
+    if name == "world" {
+        return Save::World;
+    }
+    let mut parts = name.splitn(2, ':');
+    let kind = parts.next().unwrap();
+    let arg = parts.next()
+        .unwrap_or_else(|| die(&format!("bad save {:?} (expected KIND:NAME)", name)));
+    let stable_id = || arg.parse::<StableId>()
+        .unwrap_or_else(|_| die(&format!("bad stable ID {:?}", arg)));
+    match kind {
+        "client" => Save::Client(arg.to_owned()),
+        "plane" => Save::Plane(stable_id()),
+        "terrain_chunk" => Save::TerrainChunk(stable_id()),
+        _ => die(&format!("unknown save kind {:?}", kind)),
+    }
+}
+
+fn load_bundle(s: &Storage, name: &str) -> Bundle {
+    let file = match parse_save(name) {
+        Save::World => s.open_world_file(),
+        Save::Client(ref n) => s.open_client_file(n),
+        Save::Plane(id) => s.open_plane_file(Stable::new(id)),
+        Save::TerrainChunk(id) => s.open_terrain_chunk_file(Stable::new(id)),
+    };
+    let mut file = file.unwrap_or_else(|| die(&format!("no such save: {}", name)));
+
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf)
+        .unwrap_or_else(|e| die(&format!("{}: {}", name, e)));
+    let flat = FlatView::from_bytes(&buf)
+        .unwrap_or_else(|e| die(&format!("{}: {}", name, e)));
+    flat.unflatten_bundle()
+}
+
+/// Write `b` to the save `name`.  The old contents are replaced only once the new bundle is
+/// complete.
+fn save_bundle(s: &Storage, name: &str, b: &Bundle) {
+    let mut file = match parse_save(name) {
+        Save::World => s.create_world_file(),
+        Save::Client(ref n) => s.create_client_file(n),
+        Save::Plane(id) => s.create_plane_file(Stable::new(id)),
+        Save::TerrainChunk(id) => s.create_terrain_chunk_file(Stable::new(id)),
+    };
+    let mut flat = Flat::new();
+    flat.flatten_bundle(b);
+    let result = flat.write(&mut file).and_then(|_| file.commit());
+    if let Err(e) = result {
+        die(&format!("{}: {}", name, e));
+    }
+}
+
+fn load_json(path: &str) -> Json {
+    let mut s = String::new();
+    File::open(path).and_then(|mut f| f.read_to_string(&mut s))
+        .unwrap_or_else(|e| die(&format!("{}: {}", path, e)));
+    Json::from_str(&s).unwrap_or_else(|e| die(&format!("{}: {}", path, e)))
+}
+
+
+fn saves(s: &Storage) {
+    if s.open_world_file().is_some() {
+        println!("world");
+    }
+    for name in s.list_clients() {
+        println!("client:{}", name);
+    }
+    for id in s.list_planes() {
+        println!("plane:{}", id.unwrap());
+    }
+    for id in s.list_terrain_chunks() {
+        println!("terrain_chunk:{}", id.unwrap());
+    }
+}
+
+fn dump(s: &Storage, name: &str) {
+    let b = load_bundle(s, name);
+    println!("{}", json::encode_bundle(&b).pretty());
+}
+
+fn load(s: &Storage, json_path: &str, name: &str) {
+    let j = load_json(json_path);
+    let b = json::decode_bundle(&j)
+        .unwrap_or_else(|e| die(&format!("{}: {}", json_path, e.msg)));
+    save_bundle(s, name, &b);
+}
+
+fn list(s: &Storage, name: &str) {
+    let b = load_bundle(s, name);
+    if let Some(ref w) = b.world {
+        println!("world: now {}, next ids: client {}, entity {}, inventory {}, plane {}, \
+                  terrain_chunk {}, structure {}",
+                 w.now, w.next_client, w.next_entity, w.next_inventory, w.next_plane,
+                 w.next_terrain_chunk, w.next_structure);
+    }
+    for (i, c) in b.clients.iter().enumerate() {
+        println!("client:{}  stable {}  {:?}", i, c.stable_id, c.name);
+    }
+    for (i, e) in b.entities.iter().enumerate() {
+        println!("entity:{}  stable {}  plane {}  {:?}",
+                 i, e.stable_id, e.stable_plane.unwrap(), e.attachment);
+    }
+    for (i, inv) in b.inventories.iter().enumerate() {
+        println!("inventory:{}  stable {}  {} slots  {:?}",
+                 i, inv.stable_id, inv.contents.len(), inv.attachment);
+    }
+    for (i, p) in b.planes.iter().enumerate() {
+        println!("plane:{}  stable {}  {:?}  {} saved chunks",
+                 i, p.stable_id, p.name, p.saved_chunks.len());
+    }
+    for (i, tc) in b.terrain_chunks.iter().enumerate() {
+        println!("terrain_chunk:{}  stable {}  plane {}  cpos ({}, {})",
+                 i, tc.stable_id, tc.stable_plane.unwrap(), tc.cpos.x, tc.cpos.y);
+    }
+    for (i, s) in b.structures.iter().enumerate() {
+        let template = b.templates.get(s.template as usize).map_or("?", |t| &**t);
+        println!("structure:{}  stable {}  {}  at ({}, {}, {})",
+                 i, s.stable_id, template, s.pos.x, s.pos.y, s.pos.z);
+    }
+}
+
+
+/// Print the differences between `a` and `b`, one line per changed leaf.  Returns the number of
+/// differences found.
+fn diff_json(path: &str, a: &Json, b: &Json) -> usize {
+    match (a, b) {
+        (&Json::Object(ref a), &Json::Object(ref b)) => {
+            let mut count = 0;
+            for (k, va) in a {
+                let sub = format!("{}.{}", path, k);
+                match b.get(k) {
+                    Some(vb) => count += diff_json(&sub, va, vb),
+                    None => {
+                        println!("- {}: {}", sub, va);
+                        count += 1;
+                    },
+                }
+            }
+            for (k, vb) in b {
+                if !a.contains_key(k) {
+                    println!("+ {}.{}: {}", path, k, vb);
+                    count += 1;
+                }
+            }
+            count
+        },
+
+        (&Json::Array(ref a), &Json::Array(ref b)) => {
+            let mut count = 0;
+            for (i, (va, vb)) in a.iter().zip(b.iter()).enumerate() {
+                count += diff_json(&format!("{}[{}]", path, i), va, vb);
+            }
+            for (i, va) in a.iter().enumerate().skip(b.len()) {
+                println!("- {}[{}]: {}", path, i, va);
+                count += 1;
+            }
+            for (i, vb) in b.iter().enumerate().skip(a.len()) {
+                println!("+ {}[{}]: {}", path, i, vb);
+                count += 1;
+            }
+            count
+        },
+
+        _ => {
+            if a != b {
+                println!("~ {}: {} -> {}", path, a, b);
+                1
+            } else {
+                0
+            }
+        },
+    }
+}
+
+fn diff(s: &Storage, name1: &str, name2: &str) {
+    let a = json::encode_bundle(&load_bundle(s, name1));
+    let b = json::encode_bundle(&load_bundle(s, name2));
+    if diff_json("", &a, &b) > 0 {
+        process::exit(1);
+    }
+}
+
+
+fn find_extra<'a>(b: &'a mut Bundle, obj: &str) -> &'a mut Extra {
+    let (kind, idx) =
+        if obj == "world" {
+            ("world", 0)
+        } else {
+            let mut parts = obj.splitn(2, ':');
+            let kind = parts.next().unwrap();
+            let idx = parts.next().and_then(|s| s.parse::<usize>().ok())
+                .unwrap_or_else(|| die(&format!("bad object {:?} (expected KIND:INDEX)", obj)));
+            (kind, idx)
+        };
+
+    let extra = match kind {
+        "world" => b.world.as_mut().map(|x| &mut x.extra),
+        "client" => b.clients.get_mut(idx).map(|x| &mut x.extra),
+        "entity" => b.entities.get_mut(idx).map(|x| &mut x.extra),
+        "inventory" => b.inventories.get_mut(idx).map(|x| &mut x.extra),
+        "plane" => b.planes.get_mut(idx).map(|x| &mut x.extra),
+        "terrain_chunk" => b.terrain_chunks.get_mut(idx).map(|x| &mut x.extra),
+        "structure" => b.structures.get_mut(idx).map(|x| &mut x.extra),
+        _ => die(&format!("unknown object kind {:?}", kind)),
+    };
+    extra.unwrap_or_else(|| die(&format!("no such object: {}", obj)))
+}
+
+fn get_extra(s: &Storage, name: &str, obj: &str, key: Option<&str>) {
+    let mut b = load_bundle(s, name);
+    let e = find_extra(&mut b, obj);
+    let j = json::encode_extra(e);
+    match key {
+        None => println!("{}", j.pretty()),
+        Some(k) => match j.find(k) {
+            Some(v) => println!("{}", v.pretty()),
+            None => die(&format!("{} has no extra key {:?}", obj, k)),
+        },
+    }
+}
+
+fn set_extra(s: &Storage, name: &str, obj: &str, key: &str, value: &str) {
+    let j = Json::from_str(value)
+        .unwrap_or_else(|e| die(&format!("bad JSON value: {}", e)));
+    let mut b = load_bundle(s, name);
+    {
+        let e = find_extra(&mut b, obj);
+        json::set_extra(e, key, &j).unwrap_or_else(|e| die(&e.msg));
+    }
+    save_bundle(s, name, &b);
+}
+
+fn remove_extra(s: &Storage, name: &str, obj: &str, key: &str) {
+    let mut b = load_bundle(s, name);
+    {
+        let e = find_extra(&mut b, obj);
+        if !e.contains(key) {
+            die(&format!("{} has no extra key {:?}", obj, key));
+        }
+        e.remove(key);
+    }
+    save_bundle(s, name, &b);
+}
+
+
+fn main() {
+    let args = env::args().collect::<Vec<_>>();
+    if args.len() < 3 {
+        usage();
+    }
+    let s = storage::open(&args[1]);
+    let s = &*s;
+    let a = &args[3..];
+    match (&*args[2], a.len()) {
+        ("saves", 0) => saves(s),
+        ("dump", 1) => dump(s, &a[0]),
+        ("load", 2) => load(s, &a[0], &a[1]),
+        ("list", 1) => list(s, &a[0]),
+        ("diff", 2) => diff(s, &a[0], &a[1]),
+        ("get-extra", 2) => get_extra(s, &a[0], &a[1], None),
+        ("get-extra", 3) => get_extra(s, &a[0], &a[1], Some(&a[2])),
+        ("set-extra", 4) => set_extra(s, &a[0], &a[1], &a[2], &a[3]),
+        ("remove-extra", 3) => remove_extra(s, &a[0], &a[1], &a[2]),
+        _ => usage(),
+    }
+}
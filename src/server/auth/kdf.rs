@@ -0,0 +1,391 @@
+//! Password hashing.  This is a straightforward implementation of scrypt (RFC 7914), along with
+//! the SHA-256, HMAC and PBKDF2 primitives it is built on.  None of this is performance-critical
+//! beyond the scrypt inner loop, so the code favors clarity over speed.
+
+
+const SHA256_INIT: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a,
+    0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// Incremental SHA-256 state.
+#[derive(Clone)]
+struct Sha256 {
+    state: [u32; 8],
+    buf: [u8; 64],
+    buf_len: usize,
+    total_len: u64,
+}
+
+impl Sha256 {
+    fn new() -> Sha256 {
+        Sha256 {
+            state: SHA256_INIT,
+            buf: [0; 64],
+            buf_len: 0,
+            total_len: 0,
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        self.total_len += data.len() as u64;
+
+        while data.len() > 0 {
+            let n = ::std::cmp::min(64 - self.buf_len, data.len());
+            for i in 0 .. n {
+                self.buf[self.buf_len + i] = data[i];
+            }
+            self.buf_len += n;
+            data = &data[n..];
+
+            if self.buf_len == 64 {
+                let block = self.buf;
+                self.compress(&block);
+                self.buf_len = 0;
+            }
+        }
+    }
+
+    fn finish(mut self) -> [u8; 32] {
+        let bit_len = self.total_len * 8;
+
+        self.update(&[0x80]);
+        while self.buf_len != 56 {
+            self.update(&[0]);
+        }
+        let mut len_bytes = [0; 8];
+        for i in 0 .. 8 {
+            len_bytes[i] = (bit_len >> (56 - 8 * i)) as u8;
+        }
+        self.update(&len_bytes);
+        debug_assert!(self.buf_len == 0);
+
+        let mut out = [0; 32];
+        for i in 0 .. 8 {
+            write_u32_be(&mut out[i * 4 .. i * 4 + 4], self.state[i]);
+        }
+        out
+    }
+
+    fn compress(&mut self, block: &[u8; 64]) {
+        let mut w = [0u32; 64];
+        for i in 0 .. 16 {
+            w[i] = read_u32_be(&block[i * 4 .. i * 4 + 4]);
+        }
+        for i in 16 .. 64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+
+        let mut a = self.state[0];
+        let mut b = self.state[1];
+        let mut c = self.state[2];
+        let mut d = self.state[3];
+        let mut e = self.state[4];
+        let mut f = self.state[5];
+        let mut g = self.state[6];
+        let mut h = self.state[7];
+
+        for i in 0 .. 64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h.wrapping_add(s1).wrapping_add(ch)
+                      .wrapping_add(SHA256_K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        self.state[0] = self.state[0].wrapping_add(a);
+        self.state[1] = self.state[1].wrapping_add(b);
+        self.state[2] = self.state[2].wrapping_add(c);
+        self.state[3] = self.state[3].wrapping_add(d);
+        self.state[4] = self.state[4].wrapping_add(e);
+        self.state[5] = self.state[5].wrapping_add(f);
+        self.state[6] = self.state[6].wrapping_add(g);
+        self.state[7] = self.state[7].wrapping_add(h);
+    }
+}
+
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut s = Sha256::new();
+    s.update(data);
+    s.finish()
+}
+
+
+/// HMAC-SHA256 with a fixed key.  The inner and outer hash states are computed once up front so
+/// that PBKDF2 can reuse them for every block.
+#[derive(Clone)]
+struct HmacSha256 {
+    inner: Sha256,
+    outer: Sha256,
+}
+
+impl HmacSha256 {
+    fn new(key: &[u8]) -> HmacSha256 {
+        let mut key_block = [0; 64];
+        if key.len() > 64 {
+            let digest = sha256(key);
+            for i in 0 .. 32 {
+                key_block[i] = digest[i];
+            }
+        } else {
+            for i in 0 .. key.len() {
+                key_block[i] = key[i];
+            }
+        }
+
+        let mut ipad = [0; 64];
+        let mut opad = [0; 64];
+        for i in 0 .. 64 {
+            ipad[i] = key_block[i] ^ 0x36;
+            opad[i] = key_block[i] ^ 0x5c;
+        }
+
+        let mut inner = Sha256::new();
+        inner.update(&ipad);
+        let mut outer = Sha256::new();
+        outer.update(&opad);
+
+        HmacSha256 {
+            inner: inner,
+            outer: outer,
+        }
+    }
+
+    fn mac(&self, parts: &[&[u8]]) -> [u8; 32] {
+        let mut inner = self.inner.clone();
+        for part in parts {
+            inner.update(part);
+        }
+        let inner_digest = inner.finish();
+
+        let mut outer = self.outer.clone();
+        outer.update(&inner_digest);
+        outer.finish()
+    }
+}
+
+pub fn pbkdf2_sha256(password: &[u8], salt: &[u8], iterations: u32, out: &mut [u8]) {
+    let hmac = HmacSha256::new(password);
+
+    for (block_idx, chunk) in out.chunks_mut(32).enumerate() {
+        let mut idx_bytes = [0; 4];
+        write_u32_be(&mut idx_bytes, block_idx as u32 + 1);
+
+        let mut u = hmac.mac(&[salt, &idx_bytes]);
+        let mut t = u;
+        for _ in 1 .. iterations {
+            u = hmac.mac(&[&u]);
+            for i in 0 .. 32 {
+                t[i] ^= u[i];
+            }
+        }
+
+        for i in 0 .. chunk.len() {
+            chunk[i] = t[i];
+        }
+    }
+}
+
+
+/// Cost parameters for scrypt.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Params {
+    /// Base-2 logarithm of the CPU/memory cost `N`.
+    pub log_n: u8,
+    /// Block size `r`.
+    pub r: u32,
+    /// Parallelization `p`.
+    pub p: u32,
+}
+
+impl Params {
+    pub fn valid(&self) -> bool {
+        self.log_n > 0 && self.log_n < 32 &&
+        self.r > 0 && self.p > 0 &&
+        // Keep the scratch space below 1GB, so a corrupt database entry can't exhaust memory.
+        (self.r as u64) * 128 * (1u64 << self.log_n) <= 1 << 30 &&
+        (self.r as u64) * (self.p as u64) < 1 << 30
+    }
+}
+
+fn salsa20_8(b: &mut [u32; 16]) {
+    let mut x = *b;
+    for _ in 0 .. 4 {
+        macro_rules! qr {
+            ($a:expr, $b:expr, $c:expr, $d:expr) => {
+                x[$b] ^= x[$a].wrapping_add(x[$d]).rotate_left(7);
+                x[$c] ^= x[$b].wrapping_add(x[$a]).rotate_left(9);
+                x[$d] ^= x[$c].wrapping_add(x[$b]).rotate_left(13);
+                x[$a] ^= x[$d].wrapping_add(x[$c]).rotate_left(18);
+            };
+        }
+        // Columns
+        qr!(0, 4, 8, 12);
+        qr!(5, 9, 13, 1);
+        qr!(10, 14, 2, 6);
+        qr!(15, 3, 7, 11);
+        // Rows
+        qr!(0, 1, 2, 3);
+        qr!(5, 6, 7, 4);
+        qr!(10, 11, 8, 9);
+        qr!(15, 12, 13, 14);
+    }
+    for i in 0 .. 16 {
+        b[i] = b[i].wrapping_add(x[i]);
+    }
+}
+
+/// `scryptBlockMix`.  `b` and `out` each hold `2 * r` 64-byte blocks, as 16-word chunks.
+fn block_mix(b: &[u32], out: &mut [u32], r: usize) {
+    let mut x = [0u32; 16];
+    for i in 0 .. 16 {
+        x[i] = b[(2 * r - 1) * 16 + i];
+    }
+
+    for i in 0 .. 2 * r {
+        for j in 0 .. 16 {
+            x[j] ^= b[i * 16 + j];
+        }
+        salsa20_8(&mut x);
+
+        // Even blocks go to the first half of the output, odd blocks to the second.
+        let dest = (i / 2 + (i % 2) * r) * 16;
+        for j in 0 .. 16 {
+            out[dest + j] = x[j];
+        }
+    }
+}
+
+/// `scryptROMix`, operating on a single `128 * r` byte chunk of the PBKDF2 output.
+fn ro_mix(block: &mut [u8], log_n: u8, r: usize) {
+    let words = 32 * r;
+    let n = 1usize << log_n;
+
+    let mut x = vec![0u32; words];
+    for i in 0 .. words {
+        x[i] = read_u32_le(&block[i * 4 .. i * 4 + 4]);
+    }
+
+    let mut v = vec![0u32; words * n];
+    let mut tmp = vec![0u32; words];
+    for i in 0 .. n {
+        for j in 0 .. words {
+            v[i * words + j] = x[j];
+        }
+        block_mix(&x, &mut tmp, r);
+        ::std::mem::swap(&mut x, &mut tmp);
+    }
+
+    for _ in 0 .. n {
+        // `Integerify`: the first word of the last 64-byte block, mod N.
+        let j = x[(2 * r - 1) * 16] as usize & (n - 1);
+        for k in 0 .. words {
+            x[k] ^= v[j * words + k];
+        }
+        block_mix(&x, &mut tmp, r);
+        ::std::mem::swap(&mut x, &mut tmp);
+    }
+
+    for i in 0 .. words {
+        write_u32_le(&mut block[i * 4 .. i * 4 + 4], x[i]);
+    }
+}
+
+/// Derive `out.len()` bytes from `password` and `salt`.  Panics if `params` is not `valid()`.
+pub fn scrypt(password: &[u8], salt: &[u8], params: Params, out: &mut [u8]) {
+    assert!(params.valid(), "invalid scrypt parameters");
+    let r = params.r as usize;
+    let p = params.p as usize;
+
+    let mut b = vec![0u8; 128 * r * p];
+    pbkdf2_sha256(password, salt, 1, &mut b);
+
+    for chunk in b.chunks_mut(128 * r) {
+        ro_mix(chunk, params.log_n, r);
+    }
+
+    pbkdf2_sha256(password, &b, 1, out);
+}
+
+
+/// Compare two byte strings without short-circuiting on the first mismatch.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut acc = 0;
+    for (&x, &y) in a.iter().zip(b.iter()) {
+        acc |= x ^ y;
+    }
+    acc == 0
+}
+
+
+fn read_u32_be(b: &[u8]) -> u32 {
+    (b[0] as u32) << 24 | (b[1] as u32) << 16 | (b[2] as u32) << 8 | (b[3] as u32)
+}
+
+fn write_u32_be(b: &mut [u8], x: u32) {
+    b[0] = (x >> 24) as u8;
+    b[1] = (x >> 16) as u8;
+    b[2] = (x >> 8) as u8;
+    b[3] = x as u8;
+}
+
+fn read_u32_le(b: &[u8]) -> u32 {
+    (b[3] as u32) << 24 | (b[2] as u32) << 16 | (b[1] as u32) << 8 | (b[0] as u32)
+}
+
+fn write_u32_le(b: &mut [u8], x: u32) {
+    b[3] = (x >> 24) as u8;
+    b[2] = (x >> 16) as u8;
+    b[1] = (x >> 8) as u8;
+    b[0] = x as u8;
+}
+
+
+pub fn to_hex(b: &[u8]) -> String {
+    let mut s = String::with_capacity(b.len() * 2);
+    for &x in b {
+        s.push_str(&format!("{:02x}", x));
+    }
+    s
+}
+
+pub fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 || !s.bytes().all(|c| c < 0x80) {
+        return None;
+    }
+    let mut v = Vec::with_capacity(s.len() / 2);
+    for i in 0 .. s.len() / 2 {
+        match u8::from_str_radix(&s[i * 2 .. i * 2 + 2], 16) {
+            Ok(x) => v.push(x),
+            Err(_) => return None,
+        }
+    }
+    Some(v)
+}
@@ -0,0 +1,568 @@
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::hash::{SipHasher, Hash, Hasher};
+use std::path::Path;
+use std::result;
+use std::sync::mpsc::{self, Sender, Receiver};
+use std::thread;
+use rand;
+use time;
+
+use rusqlite::{self, Connection};
+use rusqlite::types::ToSql;
+use rusqlite_ffi::ErrorCode;
+
+use types::*;
+use util::StrError;
+
+pub mod kdf;
+pub mod schema;
+mod worker;
+
+
+pub type AuthEvent = worker::Response;
+
+/// Account database.  Secrets are hashed on a worker thread, so logins, registrations and secret
+/// changes are split in two: `start_login`, `start_register` or `start_change_secret` sends the
+/// secret off for hashing, and the engine passes the worker's reply to `process` to get the
+/// outcome.
+pub struct Auth {
+    conn: Connection,
+    send: Sender<worker::Command>,
+    recv: Receiver<AuthEvent>,
+    /// The request waiting on the worker for each wire.  A wire has at most one.
+    pending: HashMap<WireId, Pending>,
+    next_token: u32,
+}
+
+struct Pending {
+    /// Matches the token sent to the worker.  If the request was cancelled and the wire ID reused,
+    /// the reply to the old request won't match the new one.
+    token: u32,
+    name: String,
+    kind: PendingKind,
+}
+
+enum PendingKind {
+    /// `known` is `false` if the name had no account, in which case the worker checked the
+    /// secret against a dummy hash.
+    Login { known: bool },
+    Register { appearance: u32 },
+    ChangeSecret,
+}
+
+/// The result of a login or registration, once hashing is done.
+pub enum Outcome {
+    Login(String, Result<LoginResult>),
+    /// `Ok(false)` means the name is already registered.
+    Register(String, u32, Result<bool>),
+    /// `Ok(false)` means the old secret didn't match, or the account no longer exists.
+    ChangeSecret(String, Result<bool>),
+}
+
+/// Outcome of a login attempt that didn't hit a database error.
+#[derive(Clone, Debug)]
+pub enum LoginResult {
+    Ok,
+    /// The name is unknown or the secret doesn't match.  These are deliberately not distinguished.
+    BadSecret,
+    /// The secret was correct, but the account is banned.
+    Banned(BanInfo),
+}
+
+#[derive(Clone, Debug)]
+pub struct BanInfo {
+    pub reason: String,
+    /// Unix time (in milliseconds) when the ban is lifted, or `None` if the ban is permanent.
+    pub expires: Option<Time>,
+}
+
+impl BanInfo {
+    /// Human-readable explanation, suitable for sending to the banned client.
+    pub fn describe(&self) -> String {
+        match self.expires {
+            Some(t) => {
+                let tm = time::at_utc(time::Timespec::new(t / 1000, 0));
+                format!("banned until {}: {}", tm.rfc3339(), self.reason)
+            },
+            None => format!("banned: {}", self.reason),
+        }
+    }
+}
+
+/// Permission level of an account.  Roles are ordered, so each role can do anything the roles
+/// below it can.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Role {
+    Player = 0,
+    Moderator = 1,
+    Admin = 2,
+}
+
+impl Role {
+    pub fn from_primitive(x: u8) -> Option<Role> {
+        match x {
+            0 => Some(Role::Player),
+            1 => Some(Role::Moderator),
+            2 => Some(Role::Admin),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Player => "player",
+            Role::Moderator => "moderator",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl Auth {
+    pub fn new<P: AsRef<Path>>(db_path: &P) -> Result<Auth> {
+        let conn = try!(Connection::open(db_path));
+        try!(schema::upgrade(&conn));
+
+        let (send_cmd, recv_cmd) = mpsc::channel();
+        let (send_result, recv_result) = mpsc::channel();
+        // The thread exits once `send` is dropped.
+        thread::spawn(move || worker::run(recv_cmd, send_result));
+
+        Ok(Auth {
+            conn: conn,
+            send: send_cmd,
+            recv: recv_result,
+            pending: HashMap::new(),
+            next_token: 0,
+        })
+    }
+
+    pub fn receiver(&self) -> &Receiver<AuthEvent> {
+        &self.recv
+    }
+
+    /// Number of requests waiting on the worker.
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Check whether `wire_id` has a request waiting on the worker.  Callers must not start
+    /// another one until it finishes.
+    pub fn is_pending(&self, wire_id: WireId) -> bool {
+        self.pending.contains_key(&wire_id)
+    }
+
+    fn add_pending(&mut self, wire_id: WireId, name: &str, kind: PendingKind) -> u32 {
+        assert!(!self.is_pending(wire_id), "{:?} already has a request pending", wire_id);
+        let token = self.next_token;
+        self.next_token = self.next_token.wrapping_add(1);
+        self.pending.insert(wire_id, Pending {
+            token: token,
+            name: name.to_owned(),
+            kind: kind,
+        });
+        token
+    }
+
+    fn stored_hash(&mut self, name: &str) -> Result<Option<String>> {
+        let mut stmt = try!(self.conn.prepare("SELECT secret FROM auth WHERE name = $1"));
+        for row in try!(stmt.query(&[&name as &ToSql])) {
+            let row = try!(row);
+            return Ok(Some(row.get(0)));
+        }
+        Ok(None)
+    }
+
+    /// Start checking `secret` for a login as `name` on `wire_id`.
+    pub fn start_login(&mut self, wire_id: WireId, name: &str, secret: Secret) -> Result<()> {
+        let opt_hash = try!(self.stored_hash(name));
+        let known = opt_hash.is_some();
+        let token = self.add_pending(wire_id, name, PendingKind::Login { known: known });
+        self.send.send(worker::Command::Check(wire_id, token, secret, opt_hash)).unwrap();
+        Ok(())
+    }
+
+    /// Start hashing `secret` for a new account `name`.  `appearance` is passed back unchanged in
+    /// the `Outcome`.
+    pub fn start_register(&mut self,
+                          wire_id: WireId,
+                          name: &str,
+                          secret: Secret,
+                          appearance: u32) {
+        let token = self.add_pending(wire_id, name,
+                                     PendingKind::Register { appearance: appearance });
+        self.send.send(worker::Command::Hash(wire_id, token, secret)).unwrap();
+    }
+
+    /// Start replacing the secret of account `name`, on behalf of the client on `wire_id`.  The
+    /// worker checks `old_secret` against the stored hash, and hashes `new_secret` with a fresh
+    /// salt only if it matches.
+    pub fn start_change_secret(&mut self,
+                               wire_id: WireId,
+                               name: &str,
+                               old_secret: Secret,
+                               new_secret: Secret) -> Result<()> {
+        let opt_hash = try!(self.stored_hash(name));
+        let token = self.add_pending(wire_id, name, PendingKind::ChangeSecret);
+        self.send.send(worker::Command::Change(wire_id, token, old_secret, opt_hash, new_secret))
+            .unwrap();
+        Ok(())
+    }
+
+    /// Forget any request in progress on `wire_id`.  Call this when the wire
+    /// closes, so a later connection that reuses the ID can't pick up the result.
+    pub fn cancel(&mut self, wire_id: WireId) {
+        self.pending.remove(&wire_id);
+    }
+
+    /// Finish the request that `evt` is the reply for.  Returns `None` if it was
+    /// cancelled in the meantime.  `now` is the current Unix time in milliseconds,
+    /// used for checking ban expiry.
+    pub fn process(&mut self, evt: AuthEvent, now: Time) -> Option<(WireId, Outcome)> {
+        let (wire_id, token) = match evt {
+            worker::Response::Checked(wire_id, token, _, _) => (wire_id, token),
+            worker::Response::Hashed(wire_id, token, _) => (wire_id, token),
+            worker::Response::Changed(wire_id, token, _) => (wire_id, token),
+        };
+        if self.pending.get(&wire_id).map_or(true, |p| p.token != token) {
+            return None;
+        }
+        let p = self.pending.remove(&wire_id).unwrap();
+
+        let outcome = match (p.kind, evt) {
+            (PendingKind::Login { known }, worker::Response::Checked(_, _, matched, rehash)) => {
+                let result = self.finish_login(&p.name, known && matched, rehash, now);
+                Outcome::Login(p.name, result)
+            },
+            (PendingKind::Register { appearance }, worker::Response::Hashed(_, _, hash)) => {
+                let result = self.finish_register(&p.name, &hash);
+                Outcome::Register(p.name, appearance, result)
+            },
+            (PendingKind::ChangeSecret, worker::Response::Changed(_, _, opt_hash)) => {
+                let result = self.finish_change_secret(&p.name, opt_hash);
+                Outcome::ChangeSecret(p.name, result)
+            },
+            _ => unreachable!(),
+        };
+        Some((wire_id, outcome))
+    }
+
+    fn finish_login(&mut self,
+                    name: &str,
+                    matched: bool,
+                    rehash: Option<String>,
+                    now: Time) -> Result<LoginResult> {
+        if !matched {
+            return Ok(LoginResult::BadSecret);
+        }
+
+        if let Some(hash) = rehash {
+            info!("upgrading secret hash for {}", name);
+            try!(self.conn.execute("UPDATE auth SET secret = $2 WHERE name = $1",
+                                   &[&name as &ToSql,
+                                     &&*hash as &ToSql]));
+        }
+
+        match try!(self.ban_info(name, now)) {
+            Some(info) => Ok(LoginResult::Banned(info)),
+            None => Ok(LoginResult::Ok),
+        }
+    }
+
+    fn finish_register(&mut self, name: &str, hash: &str) -> Result<bool> {
+        let result = self.conn.execute("INSERT INTO auth (name, secret)
+                                        VALUES ($1, $2)",
+                                       &[&name as &ToSql,
+                                         &&*hash as &ToSql]);
+        match result {
+            Ok(_) => Ok(true),
+            // Constraint violation means the username is already registered.
+            Err(rusqlite::Error::SqliteFailure(ref e, _))
+                if e.code == ErrorCode::ConstraintViolation => Ok(false),
+            Err(e) => Err(Error::Sqlite(e)),
+        }
+    }
+
+    fn finish_change_secret(&mut self, name: &str, opt_hash: Option<String>) -> Result<bool> {
+        let hash = unwrap_or!(opt_hash, return Ok(false));
+        let count = try!(self.conn.execute("UPDATE auth SET secret = $2 WHERE name = $1",
+                                           &[&name as &ToSql,
+                                             &&*hash as &ToSql]));
+        Ok(count > 0)
+    }
+
+    /// Delete the account `name`, along with any ban or role for it.  Returns `false` if there is
+    /// no such account.
+    pub fn delete(&mut self, name: &str) -> Result<bool> {
+        try!(self.conn.execute_batch("BEGIN"));
+        let result = self.conn.execute("DELETE FROM bans WHERE name = $1", &[&name as &ToSql])
+            .and_then(|_| self.conn.execute("DELETE FROM roles WHERE name = $1",
+                                            &[&name as &ToSql]))
+            .and_then(|_| self.conn.execute("DELETE FROM auth WHERE name = $1",
+                                            &[&name as &ToSql]));
+        match result {
+            Ok(count) => {
+                try!(self.conn.execute_batch("COMMIT"));
+                Ok(count > 0)
+            },
+            Err(e) => {
+                warn_on_err!(self.conn.execute_batch("ROLLBACK"));
+                Err(From::from(e))
+            },
+        }
+    }
+
+    /// Get the role of account `name`.  Unknown accounts are treated as `Role::Player`.
+    pub fn role(&mut self, name: &str) -> Result<Role> {
+        let mut stmt = try!(self.conn.prepare("SELECT role FROM roles WHERE name = $1"));
+        for row in try!(stmt.query(&[&name as &ToSql])) {
+            let row = try!(row);
+            let raw: i64 = row.get(0);
+            let opt_role = if raw >= 0 && raw <= 255 { Role::from_primitive(raw as u8) }
+                           else { None };
+            return Ok(unwrap!(opt_role, "bad role value in database"));
+        }
+        Ok(Role::Player)
+    }
+
+    /// Set the role of account `name`.  Returns `false` if there is no such account.
+    pub fn set_role(&mut self, name: &str, role: Role) -> Result<bool> {
+        if !try!(self.exists(name)) {
+            return Ok(false);
+        }
+        if role == Role::Player {
+            try!(self.conn.execute("DELETE FROM roles WHERE name = $1", &[&name as &ToSql]));
+        } else {
+            try!(self.conn.execute("INSERT OR REPLACE INTO roles (name, role)
+                                    VALUES ($1, $2)",
+                                   &[&name as &ToSql,
+                                     &(role as i64) as &ToSql]));
+        }
+        Ok(true)
+    }
+
+    pub fn exists(&mut self, name: &str) -> Result<bool> {
+        let mut stmt = try!(self.conn.prepare("SELECT 1 FROM auth WHERE name = $1"));
+        for row in try!(stmt.query(&[&name as &ToSql])) {
+            try!(row);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Ban the account `name`, replacing any existing ban.  Returns `false` if there is no such
+    /// account.
+    pub fn ban(&mut self, name: &str, reason: &str, expires: Option<Time>) -> Result<bool> {
+        if !try!(self.exists(name)) {
+            return Ok(false);
+        }
+        try!(self.conn.execute("INSERT OR REPLACE INTO bans (name, reason, expires)
+                                VALUES ($1, $2, $3)",
+                               &[&name as &ToSql,
+                                 &reason as &ToSql,
+                                 &expires as &ToSql]));
+        Ok(true)
+    }
+
+    /// Lift the ban on `name`.  Returns `false` if the account was not banned.
+    pub fn unban(&mut self, name: &str) -> Result<bool> {
+        let count = try!(self.conn.execute("DELETE FROM bans WHERE name = $1",
+                                           &[&name as &ToSql]));
+        Ok(count > 0)
+    }
+
+    /// Get the active ban on `name`, if any.  Bans that have expired as of `now` are removed.
+    pub fn ban_info(&mut self, name: &str, now: Time) -> Result<Option<BanInfo>> {
+        let info = {
+            let mut stmt = try!(self.conn.prepare(
+                    "SELECT reason, expires FROM bans WHERE name = $1"));
+            let mut info = None;
+            for row in try!(stmt.query(&[&name as &ToSql])) {
+                let row = try!(row);
+                info = Some(BanInfo {
+                    reason: row.get(0),
+                    expires: row.get(1),
+                });
+                break;
+            }
+            info
+        };
+
+        match info {
+            Some(ref info) if info.expires.map_or(false, |t| t <= now) => {
+                info!("ban on {} has expired", name);
+                try!(self.unban(name));
+                Ok(None)
+            },
+            _ => Ok(info),
+        }
+    }
+}
+
+
+
+pub type Secret = [u32; 4];
+
+/// Hash format version.  Version 0 (SipHash with a random key) is no longer generated, but is
+/// still accepted and upgraded on login.
+const HASH_VERSION: u32 = 1;
+
+/// scrypt parameters for new hashes.  With N = 2^14 and r = 8, each check uses 16MB of scratch
+/// space.  Stored hashes record their own parameters, so these can be raised later; old hashes
+/// get upgraded the next time their owner logs in.
+const KDF_PARAMS: kdf::Params = kdf::Params { log_n: 14, r: 8, p: 1 };
+const SALT_LEN: usize = 16;
+const HASH_LEN: usize = 32;
+
+fn secret_bytes(s: &Secret) -> [u8; 16] {
+    let mut b = [0; 16];
+    for (i, &x) in s.iter().enumerate() {
+        b[i * 4 + 0] = x as u8;
+        b[i * 4 + 1] = (x >> 8) as u8;
+        b[i * 4 + 2] = (x >> 16) as u8;
+        b[i * 4 + 3] = (x >> 24) as u8;
+    }
+    b
+}
+
+fn hash_secret(s: &Secret) -> String {
+    let mut salt = [0; SALT_LEN];
+    for b in salt.iter_mut() {
+        *b = rand::random();
+    }
+
+    let mut hash = [0; HASH_LEN];
+    kdf::scrypt(&secret_bytes(s), &salt, KDF_PARAMS, &mut hash);
+
+    format!("{};{};{};{};{};{}",
+            HASH_VERSION,
+            KDF_PARAMS.log_n,
+            KDF_PARAMS.r,
+            KDF_PARAMS.p,
+            kdf::to_hex(&salt),
+            kdf::to_hex(&hash))
+}
+
+/// Run the KDF with the current parameters against a fixed salt, for login attempts on names that
+/// have no account.  This makes them take as long as attempts with a wrong secret, so response
+/// times don't reveal which names are registered.
+fn check_dummy(s: &Secret) -> bool {
+    let mut hash = [0; HASH_LEN];
+    kdf::scrypt(&secret_bytes(s), &[0; SALT_LEN], KDF_PARAMS, &mut hash);
+    kdf::constant_time_eq(&hash, &[0; HASH_LEN])
+}
+
+enum SecretMatch {
+    Yes,
+    No,
+    YesNeedsRehash,
+}
+
+fn check_secret(s: &Secret, hash: &str) -> SecretMatch {
+    let idx = unwrap_or!(hash.find(';'), return SecretMatch::No);
+    let version: u32 = unwrap_or!(hash[..idx].parse().ok(), return SecretMatch::No);
+    let rest = &hash[(idx + 1)..];
+
+    let result = match version {
+        0 => check_secret_v0(s, rest).map(|ok| (ok, true)),
+        1 => check_secret_v1(s, rest),
+        _ => None,
+    };
+
+    match result {
+        Some((true, false)) => SecretMatch::Yes,
+        Some((true, true)) => SecretMatch::YesNeedsRehash,
+        Some((false, _)) => SecretMatch::No,
+        None => {
+            warn!("malformed secret hash (version {})", version);
+            SecretMatch::No
+        },
+    }
+}
+
+fn check_secret_v0(s: &Secret, hash: &str) -> Option<bool> {
+    let mut iter = hash.split(';');
+    let salt0 = unwrap_or!(iter.next().and_then(|x| x.parse().ok()), return None);
+    let salt1 = unwrap_or!(iter.next().and_then(|x| x.parse().ok()), return None);
+    let expect_hash: u64 = unwrap_or!(iter.next().and_then(|x| x.parse().ok()), return None);
+
+    let mut sip = SipHasher::new_with_keys(salt0, salt1);
+    for x in s.iter() {
+        x.hash(&mut sip);
+    }
+    let hash = sip.finish();
+
+    Some(hash == expect_hash)
+}
+
+/// Returns `Some((matched, needs_rehash))`, or `None` if the hash string is malformed.
+fn check_secret_v1(s: &Secret, hash: &str) -> Option<(bool, bool)> {
+    let mut iter = hash.split(';');
+    let log_n = unwrap_or!(iter.next().and_then(|x| x.parse().ok()), return None);
+    let r = unwrap_or!(iter.next().and_then(|x| x.parse().ok()), return None);
+    let p = unwrap_or!(iter.next().and_then(|x| x.parse().ok()), return None);
+    let salt = unwrap_or!(iter.next().and_then(kdf::from_hex), return None);
+    let expect_hash = unwrap_or!(iter.next().and_then(kdf::from_hex), return None);
+
+    let params = kdf::Params { log_n: log_n, r: r, p: p };
+    if !params.valid() || expect_hash.len() == 0 {
+        return None;
+    }
+
+    let mut hash = vec![0; expect_hash.len()];
+    kdf::scrypt(&secret_bytes(s), &salt, params, &mut hash);
+
+    let matched = kdf::constant_time_eq(&hash, &expect_hash);
+    let needs_rehash = params != KDF_PARAMS ||
+                       salt.len() != SALT_LEN ||
+                       expect_hash.len() != HASH_LEN;
+    Some((matched, needs_rehash))
+}
+
+
+#[derive(Debug)]
+pub enum Error {
+    Str(StrError),
+    Sqlite(rusqlite::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Str(ref e) => e.fmt(f),
+            Error::Sqlite(ref e) => e.fmt(f),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Str(ref e) => e.description(),
+            Error::Sqlite(ref e) => e.description(),
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            Error::Str(ref e) => Some(e as &error::Error),
+            // SqliteError doesn't implement Error.
+            Error::Sqlite(ref e) => Some(e as &error::Error),
+        }
+    }
+}
+
+impl From<StrError> for Error {
+    fn from(e: StrError) -> Error {
+        Error::Str(e)
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(e: rusqlite::Error) -> Error {
+        Error::Sqlite(e)
+    }
+}
+
+pub type Result<T> = result::Result<T, Error>;
@@ -0,0 +1,77 @@
+//! Versioning for the auth database.  `MIGRATIONS[i]` upgrades the schema from version `i` to
+//! version `i + 1`.  Databases created before versioning was introduced have no `schema_version`
+//! table and are treated as version 0; the first migration is written to be a no-op on them.
+
+use rusqlite::Connection;
+use rusqlite::types::ToSql;
+
+use super::Result;
+
+
+const MIGRATIONS: &'static [&'static str] = &[
+    // 0 -> 1: the original `auth` table.
+    "CREATE TABLE IF NOT EXISTS auth (
+        name      TEXT NOT NULL UNIQUE,
+        secret    TEXT NOT NULL
+    );",
+
+    // 1 -> 2: bans.  `expires` is a Unix timestamp in milliseconds, or NULL for a permanent ban.
+    "CREATE TABLE bans (
+        name      TEXT NOT NULL UNIQUE,
+        reason    TEXT NOT NULL,
+        expires   INTEGER
+    );",
+
+    // 2 -> 3: roles.  Accounts with no entry here have `Role::Player`.
+    "CREATE TABLE roles (
+        name      TEXT NOT NULL UNIQUE,
+        role      INTEGER NOT NULL
+    );",
+];
+
+pub const CURRENT_VERSION: i64 = 3;
+
+
+fn get_version(conn: &Connection) -> Result<i64> {
+    try!(conn.execute("CREATE TABLE IF NOT EXISTS schema_version (
+                       version   INTEGER NOT NULL
+                       )", &[]));
+
+    let mut stmt = try!(conn.prepare("SELECT version FROM schema_version"));
+    for row in try!(stmt.query(&[])) {
+        let row = try!(row);
+        return Ok(row.get(0));
+    }
+
+    try!(conn.execute("INSERT INTO schema_version (version) VALUES (0)", &[]));
+    Ok(0)
+}
+
+/// Bring the database schema up to `CURRENT_VERSION`.  Each migration runs in its own
+/// transaction, so an interrupted upgrade leaves the database at some valid intermediate version.
+pub fn upgrade(conn: &Connection) -> Result<()> {
+    assert!(MIGRATIONS.len() as i64 == CURRENT_VERSION);
+
+    let mut version = try!(get_version(conn));
+    if version > CURRENT_VERSION {
+        fail!("auth database was created by a newer server version");
+    }
+
+    while version < CURRENT_VERSION {
+        info!("upgrading auth database from version {} to {}", version, version + 1);
+        try!(conn.execute_batch("BEGIN"));
+        let result = conn.execute_batch(MIGRATIONS[version as usize])
+            .and_then(|_| conn.execute("UPDATE schema_version SET version = $1",
+                                       &[&(version + 1) as &ToSql]));
+        match result {
+            Ok(_) => try!(conn.execute_batch("COMMIT")),
+            Err(e) => {
+                warn_on_err!(conn.execute_batch("ROLLBACK"));
+                return Err(From::from(e));
+            },
+        }
+        version += 1;
+    }
+
+    Ok(())
+}
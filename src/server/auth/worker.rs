@@ -0,0 +1,69 @@
+//! Background thread for secret hashing.  Each scrypt call takes tens of milliseconds, which is
+//! too long to spend on the engine thread for every login and registration.  The worker only does
+//! the hashing; all database access stays with `Auth`.
+use std::sync::mpsc::{Sender, Receiver};
+
+use types::*;
+
+use super::{Secret, SecretMatch};
+use super::{check_secret, check_dummy, hash_secret};
+
+
+pub enum Command {
+    /// Check a secret against a stored hash.  If there is no stored hash, the secret is checked
+    /// against a dummy one instead, so unknown names take as long as wrong secrets.
+    Check(WireId, u32, Secret, Option<String>),
+    /// Hash a secret for a new account.
+    Hash(WireId, u32, Secret),
+    /// Check the old secret against the stored hash, as for `Check`, and hash the new secret if
+    /// it matches.
+    Change(WireId, u32, Secret, Option<String>, Secret),
+}
+
+pub enum Response {
+    /// Whether the secret matched, and a new hash to store if the old one was outdated.
+    Checked(WireId, u32, bool, Option<String>),
+    Hashed(WireId, u32, String),
+    /// The new hash, or `None` if the old secret didn't match.
+    Changed(WireId, u32, Option<String>),
+}
+
+pub fn run(recv: Receiver<Command>, send: Sender<Response>) {
+    for cmd in recv.iter() {
+        let resp = match cmd {
+            Command::Check(wire_id, token, secret, opt_hash) => {
+                let (matched, rehash) = match opt_hash {
+                    Some(hash) => match check_secret(&secret, &hash) {
+                        SecretMatch::Yes => (true, None),
+                        SecretMatch::No => (false, None),
+                        SecretMatch::YesNeedsRehash => (true, Some(hash_secret(&secret))),
+                    },
+                    // `Auth` ignores the result for unknown names, but it's still sent back so
+                    // the work can't be optimized away.
+                    None => (check_dummy(&secret), None),
+                };
+                Response::Checked(wire_id, token, matched, rehash)
+            },
+
+            Command::Hash(wire_id, token, secret) =>
+                Response::Hashed(wire_id, token, hash_secret(&secret)),
+
+            Command::Change(wire_id, token, old_secret, opt_hash, new_secret) => {
+                let matched = match opt_hash {
+                    Some(hash) => match check_secret(&old_secret, &hash) {
+                        SecretMatch::Yes | SecretMatch::YesNeedsRehash => true,
+                        SecretMatch::No => false,
+                    },
+                    None => false,
+                };
+                let new_hash = if matched { Some(hash_secret(&new_secret)) } else { None };
+                Response::Changed(wire_id, token, new_hash)
+            },
+        };
+
+        if send.send(resp).is_err() {
+            // `Auth` is gone.
+            break;
+        }
+    }
+}
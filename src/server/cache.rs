@@ -3,11 +3,15 @@
 //! and any structures that overlap it.  External callers notify the `TerrainCache` when something
 //! changes in the world, so the cache can recompute the data for the relevant chunks.  Then other
 //! engine parts (such as the physics engine) can query the cache for information about terrain.
+//!
+//! The cache also remembers the terrain blocks of each chunk as they were last sent to clients, so
+//! that block changes can be sent as a small diff instead of resending the whole chunk.
 use std::collections::HashMap;
 
 use types::*;
 use util::StrResult;
 use libphysics::{CHUNK_BITS, CHUNK_SIZE};
+use libphysics::Surface;
 
 use world::World;
 use world::object::*;
@@ -19,7 +23,13 @@ pub struct TerrainCache {
 
 pub struct CacheEntry {
     pub shape: [Shape; 1 << (3 * CHUNK_BITS)],
+    pub surface: [Surface; 1 << (3 * CHUNK_BITS)],
     pub layer_mask: [u8; 1 << (3 * CHUNK_BITS)],
+    /// Copy of the chunk's blocks as of the last `update_region`.
+    pub blocks: BlockChunk,
+    /// Blocks that have changed since the last `clear_block_changes`, as `(index, new_block)`
+    /// pairs.
+    pub block_changes: Vec<(u16, BlockId)>,
 }
 
 impl TerrainCache {
@@ -35,6 +45,9 @@ impl TerrainCache {
         let base = cpos.extend(0) * scalar(CHUNK_SIZE);
         let bounds = Region::new(base, base + scalar(CHUNK_SIZE));
         try!(compute_shape(w, pid, cpos, bounds, &mut entry));
+        try!(diff_blocks(w, pid, cpos, bounds, &mut entry));
+        // Clients receive the whole chunk when it appears, so there's nothing to diff against.
+        entry.block_changes.clear();
 
         self.cache.insert((pid, cpos), entry);
         Ok(())
@@ -55,10 +68,18 @@ impl TerrainCache {
                 // During (3), the hook tries to update the cache.  The cache entry still exists
                 // (because (4) hasn't happened yet), but the chunk is gone.
                 let _ = compute_shape(w, pid, cpos, bounds, entry);
+                let _ = diff_blocks(w, pid, cpos, bounds, entry);
             }
         }
     }
 
+    /// Forget the accumulated `block_changes` for a chunk, once they have been sent out.
+    pub fn clear_block_changes(&mut self, pid: PlaneId, cpos: V2) {
+        if let Some(entry) = self.cache.get_mut(&(pid, cpos)) {
+            entry.block_changes.clear();
+        }
+    }
+
     pub fn get(&self, pid: PlaneId, cpos: V2) -> Option<&CacheEntry> {
         self.cache.get(&(pid, cpos))
     }
@@ -68,7 +89,10 @@ impl CacheEntry {
     pub fn new() -> CacheEntry {
         CacheEntry {
             shape: [Shape::Empty; 1 << (3 * CHUNK_BITS)],
+            surface: [Surface::normal(); 1 << (3 * CHUNK_BITS)],
             layer_mask: [0; 1 << (3 * CHUNK_BITS)],
+            blocks: EMPTY_CHUNK,
+            block_changes: Vec::new(),
         }
     }
 }
@@ -88,6 +112,7 @@ fn compute_shape(w: &World,
     for p in bounds.points() {
         let idx = chunk.bounds().index(p);
         entry.shape[idx] = data.block_data.shape(chunk.block(idx));
+        entry.surface[idx] = data.block_data.surface(chunk.block(idx));
         entry.layer_mask[idx] = 0;
     }
 
@@ -98,6 +123,12 @@ fn compute_shape(w: &World,
             let c_idx = chunk.bounds().index(p);
             if shape_overrides(entry.shape[c_idx], template.shape[s_idx]) {
                 entry.shape[c_idx] = template.shape[s_idx];
+                // Structures have no surface data of their own.  Anything solid enough to stand
+                // on covers up the terrain underneath.
+                // NB: keep this in sync with libclient/terrain.rs  get_surface()
+                if !template.shape[s_idx].is_empty() {
+                    entry.surface[c_idx] = Surface::normal();
+                }
             }
             entry.layer_mask[c_idx] |= 1 << (template.layer as usize);
         }
@@ -106,6 +137,29 @@ fn compute_shape(w: &World,
     Ok(())
 }
 
+fn diff_blocks(w: &World,
+               pid: PlaneId,
+               cpos: V2,
+               bounds: Region,
+               entry: &mut CacheEntry) -> StrResult<()> {
+    let p = unwrap!(w.get_plane(pid));
+    let chunk = unwrap!(p.get_terrain_chunk(cpos));
+    let bounds = bounds.intersect(chunk.bounds());
+
+    for p in bounds.points() {
+        let idx = chunk.bounds().index(p);
+        let block = chunk.block(idx);
+        if entry.blocks[idx] != block {
+            entry.blocks[idx] = block;
+            // A block that changes twice appears twice.  Clients apply changes in order, so the
+            // later one wins.
+            entry.block_changes.push((idx as u16, block));
+        }
+    }
+
+    Ok(())
+}
+
 fn shape_overrides(old: Shape, new: Shape) -> bool {
     match (old, new) {
         (Shape::Empty, _) => true,
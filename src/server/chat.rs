@@ -0,0 +1,418 @@
+//! Chat state tracking.  The chat system keeps a bounded history of recent messages, enforces
+//! per-client rate limits and mutes, runs messages through a list of filters, and records
+//! everything to an on-disk log.  On startup, the history is reloaded from the tail of that log,
+//! and mutes are reloaded from a separate mute file that is rewritten whenever they change.
+//!
+//! Like `vision`, the chat system doesn't consult the `World` directly.  Deciding who should
+//! receive a message (for example, who is close enough to hear a local message) and actually
+//! sending it are left to `logic::chat`.
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use types::*;
+
+
+/// Number of messages retained in the in-memory history.
+pub const HISTORY_LEN: usize = 100;
+
+/// Number of recent global messages sent to clients when they log in.
+pub const LOGIN_HISTORY_LEN: usize = 20;
+
+/// Maximum size of the chat log before it gets rotated.
+const MAX_LOG_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Maximum length of a chat message, in bytes.
+pub const MAX_MESSAGE_LEN: usize = 400;
+
+/// Rate limiting uses a token bucket: each message costs one token, and tokens refill at a fixed
+/// rate up to a maximum.  This allows short bursts while capping the long-term rate.
+pub const RATE_BURST: u32 = 5;
+pub const RATE_REFILL_MS: Time = 2000;
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Channel {
+    /// Sent to every client.
+    Global,
+    /// Sent to clients near the sender.
+    Local,
+    /// Sent to a single client.
+    Whisper,
+}
+
+impl Channel {
+    pub fn name(self) -> &'static str {
+        match self {
+            Channel::Global => "global",
+            Channel::Local => "local",
+            Channel::Whisper => "whisper",
+        }
+    }
+
+    pub fn from_name(s: &str) -> Option<Channel> {
+        match s {
+            "global" => Some(Channel::Global),
+            "local" => Some(Channel::Local),
+            "whisper" => Some(Channel::Whisper),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Message {
+    pub time: Time,
+    pub channel: Channel,
+    pub sender: String,
+    /// Recipient name, for `Channel::Whisper`.
+    pub target: Option<String>,
+    pub text: String,
+}
+
+impl Message {
+    /// Format the message for a `ChatUpdate` response.  The sender's name must come before the
+    /// tab, in angle brackets, so the client-side `/ignore` can recognize it.
+    pub fn format(&self) -> String {
+        match self.channel {
+            Channel::Global => format!("<{}>\t{}", self.sender, self.text),
+            Channel::Local => format!("<{}>\t(local) {}", self.sender, self.text),
+            Channel::Whisper => format!("<{}>\t(whisper to {}) {}",
+                                        self.sender,
+                                        self.target.as_ref().map_or("?", |s| s),
+                                        self.text),
+        }
+    }
+}
+
+
+/// A filter inspects each message before it is sent.  Filters can rewrite the message text or
+/// reject the message entirely.
+pub trait Filter {
+    /// Returns the (possibly modified) text, or `Err` with a reason to show the sender if the
+    /// message should be dropped.
+    fn filter(&mut self, sender: &str, channel: Channel, text: String) -> Result<String, String>;
+}
+
+/// Removes control characters.  In particular, this removes tabs, which would otherwise let
+/// players forge the name part of a `ChatUpdate`.
+pub struct ControlCharFilter;
+
+impl Filter for ControlCharFilter {
+    fn filter(&mut self, _: &str, _: Channel, text: String) -> Result<String, String> {
+        if text.chars().any(|c| c.is_control()) {
+            Ok(text.chars().filter(|c| !c.is_control()).collect())
+        } else {
+            Ok(text)
+        }
+    }
+}
+
+/// Rejects messages that are empty after trimming whitespace.
+pub struct EmptyFilter;
+
+impl Filter for EmptyFilter {
+    fn filter(&mut self, _: &str, _: Channel, text: String) -> Result<String, String> {
+        if text.trim().len() == 0 {
+            Err(String::new())
+        } else {
+            Ok(text)
+        }
+    }
+}
+
+
+/// Reasons a message can't be sent.
+#[derive(Clone, Debug)]
+pub enum Rejection {
+    TooLong,
+    RateLimited,
+    Muted(Option<Time>),
+    Filtered(String),
+}
+
+struct RateState {
+    tokens: u32,
+    last_refill: Time,
+}
+
+
+/// Chat subsystem state
+pub struct Chat {
+    history: VecDeque<Message>,
+    rate: HashMap<ClientId, RateState>,
+    /// Muted names, with the time the mute ends (or `None` for indefinite mutes).
+    muted: HashMap<String, Option<Time>>,
+    filters: Vec<Box<Filter>>,
+    log: ChatLog,
+    mute_path: PathBuf,
+}
+
+impl Chat {
+    /// Create the chat state, loading the history from the log at `log_path` and the mutes from
+    /// `mute_path`.  Missing files are treated as empty.
+    pub fn new<P: AsRef<Path>, Q: AsRef<Path>>(log_path: P, mute_path: Q) -> Chat {
+        let log_path = log_path.as_ref().to_owned();
+        let mute_path = mute_path.as_ref().to_owned();
+
+        let mut history = VecDeque::with_capacity(HISTORY_LEN);
+        warn_on_err!(load_history(&rotated_path(&log_path), &mut history));
+        warn_on_err!(load_history(&log_path, &mut history));
+
+        let mut muted = HashMap::new();
+        warn_on_err!(load_mutes(&mute_path, &mut muted));
+
+        Chat {
+            history: history,
+            rate: HashMap::new(),
+            muted: muted,
+            filters: vec![Box::new(ControlCharFilter) as Box<Filter>,
+                          Box::new(EmptyFilter) as Box<Filter>],
+            log: ChatLog::new(log_path),
+            mute_path: mute_path,
+        }
+    }
+
+    pub fn add_filter(&mut self, filter: Box<Filter>) {
+        self.filters.push(filter);
+    }
+
+    /// Check whether `cid` (logged in as `name`) may send `text` right now, and run it through
+    /// the filters.  On success, a rate limit token is consumed and the filtered text is
+    /// returned.
+    pub fn check(&mut self,
+                 now: Time,
+                 cid: ClientId,
+                 name: &str,
+                 channel: Channel,
+                 text: String) -> Result<String, Rejection> {
+        if text.len() > MAX_MESSAGE_LEN {
+            return Err(Rejection::TooLong);
+        }
+
+        if let Some(until) = self.mute_status(now, name) {
+            return Err(Rejection::Muted(until));
+        }
+
+        let mut text = text;
+        for f in self.filters.iter_mut() {
+            text = match f.filter(name, channel, text) {
+                Ok(t) => t,
+                Err(reason) => return Err(Rejection::Filtered(reason)),
+            };
+        }
+
+        let state = self.rate.entry(cid).or_insert_with(|| RateState {
+            tokens: RATE_BURST,
+            last_refill: now,
+        });
+        if now > state.last_refill {
+            let refill = (now - state.last_refill) / RATE_REFILL_MS;
+            if refill > 0 {
+                state.tokens = ::std::cmp::min(RATE_BURST as Time,
+                                               state.tokens as Time + refill) as u32;
+                state.last_refill += refill * RATE_REFILL_MS;
+            }
+        }
+        if state.tokens == 0 {
+            return Err(Rejection::RateLimited);
+        }
+        if state.tokens == RATE_BURST {
+            // The bucket was full, so refill time spent while full doesn't count.
+            state.last_refill = now;
+        }
+        state.tokens -= 1;
+
+        Ok(text)
+    }
+
+    /// Record a message that has been sent.
+    pub fn record(&mut self, msg: Message) {
+        self.log.write(&msg);
+        if self.history.len() >= HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(msg);
+    }
+
+    /// Iterate over recent messages on `channel`, oldest first.  At most `count` messages are
+    /// returned.
+    pub fn history(&self, channel: Channel, count: usize) -> Vec<&Message> {
+        let mut result = self.history.iter().rev()
+                             .filter(|m| m.channel == channel)
+                             .take(count)
+                             .collect::<Vec<_>>();
+        result.reverse();
+        result
+    }
+
+    pub fn remove_client(&mut self, cid: ClientId) {
+        self.rate.remove(&cid);
+    }
+
+
+    /// Mute `name` until `until`, or indefinitely if `until` is `None`.
+    pub fn mute(&mut self, name: &str, until: Option<Time>) {
+        self.muted.insert(name.to_owned(), until);
+        warn_on_err!(save_mutes(&self.mute_path, &self.muted));
+    }
+
+    /// Returns `false` if `name` was not muted.
+    pub fn unmute(&mut self, name: &str) -> bool {
+        let removed = self.muted.remove(name).is_some();
+        if removed {
+            warn_on_err!(save_mutes(&self.mute_path, &self.muted));
+        }
+        removed
+    }
+
+    /// Returns `Some(until)` if `name` is currently muted.  Expired mutes are removed.
+    pub fn mute_status(&mut self, now: Time, name: &str) -> Option<Option<Time>> {
+        let until = unwrap_or!(self.muted.get(name).cloned(), return None);
+        match until {
+            Some(t) if t <= now => {
+                self.muted.remove(name);
+                warn_on_err!(save_mutes(&self.mute_path, &self.muted));
+                None
+            },
+            _ => Some(until),
+        }
+    }
+}
+
+
+/// Append-only chat log.  When the file exceeds `MAX_LOG_SIZE`, it gets renamed with a `.1`
+/// suffix (replacing any previous one) and a new file is started.  Failures are logged but
+/// otherwise ignored - losing the chat log shouldn't take down the server.
+struct ChatLog {
+    path: PathBuf,
+    file: Option<File>,
+    size: u64,
+}
+
+impl ChatLog {
+    fn new(path: PathBuf) -> ChatLog {
+        ChatLog {
+            path: path,
+            file: None,
+            size: 0,
+        }
+    }
+
+    fn open(&mut self) -> io::Result<()> {
+        let file = try!(OpenOptions::new().append(true).create(true).open(&self.path));
+        self.size = try!(file.metadata()).len();
+        self.file = Some(file);
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file = None;
+        try!(fs::rename(&self.path, rotated_path(&self.path)));
+        self.open()
+    }
+
+    fn write(&mut self, msg: &Message) {
+        if self.file.is_none() {
+            warn_on_err!(self.open());
+        }
+        if self.size >= MAX_LOG_SIZE {
+            warn_on_err!(self.rotate());
+        }
+
+        let line = format!("{}\t{}\t{}\t{}\t{}\n",
+                           msg.time,
+                           msg.channel.name(),
+                           msg.sender,
+                           msg.target.as_ref().map_or("", |s| s),
+                           msg.text);
+        if let Some(ref mut file) = self.file {
+            match file.write_all(line.as_bytes()) {
+                Ok(()) => self.size += line.len() as u64,
+                Err(e) => warn!("error writing chat log: {}", e),
+            }
+        }
+    }
+}
+
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut old_name = path.file_name().unwrap().to_owned();
+    old_name.push(".1");
+    path.with_file_name(old_name)
+}
+
+/// Append the messages from the chat log at `path` to `history`, keeping only the last
+/// `HISTORY_LEN`.  Lines that can't be parsed are skipped.
+fn load_history(path: &Path, history: &mut VecDeque<Message>) -> io::Result<()> {
+    let file = match File::open(path) {
+        Ok(f) => f,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    for line in BufReader::new(file).lines() {
+        let line = try!(line);
+        let mut parts = line.splitn(5, '\t');
+        let time = unwrap_or!(parts.next().and_then(|s| s.parse().ok()), continue);
+        let channel = unwrap_or!(parts.next().and_then(Channel::from_name), continue);
+        let sender = unwrap_or!(parts.next(), continue);
+        let target = unwrap_or!(parts.next(), continue);
+        let text = unwrap_or!(parts.next(), continue);
+
+        if history.len() >= HISTORY_LEN {
+            history.pop_front();
+        }
+        history.push_back(Message {
+            time: time,
+            channel: channel,
+            sender: sender.to_owned(),
+            target: if target.len() > 0 { Some(target.to_owned()) } else { None },
+            text: text.to_owned(),
+        });
+    }
+    Ok(())
+}
+
+/// Read the mute file at `path`.  Each line is a name and the time the mute ends, separated by a
+/// tab.  The time is empty for indefinite mutes.
+fn load_mutes(path: &Path, muted: &mut HashMap<String, Option<Time>>) -> io::Result<()> {
+    let file = match File::open(path) {
+        Ok(f) => f,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    for line in BufReader::new(file).lines() {
+        let line = try!(line);
+        let mut parts = line.splitn(2, '\t');
+        let name = unwrap_or!(parts.next(), continue);
+        let until = match parts.next() {
+            Some("") => None,
+            Some(s) => Some(unwrap_or!(s.parse().ok(), continue)),
+            None => continue,
+        };
+        muted.insert(name.to_owned(), until);
+    }
+    Ok(())
+}
+
+/// Rewrite the mute file.  The new contents are written to a temporary file first, so a crash
+/// partway through leaves the old mutes in place.
+fn save_mutes(path: &Path, muted: &HashMap<String, Option<Time>>) -> io::Result<()> {
+    let mut tmp_name = path.file_name().unwrap().to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    {
+        let mut file = try!(File::create(&tmp_path));
+        for (name, until) in muted {
+            match *until {
+                Some(t) => try!(write!(file, "{}\t{}\n", name, t)),
+                None => try!(write!(file, "{}\t\n", name)),
+            }
+        }
+        try!(file.sync_all());
+    }
+    fs::rename(&tmp_path, path)
+}
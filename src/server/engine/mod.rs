@@ -1,11 +1,14 @@
 use std::boxed::FnBox;
 use std::error::Error;
+use std::mem;
 use std::sync::mpsc::{Sender, Receiver};
 
 use types::*;
+use util;
 
-use auth::{Auth, Secret};
+use auth::{self, Auth, AuthEvent, LoginResult, Outcome};
 use cache::TerrainCache;
+use chat::Chat;
 use chunks::Chunks;
 use data::Data;
 use logic;
@@ -14,7 +17,7 @@ use messages::{Messages, MessageEvent};
 use messages::{Event, ControlEvent, WireEvent, ClientEvent};
 use messages::SyncKind;
 use messages::{ControlResponse, WireResponse, ClientResponse};
-use msg::{Request, Response};
+use msg::{Request, Response, PROTOCOL_VERSION};
 use physics::Physics;
 use script::ScriptHooks;
 use storage::Storage;
@@ -48,6 +51,15 @@ pub struct Engine<'d> {
     pub chunks: Chunks<'d>,
     pub cache: TerrainCache,
     pub terrain_gen: TerrainGen,
+    pub chat: Chat,
+}
+
+/// An event for the main loop to handle.
+pub enum LoopEvent {
+    FromTimer(TimerEvent),
+    FromMessage(MessageEvent),
+    FromTerrainGen(TerrainGenEvent),
+    FromAuth(AuthEvent),
 }
 
 #[must_use]
@@ -81,59 +93,125 @@ impl<'d> Engine<'d> {
             chunks: Chunks::new(storage),
             cache: TerrainCache::new(),
             terrain_gen: TerrainGen::new(data, storage),
+            chat: Chat::new(storage.chat_log_path(), storage.chat_mute_path()),
         }
     }
 
     pub fn run(&mut self) {
-        use self::HandlerResult::*;
-        logic::lifecycle::start_up(self.as_ref());
-        if let Some(file) = self.storage.open_restart_file() {
-            logic::lifecycle::post_restart(self.as_ref(), file);
-            self.storage.remove_restart_file();
-        }
+        self.start_up();
 
         loop {
-            enum Event {
-                FromTimer(TimerEvent),
-                FromMessage(MessageEvent),
-                FromTerrainGen(TerrainGenEvent),
-            }
-
             let evt = {
                 let recv_timer = self.timer.receiver();
                 let recv_message = self.messages.receiver();
                 let recv_terrain_gen = self.terrain_gen.receiver();
+                let recv_auth = self.auth.receiver();
                 select! {
-                    evt = recv_timer.recv() => Event::FromTimer(evt.unwrap()),
-                    evt = recv_message.recv() => Event::FromMessage(evt.unwrap()),
-                    evt = recv_terrain_gen.recv() => Event::FromTerrainGen(evt.unwrap())
+                    evt = recv_timer.recv() => LoopEvent::FromTimer(evt.unwrap()),
+                    evt = recv_message.recv() => LoopEvent::FromMessage(evt.unwrap()),
+                    evt = recv_terrain_gen.recv() => LoopEvent::FromTerrainGen(evt.unwrap()),
+                    evt = recv_auth.recv() => LoopEvent::FromAuth(evt.unwrap())
                 }
             };
 
-            match evt {
-                Event::FromTimer(evt) => {
-                    let (cb, now) = unwrap_or!(self.timer.process(evt), continue);
-                    self.now = now;
-                    cb.call_box((self.as_ref(),));
-                },
-                Event::FromMessage(evt) => {
-                    let (evt, now) = unwrap_or!(self.messages.process(evt), continue);
-                    match self.handle(now, evt) {
-                        Continue => {},
-                        Shutdown => break,
-                        Restart => {
-                            logic::lifecycle::pre_restart(self.as_ref());
-                            break;
-                        },
-                    }
-                },
-                Event::FromTerrainGen(evt) => {
-                    self.as_ref().as_terrain_gen_fragment().process(evt);
-                },
+            if !self.process(evt) {
+                break;
             }
         }
 
+        self.shut_down();
+    }
+
+    /// Load the world and restore clients from a previous restart.  `run` calls this before
+    /// entering its main loop.
+    pub fn start_up(&mut self) {
+        logic::snapshot::apply_pending_restore(self.storage);
+        logic::lifecycle::start_up(self.as_ref());
+        if let Some(file) = self.storage.open_restart_file() {
+            if self.extra.wires_survive_restart {
+                logic::lifecycle::post_restart(self.as_ref(), file);
+            } else {
+                // The wire IDs in the file belonged to connections that are gone, and will be
+                // handed out again to new, unauthenticated ones.
+                warn!("discarding restart file: connections did not survive the restart");
+            }
+            self.storage.remove_restart_file();
+        }
+        self.flush_messages();
+    }
+
+    /// Handle one event from the main loop.  Returns `false` if the server should stop, in which
+    /// case the caller should call `shut_down` next.
+    pub fn process(&mut self, evt: LoopEvent) -> bool {
+        use self::HandlerResult::*;
+        match evt {
+            LoopEvent::FromTimer(evt) => {
+                let (cb, now) = unwrap_or!(self.timer.process(evt), return true);
+                self.now = now;
+                cb.call_box((self.as_ref(),));
+            },
+            LoopEvent::FromMessage(evt) => {
+                let (evt, now) = unwrap_or!(self.messages.process(evt), return true);
+                match self.handle(now, evt) {
+                    Continue => {},
+                    Shutdown => return false,
+                    Restart => {
+                        self.pre_restart();
+                        return false;
+                    },
+                }
+            },
+            LoopEvent::FromTerrainGen(evt) => {
+                self.as_ref().as_terrain_gen_fragment().process(evt);
+            },
+            LoopEvent::FromAuth(evt) => {
+                let (wire_id, outcome) = unwrap_or!(self.auth.process(evt, util::now()),
+                                                    return true);
+                self.now = self.messages.now();
+                self.handle_auth(wire_id, outcome);
+            },
+        }
+        self.flush_messages();
+
+        if mem::replace(&mut self.extra.restart_requested, false) {
+            self.pre_restart();
+            return false;
+        }
+        true
+    }
+
+    /// Record connected clients so they can be logged back in once the new process starts.  If
+    /// connections won't survive the restart, there is nothing to record, and the restart is just
+    /// a shutdown.
+    fn pre_restart(&mut self) {
+        if self.extra.wires_survive_restart {
+            logic::lifecycle::pre_restart(self.as_ref());
+        } else {
+            info!("connections can't be kept open across a restart; shutting down instead");
+        }
+    }
+
+    pub fn shut_down(&mut self) {
         logic::lifecycle::shut_down(self.as_ref());
+        self.messages.flush_all();
+    }
+
+    /// Send queued responses, and disconnect clients that have fallen too far behind.  Clients
+    /// that are out of budget for now get the rest of their responses on a later tick.
+    fn flush_messages(&mut self) {
+        for wire_id in self.messages.take_overflowed_wires() {
+            self.kick_wire(wire_id, "Connection too slow");
+        }
+
+        if let Some(when) = self.messages.flush() {
+            if !self.extra.message_flush_pending {
+                self.extra.message_flush_pending = true;
+                self.timer.schedule(when, |mut eng: EngineRef| {
+                    // `process` flushes after every event, including this one.
+                    eng.extra_mut().message_flush_pending = false;
+                });
+            }
+        }
     }
 
 
@@ -157,6 +235,7 @@ impl<'d> Engine<'d> {
             OpenWire(_wire_id) => {},
 
             CloseWire(wire_id, opt_cid) => {
+                self.auth.cancel(wire_id);
                 if let Some(cid) = opt_cid {
                     self.cleanup_client(cid);
                 }
@@ -164,6 +243,10 @@ impl<'d> Engine<'d> {
             },
 
             ReplCommand(cookie, msg) => {
+                // The REPL is only reachable through the wrapper's control socket, so commands
+                // from it always run with full permissions.  Chat commands from clients are
+                // checked against `Auth` roles in `logic::input::chat`.
+
                 // TODO: remove
                 let mut msg = &msg as &str;
                 if msg.starts_with("@") {
@@ -204,16 +287,61 @@ impl<'d> Engine<'d> {
         use messages::WireEvent::*;
         use messages::WireResponse::*;
         match evt {
+            // Clients wait for the result of one request before sending the next.
+            Login(..) | Register(..) if self.auth.is_pending(wire_id) => {
+                self.kick_wire(wire_id, "bad request");
+            },
+
             Login(name, secret) => {
-                match self.auth.login(&*name, &secret) {
-                    Ok(true) => {
+                if let Err(e) = self.auth.start_login(wire_id, &*name, secret) {
+                    info!("{:?}: login as {} failed: auth error: {}",
+                          wire_id, name, e.description());
+                    self.kick_wire(wire_id, "login failed");
+                }
+            },
+
+            Register(name, secret, appearance) => {
+                if let Err(msg) = name_valid(&*name) {
+                    self.messages.send_wire(wire_id, RegisterResult(1, String::from(msg)));
+                } else {
+                    self.auth.start_register(wire_id, &*name, secret, appearance);
+                }
+            },
+
+            IncompatibleVersion(version) => {
+                info!("{:?}: incompatible protocol version {} (expected {})",
+                      wire_id, version, PROTOCOL_VERSION);
+                self.kick_wire(wire_id, format!("incompatible client version (server speaks \
+                                                 protocol version {}); please reload the page",
+                                                PROTOCOL_VERSION));
+            },
+
+            BadRequest => {
+                self.kick_wire(wire_id, "bad request");
+            },
+        }
+        HandlerResult::Continue
+    }
+
+    /// Finish a login, registration or secret change once its secret has been hashed.
+    fn handle_auth(&mut self, wire_id: WireId, outcome: Outcome) {
+        use messages::WireResponse::*;
+        match outcome {
+            Outcome::Login(name, result) => {
+                match result {
+                    Ok(LoginResult::Ok) => {
                         warn_on_err!(logic::client::login(self.as_ref(), wire_id, &*name));
                     },
-                    Ok(false) => {
+                    Ok(LoginResult::BadSecret) => {
                         info!("{:?}: login as {} failed: bad name/secret",
                               wire_id, name);
                         self.kick_wire(wire_id, "login failed")
                     },
+                    Ok(LoginResult::Banned(ban)) => {
+                        info!("{:?}: login as {} failed: account is banned ({})",
+                              wire_id, name, ban.reason);
+                        self.kick_wire(wire_id, ban.describe())
+                    },
                     Err(e) => {
                         info!("{:?}: login as {} failed: auth error: {}",
                               wire_id, name, e.description());
@@ -222,16 +350,15 @@ impl<'d> Engine<'d> {
                 }
             },
 
-            Register(name, secret, appearance) => {
-                let (code, msg) = self.do_register(wire_id, name, secret, appearance);
+            Outcome::Register(name, appearance, result) => {
+                let (code, msg) = self.finish_register(wire_id, name, appearance, result);
                 self.messages.send_wire(wire_id, RegisterResult(code, msg));
             },
 
-            BadRequest => {
-                self.kick_wire(wire_id, "bad request");
+            Outcome::ChangeSecret(name, result) => {
+                logic::client::secret_changed(self.as_ref(), wire_id, &name, result);
             },
         }
-        HandlerResult::Continue
     }
 
     fn handle_client(&mut self,
@@ -258,8 +385,7 @@ impl<'d> Engine<'d> {
             },
 
             CraftRecipe(station_sid, iid, recipe_id, count) => {
-                warn_on_err!(logic::items::craft_recipe(self.as_ref(),
-                                                        station_sid, iid, recipe_id, count));
+                logic::input::craft_recipe(self.as_ref(), cid, station_sid, iid, recipe_id, count);
             },
 
             Chat(msg) => {
@@ -281,6 +407,11 @@ impl<'d> Engine<'d> {
                                     move |eng| logic::input::use_ability(eng, cid, item_id, args));
             },
 
+            UseAction(time, action_id, args) => {
+                self.timer.schedule(time,
+                                    move |eng| logic::input::use_action(eng, cid, action_id, args));
+            },
+
             BadRequest => {
                 self.kick_client(cid, "bad request");
             },
@@ -294,9 +425,11 @@ impl<'d> Engine<'d> {
     }
 
     fn cleanup_wire(&mut self, wire_id: WireId) {
+        self.auth.cancel(wire_id);
         if let Some(cid) = self.messages.wire_to_client(wire_id) {
             self.cleanup_client(cid);
         }
+        self.messages.forget_wire(wire_id);
     }
 
     pub fn kick_client<'a, S: Into<String>>(&mut self, cid: ClientId, msg: S) {
@@ -320,16 +453,12 @@ impl<'d> Engine<'d> {
     }
 
 
-    fn do_register(&mut self,
-                   wire_id: WireId,
-                   name: String,
-                   secret: Secret,
-                   appearance: u32) -> (u32, String) {
-        if let Err(msg) = name_valid(&*name) {
-            return (1, String::from(msg));
-        }
-
-        match self.auth.register(&*name, &secret) {
+    fn finish_register(&mut self,
+                       wire_id: WireId,
+                       name: String,
+                       appearance: u32,
+                       result: auth::Result<bool>) -> (u32, String) {
+        match result {
             Ok(true) => {
                 info!("{:?}: registered as {}", wire_id, name);
                 match logic::client::register(self.as_ref(), &*name, appearance) {
@@ -9,6 +9,7 @@ bitflags! {
         const INPUT_UP =        0x0004,
         const INPUT_DOWN =      0x0008,
         const INPUT_RUN =       0x0010,
+        const INPUT_JUMP =      0x0020,
     }
 }
 
@@ -22,41 +23,9 @@ impl InputBits {
             if self.contains(INPUT_DOWN) { 1 } else { 0 };
         // TODO: player speed handling shouldn't be here
         let speed = if self.contains(INPUT_RUN) { 150 } else { 50 };
-        V3::new(x, y, 0) * scalar(speed)
+        // Only the sign of `z` matters to the physics engine.  It always jumps at the same speed.
+        let z = if self.contains(INPUT_JUMP) { 1 } else { 0 };
+        V3::new(x, y, 0) * scalar(speed) + V3::new(0, 0, z)
     }
 }
 
-
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
-pub struct ActionId(pub u16);
-
-macro_rules! action_ids {
-    ($($name:ident = $val:expr,)*) => {
-        $( pub const $name: ActionId = ActionId($val); )*
-    }
-}
-
-action_ids! {
-    ACTION_USE =        1,
-    ACTION_INVENTORY =  2,
-    ACTION_USE_ITEM =   3,
-}
-
-
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
-pub enum Action {
-    Use,
-    Inventory,
-    UseItem(ItemId),
-}
-
-impl Action {
-    pub fn decode(action: u16, arg: u32) -> Option<Action> {
-        match (action, arg) {
-            (1, 0) => Some(Action::Use),
-            (2, 0) => Some(Action::Inventory),
-            (3, _) => Some(Action::UseItem(arg as ItemId)),
-            _ => None,
-        }
-    }
-}
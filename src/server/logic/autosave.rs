@@ -0,0 +1,269 @@
+//! Incremental autosave.  The `world::Hooks` implementations mark terrain chunks, planes, and
+//! clients as dirty when they change.  Every `AUTOSAVE_INTERVAL`, the current dirty set becomes
+//! the pending set for a new pass, and the pending objects are written out a batch at a time, so
+//! a large pass doesn't hold up the rest of the server.  Objects that are dirtied again during a
+//! pass get picked up by the next one.
+//!
+//! The world itself is written at the end of any pass that wrote something else, since it holds
+//! the ID counters needed to make sense of the other files.
+use std::collections::HashSet;
+use std::mem;
+
+use libphysics::CHUNK_SIZE;
+
+use types::*;
+
+use engine::split::EngineRef;
+use logic;
+use world::Fragment;
+use world::bundle;
+use world::flags;
+use world::object::*;
+
+
+/// Time between the start of one autosave pass and the start of the next.
+const AUTOSAVE_INTERVAL: Time = 60_000;
+
+/// Maximum number of objects to write in a single batch.
+const BATCH_SIZE: usize = 16;
+
+/// Delay between batches within a pass.
+const BATCH_DELAY: Time = 50;
+
+
+#[derive(Default)]
+struct DirtySet {
+    terrain_chunks: HashSet<(PlaneId, V2)>,
+    planes: HashSet<PlaneId>,
+    clients: HashSet<ClientId>,
+    world: bool,
+}
+
+impl DirtySet {
+    fn is_empty(&self) -> bool {
+        self.terrain_chunks.len() == 0 &&
+        self.planes.len() == 0 &&
+        self.clients.len() == 0
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Item {
+    TerrainChunk(PlaneId, V2),
+    Plane(PlaneId),
+    Client(ClientId),
+}
+
+/// Dirty tracking state.  Lives in `logic::extra::Extra`.
+pub struct Autosave {
+    dirty: DirtySet,
+    pending: DirtySet,
+}
+
+impl Autosave {
+    pub fn new() -> Autosave {
+        Autosave {
+            dirty: DirtySet::default(),
+            pending: DirtySet::default(),
+        }
+    }
+
+    pub fn mark_terrain_chunk(&mut self, pid: PlaneId, cpos: V2) {
+        self.dirty.terrain_chunks.insert((pid, cpos));
+    }
+
+    /// Mark the terrain chunk containing `pos`.
+    pub fn mark_terrain_chunk_at(&mut self, pid: PlaneId, pos: V3) {
+        let cpos = pos.reduce().div_floor(scalar(CHUNK_SIZE));
+        self.mark_terrain_chunk(pid, cpos);
+    }
+
+    pub fn mark_plane(&mut self, pid: PlaneId) {
+        self.dirty.planes.insert(pid);
+    }
+
+    pub fn mark_client(&mut self, cid: ClientId) {
+        self.dirty.clients.insert(cid);
+    }
+
+    pub fn mark_world(&mut self) {
+        self.dirty.world = true;
+    }
+
+    // The `forget_*` methods are called when an object is destroyed.  Objects are always saved
+    // before being unloaded, and the transient ID may be reused afterward, so the old mark must
+    // not be carried over.
+
+    pub fn forget_terrain_chunk(&mut self, pid: PlaneId, cpos: V2) {
+        self.dirty.terrain_chunks.remove(&(pid, cpos));
+        self.pending.terrain_chunks.remove(&(pid, cpos));
+    }
+
+    pub fn forget_plane(&mut self, pid: PlaneId) {
+        self.dirty.planes.remove(&pid);
+        self.pending.planes.remove(&pid);
+    }
+
+    pub fn forget_client(&mut self, cid: ClientId) {
+        self.dirty.clients.remove(&cid);
+        self.pending.clients.remove(&cid);
+    }
+
+    fn begin_pass(&mut self) {
+        self.pending = mem::replace(&mut self.dirty, DirtySet::default());
+    }
+
+    /// Take the next object to write in the current pass.  Terrain chunks come first, since
+    /// saving a chunk for the first time also changes its plane.
+    fn next_pending(&mut self) -> Option<Item> {
+        if let Some(&(pid, cpos)) = self.pending.terrain_chunks.iter().next() {
+            self.pending.terrain_chunks.remove(&(pid, cpos));
+            return Some(Item::TerrainChunk(pid, cpos));
+        }
+        if let Some(&pid) = self.pending.planes.iter().next() {
+            self.pending.planes.remove(&pid);
+            return Some(Item::Plane(pid));
+        }
+        if let Some(&cid) = self.pending.clients.iter().next() {
+            self.pending.clients.remove(&cid);
+            return Some(Item::Client(cid));
+        }
+        None
+    }
+}
+
+
+/// Schedule the first autosave pass.  Called once, at startup.
+pub fn start(mut eng: EngineRef) {
+    let when = eng.now() + AUTOSAVE_INTERVAL;
+    eng.timer_mut().schedule(when, move |eng| run(eng));
+}
+
+fn run(mut eng: EngineRef) {
+    if eng.extra().autosave.pending.is_empty() {
+        eng.extra_mut().autosave.begin_pass();
+    }
+
+    for _ in 0 .. BATCH_SIZE {
+        let item = unwrap_or!(eng.extra_mut().autosave.next_pending(), break);
+        let result = match item {
+            Item::TerrainChunk(pid, cpos) => save_terrain_chunk(eng.borrow(), pid, cpos),
+            Item::Plane(pid) => save_plane(eng.borrow(), pid),
+            Item::Client(cid) => save_client(eng.borrow(), cid),
+        };
+        match result {
+            Ok(true) => eng.extra_mut().autosave.pending.world = true,
+            Ok(false) => {},
+            Err(e) => warn!("autosave: error saving {:?}: {}", item, e),
+        }
+    }
+
+    let now = eng.now();
+    if !eng.extra().autosave.pending.is_empty() {
+        eng.timer_mut().schedule(now + BATCH_DELAY, move |eng| run(eng));
+        return;
+    }
+
+    if mem::replace(&mut eng.extra_mut().autosave.pending.world, false) {
+        warn_on_err!(logic::lifecycle::save_world(eng.borrow()));
+    }
+    eng.timer_mut().schedule(now + AUTOSAVE_INTERVAL, move |eng| run(eng));
+}
+
+
+/// Write every loaded object and the world right away, outside the usual autosave passes.  Used
+/// when the files on disk need to be complete, such as when taking a snapshot.
+pub fn save_all(mut eng: EngineRef) -> bundle::Result<()> {
+    let chunks = eng.world().terrain_chunks()
+                    .map(|tc| (tc.plane_id(), tc.chunk_pos()))
+                    .collect::<Vec<_>>();
+    for (pid, cpos) in chunks {
+        try!(save_terrain_chunk(eng.borrow(), pid, cpos));
+    }
+
+    let pids = eng.world().planes().map(|p| p.id()).collect::<Vec<_>>();
+    for pid in pids {
+        try!(save_plane(eng.borrow(), pid));
+    }
+
+    let cids = eng.world().clients().map(|c| c.id()).collect::<Vec<_>>();
+    for cid in cids {
+        try!(save_client(eng.borrow(), cid));
+    }
+
+    try!(logic::lifecycle::save_world(eng.borrow()));
+
+    // Everything is on disk now, so the current pass (if any) has nothing left to do.
+    let autosave = &mut eng.extra_mut().autosave;
+    autosave.dirty = DirtySet::default();
+    autosave.pending = DirtySet::default();
+    Ok(())
+}
+
+
+// Each `save_*` function returns `Ok(false)` if there was nothing to write, for example because
+// the object was unloaded since it was marked.
+
+fn save_terrain_chunk(mut eng: EngineRef, pid: PlaneId, cpos: V2) -> bundle::Result<bool> {
+    let first_save = {
+        let p = unwrap_or!(eng.world().get_plane(pid), return Ok(false));
+        let tc = unwrap_or!(p.get_terrain_chunk(cpos), return Ok(false));
+        // Same as in `logic::chunks`: chunks still waiting on terrain_gen contain only
+        // placeholder blocks.
+        if tc.flags().contains(flags::TC_GENERATION_PENDING) {
+            return Ok(false);
+        }
+        p.get_saved_terrain_chunk_id(cpos).is_none()
+    };
+
+    let stable_tcid = eng.as_hidden_world_fragment().plane_mut(pid).save_terrain_chunk(cpos);
+    {
+        let p = eng.world().plane(pid);
+        let tc = p.terrain_chunk(cpos);
+
+        let mut exporter = bundle::Exporter::new(eng.data());
+        exporter.add_terrain_chunk(&tc);
+        let b = exporter.finish();
+
+        let mut file = eng.storage().create_terrain_chunk_file(stable_tcid);
+        try!(bundle::write_bundle(&mut file, &b));
+        try!(file.commit());
+    }
+
+    if first_save {
+        // The plane's list of saved chunks changed, so it needs to be written in this pass too.
+        eng.extra_mut().autosave.pending.planes.insert(pid);
+    }
+    Ok(true)
+}
+
+fn save_plane(mut eng: EngineRef, pid: PlaneId) -> bundle::Result<bool> {
+    if eng.world().get_plane(pid).is_none() {
+        return Ok(false);
+    }
+
+    let stable_pid = eng.as_hidden_world_fragment().plane_mut(pid).stable_id();
+    let p = eng.world().plane(pid);
+
+    let mut exporter = bundle::Exporter::new(eng.data());
+    exporter.add_plane(&p);
+    let b = exporter.finish();
+
+    let mut file = eng.storage().create_plane_file(stable_pid);
+    try!(bundle::write_bundle(&mut file, &b));
+    try!(file.commit());
+    Ok(true)
+}
+
+fn save_client(eng: EngineRef, cid: ClientId) -> bundle::Result<bool> {
+    let c = unwrap_or!(eng.world().get_client(cid), return Ok(false));
+
+    let mut exporter = bundle::Exporter::new(eng.data());
+    exporter.add_client(&c);
+    let b = exporter.finish();
+
+    let mut file = eng.storage().create_client_file(c.name());
+    try!(bundle::write_bundle(&mut file, &b));
+    try!(file.commit());
+    Ok(true)
+}
@@ -0,0 +1,120 @@
+use libphysics::{CHUNK_SIZE, TILE_SIZE};
+
+use types::*;
+
+use chat::{self, Channel, Message, Rejection};
+use engine::split::EngineRef;
+use messages::ClientResponse;
+use world::object::*;
+use vision;
+
+
+/// Send a chat message from `cid` on `channel`.  `target` must be set for `Channel::Whisper`.
+/// Mutes, rate limits, and filters are applied here, and the sender is told if the message was
+/// rejected.
+pub fn send(mut eng: EngineRef,
+            cid: ClientId,
+            channel: Channel,
+            target: Option<ClientId>,
+            text: String) {
+    let now = eng.now();
+    let name = unwrap_or!(eng.world().get_client(cid)).name().to_owned();
+
+    let result = eng.chat_mut().check(now, cid, &name, channel, text);
+    let text = match result {
+        Ok(t) => t,
+        Err(Rejection::TooLong) => {
+            warn!("{:?}: bad request: chat message too long", cid);
+            return;
+        },
+        Err(Rejection::RateLimited) => {
+            notify(eng, cid, "You are sending messages too quickly.");
+            return;
+        },
+        Err(Rejection::Muted(until)) => {
+            let msg = match until {
+                Some(t) => format!("You are muted for {} more seconds.",
+                                   (t - now + 999) / 1000),
+                None => "You are muted.".to_owned(),
+            };
+            notify(eng, cid, &msg);
+            return;
+        },
+        Err(Rejection::Filtered(reason)) => {
+            if reason.len() > 0 {
+                notify(eng, cid, &reason);
+            }
+            return;
+        },
+    };
+
+    let target_name = match target {
+        Some(target_cid) => {
+            let c = unwrap_or!(eng.world().get_client(target_cid));
+            Some(c.name().to_owned())
+        },
+        None => None,
+    };
+
+    let msg = Message {
+        time: now,
+        channel: channel,
+        sender: name,
+        target: target_name,
+        text: text,
+    };
+    let resp = ClientResponse::ChatUpdate(msg.format());
+
+    match channel {
+        Channel::Global => {
+            eng.messages_mut().broadcast_clients(resp);
+        },
+        Channel::Local => {
+            for recipient in local_recipients(eng.borrow(), cid) {
+                eng.messages().send_client(recipient, resp.clone());
+            }
+        },
+        Channel::Whisper => {
+            let target_cid = target.unwrap();
+            eng.messages().send_client(target_cid, resp.clone());
+            if target_cid != cid {
+                eng.messages().send_client(cid, resp);
+            }
+        },
+    }
+
+    eng.chat_mut().record(msg);
+}
+
+/// Find the clients close enough to `cid` to receive its local messages.  This uses the same
+/// region as the sender's own view, so local chat reaches everyone the sender can see.
+fn local_recipients(eng: EngineRef, cid: ClientId) -> Vec<ClientId> {
+    let now = eng.now();
+    let (pid, region) = {
+        let c = unwrap_or!(eng.world().get_client(cid), return Vec::new());
+        let pawn = unwrap_or!(c.pawn(), return Vec::new());
+        (pawn.plane_id(), vision::vision_region(pawn.pos(now)))
+    };
+
+    eng.world().clients().filter(|c| {
+        match c.pawn() {
+            Some(pawn) => {
+                let cpos = pawn.pos(now).reduce().div_floor(scalar(CHUNK_SIZE * TILE_SIZE));
+                pawn.plane_id() == pid && region.contains(cpos)
+            },
+            None => false,
+        }
+    }).map(|c| c.id()).collect()
+}
+
+/// Send the recent global chat history to `cid`.  Used when a client logs in.
+pub fn send_history(eng: EngineRef, cid: ClientId) {
+    for msg in eng.chat().history(Channel::Global, chat::LOGIN_HISTORY_LEN) {
+        eng.messages().send_client(cid, ClientResponse::ChatUpdate(msg.format()));
+    }
+}
+
+/// Send a server message to a single client.
+pub fn notify(eng: EngineRef, cid: ClientId, msg: &str) {
+    eng.messages().send_client(cid, ClientResponse::ChatUpdate(format!("***\t{}", msg)));
+}
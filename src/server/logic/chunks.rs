@@ -52,6 +52,7 @@ impl<'a, 'd> chunks::Provider for ChunkProvider<'a, 'd> {
 
             let mut file = self.storage().create_plane_file(stable_pid);
             try!(bundle::write_bundle(&mut file, &b));
+            try!(file.commit());
         }
         try!(world::Fragment::destroy_plane(&mut self.as_hidden_world_fragment(), pid));
         Ok(())
@@ -91,6 +92,7 @@ impl<'a, 'd> chunks::Provider for ChunkProvider<'a, 'd> {
 
                 let mut file = self.storage().create_terrain_chunk_file(stable_tcid);
                 try!(bundle::write_bundle(&mut file, &b));
+                try!(file.commit());
             }
 
             tc.id()
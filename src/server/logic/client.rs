@@ -1,5 +1,7 @@
 use types::*;
+use util;
 
+use auth::{self, BanInfo, Role};
 use chunks;
 use engine::split::EngineRef;
 use logic;
@@ -49,6 +51,7 @@ pub fn register(eng: EngineRef, name: &str, appearance: u32) -> bundle::Result<(
 
     let mut file = eng.storage().create_client_file(name);
     try!(bundle::write_bundle(&mut file, &b));
+    try!(file.commit());
 
     Ok(())
 }
@@ -98,6 +101,8 @@ pub fn login(mut eng: EngineRef, wire_id: WireId, name: &str) -> bundle::Result<
                                                              cycle_base,
                                                              DAY_NIGHT_CYCLE_MS));
 
+    logic::chat::send_history(eng.borrow(), cid);
+
     vision::Fragment::add_client(&mut eng.as_vision_fragment(), cid, pid, region);
     warn_on_err!(eng.script_hooks().call_client_login(eng.borrow(), cid));
     eng.messages().send_client(cid, ClientResponse::SyncStatus(SyncKind::Ok));
@@ -107,6 +112,7 @@ pub fn login(mut eng: EngineRef, wire_id: WireId, name: &str) -> bundle::Result<
 
 pub fn logout(mut eng: EngineRef, cid: ClientId) -> bundle::Result<()> {
     eng.messages_mut().remove_client(cid);
+    eng.chat_mut().remove_client(cid);
 
     let old_region = eng.vision().client_view_area(cid);
     let old_pid = eng.vision().client_view_plane(cid);
@@ -127,11 +133,125 @@ pub fn logout(mut eng: EngineRef, cid: ClientId) -> bundle::Result<()> {
 
         let mut file = eng.storage().create_client_file(c.name());
         try!(bundle::write_bundle(&mut file, &b));
+        try!(file.commit());
     }
+    eng.extra_mut().autosave.forget_client(cid);
     try!(world::Fragment::destroy_client(&mut eng.as_world_fragment(), cid));
     Ok(())
 }
 
+/// Ban the account `name`, kicking its client if it is currently online.  `duration` is in
+/// milliseconds, or `None` for a permanent ban.  Returns `false` if there is no such account.
+pub fn ban(mut eng: EngineRef,
+           name: &str,
+           reason: &str,
+           duration: Option<Time>) -> auth::Result<bool> {
+    let info = BanInfo {
+        reason: reason.to_owned(),
+        expires: duration.map(|d| util::now() + d),
+    };
+    if !try!(eng.auth_mut().ban(name, &info.reason, info.expires)) {
+        return Ok(false);
+    }
+    info!("banned {}: {}", name, reason);
+
+    if let Some(cid) = eng.messages().name_to_client(name) {
+        eng.borrow().unwrap().kick_client(cid, info.describe());
+    }
+    Ok(true)
+}
+
+/// Delete the account `name` and its saved client data, kicking its client if it is currently
+/// online.  Returns `false` if there is no such account.
+pub fn delete_account(mut eng: EngineRef, name: &str) -> auth::Result<bool> {
+    // Kick first, since logging out writes the client file.
+    if let Some(cid) = eng.messages().name_to_client(name) {
+        eng.borrow().unwrap().kick_client(cid, "account deleted");
+    }
+
+    if !try!(eng.auth_mut().delete(name)) {
+        return Ok(false);
+    }
+    eng.storage().remove_client_file(name);
+    info!("deleted account {}", name);
+    Ok(true)
+}
+
+/// Start changing the login secret of the account logged in as `cid`.  The secret is hashed on
+/// the auth worker; the client gets a chat message once the change is done or refused.  Returns
+/// `false` if the client already has an auth request in progress.
+pub fn change_secret(mut eng: EngineRef,
+                     cid: ClientId,
+                     old_secret: auth::Secret,
+                     new_secret: auth::Secret) -> auth::Result<bool> {
+    let name = unwrap!(eng.world().get_client(cid)).name().to_owned();
+    let wire_id = unwrap!(eng.messages().client_to_wire(cid));
+    if eng.auth().is_pending(wire_id) {
+        return Ok(false);
+    }
+    try!(eng.auth_mut().start_change_secret(wire_id, &name, old_secret, new_secret));
+    Ok(true)
+}
+
+/// Report the outcome of `change_secret` to the client on `wire_id`, if it's still logged in.
+pub fn secret_changed(mut eng: EngineRef,
+                      wire_id: WireId,
+                      name: &str,
+                      result: auth::Result<bool>) {
+    let msg = match result {
+        Ok(true) => {
+            info!("{:?}: changed secret for {}", wire_id, name);
+            "Your login secret has been changed"
+        },
+        Ok(false) => {
+            info!("{:?}: secret change for {} failed: bad secret", wire_id, name);
+            "Login secret not changed: the old secret is incorrect"
+        },
+        Err(e) => {
+            warn!("{:?}: secret change for {} failed: auth error: {}", wire_id, name, e);
+            "Login secret not changed: an internal error occurred"
+        },
+    };
+    if let Some(cid) = eng.messages().wire_to_client(wire_id) {
+        let msg = format!("***\t{}", msg);
+        eng.messages().send_client(cid, ClientResponse::ChatUpdate(msg));
+    }
+}
+
+/// Get the role of the account logged in as `cid`.  Errors are logged and treated as
+/// `Role::Player`.
+pub fn role(mut eng: EngineRef, cid: ClientId) -> Role {
+    let name = {
+        let c = unwrap_or!(eng.world().get_client(cid), return Role::Player);
+        // Clients with the legacy `superuser` flag keep full access.
+        if let Some(extra::View::Value(extra::Value::Bool(true))) = c.extra().get("superuser") {
+            return Role::Admin;
+        }
+        c.name().to_owned()
+    };
+    match eng.auth_mut().role(&name) {
+        Ok(r) => r,
+        Err(e) => {
+            warn!("{:?}: error looking up role for {}: {}", cid, name, e);
+            Role::Player
+        },
+    }
+}
+
+/// Set the role of account `name`.  Returns `false` if there is no such account.
+pub fn set_role(mut eng: EngineRef, name: &str, role: Role) -> auth::Result<bool> {
+    if !try!(eng.auth_mut().set_role(name, role)) {
+        return Ok(false);
+    }
+    info!("set role of {} to {}", name, role.name());
+
+    if let Some(cid) = eng.messages().name_to_client(name) {
+        let msg = format!("***\tYour role is now: {}", role.name());
+        eng.messages().send_client(cid, ClientResponse::ChatUpdate(msg));
+    }
+    Ok(true)
+}
+
 pub fn update_view(mut eng: EngineRef, cid: ClientId) {
     let now = eng.now();
 
@@ -0,0 +1,139 @@
+//! Damage, healing, death, and respawning.  Health itself is stored on `world::Entity`.  This
+//! module changes it, shows damage to nearby clients, and runs the `entity_death` and
+//! `entity_respawn` script hooks.
+//!
+//! Only client pawns respawn.  A dead pawn sits still for `RESPAWN_DELAY`, then gets its full
+//! health back, and the `entity_respawn` hook decides where it reappears.  Other entities are
+//! destroyed right after the `entity_death` hook runs, unless the hook healed them.
+use std::cmp;
+
+use types::*;
+use util::StrResult;
+
+use engine::split::EngineRef;
+use logic;
+use physics;
+use vision;
+use world::{self, Activity, Health};
+use world::Fragment as World_Fragment;
+use world::object::*;
+
+
+/// How long a dead pawn waits before respawning.
+pub const RESPAWN_DELAY: Time = 5000;
+
+
+/// Deal `amount` damage to entity `eid`.  Entities that are already dead can't take more damage.
+pub fn damage(mut eng: EngineRef,
+              eid: EntityId,
+              amount: u16,
+              attacker: Option<EntityId>) -> StrResult<()> {
+    let old = unwrap!(eng.world().get_entity(eid)).health();
+    if old.is_dead() {
+        return Ok(());
+    }
+
+    let new = Health {
+        hp: old.hp.saturating_sub(amount),
+        max: old.max,
+    };
+    eng.as_world_fragment().entity_mut(eid).set_health(new);
+    vision::Fragment::show_entity_damage(&mut eng.as_vision_fragment(), eid, amount);
+
+    if new.is_dead() {
+        die(eng, eid, attacker);
+    }
+    Ok(())
+}
+
+/// Restore up to `amount` health to entity `eid`.  Dead entities can't be healed this way; they
+/// have to wait to respawn.
+pub fn heal(mut eng: EngineRef, eid: EntityId, amount: u16) -> StrResult<()> {
+    let old = unwrap!(eng.world().get_entity(eid)).health();
+    if old.is_dead() {
+        return Ok(());
+    }
+
+    let new = Health {
+        hp: cmp::min(old.hp.saturating_add(amount), old.max),
+        max: old.max,
+    };
+    if new != old {
+        eng.as_world_fragment().entity_mut(eid).set_health(new);
+    }
+    Ok(())
+}
+
+/// Change the maximum health of entity `eid`.  Current health is clamped to the new maximum, but
+/// is not otherwise changed.
+pub fn set_max_health(mut eng: EngineRef, eid: EntityId, max: u16) -> StrResult<()> {
+    if max == 0 {
+        fail!("maximum health must be positive");
+    }
+    let old = unwrap!(eng.world().get_entity(eid)).health();
+    let new = Health {
+        hp: cmp::min(old.hp, max),
+        max: max,
+    };
+    eng.as_world_fragment().entity_mut(eid).set_health(new);
+    Ok(())
+}
+
+
+fn is_pawn(eng: &EngineRef, eid: EntityId) -> bool {
+    match eng.world().get_entity(eid).map(|e| e.attachment()) {
+        Some(world::EntityAttachment::Client(_)) => true,
+        _ => false,
+    }
+}
+
+fn die(mut eng: EngineRef, eid: EntityId, attacker: Option<EntityId>) {
+    logic::walk::cancel(eng.borrow(), eid);
+    warn_on_err!(eng.script_hooks().call_entity_death(eng.borrow(), eid, attacker));
+
+    // The hook may have destroyed or healed the entity.
+    let dead = eng.world().get_entity(eid).map_or(false, |e| e.health().is_dead());
+    if !dead {
+        return;
+    }
+
+    if !is_pawn(&eng, eid) {
+        warn_on_err!(eng.as_world_fragment().destroy_entity(eid));
+        return;
+    }
+
+    let anim = {
+        let e = eng.world().entity(eid);
+        let name = format!("pony//sit-{}", physics::anim_dir(e.facing()));
+        eng.data().animations.get_id(&name)
+    };
+    eng.as_world_fragment().entity_mut(eid).set_activity(Activity::Special(anim, false));
+
+    let when = eng.now() + RESPAWN_DELAY;
+    let cookie = eng.timer_mut().schedule(when, move |eng| respawn(eng, eid));
+    eng.extra_mut().respawn_timer.insert(eid, cookie);
+}
+
+/// Bring dead entity `eid` back to full health, and let the `entity_respawn` hook move it
+/// somewhere safe.  Called when the respawn timer fires, and also for entities that were saved
+/// while dead.
+pub fn respawn(mut eng: EngineRef, eid: EntityId) {
+    // If this was called by the timer, the cookie must not be cancelled later.
+    eng.extra_mut().respawn_timer.remove(&eid);
+
+    let max = {
+        let e = unwrap_or!(eng.world().get_entity(eid));
+        if !e.health().is_dead() {
+            return;
+        }
+        e.health().max
+    };
+
+    {
+        let mut wf = eng.as_world_fragment();
+        let mut e = wf.entity_mut(eid);
+        e.set_health(Health::new(max));
+        e.set_activity(Activity::Move);
+    }
+    warn_on_err!(eng.script_hooks().call_entity_respawn(eng.borrow(), eid));
+}
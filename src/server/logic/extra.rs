@@ -1,13 +1,41 @@
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::thread::JoinHandle;
 
 use types::*;
 
+use logic::autosave::Autosave;
+use logic::npc::Npc;
+use logic::walk::Walk;
 use timer;
 
 
 pub struct Extra {
     pub client_view_update_timer: HashMap<ClientId, timer::Cookie>,
-    pub entity_physics_update_timer: HashMap<EntityId, timer::Cookie>,
+    /// Pending physics updates, with the time each one is due.
+    pub entity_physics_update_timer: HashMap<EntityId, (Time, timer::Cookie)>,
+    /// Walks in progress, started by `logic::walk::start`.
+    pub entity_walk: HashMap<EntityId, Walk>,
+    /// State of each NPC, managed by `logic::npc`.
+    pub npcs: HashMap<EntityId, Npc>,
+    /// Timers for dead pawns waiting to respawn.  See `logic::combat`.
+    pub respawn_timer: HashMap<EntityId, timer::Cookie>,
+    pub autosave: Autosave,
+    /// Set to make the engine restart (via `logic::lifecycle::pre_restart`) once the current
+    /// event has been handled.
+    pub restart_requested: bool,
+    /// Whether client connections stay open while the server process restarts.  True under the
+    /// wrapper, which holds the websockets.  When the server accepts connections itself, they
+    /// close with the process, so a restart has to act as a plain shutdown.
+    pub wires_survive_restart: bool,
+    /// Set while a timer is pending to resume sending queued responses.  See
+    /// `Engine::flush_messages`.
+    pub message_flush_pending: bool,
+    /// The thread writing out the most recent snapshot.  See `logic::snapshot`.
+    pub snapshot_thread: Option<JoinHandle<()>>,
+    /// Set while `snapshot_thread` is still running.
+    pub snapshot_busy: Arc<AtomicBool>,
 }
 
 impl Extra {
@@ -15,6 +43,15 @@ impl Extra {
         Extra {
             client_view_update_timer: HashMap::new(),
             entity_physics_update_timer: HashMap::new(),
+            entity_walk: HashMap::new(),
+            npcs: HashMap::new(),
+            respawn_timer: HashMap::new(),
+            autosave: Autosave::new(),
+            restart_requested: false,
+            wires_survive_restart: true,
+            message_flush_pending: false,
+            snapshot_thread: None,
+            snapshot_busy: Arc::new(AtomicBool::new(false)),
         }
     }
 }
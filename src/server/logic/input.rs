@@ -1,8 +1,10 @@
 use types::*;
 
+use auth::Role;
+use chat::{self, Channel};
 use engine::split::EngineRef;
 use input::{InputBits};
-use messages::ClientResponse;
+use logic;
 use msg::ExtraArg;
 use physics;
 use world::object::*;
@@ -19,18 +21,55 @@ pub fn input(mut eng: EngineRef, cid: ClientId, input: InputBits) {
     }
 }
 
+/// Check whether the pawn of `cid` is dead.  Dead pawns can't do anything until they respawn.
+fn pawn_dead(eng: &EngineRef, cid: ClientId) -> bool {
+    eng.world().get_client(cid).and_then(|c| c.pawn()).map_or(false, |e| e.health().is_dead())
+}
+
 pub fn interact(eng: EngineRef, cid: ClientId, args: Option<ExtraArg>) {
+    if pawn_dead(&eng, cid) {
+        return;
+    }
     warn_on_err!(eng.script_hooks().call_client_interact(eng, cid, args));
 }
 
 pub fn use_item(eng: EngineRef, cid: ClientId, item_id: ItemId, args: Option<ExtraArg>) {
+    if pawn_dead(&eng, cid) {
+        return;
+    }
     warn_on_err!(eng.script_hooks().call_client_use_item(eng, cid, item_id, args));
 }
 
 pub fn use_ability(eng: EngineRef, cid: ClientId, item_id: ItemId, args: Option<ExtraArg>) {
+    if pawn_dead(&eng, cid) {
+        return;
+    }
     warn_on_err!(eng.script_hooks().call_client_use_ability(eng, cid, item_id, args));
 }
 
+pub fn use_action(eng: EngineRef, cid: ClientId, action_id: ActionId, args: Option<ExtraArg>) {
+    if action_id == NO_ACTION || eng.data().actions.get_name(action_id).is_none() {
+        warn!("{:?}: bad request: no such action: {}", cid, action_id);
+        return;
+    }
+    if pawn_dead(&eng, cid) {
+        return;
+    }
+    warn_on_err!(eng.script_hooks().call_client_use_action(eng, cid, action_id, args));
+}
+
+pub fn craft_recipe(eng: EngineRef,
+                    cid: ClientId,
+                    station_sid: StructureId,
+                    iid: InventoryId,
+                    recipe_id: RecipeId,
+                    count: u16) {
+    if pawn_dead(&eng, cid) {
+        return;
+    }
+    warn_on_err!(logic::items::craft_recipe(eng, station_sid, iid, recipe_id, count));
+}
+
 pub fn open_inventory(_eng: EngineRef, cid: ClientId) {
     error!("UNIMPLEMENTED: open_inventory - called by {:?}", cid);
 }
@@ -41,17 +80,58 @@ pub fn unsubscribe_inventory(mut eng: EngineRef, cid: ClientId, iid: InventoryId
 }
 
 pub fn chat(mut eng: EngineRef, cid: ClientId, msg: String) {
+    if msg.len() > chat::MAX_MESSAGE_LEN {
+        warn!("{:?}: bad request: chat message too long ({})", cid, msg.len());
+        return;
+    }
+
     if msg.starts_with("/") {
-        warn_on_err!(eng.script_hooks().call_client_chat_command(eng, cid, &msg));
-    } else {
-        if msg.len() > 400 {
-            warn!("{:?}: bad request: chat message too long ({})", cid, msg.len());
+        let (cmd, rest) = match msg.find(' ') {
+            Some(idx) => (&msg[1..idx], &msg[idx + 1..]),
+            None => (&msg[1..], ""),
+        };
+
+        match cmd {
+            "l" | "local" => {
+                logic::chat::send(eng, cid, Channel::Local, None, rest.to_owned());
+                return;
+            },
+            "w" | "whisper" => {
+                match find_whisper_target(eng.borrow(), rest) {
+                    Some((target, text)) =>
+                        logic::chat::send(eng, cid, Channel::Whisper, Some(target), text),
+                    None =>
+                        logic::chat::notify(eng, cid, "Usage: /w <name> <message>"),
+                }
+                return;
+            },
+            _ => {},
+        }
+
+        let required = eng.script_hooks().chat_command_role(cmd);
+        if required > Role::Player && logic::client::role(eng.borrow(), cid) < required {
+            // Respond the same way as for a nonexistent command, so players can't probe for
+            // restricted ones.
+            info!("{:?}: denied access to command {:?} (requires {})",
+                  cid, msg, required.name());
+            logic::chat::notify(eng, cid, &format!("Unknown command: /{}", cmd));
             return;
         }
+        warn_on_err!(eng.script_hooks().call_client_chat_command(eng, cid, &msg));
+    } else {
+        logic::chat::send(eng, cid, Channel::Global, None, msg);
+    }
+}
 
-        let msg_out = format!("<{}>\t{}",
-                              eng.world().client(cid).name(),
-                              msg);
-        eng.messages_mut().broadcast_clients(ClientResponse::ChatUpdate(msg_out));
+/// Split the argument of a `/w` command into the target client and the message text.  Names may
+/// contain spaces, so this tries the longest matching prefix first.
+fn find_whisper_target(eng: EngineRef, args: &str) -> Option<(ClientId, String)> {
+    let words = args.split(' ').collect::<Vec<_>>();
+    for i in (1 .. words.len()).rev() {
+        let name = words[..i].join(" ");
+        if let Some(cid) = eng.messages().name_to_client(&name) {
+            return Some((cid, words[i..].join(" ")));
+        }
     }
+    None
 }
@@ -6,12 +6,21 @@ use util::now;
 use engine::split::EngineRef;
 use logic;
 use messages::{ClientResponse, SyncKind};
+use msg::Capabilities;
 use wire::{WireWriter, WireReader};
 use world::Fragment;
 use world::bundle;
 use world::object::*;
 
 
+/// Identifies the version header at the start of the restart file.  Files written before the
+/// header was added (version 0) start directly with a client record, and client records never use
+/// `CONTROL_WIRE_ID`.
+const RESTART_FILE_MAGIC: u32 = 0x52535430;    // "RST0"
+/// Version 0 records only each client's name.  Version 1 adds its negotiated capabilities.
+const RESTART_FILE_VERSION: u16 = 1;
+
+
 pub fn start_up(mut eng: EngineRef) {
     let world_time =
         if let Some(mut file) = eng.storage().open_world_file() {
@@ -47,6 +56,9 @@ pub fn start_up(mut eng: EngineRef) {
     }
 
     warn_on_err!(eng.script_hooks().call_server_startup(eng.borrow()));
+
+    logic::autosave::start(eng.borrow());
+    logic::npc::start(eng.borrow());
 }
 
 
@@ -66,16 +78,22 @@ pub fn shut_down(mut eng: EngineRef) {
 
     warn_on_err!(eng.script_hooks().call_server_shutdown(eng.borrow()));
 
-    {
-        let mut exporter = bundle::Exporter::new(eng.data());
-        exporter.add_world(eng.world());
-        let mut b = exporter.finish();
-        b.world.as_mut().unwrap().now = eng.now();
-        let b = b;
-
-        let mut file = eng.storage().create_world_file();
-        warn_on_err!(bundle::write_bundle(&mut file, &b));
-    }
+    warn_on_err!(save_world(eng.borrow()));
+    logic::snapshot::wait(eng.borrow());
+}
+
+/// Write the world bundle, including the current world time.
+pub fn save_world(eng: EngineRef) -> bundle::Result<()> {
+    let mut exporter = bundle::Exporter::new(eng.data());
+    exporter.add_world(eng.world());
+    let mut b = exporter.finish();
+    b.world.as_mut().unwrap().now = eng.now();
+    let b = b;
+
+    let mut file = eng.storage().create_world_file();
+    try!(bundle::write_bundle(&mut file, &b));
+    try!(file.commit());
+    Ok(())
 }
 
 
@@ -86,18 +104,25 @@ pub fn pre_restart(eng: EngineRef) {
 
     {
         info!("recording clients to file...");
-        let file = eng.storage().create_restart_file();
-        let mut ww = WireWriter::new(file);
-        for c in eng.world().clients() {
-            let wire_id = match eng.messages().client_to_wire(c.id()) {
-                Some(x) => x,
-                None => {
-                    warn!("no wire for client {:?}", c.id());
-                    continue;
-                },
-            };
-            ww.write_msg(wire_id, c.name()).unwrap();
+        let mut file = eng.storage().create_restart_file();
+        {
+            let mut ww = WireWriter::new(&mut file);
+            ww.write_msg(CONTROL_WIRE_ID, (RESTART_FILE_MAGIC, RESTART_FILE_VERSION)).unwrap();
+            for c in eng.world().clients() {
+                let wire_id = match eng.messages().client_to_wire(c.id()) {
+                    Some(x) => x,
+                    None => {
+                        warn!("no wire for client {:?}", c.id());
+                        continue;
+                    },
+                };
+                let caps = eng.messages().client_capabilities(c.id())
+                              .map_or(0, |caps| caps.bits());
+                // Shuffle order since the String must be last on the wire
+                ww.write_msg(wire_id, (caps, c.name())).unwrap();
+            }
         }
+        file.commit().unwrap();
     }
 }
 
@@ -105,8 +130,34 @@ pub fn post_restart(mut eng: EngineRef, file: File) {
     info!("retrieving clients from file...");
 
     let mut wr = WireReader::new(file);
+    let mut version = 0;
     while let Ok(wire_id) = wr.read_header() {
-        let name = wr.read::<String>().unwrap();
+        if wire_id == CONTROL_WIRE_ID {
+            let (magic, v): (u32, u16) = unwrap_or!(wr.read().ok(), break);
+            if magic != RESTART_FILE_MAGIC || v > RESTART_FILE_VERSION {
+                warn!("unrecognized restart file header: {:x}, version {}", magic, v);
+                break;
+            }
+            version = v;
+            continue;
+        }
+
+        let result =
+            if version == 0 {
+                wr.read().map(|name: String| (0, name))
+            } else {
+                wr.read::<(u32, String)>()
+            };
+        let (caps, name) = match result {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("bad client record for {:?} in restart file: {}", wire_id, e);
+                continue;
+            },
+        };
+        // Restored clients don't redo the `Hello` handshake, so carry over what they negotiated
+        // before the restart.
+        eng.messages_mut().set_wire_capabilities(wire_id, Capabilities::from_bits_truncate(caps));
         warn_on_err!(logic::client::login(eng.borrow(), wire_id, &name));
     }
 
@@ -1,9 +1,15 @@
+pub mod autosave;
+pub mod chat;
 pub mod chunks;
 pub mod client;
+pub mod combat;
 pub mod input;
 pub mod items;
 pub mod lifecycle;
 pub mod vision;
 pub mod world;
 pub mod misc;
+pub mod npc;
+pub mod snapshot;
+pub mod walk;
 pub mod extra;
@@ -0,0 +1,596 @@
+//! Behaviour for non-player entities.  Each NPC runs a small state machine, stepped by a timer
+//! every `THINK_INTERVAL`.  The built-in behaviours move the NPC around using `logic::walk`, and
+//! the NPC's `Temperament` decides how it reacts when a player comes near.  Scripts can define
+//! additional behaviours, which are stepped by calling the `npc_think` script hook.  NPCs chasing
+//! a target only plan a new path when the target moves to a different tile.
+//!
+//! Thinking is skipped for NPCs that no client can see.  They check back every `DORMANT_INTERVAL`
+//! instead, and NPCs created by the spawner are destroyed once they've gone unseen for
+//! `DESPAWN_DELAY`, or as soon as the chunk they're standing in is unloaded.
+//!
+//! NPC state is recorded in the entity's `extra` under `EXTRA_KEY`, so it can be restored when
+//! the entity is loaded again.  Built-in behaviours aren't saved, so a restored NPC starts out
+//! idle unless it was running a script behaviour.
+use std::cmp;
+use rand::{self, Rng};
+use libphysics::{CHUNK_SIZE, TILE_SIZE};
+
+use types::*;
+use util::StrResult;
+
+use cache::TerrainCache;
+use engine::split::EngineRef;
+use logic::walk;
+use pathfind;
+use physics::ChunksSource;
+use timer;
+use world::World;
+use world::extra::{Value, View, ViewMut};
+use world::Fragment as World_Fragment;
+use world::object::*;
+
+
+/// Time between think steps for an NPC that a client can see.
+const THINK_INTERVAL: Time = 1000;
+
+/// Time between visibility checks for an NPC that no client can see.
+const DORMANT_INTERVAL: Time = 5000;
+
+/// How long a spawned NPC can go unseen before it's removed.
+const DESPAWN_DELAY: Time = 60_000;
+
+/// Distance (in tiles) at which NPCs notice players.
+const SENSE_RANGE: i32 = 6;
+
+/// Distance (in tiles) at which NPCs give up chasing or fleeing.
+const LEASH_RANGE: i32 = 12;
+
+/// Distance (in tiles) that wandering NPCs stray from home.
+const WANDER_RADIUS: i32 = 5;
+
+/// Distance (in tiles) that a fleeing NPC tries to put between itself and the threat.
+const FLEE_DIST: i32 = 6;
+
+/// Distance (in tiles) that a following NPC tries to keep from its target.
+const FOLLOW_DIST: i32 = 2;
+
+/// Distance (in pixels) from which an NPC can attack.
+const ATTACK_RANGE: i32 = TILE_SIZE * 3 / 2;
+
+/// Minimum time between attacks.
+const ATTACK_COOLDOWN: Time = 1500;
+
+/// Time between spawn checks.
+const SPAWN_INTERVAL: Time = 10_000;
+
+/// Spawned NPCs appear between `SPAWN_MIN_DIST` and `SPAWN_MAX_DIST` tiles from a player.
+const SPAWN_MIN_DIST: i32 = 6;
+const SPAWN_MAX_DIST: i32 = 12;
+
+/// Stop spawning near a player once this many spawned NPCs are within `SPAWN_MAX_DIST`.
+const MAX_NEARBY: usize = 4;
+
+pub const EXTRA_KEY: &'static str = "npc";
+
+
+/// How an NPC reacts to nearby players.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Temperament {
+    /// Ignores players.
+    Passive = 0,
+    /// Runs away from players.
+    Timid = 1,
+    /// Attacks players.
+    Hostile = 2,
+}
+
+impl Temperament {
+    pub fn from_primitive(x: u8) -> Option<Temperament> {
+        match x {
+            0 => Some(Temperament::Passive),
+            1 => Some(Temperament::Timid),
+            2 => Some(Temperament::Hostile),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Behavior {
+    /// Stand still, occasionally switching to `Wander`.
+    Idle,
+    /// Walk to a random spot near home, then go back to `Idle`.
+    Wander,
+    /// Stay close to an entity until it goes away.
+    Follow(EntityId),
+    /// Run from an entity until it's out of range.
+    Flee(EntityId),
+    /// Chase an entity, and attack it when close enough.
+    Attack(EntityId),
+    /// A behaviour implemented by a script.  Each step calls the `npc_think` hook with this name.
+    Script(String),
+}
+
+impl Behavior {
+    /// Get the behaviour called `name`.  Any name that isn't built in refers to a script
+    /// behaviour.  Returns `None` if `target` is missing for a behaviour that needs one.
+    pub fn from_name(name: &str, target: Option<EntityId>) -> Option<Behavior> {
+        match name {
+            "idle" => Some(Behavior::Idle),
+            "wander" => Some(Behavior::Wander),
+            "follow" => target.map(Behavior::Follow),
+            "flee" => target.map(Behavior::Flee),
+            "attack" => target.map(Behavior::Attack),
+            _ => Some(Behavior::Script(name.to_owned())),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match *self {
+            Behavior::Idle => "idle",
+            Behavior::Wander => "wander",
+            Behavior::Follow(_) => "follow",
+            Behavior::Flee(_) => "flee",
+            Behavior::Attack(_) => "attack",
+            Behavior::Script(ref name) => name,
+        }
+    }
+
+    fn target(&self) -> Option<EntityId> {
+        match *self {
+            Behavior::Follow(eid) |
+            Behavior::Flee(eid) |
+            Behavior::Attack(eid) => Some(eid),
+            _ => None,
+        }
+    }
+}
+
+
+pub struct Npc {
+    temperament: Temperament,
+    behavior: Behavior,
+    /// Wandering stays near this point.
+    home: V3,
+    /// Set for NPCs created by the spawner.  These are removed once no one has seen them for a
+    /// while.
+    spawned: bool,
+    /// Timer for the next think step.  `None` only while the step is running.
+    think_timer: Option<timer::Cookie>,
+    /// Last time a client could see this NPC.
+    last_seen: Time,
+    /// Earliest time this NPC can attack again.
+    next_attack: Time,
+}
+
+impl Npc {
+    pub fn think_timer(&self) -> Option<timer::Cookie> {
+        self.think_timer
+    }
+
+    /// Earliest time this NPC can attack again.  Each attack pushes this back by
+    /// `ATTACK_COOLDOWN`.
+    pub fn next_attack(&self) -> Time {
+        self.next_attack
+    }
+}
+
+
+/// Turn entity `eid` into an NPC.  Any previous NPC state for the entity is replaced.
+pub fn add(mut eng: EngineRef,
+           eid: EntityId,
+           temperament: Temperament,
+           spawned: bool) -> StrResult<()> {
+    remove(eng.borrow(), eid);
+
+    let home = unwrap!(eng.world().get_entity(eid)).pos(eng.now());
+    {
+        let mut wf = eng.as_world_fragment();
+        let mut e = unwrap!(wf.get_entity_mut(eid));
+        let mut x = e.extra_mut().set_hash(EXTRA_KEY);
+        x.borrow().set("temperament", Value::Int(temperament as i64));
+        x.borrow().set("home", Value::V3(home));
+        x.borrow().set("spawned", Value::Bool(spawned));
+    }
+
+    insert(eng, eid, temperament, Behavior::Idle, home, spawned);
+    Ok(())
+}
+
+/// Restore the NPC state saved in the `extra` of entity `eid`.  Called shortly after any entity
+/// with NPC state is loaded.
+pub fn restore(eng: EngineRef, eid: EntityId) {
+    if eng.extra().npcs.contains_key(&eid) {
+        return;
+    }
+
+    let info = {
+        let e = unwrap_or!(eng.world().get_entity(eid));
+        let x = match e.extra().get(EXTRA_KEY) {
+            Some(View::Hash(x)) => x,
+            _ => return,
+        };
+        let temperament = match x.get("temperament") {
+            Some(View::Value(Value::Int(i))) => Temperament::from_primitive(i as u8),
+            _ => None,
+        };
+        let home = match x.get("home") {
+            Some(View::Value(Value::V3(pos))) => pos,
+            _ => e.pos(eng.now()),
+        };
+        let spawned = match x.get("spawned") {
+            Some(View::Value(Value::Bool(b))) => b,
+            _ => false,
+        };
+        let behavior = match x.get("behavior") {
+            Some(View::Value(Value::Str(name))) => Behavior::Script(name),
+            _ => Behavior::Idle,
+        };
+        temperament.map(|t| (t, behavior, home, spawned))
+    };
+    let (temperament, behavior, home, spawned) =
+        unwrap_or!(info, { warn!("bad NPC state for {:?}", eid); return });
+
+    insert(eng, eid, temperament, behavior, home, spawned);
+}
+
+fn insert(mut eng: EngineRef,
+          eid: EntityId,
+          temperament: Temperament,
+          behavior: Behavior,
+          home: V3,
+          spawned: bool) {
+    let now = eng.now();
+    let cookie = eng.timer_mut().schedule(now, move |eng| think(eng, eid));
+    eng.extra_mut().npcs.insert(eid, Npc {
+        temperament: temperament,
+        behavior: behavior,
+        home: home,
+        spawned: spawned,
+        think_timer: Some(cookie),
+        last_seen: now,
+        next_attack: now,
+    });
+}
+
+/// Turn NPC `eid` back into an ordinary entity.
+pub fn remove(mut eng: EngineRef, eid: EntityId) {
+    let npc = unwrap_or!(eng.extra_mut().npcs.remove(&eid));
+    if let Some(cookie) = npc.think_timer {
+        eng.timer_mut().cancel(cookie);
+    }
+    walk::cancel(eng.borrow(), eid);
+    if let Some(mut e) = eng.as_world_fragment().get_entity_mut(eid) {
+        e.extra_mut().remove(EXTRA_KEY);
+    }
+}
+
+/// Get the current behaviour of NPC `eid`, or `None` if `eid` is not an NPC.
+pub fn behavior(eng: EngineRef, eid: EntityId) -> Option<Behavior> {
+    eng.extra().npcs.get(&eid).map(|npc| npc.behavior.clone())
+}
+
+/// Switch NPC `eid` to a new behaviour.  The new behaviour takes effect right away.
+pub fn set_behavior(mut eng: EngineRef, eid: EntityId, behavior: Behavior) -> StrResult<()> {
+    unwrap!(eng.extra_mut().npcs.get_mut(&eid)).behavior = behavior.clone();
+
+    {
+        // Only script behaviours are saved.  The others are chosen again after loading.
+        let mut wf = eng.as_world_fragment();
+        let mut e = unwrap!(wf.get_entity_mut(eid));
+        if let Some(ViewMut::Hash(mut x)) = e.extra_mut().get_mut(EXTRA_KEY) {
+            if let Behavior::Script(ref name) = behavior {
+                x.borrow().set("behavior", Value::Str(name.clone()));
+            } else {
+                x.borrow().remove("behavior");
+            }
+        }
+    }
+
+    walk::cancel(eng.borrow(), eid);
+    let now = eng.now();
+    schedule(eng, eid, now);
+    Ok(())
+}
+
+fn schedule(mut eng: EngineRef, eid: EntityId, when: Time) {
+    let old = unwrap_or!(eng.extra_mut().npcs.get_mut(&eid)).think_timer.take();
+    if let Some(cookie) = old {
+        eng.timer_mut().cancel(cookie);
+    }
+    let cookie = eng.timer_mut().schedule(when, move |eng| think(eng, eid));
+    eng.extra_mut().npcs.get_mut(&eid).unwrap().think_timer = Some(cookie);
+}
+
+
+fn think(mut eng: EngineRef, eid: EntityId) {
+    let now = eng.now();
+    let (spawned, last_seen) = {
+        let npc = unwrap_or!(eng.extra_mut().npcs.get_mut(&eid));
+        // The timer has fired, so it must not be cancelled.
+        npc.think_timer = None;
+        (npc.spawned, npc.last_seen)
+    };
+    if eng.world().get_entity(eid).is_none() {
+        eng.extra_mut().npcs.remove(&eid);
+        return;
+    }
+
+    if !eng.vision().entity_is_visible(eid) {
+        let forgotten = now - last_seen >= DESPAWN_DELAY || !in_loaded_chunk(eng.world(), now, eid);
+        if spawned && forgotten {
+            // Destroying the entity also cleans up the NPC state.
+            warn_on_err!(eng.as_world_fragment().destroy_entity(eid));
+            return;
+        }
+        schedule(eng, eid, now + DORMANT_INTERVAL);
+        return;
+    }
+    eng.extra_mut().npcs.get_mut(&eid).unwrap().last_seen = now;
+
+    react(eng.borrow(), eid);
+    act(eng.borrow(), eid);
+
+    // The behaviour may have removed the NPC or destroyed the entity.
+    if eng.extra().npcs.get(&eid).map_or(false, |npc| npc.think_timer.is_none()) {
+        schedule(eng, eid, now + THINK_INTERVAL);
+    }
+}
+
+/// Let the NPC's temperament override its current behaviour when a player is nearby.
+fn react(mut eng: EngineRef, eid: EntityId) {
+    let now = eng.now();
+    let new_behavior = {
+        let npc = eng.extra().npcs.get(&eid).unwrap();
+        match npc.behavior {
+            Behavior::Idle | Behavior::Wander => {},
+            _ => return,
+        }
+
+        let e = eng.world().entity(eid);
+        let pawn = unwrap_or!(nearest_pawn(eng.world(), now, e.plane_id(), e.pos(now),
+                                           SENSE_RANGE * TILE_SIZE));
+        match npc.temperament {
+            Temperament::Passive => return,
+            Temperament::Timid => Behavior::Flee(pawn),
+            Temperament::Hostile => Behavior::Attack(pawn),
+        }
+    };
+    walk::cancel(eng.borrow(), eid);
+    eng.extra_mut().npcs.get_mut(&eid).unwrap().behavior = new_behavior;
+}
+
+fn act(mut eng: EngineRef, eid: EntityId) {
+    let now = eng.now();
+    let (behavior, home) = {
+        let npc = eng.extra().npcs.get(&eid).unwrap();
+        (npc.behavior.clone(), npc.home)
+    };
+    let (pos, pid) = {
+        let e = eng.world().entity(eid);
+        (e.pos(now), e.plane_id())
+    };
+
+    // Get the position of the target, and its distance from the NPC.  Targets that have died,
+    // left the plane, or gone out of range are lost.
+    let target = behavior.target().and_then(|target| {
+        let t = unwrap_or!(eng.world().get_entity(target), return None);
+        let t_pos = t.pos(now);
+        let dist = distance(pos, t_pos);
+        if t.health().is_dead() || t.plane_id() != pid || dist > LEASH_RANGE * TILE_SIZE {
+            None
+        } else {
+            Some((target, t_pos, dist))
+        }
+    });
+    if behavior.target().is_some() && target.is_none() {
+        go_idle(eng, eid);
+        return;
+    }
+
+    match behavior {
+        Behavior::Idle => {
+            if rand::thread_rng().gen_range(0, 4) == 0 {
+                if wander(eng.borrow(), eid, home) {
+                    eng.extra_mut().npcs.get_mut(&eid).unwrap().behavior = Behavior::Wander;
+                }
+            }
+        },
+
+        Behavior::Wander => {
+            if !walk::is_walking(eng.borrow(), eid) {
+                go_idle(eng, eid);
+            }
+        },
+
+        Behavior::Follow(_) => {
+            let (_, t_pos, dist) = target.unwrap();
+            if dist > FOLLOW_DIST * TILE_SIZE {
+                let run = dist > 2 * FOLLOW_DIST * TILE_SIZE;
+                chase(eng.borrow(), eid, t_pos, run);
+            } else {
+                walk::cancel(eng.borrow(), eid);
+            }
+        },
+
+        Behavior::Flee(_) => {
+            let (_, t_pos, dist) = target.unwrap();
+            if dist > FLEE_DIST * TILE_SIZE && !walk::is_walking(eng.borrow(), eid) {
+                go_idle(eng, eid);
+            } else if !walk::is_walking(eng.borrow(), eid) {
+                flee(eng.borrow(), eid, pos, t_pos);
+            }
+        },
+
+        Behavior::Attack(_) => {
+            let (t_eid, t_pos, dist) = target.unwrap();
+            if dist <= ATTACK_RANGE {
+                walk::cancel(eng.borrow(), eid);
+                let ready = {
+                    let npc = eng.extra_mut().npcs.get_mut(&eid).unwrap();
+                    if now >= npc.next_attack {
+                        npc.next_attack = now + ATTACK_COOLDOWN;
+                        true
+                    } else {
+                        false
+                    }
+                };
+                if ready {
+                    warn_on_err!(eng.script_hooks().call_npc_attack(eng.borrow(), eid, t_eid));
+                }
+            } else {
+                chase(eng.borrow(), eid, t_pos, false);
+            }
+        },
+
+        Behavior::Script(ref name) => {
+            warn_on_err!(eng.script_hooks().call_npc_think(eng.borrow(), eid, name));
+        },
+    }
+}
+
+fn go_idle(mut eng: EngineRef, eid: EntityId) {
+    walk::cancel(eng.borrow(), eid);
+    if let Some(npc) = eng.extra_mut().npcs.get_mut(&eid) {
+        npc.behavior = Behavior::Idle;
+    }
+}
+
+/// Walk toward a target at `t_pos`.  Pathfinding is expensive, so a new path is only planned if
+/// the NPC isn't walking yet or the target has moved to a different tile since the last one.
+fn chase(mut eng: EngineRef, eid: EntityId, t_pos: V3, run: bool) {
+    if walk::goal(eng.borrow(), eid) == Some(pathfind::entity_tile(t_pos)) {
+        return;
+    }
+    warn_on_err!(walk::start(eng, eid, t_pos, run));
+}
+
+/// Start walking to a random spot near `home`.  Returns `false` if no reachable spot was found.
+fn wander(mut eng: EngineRef, eid: EntityId, home: V3) -> bool {
+    let mut rng = rand::thread_rng();
+    for _ in 0 .. 3 {
+        let dx = rng.gen_range(-WANDER_RADIUS, WANDER_RADIUS + 1);
+        let dy = rng.gen_range(-WANDER_RADIUS, WANDER_RADIUS + 1);
+        let dest = home + V3::new(dx, dy, 0) * scalar(TILE_SIZE);
+        match walk::start(eng.borrow(), eid, dest, false) {
+            Ok(true) => return true,
+            Ok(false) => {},
+            Err(e) => {
+                warn!("failed to start wandering for {:?}: {}", eid, e);
+                return false;
+            },
+        }
+    }
+    false
+}
+
+/// Start running away from `threat`.  Tries heading straight away first, then along each axis.
+fn flee(mut eng: EngineRef, eid: EntityId, pos: V3, threat: V3) {
+    let away = (pos - threat).reduce().signum();
+    let away = if away == scalar(0) { V2::new(1, 0) } else { away };
+    let dirs = [away, V2::new(away.x, 0), V2::new(0, away.y), V2::new(-away.y, away.x)];
+    for &dir in &dirs {
+        if dir == scalar(0) {
+            continue;
+        }
+        let dest = pos + (dir * scalar(FLEE_DIST * TILE_SIZE)).extend(0);
+        match walk::start(eng.borrow(), eid, dest, true) {
+            Ok(true) => return,
+            Ok(false) => {},
+            Err(e) => {
+                warn!("failed to start fleeing for {:?}: {}", eid, e);
+                return;
+            },
+        }
+    }
+}
+
+
+/// Distance between two points, for deciding what's in range.  Only x and y are counted.
+fn distance(a: V3, b: V3) -> i32 {
+    (a - b).reduce().abs().max()
+}
+
+/// Check whether the chunk under entity `eid` is loaded.  Chunks are only kept loaded around
+/// players, so an NPC outside them has no one nearby to see it.
+fn in_loaded_chunk(w: &World, now: Time, eid: EntityId) -> bool {
+    let e = w.entity(eid);
+    let cpos = e.pos(now).reduce().div_floor(scalar(CHUNK_SIZE * TILE_SIZE));
+    w.get_plane(e.plane_id()).map_or(false, |p| p.get_terrain_chunk(cpos).is_some())
+}
+
+/// Find the closest living client-controlled entity within `range` pixels of `pos` on plane
+/// `pid`.
+fn nearest_pawn(w: &World, now: Time, pid: PlaneId, pos: V3, range: i32) -> Option<EntityId> {
+    w.clients()
+     .filter_map(|c| c.pawn())
+     .filter(|e| e.plane_id() == pid && !e.health().is_dead())
+     .map(|e| (distance(e.pos(now), pos), e.id()))
+     .filter(|&(dist, _)| dist <= range)
+     .min()
+     .map(|(_, eid)| eid)
+}
+
+
+/// Start the periodic spawn check.  Called once at startup.
+pub fn start(mut eng: EngineRef) {
+    let when = eng.now() + SPAWN_INTERVAL;
+    eng.timer_mut().schedule(when, move |eng| spawn_tick(eng));
+}
+
+/// Pick a spot near each player that doesn't have too many spawned NPCs around already, and
+/// pass it to the `npc_spawn` script hook.  The script decides what (if anything) appears there,
+/// based on the plane and biome.
+fn spawn_tick(mut eng: EngineRef) {
+    let now = eng.now();
+    eng.timer_mut().schedule(now + SPAWN_INTERVAL, move |eng| spawn_tick(eng));
+
+    let mut spots = Vec::new();
+    {
+        let w = eng.world();
+        let npcs = &eng.extra().npcs;
+        let mut rng = rand::thread_rng();
+        for c in w.clients() {
+            let pawn = unwrap_or!(c.pawn(), continue);
+            let pid = pawn.plane_id();
+            let pos = pawn.pos(now);
+
+            let nearby = npcs.iter().filter(|&(&eid, npc)| {
+                npc.spawned && w.get_entity(eid).map_or(false, |e| {
+                    e.plane_id() == pid && distance(e.pos(now), pos) <= SPAWN_MAX_DIST * TILE_SIZE
+                })
+            }).count();
+            if nearby >= MAX_NEARBY {
+                continue;
+            }
+
+            if let Some(spot) = pick_spot(eng.cache(), pid, pos, &mut rng) {
+                spots.push((pid, spot));
+            }
+        }
+    }
+
+    for (pid, pos) in spots {
+        warn_on_err!(eng.script_hooks().call_npc_spawn(eng.borrow(), pid, pos));
+    }
+}
+
+/// Pick a random place for an entity to stand, at a suitable distance from `center`.  Returns
+/// the position in pixels, or `None` if the chosen spot is no good.
+fn pick_spot<R: Rng>(cache: &TerrainCache, pid: PlaneId, center: V3, rng: &mut R) -> Option<V3> {
+    let dx = rng.gen_range(-SPAWN_MAX_DIST, SPAWN_MAX_DIST + 1);
+    let dy = rng.gen_range(-SPAWN_MAX_DIST, SPAWN_MAX_DIST + 1);
+    if cmp::max(dx.abs(), dy.abs()) < SPAWN_MIN_DIST {
+        return None;
+    }
+
+    let source = ChunksSource::new(cache, scalar(0), pid);
+    let base = pathfind::entity_tile(center) + V3::new(dx, dy, 0);
+    for &dz in &[0, 1, -1, 2, -2] {
+        let tile = base + V3::new(0, 0, dz);
+        if pathfind::standable(&source, tile) {
+            return Some(tile * scalar(TILE_SIZE));
+        }
+    }
+    None
+}
@@ -0,0 +1,191 @@
+//! Point-in-time snapshots of the whole world.  A snapshot flushes every loaded object to storage,
+//! then copies the saved world into a new directory under `Storage::snapshot_dir`, named for the
+//! current (UTC) time.  Only the newest `KEEP_SNAPSHOTS` are kept.
+//!
+//! Copying a large world can take a while, so it happens on a background thread.  The engine
+//! only takes a cheap point-in-time view of the saved world (`Storage::freeze_world`), so later
+//! saves can't leak into the snapshot.  Only one snapshot can be in progress at a time.
+//!
+//! Restoring can't be done while the world is loaded, so `request_restore` records the snapshot
+//! name and restarts the server.  The files are swapped in by `apply_pending_restore` on the next
+//! startup, before the world is loaded, and `post_restart` then logs everyone back in as usual.
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::thread;
+
+use time;
+
+use engine::split::EngineRef;
+use logic;
+use storage::{self, Storage, FileStorage};
+use world::bundle;
+
+
+/// Number of snapshots to keep.  Older ones are removed each time a new snapshot is taken.
+const KEEP_SNAPSHOTS: usize = 10;
+
+/// Snapshots in progress are written under this suffix, then renamed once complete.
+const TMP_SUFFIX: &'static str = ".tmp";
+
+
+/// Take a snapshot.  Returns the name of the new snapshot, which shows up in `list` once it has
+/// been written out.
+pub fn create(mut eng: EngineRef) -> bundle::Result<String> {
+    if eng.extra().snapshot_busy.load(Ordering::SeqCst) {
+        fail!("the previous snapshot is still being written");
+    }
+    wait(eng.borrow());
+
+    try!(logic::autosave::save_all(eng.borrow()));
+
+    // Milliseconds keep snapshots taken in quick succession apart, and still sort correctly.  The
+    // counter is only for the rare case where that isn't enough.
+    let now = time::now_utc();
+    let base_name = format!("{}.{:03}",
+                            now.strftime("%Y%m%d-%H%M%S").unwrap(),
+                            now.tm_nsec / 1000000);
+    let snapshot_dir = eng.storage().snapshot_dir();
+    let mut name = base_name.clone();
+    let mut counter = 1;
+    while snapshot_dir.join(&name).exists() {
+        counter += 1;
+        name = format!("{}-{}", base_name, counter);
+    }
+
+    // The staging directory ends in `TMP_SUFFIX` too, so `list` skips it.
+    let staging = snapshot_dir.join(format!("{}.staging{}", name, TMP_SUFFIX));
+    if staging.exists() {
+        try!(fs::remove_dir_all(&staging));
+    }
+    let frozen = match eng.storage().freeze_world(&staging) {
+        Ok(x) => x,
+        Err(e) => {
+            let _ = fs::remove_dir_all(&staging);
+            return Err(From::from(e));
+        },
+    };
+
+    let busy = eng.extra().snapshot_busy.clone();
+    busy.store(true, Ordering::SeqCst);
+    let thread_name = name.clone();
+    let thread = thread::spawn(move || {
+        warn_on_err!(write(&*frozen, &snapshot_dir, &thread_name));
+        drop(frozen);
+        if staging.exists() {
+            warn_on_err!(fs::remove_dir_all(&staging));
+        }
+        busy.store(false, Ordering::SeqCst);
+    });
+    eng.extra_mut().snapshot_thread = Some(thread);
+    Ok(name)
+}
+
+/// Copy the world in `saved` to a new snapshot `name` under `snapshot_dir`.  Runs on the
+/// snapshot thread.
+fn write(saved: &Storage, snapshot_dir: &Path, name: &str) -> io::Result<()> {
+    let dir = snapshot_dir.join(name);
+    let tmp_dir = snapshot_dir.join(format!("{}{}", name, TMP_SUFFIX));
+
+    // Leftovers from an interrupted snapshot would otherwise get mixed into this one.
+    if tmp_dir.exists() {
+        try!(fs::remove_dir_all(&tmp_dir));
+    }
+    try!(storage::copy_world(saved, &FileStorage::new(&tmp_dir)));
+    try!(fs::rename(&tmp_dir, &dir));
+    info!("created snapshot {}", name);
+
+    prune(snapshot_dir);
+    Ok(())
+}
+
+/// Wait for the snapshot being written in the background, if any, to finish.
+pub fn wait(mut eng: EngineRef) {
+    if let Some(thread) = eng.extra_mut().snapshot_thread.take() {
+        if thread.join().is_err() {
+            error!("snapshot thread panicked");
+        }
+    }
+}
+
+/// List the names of all complete snapshots, oldest first.
+pub fn list(storage: &Storage) -> Vec<String> {
+    list_dir(&storage.snapshot_dir())
+}
+
+fn list_dir(snapshot_dir: &Path) -> Vec<String> {
+    let entries = match fs::read_dir(snapshot_dir) {
+        Ok(x) => x,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Vec::new(),
+        Err(e) => panic!("error listing snapshots: {}", e),
+    };
+
+    let mut names = Vec::new();
+    for ent in entries {
+        let ent = ent.unwrap();
+        if !ent.file_type().unwrap().is_dir() {
+            continue;
+        }
+        if let Ok(name) = ent.file_name().into_string() {
+            if !name.ends_with(TMP_SUFFIX) {
+                names.push(name);
+            }
+        }
+    }
+    // Names are timestamps, so sorting them puts them in chronological order.
+    names.sort();
+    names
+}
+
+fn prune(snapshot_dir: &Path) {
+    let names = list_dir(snapshot_dir);
+    if names.len() <= KEEP_SNAPSHOTS {
+        return;
+    }
+    for name in &names[.. names.len() - KEEP_SNAPSHOTS] {
+        info!("removing old snapshot {}", name);
+        warn_on_err!(fs::remove_dir_all(snapshot_dir.join(name)));
+    }
+}
+
+
+/// Restore snapshot `name`.  This restarts the server.
+pub fn request_restore(mut eng: EngineRef, name: &str) -> bundle::Result<()> {
+    if !list(eng.storage()).iter().any(|n| n == name) {
+        fail!("no such snapshot");
+    }
+
+    {
+        let mut file = eng.storage().create_pending_restore_file();
+        try!(file.write_all(name.as_bytes()));
+        try!(file.commit());
+    }
+    info!("restoring snapshot {} after restart", name);
+    eng.extra_mut().restart_requested = true;
+    Ok(())
+}
+
+/// Replace the saved world with the snapshot recorded by `request_restore`, if there is one.
+/// Called at startup, before anything is loaded.
+pub fn apply_pending_restore(storage: &Storage) {
+    let path = storage.pending_restore_path();
+    let mut name = String::new();
+    match File::open(&path).and_then(|mut f| f.read_to_string(&mut name)) {
+        Ok(_) => {},
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return,
+        Err(e) => panic!("error reading {:?}: {}", path, e),
+    }
+
+    let dir = storage.snapshot_dir().join(&name);
+    if !dir.is_dir() {
+        error!("can't restore snapshot {}: it no longer exists", name);
+    } else {
+        info!("restoring snapshot {}", name);
+        // If this fails partway, the pending restore file is still there, so the next startup
+        // will try again from the beginning.
+        storage::clear_world(storage);
+        storage::copy_world(&FileStorage::new(&dir), storage).unwrap();
+    }
+    fs::remove_file(&path).unwrap();
+}
@@ -4,28 +4,63 @@ use types::*;
 
 use engine::glue::*;
 use messages::ClientResponse;
+use msg::CAP_TERRAIN_DELTA;
+use physics;
 use world;
 use world::object::*;
 use vision;
 
 
+/// Updates touching more blocks than this are sent as a whole `TerrainChunk` instead.
+const MAX_BLOCK_CHANGES: usize = 256;
+
+fn send_terrain_chunk(h: &mut VisionHooks, cid: ClientId, tcid: TerrainChunkId) {
+    use util::encode_rle16;
+    let tc = unwrap_or!(h.world().get_terrain_chunk(tcid),
+        { warn!("no terrain available for {:?}", tcid); return });
+    let cpos = tc.chunk_pos();
+    let data = encode_rle16(tc.blocks().iter().map(|&x| x));
+    h.messages().send_client(cid, ClientResponse::TerrainChunk(cpos, data));
+}
+
 impl<'a, 'd> vision::Hooks for VisionHooks<'a, 'd> {
     fn on_terrain_chunk_appear(&mut self,
                                cid: ClientId,
                                tcid: TerrainChunkId) {
-        self.on_terrain_chunk_update(cid, tcid);
+        send_terrain_chunk(self, cid, tcid);
     }
 
     fn on_terrain_chunk_update(&mut self,
                                cid: ClientId,
                                tcid: TerrainChunkId) {
-        use util::encode_rle16;
         trace!("terrain chunk update: {:?}, {:?}", cid, tcid);
-        let tc = unwrap_or!(self.world().get_terrain_chunk(tcid),
-            { warn!("no terrain available for {:?}", tcid); return });
-        let cpos = tc.chunk_pos();
-        let data = encode_rle16(tc.blocks().iter().map(|&x| x));
-        self.messages().send_client(cid, ClientResponse::TerrainChunk(cpos, data));
+        let caps = unwrap_or!(self.messages().client_capabilities(cid));
+        if !caps.contains(CAP_TERRAIN_DELTA) {
+            send_terrain_chunk(self, cid, tcid);
+            return;
+        }
+
+        let (pid, cpos) = {
+            let tc = unwrap_or!(self.world().get_terrain_chunk(tcid),
+                { warn!("no terrain available for {:?}", tcid); return });
+            (tc.plane_id(), tc.chunk_pos())
+        };
+        let changes = match self.cache().get(pid, cpos) {
+            Some(entry) => entry.block_changes.clone(),
+            None => {
+                send_terrain_chunk(self, cid, tcid);
+                return;
+            },
+        };
+
+        if changes.is_empty() {
+            return;
+        } else if changes.len() > MAX_BLOCK_CHANGES {
+            // Past this point, the run-length encoded chunk is usually smaller.
+            send_terrain_chunk(self, cid, tcid);
+        } else {
+            self.messages().send_client(cid, ClientResponse::TerrainBlocksUpdate(cpos, changes));
+        }
     }
 
 
@@ -42,11 +77,14 @@ impl<'a, 'd> vision::Hooks for VisionHooks<'a, 'd> {
                 } else {
                     String::new()
                 };
+            let groups = physics::entity_groups(&*entity).bits();
 
-            self.messages().send_client(cid, ClientResponse::EntityAppear(eid, appearance, name));
+            self.messages().send_client(cid,
+                                        ClientResponse::EntityAppear(eid, appearance, name, groups));
         }
 
         self.on_entity_motion_update(cid, eid);
+        self.on_entity_health_update(cid, eid);
     }
 
     fn on_entity_disappear(&mut self, cid: ClientId, eid: EntityId) {
@@ -76,6 +114,17 @@ impl<'a, 'd> vision::Hooks for VisionHooks<'a, 'd> {
         self.on_entity_appear(cid, eid);
     }
 
+    fn on_entity_health_update(&mut self, cid: ClientId, eid: EntityId) {
+        trace!("on_entity_health_update({:?}, {:?})", cid, eid);
+        let health = self.world().entity(eid).health();
+        self.messages().send_client(cid, ClientResponse::EntityHealth(eid, health.hp, health.max));
+    }
+
+    fn on_entity_damage(&mut self, cid: ClientId, eid: EntityId, amount: u16) {
+        trace!("on_entity_damage({:?}, {:?}, {})", cid, eid, amount);
+        self.messages().send_client(cid, ClientResponse::EntityDamage(eid, amount));
+    }
+
 
     fn on_plane_change(&mut self,
                        cid: ClientId,
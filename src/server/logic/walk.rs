@@ -0,0 +1,230 @@
+//! The "walk to point" activity for scripted entities.  `start` plans a tile path with
+//! `pathfind`, then the entity is steered along it one straight segment at a time.  Each segment
+//! is an ordinary physics motion, cut short so that it ends exactly on the next waypoint.  When the
+//! motion ends, the usual physics update calls back into `step` to start the next segment.
+//!
+//! If the entity stops making progress (for example, another entity is in the way), it waits a
+//! moment and then plans a new path from wherever it is.  The `entity_walk_done` script hook is
+//! called once the entity arrives or gives up.  Walks are not saved, so a restart leaves the
+//! entity standing wherever it was.
+use std::cmp;
+use std::collections::VecDeque;
+use libphysics::TILE_SIZE;
+
+use types::*;
+use util::StrResult;
+
+use engine::split::EngineRef;
+use pathfind;
+use physics;
+use timer;
+use world::{Activity, Motion};
+use world::Fragment as World_Fragment;
+use world::object::*;
+
+
+/// How long to wait before planning a new path after getting stuck.
+const RETRY_DELAY: Time = 1000;
+
+/// Give up after this many attempts in a row that make no progress.
+const MAX_RETRIES: u8 = 3;
+
+
+pub struct Walk {
+    /// Tile coordinates of the destination.
+    goal: V3,
+    /// Remaining corners of the path, in pixels.  Only x and y are steered directly; physics
+    /// takes care of z when following ramps.
+    waypoints: VecDeque<V2>,
+    speed: i32,
+    /// Number of consecutive attempts that failed to make progress.
+    retries: u8,
+    /// Timer for the next retry, if the entity is currently stuck.
+    retry_timer: Option<timer::Cookie>,
+}
+
+impl Walk {
+    pub fn retry_timer(&self) -> Option<timer::Cookie> {
+        self.retry_timer
+    }
+}
+
+
+/// Reduce a tile path to the pixel positions where it starts, turns, and ends.
+fn waypoints(path: &[V3]) -> VecDeque<V2> {
+    let px = |p: V3| p.reduce() * scalar(TILE_SIZE);
+    let mut result = VecDeque::new();
+    result.push_back(px(path[0]));
+    for i in 1 .. path.len() {
+        let turn = i + 1 < path.len() &&
+            (path[i] - path[i - 1]).reduce() != (path[i + 1] - path[i]).reduce();
+        if turn || i + 1 == path.len() {
+            result.push_back(px(path[i]));
+        }
+    }
+    result
+}
+
+fn plan(eng: &EngineRef, eid: EntityId, goal: V3) -> StrResult<Option<VecDeque<V2>>> {
+    let e = unwrap!(eng.world().get_entity(eid));
+    let start = pathfind::entity_tile(e.pos(eng.now()));
+    let path = pathfind::find_path_in_cache(eng.cache(), e.plane_id(), start, goal);
+    Ok(path.map(|p| waypoints(&p)))
+}
+
+
+/// Make entity `eid` walk to pixel position `pos`, replacing any walk already in progress.
+/// Returns `false` if there is currently no path to `pos`.
+pub fn start(mut eng: EngineRef, eid: EntityId, pos: V3, run: bool) -> StrResult<bool> {
+    cancel(eng.borrow(), eid);
+
+    let goal = pathfind::entity_tile(pos);
+    let waypoints = unwrap_or!(try!(plan(&eng, eid, goal)), return Ok(false));
+    eng.extra_mut().entity_walk.insert(eid, Walk {
+        goal: goal,
+        waypoints: waypoints,
+        // TODO: player speed handling shouldn't be here
+        speed: if run { 150 } else { 50 },
+        retries: 0,
+        retry_timer: None,
+    });
+
+    if eng.world().entity(eid).activity() != Activity::Move {
+        // Changing the activity schedules a physics update, which will call `step`.
+        eng.as_world_fragment().entity_mut(eid).set_activity(Activity::Move);
+    } else {
+        step(eng, eid);
+    }
+    Ok(true)
+}
+
+/// Stop the walk in progress for `eid`, if any, without running the script hook.
+pub fn cancel(mut eng: EngineRef, eid: EntityId) {
+    let walk = unwrap_or!(eng.extra_mut().entity_walk.remove(&eid));
+    if let Some(cookie) = walk.retry_timer {
+        eng.timer_mut().cancel(cookie);
+    }
+    if eng.world().get_entity(eid).map_or(false, |e| e.activity() == Activity::Move) {
+        set_velocity(eng, eid, scalar(0));
+    }
+}
+
+pub fn is_walking(eng: EngineRef, eid: EntityId) -> bool {
+    eng.extra().entity_walk.contains_key(&eid)
+}
+
+/// Get the destination tile of the walk in progress for `eid`, if any.
+pub fn goal(eng: EngineRef, eid: EntityId) -> Option<V3> {
+    eng.extra().entity_walk.get(&eid).map(|walk| walk.goal)
+}
+
+fn finish(mut eng: EngineRef, eid: EntityId, arrived: bool) {
+    cancel(eng.borrow(), eid);
+    warn_on_err!(eng.script_hooks().call_entity_walk_done(eng, eid, arrived));
+}
+
+fn set_velocity(mut eng: EngineRef, eid: EntityId, velocity: V3) {
+    let now = eng.now();
+    warn_on_err!(physics::Fragment::set_velocity(
+            &mut eng.as_physics_fragment(), now, eid, velocity));
+}
+
+
+/// Start the next segment of the walk.  Called whenever the entity's motion ends.
+pub fn step(mut eng: EngineRef, eid: EntityId) {
+    let now = eng.now();
+    let info = eng.world().get_entity(eid).map(|e| (e.pos(now), e.activity()));
+    let (pos, activity) = unwrap_or!(info, { eng.extra_mut().entity_walk.remove(&eid); return });
+
+    if activity != Activity::Move {
+        // Something else took over the entity, such as a script setting a special animation.
+        finish(eng.borrow(), eid, false);
+        warn_on_err!(physics::Fragment::update(&mut eng.as_physics_fragment(), now, eid));
+        return;
+    }
+
+    let (target, speed) = {
+        let walk = unwrap_or!(eng.extra_mut().entity_walk.get_mut(&eid));
+        if walk.retry_timer.is_some() {
+            // Waiting to retry.  Some unrelated physics update ended the motion.
+            return;
+        }
+        while walk.waypoints.front() == Some(&pos.reduce()) {
+            walk.waypoints.pop_front();
+        }
+        (walk.waypoints.front().cloned(), walk.speed)
+    };
+    let target = unwrap_or!(target, { finish(eng, eid, true); return });
+
+    // Move along one axis at a time, so that each motion is a straight line that passes exactly
+    // through `target`.  This only matters for reaching the first waypoint; after that, each
+    // segment of the path is already axis-aligned.
+    let delta = target - pos.reduce();
+    let (dir, dist) =
+        if delta.x != 0 {
+            (V2::new(delta.x.signum(), 0), delta.x.abs())
+        } else {
+            (V2::new(0, delta.y.signum()), delta.y.abs())
+        };
+    set_velocity(eng.borrow(), eid, dir.extend(0) * scalar(speed));
+
+    let motion = eng.world().entity(eid).motion().clone();
+    let travel = (motion.end_pos - motion.start_pos).reduce().abs().max();
+    if travel == 0 {
+        stuck(eng, eid);
+        return;
+    }
+    eng.extra_mut().entity_walk.get_mut(&eid).unwrap().retries = 0;
+
+    if travel > dist {
+        // Stop at the waypoint.  The end of this motion will trigger the next `step`.
+        let offset = (motion.end_pos - motion.start_pos) * scalar(dist) / scalar(travel);
+        let duration = motion.duration as i32 * dist / travel;
+        let new_motion = Motion {
+            start_time: motion.start_time,
+            duration: cmp::max(duration, 1) as Duration,
+            start_pos: motion.start_pos,
+            end_pos: motion.start_pos + offset,
+        };
+        eng.as_world_fragment().entity_mut(eid).set_motion(new_motion);
+    }
+}
+
+/// Handle an entity that can't move toward its next waypoint.  Stop, and plan a new path after a
+/// short delay.
+fn stuck(mut eng: EngineRef, eid: EntityId) {
+    let give_up = {
+        let walk = eng.extra_mut().entity_walk.get_mut(&eid).unwrap();
+        walk.retries += 1;
+        walk.retries > MAX_RETRIES
+    };
+    if give_up {
+        finish(eng, eid, false);
+        return;
+    }
+
+    set_velocity(eng.borrow(), eid, scalar(0));
+    let when = eng.now() + RETRY_DELAY;
+    let cookie = eng.timer_mut().schedule(when, move |eng| retry(eng, eid));
+    eng.extra_mut().entity_walk.get_mut(&eid).unwrap().retry_timer = Some(cookie);
+}
+
+fn retry(mut eng: EngineRef, eid: EntityId) {
+    let goal = {
+        let walk = unwrap_or!(eng.extra_mut().entity_walk.get_mut(&eid));
+        walk.retry_timer = None;
+        walk.goal
+    };
+
+    match plan(&eng, eid, goal) {
+        Ok(Some(waypoints)) => {
+            eng.extra_mut().entity_walk.get_mut(&eid).unwrap().waypoints = waypoints;
+            step(eng, eid);
+        },
+        Ok(None) => finish(eng, eid, false),
+        Err(e) => {
+            warn!("failed to plan path for {:?}: {}", eid, e);
+            eng.extra_mut().entity_walk.remove(&eid);
+        },
+    }
+}
@@ -12,12 +12,19 @@ use logic;
 use messages::{ClientResponse, SyncKind};
 use physics;
 use world::{self, World, Entity, Structure};
+use world::{EntityAttachment, InventoryAttachment};
+use world::Activity;
 use world::object::*;
 use vision::{self, vision_region};
 
 
+/// Entities within this many pixels of each other get their motions rechecked.  See
+/// `check_entity_approach`.
+const APPROACH_MARGIN: i32 = 4;
+
+
 macro_rules! impl_world_Hooks {
-    ($WorldHooks:ident, $as_vision_fragment:ident) => {
+    ($WorldHooks:ident, $as_vision_fragment:ident, $autosave:expr) => {
 
 impl<'a, 'd> world::Hooks for $WorldHooks<'a, 'd> {
     // We should never get client callbacks in the HiddenWorldHooks variant.
@@ -50,18 +57,25 @@ impl<'a, 'd> world::Hooks for $WorldHooks<'a, 'd> {
         vision::Fragment::remove_terrain_chunk(&mut self.$as_vision_fragment(), tcid);
 
         self.cache_mut().remove_chunk(pid, cpos);
+        self.extra_mut().autosave.forget_terrain_chunk(pid, cpos);
     }
 
     fn on_terrain_chunk_update(&mut self, tcid: TerrainChunkId) {
-        // TODO: need a system to avoid resending the entire chunk every time.
-        let (pid, bounds) = {
+        let (pid, cpos, bounds) = {
             let tc = self.world().terrain_chunk(tcid);
-            (tc.plane_id(), tc.bounds())
+            (tc.plane_id(), tc.chunk_pos(), tc.bounds())
         };
-        vision::Fragment::update_terrain_chunk(&mut self.$as_vision_fragment(), tcid);
+        if $autosave {
+            self.extra_mut().autosave.mark_terrain_chunk(pid, cpos);
+        }
 
-        let Open { world, cache, .. } = (**self).open();
-        cache.update_region(world, pid, bounds);
+        // Update the cache first, so the vision hooks can send only the blocks that changed.
+        {
+            let Open { world, cache, .. } = (**self).open();
+            cache.update_region(world, pid, bounds);
+        }
+        vision::Fragment::update_terrain_chunk(&mut self.$as_vision_fragment(), tcid);
+        self.cache_mut().clear_block_changes(pid, cpos);
     }
 
 
@@ -78,10 +92,45 @@ impl<'a, 'd> world::Hooks for $WorldHooks<'a, 'd> {
         self.schedule_physics_update(eid, end_time);
         // Might have an owner pre-set, if it's been loaded instead of newly created.
         self.schedule_view_update(eid);
+        self.mark_entity_dirty(eid);
+
+        // Likewise, a loaded NPC needs its state restored.  Hooks can't call into `logic::npc`
+        // directly, so do it from a timer instead.
+        if self.world().entity(eid).extra().contains(logic::npc::EXTRA_KEY) {
+            let now = self.now();
+            self.timer_mut().schedule(now, move |eng| logic::npc::restore(eng, eid));
+        }
+        // An entity saved while dead would otherwise never respawn.
+        if self.world().entity(eid).health().is_dead() {
+            let now = self.now();
+            self.timer_mut().schedule(now, move |eng| logic::combat::respawn(eng, eid));
+        }
     }
 
     fn on_entity_destroy(&mut self, eid: EntityId) {
         vision::Fragment::remove_entity(&mut self.$as_vision_fragment(), eid);
+        if let Some(walk) = self.extra_mut().entity_walk.remove(&eid) {
+            if let Some(cookie) = walk.retry_timer() {
+                self.timer_mut().cancel(cookie);
+            }
+        }
+        if let Some(npc) = self.extra_mut().npcs.remove(&eid) {
+            if let Some(cookie) = npc.think_timer() {
+                self.timer_mut().cancel(cookie);
+            }
+        }
+        if let Some(cookie) = self.extra_mut().respawn_timer.remove(&eid) {
+            self.timer_mut().cancel(cookie);
+        }
+        if let Some((_, cookie)) = self.extra_mut().entity_physics_update_timer.remove(&eid) {
+            self.timer_mut().cancel(cookie);
+        }
+        // The entity is already gone, so there's no way to tell what it was attached to.  Only
+        // the world's list of children can be affected in a way that isn't reported some other
+        // way.
+        if $autosave {
+            self.extra_mut().autosave.mark_world();
+        }
     }
 
     fn on_entity_activity_change(&mut self, eid: EntityId) {
@@ -100,11 +149,19 @@ impl<'a, 'd> world::Hooks for $WorldHooks<'a, 'd> {
         trace!("entity {:?} motion changed to {:?}", eid, plane);
         vision::Fragment::set_entity_area(&mut self.$as_vision_fragment(), eid, plane, area);
         self.schedule_physics_update(eid, end_time);
+        self.check_entity_approach(eid);
         self.schedule_view_update(eid);
+        self.mark_entity_dirty(eid);
     }
 
     fn on_entity_appearance_change(&mut self, eid: EntityId) {
         vision::Fragment::update_entity_appearance(&mut self.$as_vision_fragment(), eid);
+        self.mark_entity_dirty(eid);
+    }
+
+    fn on_entity_health_change(&mut self, eid: EntityId) {
+        vision::Fragment::update_entity_health(&mut self.$as_vision_fragment(), eid);
+        self.mark_entity_dirty(eid);
     }
 
     fn on_entity_plane_change(&mut self, eid: EntityId) {
@@ -114,11 +171,14 @@ impl<'a, 'd> world::Hooks for $WorldHooks<'a, 'd> {
 
 
     fn on_structure_create(&mut self, sid: StructureId) {
-        let (pid, area) = {
+        let (pid, pos, area) = {
             let s = self.world().structure(sid);
-            (s.plane_id(), structure_area(s))
+            (s.plane_id(), s.pos(), structure_area(s))
         };
         vision::Fragment::add_structure(&mut self.$as_vision_fragment(), sid, pid, area);
+        if $autosave {
+            self.extra_mut().autosave.mark_terrain_chunk_at(pid, pos);
+        }
 
         let Open { world, cache, .. } = (**self).open();
         let s = world.structure(sid);
@@ -130,6 +190,9 @@ impl<'a, 'd> world::Hooks for $WorldHooks<'a, 'd> {
                             old_pid: PlaneId,
                             old_bounds: Region) {
         vision::Fragment::remove_structure(&mut self.$as_vision_fragment(), sid);
+        if $autosave {
+            self.extra_mut().autosave.mark_terrain_chunk_at(old_pid, old_bounds.min);
+        }
 
         {
             let Open { world, cache, .. } = (**self).open();
@@ -152,6 +215,9 @@ impl<'a, 'd> world::Hooks for $WorldHooks<'a, 'd> {
         };
         vision::Fragment::set_structure_area(&mut self.$as_vision_fragment(), sid, pid, area);
         vision::Fragment::change_structure_template(&mut self.$as_vision_fragment(), sid);
+        if $autosave {
+            self.extra_mut().autosave.mark_terrain_chunk_at(pid, old_bounds.min);
+        }
 
         let Open { world, cache, .. } = (**self).open();
         let s = world.structure(sid);
@@ -197,16 +263,94 @@ impl<'a, 'd> world::Hooks for $WorldHooks<'a, 'd> {
                            iid: InventoryId,
                            slot_idx: u8) {
         vision::Fragment::update_inventory(&mut self.$as_vision_fragment(), iid, slot_idx);
+        self.mark_inventory_dirty(iid);
+    }
+
+    fn on_inventory_destroy(&mut self, _iid: InventoryId) {
+        // See `on_entity_destroy`.
+        if $autosave {
+            self.extra_mut().autosave.mark_world();
+        }
+    }
+
+
+    fn on_plane_create(&mut self, pid: PlaneId) {
+        if $autosave {
+            self.extra_mut().autosave.mark_plane(pid);
+        }
+    }
+
+    fn on_plane_destroy(&mut self, pid: PlaneId) {
+        self.extra_mut().autosave.forget_plane(pid);
     }
 }
 
 impl<'a, 'd> $WorldHooks<'a, 'd> {
     fn schedule_physics_update(&mut self, eid: EntityId, when: Time) {
-        if let Some(cookie) = self.extra_mut().entity_physics_update_timer.remove(&eid) {
+        if let Some((_, cookie)) = self.extra_mut().entity_physics_update_timer.remove(&eid) {
             self.timer_mut().cancel(cookie);
         }
         let cookie = self.timer_mut().schedule(when, move |eng| update_physics(eng, eid));
-        self.extra_mut().entity_physics_update_timer.insert(eid, cookie);
+        self.extra_mut().entity_physics_update_timer.insert(eid, (when, cookie));
+    }
+
+    /// Like `schedule_physics_update`, but keeps the pending update if it happens sooner.
+    fn schedule_physics_update_by(&mut self, eid: EntityId, when: Time) {
+        if let Some(&(old_when, _)) = self.extra_mut().entity_physics_update_timer.get(&eid) {
+            if old_when <= when {
+                return;
+            }
+        }
+        self.schedule_physics_update(eid, when);
+    }
+
+    /// Check whether the new motion of `eid` brings it into contact with a moving entity nearby,
+    /// or lets a blocked entity continue.  Collision checks only look a short way into other
+    /// entities' motions, so in either case the motions involved get recomputed at the point
+    /// where things change.
+    fn check_entity_approach(&mut self, eid: EntityId) {
+        let now = self.now();
+        let mut updates = Vec::new();
+        {
+            let w = self.world();
+            let e = w.entity(eid);
+            if e.plane_id() == PLANE_LIMBO {
+                return;
+            }
+            let m = e.motion();
+            let size = physics::entity_size();
+            let start = m.pos(now);
+            let area = Region::new(start, start + size)
+                .join(Region::new(m.end_pos, m.end_pos + size))
+                .expand(scalar(APPROACH_MARGIN));
+
+            for oid in physics::entities_near(w, e.plane_id(), area) {
+                let o = w.entity(oid);
+                if oid == eid ||
+                   o.activity() != Activity::Move ||
+                   o.target_velocity() == scalar(0) {
+                    continue;
+                }
+
+                let om = o.motion();
+                match physics::approach(m, om, size, APPROACH_MARGIN, now) {
+                    Some((start, _)) if start > now => {
+                        updates.push((eid, start));
+                        updates.push((oid, start));
+                    },
+                    Some((_, Some(end))) if end > now && om.start_pos == om.end_pos => {
+                        // `oid` is trying to move but can't, possibly because `eid` is in the
+                        // way.  Try again once `eid` is out of reach.
+                        updates.push((oid, end));
+                    },
+                    _ => {},
+                }
+            }
+        }
+
+        for (id, when) in updates {
+            self.schedule_physics_update_by(id, when);
+        }
     }
 
     pub fn schedule_view_update(&mut self, eid: EntityId) {
@@ -262,6 +406,40 @@ impl<'a, 'd> $WorldHooks<'a, 'd> {
             self.extra_mut().client_view_update_timer.insert(cid, cookie);
         }
     }
+
+    /// Mark whatever object gets saved along with entity `eid`.
+    fn mark_entity_dirty(&mut self, eid: EntityId) {
+        if !$autosave {
+            return;
+        }
+        let attach = unwrap_or!(self.world().get_entity(eid)).attachment();
+        match attach {
+            EntityAttachment::World => self.extra_mut().autosave.mark_world(),
+            EntityAttachment::Client(cid) => self.extra_mut().autosave.mark_client(cid),
+            // Chunk entities aren't saved.
+            EntityAttachment::Chunk => {},
+        }
+    }
+
+    /// Mark whatever object gets saved along with inventory `iid`.
+    fn mark_inventory_dirty(&mut self, iid: InventoryId) {
+        if !$autosave {
+            return;
+        }
+        let attach = unwrap_or!(self.world().get_inventory(iid)).attachment();
+        match attach {
+            InventoryAttachment::World => self.extra_mut().autosave.mark_world(),
+            InventoryAttachment::Client(cid) => self.extra_mut().autosave.mark_client(cid),
+            InventoryAttachment::Entity(eid) => self.mark_entity_dirty(eid),
+            InventoryAttachment::Structure(sid) => {
+                let (pid, pos) = {
+                    let s = unwrap_or!(self.world().get_structure(sid));
+                    (s.plane_id(), s.pos())
+                };
+                self.extra_mut().autosave.mark_terrain_chunk_at(pid, pos);
+            },
+        }
+    }
 }
 
 // End of macro_rules
@@ -269,8 +447,10 @@ impl<'a, 'd> $WorldHooks<'a, 'd> {
 }
 
 
-impl_world_Hooks!(WorldHooks, as_vision_fragment);
-impl_world_Hooks!(HiddenWorldHooks, as_hidden_vision_fragment);
+// Changes made through `HiddenWorldHooks` come from loading and unloading objects, which doesn't
+// make them differ from their saved copies, so those changes aren't marked for autosave.
+impl_world_Hooks!(WorldHooks, as_vision_fragment, true);
+impl_world_Hooks!(HiddenWorldHooks, as_hidden_vision_fragment, false);
 
 
 pub fn entity_area(e: ObjectRef<Entity>) -> SmallSet<V2> {
@@ -404,6 +584,12 @@ fn compute_layer_mask_excluding(w: &World,
 
 
 fn update_physics(mut eng: EngineRef, eid: EntityId) {
+    eng.extra_mut().entity_physics_update_timer.remove(&eid);
+    if logic::walk::is_walking(eng.borrow(), eid) {
+        logic::walk::step(eng, eid);
+        return;
+    }
+
     let now = eng.now();
     warn_on_err!(physics::Fragment::update(&mut eng.as_physics_fragment(), now, eid));
     // When `update` changes the entity's motion, the hook will schedule the next update,
@@ -57,6 +57,7 @@ use rustc_serialize::json;
 mod msg;
 mod wire;
 mod tasks;
+mod websocket;
 mod timer;
 mod types;
 mod input;
@@ -64,8 +65,10 @@ mod world;
 mod pubsub;
 
 mod auth;
+mod chat;
 mod messages;
 mod physics;
+mod pathfind;
 mod chunks;
 mod terrain_gen;
 mod vision;
@@ -100,7 +103,8 @@ fn main() {
 
     // Initialize engine environment.
     let args = env::args().collect::<Vec<_>>();
-    let storage = storage::Storage::new(&args[1]);
+    let storage = storage::open(&args[1]);
+    let listen_addr = args.get(2);
 
     let block_json = read_json(storage.open_block_data());
     let item_json = read_json(storage.open_item_data());
@@ -109,13 +113,15 @@ fn main() {
     let animation_json = read_json(storage.open_animation_data());
     let sprite_layer_json = read_json(storage.open_sprite_layer_data());
     let loot_table_json = read_json(storage.open_loot_table_data());
+    let action_json = read_json(storage.open_action_data());
     let data = data::Data::from_json(block_json,
                                      item_json,
                                      recipe_json,
                                      template_json,
                                      animation_json,
                                      sprite_layer_json,
-                                     loot_table_json).unwrap();
+                                     loot_table_json,
+                                     action_json).unwrap();
 
     script::ffi_module_preinit();
     python::initialize();
@@ -125,19 +131,25 @@ fn main() {
     python::run_file(&storage.script_dir().join("boot.py")).unwrap();
 
 
-    // Start background threads.
+    // Start background threads.  Normally the wrapper handles client connections and talks to us
+    // over stdin/stdout, but if a listen address was given after the storage path, we accept
+    // websocket connections directly instead.
     let (req_send, req_recv) = channel();
     let (resp_send, resp_recv) = channel();
 
-    thread::spawn(move || {
-        let reader = io::stdin();
-        tasks::run_input(reader, req_send).unwrap();
-    });
+    if let Some(addr) = listen_addr {
+        websocket::listen(&addr[..], req_send, resp_recv).unwrap();
+    } else {
+        thread::spawn(move || {
+            let reader = io::stdin();
+            tasks::run_input(reader, req_send).unwrap();
+        });
 
-    thread::spawn(move || {
-        let writer = io::BufWriter::new(io::stdout());
-        tasks::run_output(writer, resp_recv).unwrap();
-    });
+        thread::spawn(move || {
+            let writer = io::BufWriter::new(io::stdout());
+            tasks::run_output(writer, resp_recv).unwrap();
+        });
+    }
 
 
     // Run the engine.  The engine runs inside the two `with_ref`s so that the data and storage
@@ -151,10 +163,12 @@ fn main() {
             let script_hooks = script_hooks;
 
             let mut engine = engine::Engine::new(&data,
-                                                 &storage,
+                                                 &*storage,
                                                  &script_hooks,
                                                  req_recv,
                                                  resp_send);
+            // Connections accepted by `websocket` close when the process exits.
+            engine.extra.wires_survive_restart = listen_addr.is_none();
             engine.run();
         });
     });
@@ -5,7 +5,7 @@ use libphysics::{CHUNK_SIZE, CHUNK_BITS, TILE_SIZE, TILE_BITS};
 
 use types::*;
 
-use msg;
+use msg::{self, Capabilities};
 use world;
 
 
@@ -20,6 +20,7 @@ pub struct ClientInfo {
     name: String,
     chunk_offset: (u8, u8),
     last_check: Time,
+    caps: Capabilities,
 }
 
 impl Clients {
@@ -31,8 +32,8 @@ impl Clients {
         }
     }
 
-    pub fn add(&mut self, cid: ClientId, wire_id: WireId, name: &str) {
-        let old_client = self.clients.insert(cid, ClientInfo::new(wire_id, name));
+    pub fn add(&mut self, cid: ClientId, wire_id: WireId, name: &str, caps: Capabilities) {
+        let old_client = self.clients.insert(cid, ClientInfo::new(wire_id, name, caps));
         let old_wire = self.wire_map.insert(wire_id, cid);
         let old_name = self.name_map.insert(String::from(name), cid);
         debug_assert!(old_client.is_none());
@@ -76,7 +77,7 @@ const LOCAL_SIZE: i32 = 1 << LOCAL_BITS;
 const LOCAL_MASK: i32 = LOCAL_SIZE - 1;
 
 impl ClientInfo {
-    pub fn new(wire_id: WireId, name: &str) -> ClientInfo {
+    pub fn new(wire_id: WireId, name: &str, caps: Capabilities) -> ClientInfo {
         let mut rng = rand::thread_rng();
         let offset_x = rng.gen_range(0, 8);
         let offset_y = rng.gen_range(0, 8);
@@ -85,6 +86,7 @@ impl ClientInfo {
             name: String::from(name),
             chunk_offset: (offset_x, offset_y),
             last_check: TIME_MIN,
+            caps: caps,
         }
     }
 
@@ -92,6 +94,10 @@ impl ClientInfo {
         self.wire_id
     }
 
+    pub fn capabilities(&self) -> Capabilities {
+        self.caps
+    }
+
     pub fn local_chunk_index(&self, cpos: V2) -> u16 {
         let cx = (cpos.x + self.chunk_offset.0 as i32) & LOCAL_MASK;
         let cy = (cpos.y + self.chunk_offset.1 as i32) & LOCAL_MASK;
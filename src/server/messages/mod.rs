@@ -1,4 +1,6 @@
+use std::cell::RefCell;
 use std::cmp;
+use std::collections::HashMap;
 use std::error::Error;
 use std::mem;
 use std::sync::mpsc::{Sender, Receiver};
@@ -10,20 +12,32 @@ use libphysics::TILE_SIZE;
 
 use auth::Secret;
 use input::InputBits;
-use msg::{Request, Response, InitData, ExtraArg};
+use msg::{Request, Response, ExtraArg};
+use msg::{PROTOCOL_VERSION, Capabilities, CAP_ENTITY_HEALTH, CAP_USE_ACTION, CAP_TERRAIN_DELTA};
 use world::{self, Motion};
 
-use self::clients::Clients;
+use self::clients::{Clients, ClientInfo};
+use self::queue::{SendQueues, Priority};
+pub use self::queue::QueueStats;
 
 
 mod clients;
+pub mod queue;
 
 
 pub struct Messages {
     send: Sender<(WireId, Response)>,
     recv: Receiver<(WireId, Request)>,
     clients: Clients,
+    /// Capabilities negotiated by each wire that has completed the `Hello` handshake but has not
+    /// yet logged in.
+    handshakes: HashMap<WireId, Capabilities>,
+    /// Responses waiting to go out to logged-in clients.  Sending only needs `&self`, like sending
+    /// on `send` directly, so the queues live in a `RefCell`.
+    queues: RefCell<SendQueues>,
     time_base: Time,
+    /// If set, the current world time, used in place of the system clock.  See `set_manual_now`.
+    manual_now: Option<Time>,
 }
 
 pub enum Event {
@@ -44,6 +58,9 @@ pub enum ControlEvent {
 pub enum WireEvent {
     Login(String, Secret),
     Register(String, Secret, u32),
+    /// The client's `Hello` named an unsupported protocol version, or it tried to log in without
+    /// sending `Hello` at all (reported as version 0).
+    IncompatibleVersion(u32),
     BadRequest,
 }
 
@@ -57,6 +74,7 @@ pub enum ClientEvent {
     Interact(Time, Option<ExtraArg>),
     UseItem(Time, ItemId, Option<ExtraArg>),
     UseAbility(Time, ItemId, Option<ExtraArg>),
+    UseAction(Time, ActionId, Option<ExtraArg>),
 
     BadRequest,
 }
@@ -68,12 +86,27 @@ pub enum ControlResponse {
     ReplResult(u16, String),
 }
 
+/// Responses to a wire that may not have logged in yet.  These are sent without checking
+/// capabilities, so every variant must be part of the base protocol.  Anything that depends on a
+/// negotiated capability belongs in `ClientResponse`, where `send_client` can check it.
 #[derive(Debug, Clone)]
 pub enum WireResponse {
     RegisterResult(u32, String),
     KickReason(String),
 }
 
+/// How `send_client` delivers a `ClientResponse`.
+enum Delivery {
+    /// Wait in the client's send queue with the given priority.
+    Queue(Priority),
+    /// Send everything already queued for the client, then this, regardless of budget.  Used when
+    /// the client is about to throw away its state, so that it sees everything sent before.
+    Flush,
+    /// Discard anything queued for the client, and send this right away.  Used when the client is
+    /// about to be disconnected.
+    Discard,
+}
+
 #[derive(Debug, Clone)]
 pub enum SyncKind {
     Loading,
@@ -87,11 +120,14 @@ pub enum ClientResponse {
     Init(Option<EntityId>, Time, u32, u32),
 
     TerrainChunk(V2, Vec<u16>),
+    TerrainBlocksUpdate(V2, Vec<(u16, BlockId)>),
     UnloadChunk(V2),
 
-    EntityAppear(EntityId, u32, String),
+    EntityAppear(EntityId, u32, String, u8),
     EntityUpdate(EntityId, Motion, AnimId),
     EntityGone(EntityId, Time),
+    EntityHealth(EntityId, u16, u16),
+    EntityDamage(EntityId, u16),
 
     StructureAppear(StructureId, TemplateId, V3),
     StructureGone(StructureId),
@@ -107,6 +143,7 @@ pub enum ClientResponse {
     GetInteractArgs(u32, ExtraArg),
     GetUseItemArgs(ItemId, u32, ExtraArg),
     GetUseAbilityArgs(ItemId, u32, ExtraArg),
+    GetUseActionArgs(ActionId, u32, ExtraArg),
 
     OpenDialog(Dialog),
     MainInventory(InventoryId),
@@ -115,6 +152,40 @@ pub enum ClientResponse {
     KickReason(String),
 }
 
+impl ClientResponse {
+    /// The capability a client must have negotiated to receive this response, if any.
+    fn required_capability(&self) -> Option<Capabilities> {
+        match *self {
+            ClientResponse::EntityHealth(..) |
+            ClientResponse::EntityDamage(..) => Some(CAP_ENTITY_HEALTH),
+            ClientResponse::GetUseActionArgs(..) => Some(CAP_USE_ACTION),
+            ClientResponse::TerrainBlocksUpdate(..) => Some(CAP_TERRAIN_DELTA),
+            _ => None,
+        }
+    }
+
+    fn delivery(&self) -> Delivery {
+        match *self {
+            ClientResponse::KickReason(..) => Delivery::Discard,
+
+            ClientResponse::SyncStatus(SyncKind::Reset) |
+            ClientResponse::SyncStatus(SyncKind::Refresh) => Delivery::Flush,
+
+            // `SyncStatus(Ok)` must not overtake the terrain the client is waiting for.
+            ClientResponse::TerrainChunk(..) |
+            ClientResponse::TerrainBlocksUpdate(..) |
+            ClientResponse::UnloadChunk(..) |
+            ClientResponse::StructureAppear(..) |
+            ClientResponse::StructureGone(..) |
+            ClientResponse::StructureReplace(..) |
+            ClientResponse::PlaneFlags(..) |
+            ClientResponse::SyncStatus(..) => Delivery::Queue(Priority::Low),
+
+            _ => Delivery::Queue(Priority::High),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Dialog {
     Inventory(InventoryId),
@@ -138,7 +209,10 @@ impl Messages {
             send: send,
             recv: recv,
             clients: Clients::new(),
+            handshakes: HashMap::new(),
+            queues: RefCell::new(SendQueues::new()),
             time_base: 0,
+            manual_now: None,
         }
     }
 
@@ -154,7 +228,22 @@ impl Messages {
     }
 
     fn world_now(&self) -> Time {
-        self.world_time(now())
+        match self.manual_now {
+            Some(t) => t,
+            None => self.world_time(now()),
+        }
+    }
+
+    /// The current world time, by the same clock used to timestamp incoming requests.  For events
+    /// that don't arrive through `process`.
+    pub fn now(&self) -> Time {
+        self.world_now()
+    }
+
+    /// Override the clock used to timestamp incoming requests.  Tests use this, together with
+    /// `Timer::new_manual`, to control the passage of time.
+    pub fn set_manual_now(&mut self, world_time: Option<Time>) {
+        self.manual_now = world_time;
     }
 
     // NB: This is designed to be called only once, near the beginning of server startup.  Calling
@@ -172,10 +261,30 @@ impl Messages {
     // Client lifecycle
 
     pub fn add_client(&mut self, cid: ClientId, wire_id: WireId, name: &str) {
-        self.clients.add(cid, wire_id, name);
+        let caps = self.handshakes.remove(&wire_id).unwrap_or(Capabilities::empty());
+        self.clients.add(cid, wire_id, name, caps);
+    }
+
+    /// Record the capabilities of a wire that is about to log in without a fresh handshake.  Used
+    /// when restoring clients after a server restart.
+    pub fn set_wire_capabilities(&mut self, wire_id: WireId, caps: Capabilities) {
+        self.handshakes.insert(wire_id, caps);
+    }
+
+    /// Discard any handshake state and queued responses for a wire that is closing.
+    pub fn forget_wire(&mut self, wire_id: WireId) {
+        self.handshakes.remove(&wire_id);
+        self.queues.borrow_mut().remove(wire_id);
+    }
+
+    pub fn client_capabilities(&self, cid: ClientId) -> Option<Capabilities> {
+        self.clients.get(cid).map(|c| c.capabilities())
     }
 
     pub fn remove_client(&mut self, cid: ClientId) {
+        if let Some(wire_id) = self.client_to_wire(cid) {
+            self.queues.borrow_mut().remove(wire_id);
+        }
         self.clients.remove(cid);
     }
 
@@ -229,6 +338,7 @@ impl Messages {
             Request::RemoveClient(wire_id) => {
                 // Let the caller decide when to actually remove the client.
                 let opt_cid = self.clients.wire_to_client(wire_id);
+                self.forget_wire(wire_id);
                 Some(Event::Control(ControlEvent::CloseWire(wire_id, opt_cid)))
             },
             Request::ReplCommand(cookie, cmd) =>
@@ -251,6 +361,19 @@ impl Messages {
                 self.send_raw(wire_id, Response::Pong(cookie, now.to_local()));
                 None
             },
+            Request::Hello(version, caps) => {
+                if version != PROTOCOL_VERSION {
+                    return Some(Event::Wire(wire_id, WireEvent::IncompatibleVersion(version)));
+                }
+                // Ignore any capabilities this server doesn't know about.
+                let caps = Capabilities::from_bits_truncate(caps);
+                self.handshakes.insert(wire_id, caps);
+                self.send_raw(wire_id, Response::HelloResult(PROTOCOL_VERSION, caps.bits()));
+                None
+            },
+            Request::Login(..) |
+            Request::Register(..) if !self.handshakes.contains_key(&wire_id) =>
+                Some(Event::Wire(wire_id, WireEvent::IncompatibleVersion(0))),
             Request::Login(name, secret) =>
                 Some(Event::Wire(wire_id, WireEvent::Login(name, secret))),
             Request::Register(name, secret, appearance) =>
@@ -320,6 +443,11 @@ impl Messages {
                 Ok(Some(ClientEvent::UseAbility(time, item_id, None)))
             },
 
+            Request::UseAction(time, action_id) => {
+                let time = cmp::max(time.to_global(now), now);
+                Ok(Some(ClientEvent::UseAction(time, action_id, None)))
+            },
+
 
             Request::InteractWithArgs(time, args) => {
                 let time = cmp::max(time.to_global(now), now);
@@ -336,6 +464,11 @@ impl Messages {
                 Ok(Some(ClientEvent::UseAbility(time, item_id, Some(args))))
             },
 
+            Request::UseActionWithArgs(time, action_id, args) => {
+                let time = cmp::max(time.to_global(now), now);
+                Ok(Some(ClientEvent::UseAction(time, action_id, Some(args))))
+            },
+
 
             _ => fail!("bad request: {:?}", req),
         }
@@ -344,10 +477,34 @@ impl Messages {
 
     // Response sending
 
+    /// Send `msg` as-is.  This does no capability check, so callers must only pass responses from
+    /// the base protocol, or ones `send_client` has already checked.
     fn send_raw(&self, wire_id: WireId, msg: Response) {
         self.send.send((wire_id, msg)).unwrap();
     }
 
+    /// Pass queued client responses on to the output thread, as far as each client's send budget
+    /// allows.  If some responses had to wait, returns the time when sending can resume.
+    pub fn flush(&self) -> Option<Time> {
+        let now = self.world_now();
+        self.queues.borrow_mut().flush(now, |wire_id, msg| self.send_raw(wire_id, msg))
+    }
+
+    /// Pass on every queued client response, regardless of budget.
+    pub fn flush_all(&self) {
+        self.queues.borrow_mut().flush_all(|wire_id, msg| self.send_raw(wire_id, msg));
+    }
+
+    /// Take the list of wires whose send queues overflowed since the last call.  Nothing more is
+    /// queued for those wires, so the caller should disconnect them.
+    pub fn take_overflowed_wires(&self) -> Vec<WireId> {
+        self.queues.borrow_mut().take_overflowed()
+    }
+
+    pub fn queue_stats(&self) -> QueueStats {
+        self.queues.borrow().stats()
+    }
+
     pub fn send_control(&self, resp: ControlResponse) {
         match resp {
             ControlResponse::WireClosed(wire_id) =>
@@ -376,125 +533,155 @@ impl Messages {
         };
         let wire_id = client.wire_id();
 
-        match resp {
-            ClientResponse::Init(opt_eid, time, cycle_base, cycle_ms) => {
-                let data = InitData {
-                    entity_id: opt_eid.unwrap_or(EntityId(-1_i32 as u32)),
-                    now: time.to_local(),
-                    cycle_base: cycle_base,
-                    cycle_ms: cycle_ms,
-                };
-                self.send_raw(wire_id, Response::Init(data));
+        if let Some(cap) = resp.required_capability() {
+            if !client.capabilities().contains(cap) {
+                return;
+            }
+        }
+        let delivery = resp.delivery();
+
+        let msg = encode_response(client, resp);
+        match delivery {
+            Delivery::Queue(prio) => self.queues.borrow_mut().push(wire_id, prio, msg),
+            Delivery::Flush => {
+                self.queues.borrow_mut().flush_wire(wire_id, |w, m| self.send_raw(w, m));
+                self.send_raw(wire_id, msg);
             },
-
-            ClientResponse::TerrainChunk(cpos, data) => {
-                let index = client.local_chunk_index(cpos);
-                self.send_raw(wire_id, Response::TerrainChunk(index, data));
+            Delivery::Discard => {
+                self.queues.borrow_mut().remove(wire_id);
+                self.send_raw(wire_id, msg);
             },
+        }
+    }
 
-            ClientResponse::UnloadChunk(cpos) => {
-                let index = client.local_chunk_index(cpos);
-                self.send_raw(wire_id, Response::UnloadChunk(index));
-            },
+    pub fn broadcast_clients(&self, resp: ClientResponse) {
+        for (&cid, _) in self.clients.iter() {
+            self.send_client(cid, resp.clone());
+        }
+    }
+}
 
+/// Convert `resp` to its wire format for `client`, whose chunk offset determines the local
+/// coordinates.
+fn encode_response(client: &ClientInfo, resp: ClientResponse) -> Response {
+    match resp {
+        ClientResponse::Init(opt_eid, time, cycle_base, cycle_ms) => {
+            let eid = opt_eid.unwrap_or(EntityId(-1_i32 as u32));
+            Response::Init(eid, time.to_local(), cycle_base, cycle_ms)
+        },
 
-            ClientResponse::EntityAppear(eid, appear, name) =>
-                self.send_raw(wire_id, Response::EntityAppear(eid, appear, name)),
+        ClientResponse::TerrainChunk(cpos, data) => {
+            let index = client.local_chunk_index(cpos);
+            Response::TerrainChunk(index, data)
+        },
 
-            ClientResponse::EntityUpdate(eid, motion, anim) => {
-                let wire_motion = client.local_motion(motion);
-                self.send_raw(wire_id, Response::EntityUpdate(eid, wire_motion, anim));
-            },
+        ClientResponse::TerrainBlocksUpdate(cpos, blocks) => {
+            let index = client.local_chunk_index(cpos);
+            Response::TerrainBlocksUpdate(index, blocks)
+        },
 
-            ClientResponse::EntityGone(eid, time) => {
-                let time = time.to_local();
-                self.send_raw(wire_id, Response::EntityGone(eid, time));
-            },
+        ClientResponse::UnloadChunk(cpos) => {
+            let index = client.local_chunk_index(cpos);
+            Response::UnloadChunk(index)
+        },
 
 
-            ClientResponse::StructureAppear(sid, template_id, pos) => {
-                let local_pos = client.local_pos_tuple(pos * scalar(TILE_SIZE));
-                self.send_raw(wire_id, Response::StructureAppear(sid, template_id, local_pos));
-            },
+        ClientResponse::EntityAppear(eid, appear, name, groups) =>
+            Response::EntityAppear(eid, appear, name, groups),
 
-            ClientResponse::StructureGone(sid) => {
-                self.send_raw(wire_id, Response::StructureGone(sid));
-            },
+        ClientResponse::EntityUpdate(eid, motion, anim) => {
+            let wire_motion = client.local_motion(motion);
+            Response::EntityUpdate(eid, wire_motion, anim)
+        },
 
-            ClientResponse::StructureReplace(sid, template_id) => {
-                self.send_raw(wire_id, Response::StructureReplace(sid, template_id));
-            },
+        ClientResponse::EntityGone(eid, time) => {
+            let time = time.to_local();
+            Response::EntityGone(eid, time)
+        },
 
+        ClientResponse::EntityHealth(eid, hp, max) =>
+            Response::EntityHealth(eid, hp, max),
 
-            ClientResponse::InventoryAppear(iid, ref all_items) => {
-                let all_slot_data = all_items.iter().map(|&x| encode_item(x)).collect();
-                self.send_raw(wire_id, Response::InventoryAppear(iid, all_slot_data));
-            },
+        ClientResponse::EntityDamage(eid, amount) =>
+            Response::EntityDamage(eid, amount),
 
-            ClientResponse::InventoryGone(iid) => {
-                self.send_raw(wire_id, Response::InventoryGone(iid));
-            },
 
-            ClientResponse::InventoryUpdate(iid, slot_idx, item) => {
-                let slot_data = encode_item(item);
-                self.send_raw(wire_id, Response::InventoryUpdate(iid, slot_idx, slot_data));
-            },
+        ClientResponse::StructureAppear(sid, template_id, pos) => {
+            let local_pos = client.local_pos_tuple(pos * scalar(TILE_SIZE));
+            Response::StructureAppear(sid, template_id, local_pos)
+        },
 
+        ClientResponse::StructureGone(sid) =>
+            Response::StructureGone(sid),
 
-            ClientResponse::PlaneFlags(flags) =>
-                self.send_raw(wire_id, Response::PlaneFlags(flags)),
+        ClientResponse::StructureReplace(sid, template_id) =>
+            Response::StructureReplace(sid, template_id),
 
-            ClientResponse::SyncStatus(kind) => {
-                let arg = match kind {
-                    SyncKind::Loading => 0,
-                    SyncKind::Ok => 1,
-                    SyncKind::Reset => 2,
-                    SyncKind::Refresh => 3,
-                };
-                self.send_raw(wire_id, Response::SyncStatus(arg))
-            },
 
+        ClientResponse::InventoryAppear(iid, ref all_items) => {
+            let all_slot_data = all_items.iter().map(|&x| encode_item(x)).collect();
+            Response::InventoryAppear(iid, all_slot_data)
+        },
 
-            ClientResponse::GetInteractArgs(dialog_id, parts) =>
-                self.send_raw(wire_id, Response::GetInteractArgs(dialog_id, parts)),
+        ClientResponse::InventoryGone(iid) =>
+            Response::InventoryGone(iid),
 
-            ClientResponse::GetUseItemArgs(item_id, dialog_id, parts) =>
-                self.send_raw(wire_id, Response::GetUseItemArgs(item_id, dialog_id, parts)),
+        ClientResponse::InventoryUpdate(iid, slot_idx, item) => {
+            let slot_data = encode_item(item);
+            Response::InventoryUpdate(iid, slot_idx, slot_data)
+        },
 
-            ClientResponse::GetUseAbilityArgs(item_id, dialog_id, parts) =>
-                self.send_raw(wire_id, Response::GetUseAbilityArgs(item_id, dialog_id, parts)),
 
+        ClientResponse::PlaneFlags(flags) =>
+            Response::PlaneFlags(flags),
 
-            ClientResponse::OpenDialog(dialog) => {
-                match dialog {
-                    Dialog::Inventory(iid) => 
-                        self.send_raw(wire_id, Response::OpenDialog(0, vec![iid.unwrap()])),
-                    Dialog::Container(iid1, iid2) => 
-                        self.send_raw(wire_id, Response::OpenDialog(1, vec![iid1.unwrap(),
-                                                                            iid2.unwrap()])),
-                    Dialog::Crafting(template_id, sid, iid) =>
-                        self.send_raw(wire_id, Response::OpenCrafting(template_id, sid, iid)),
-                }
-            },
+        ClientResponse::SyncStatus(kind) => {
+            let arg = match kind {
+                SyncKind::Loading => 0,
+                SyncKind::Ok => 1,
+                SyncKind::Reset => 2,
+                SyncKind::Refresh => 3,
+            };
+            Response::SyncStatus(arg)
+        },
 
-            ClientResponse::MainInventory(iid) =>
-                self.send_raw(wire_id, Response::MainInventory(iid)),
 
-            ClientResponse::AbilityInventory(iid) =>
-                self.send_raw(wire_id, Response::AbilityInventory(iid)),
+        ClientResponse::GetInteractArgs(dialog_id, parts) =>
+            Response::GetInteractArgs(dialog_id, parts),
 
-            ClientResponse::ChatUpdate(msg) =>
-                self.send_raw(wire_id, Response::ChatUpdate(msg)),
+        ClientResponse::GetUseItemArgs(item_id, dialog_id, parts) =>
+            Response::GetUseItemArgs(item_id, dialog_id, parts),
 
-            ClientResponse::KickReason(msg) =>
-                self.send_raw(wire_id, Response::KickReason(msg)),
-        }
-    }
+        ClientResponse::GetUseAbilityArgs(item_id, dialog_id, parts) =>
+            Response::GetUseAbilityArgs(item_id, dialog_id, parts),
 
-    pub fn broadcast_clients(&self, resp: ClientResponse) {
-        for (&cid, _) in self.clients.iter() {
-            self.send_client(cid, resp.clone());
-        }
+        ClientResponse::GetUseActionArgs(action_id, dialog_id, parts) =>
+            Response::GetUseActionArgs(action_id, dialog_id, parts),
+
+
+        ClientResponse::OpenDialog(dialog) => {
+            match dialog {
+                Dialog::Inventory(iid) => 
+                    Response::OpenDialog(0, vec![iid.unwrap()]),
+                Dialog::Container(iid1, iid2) => 
+                    Response::OpenDialog(1, vec![iid1.unwrap(),
+                                                 iid2.unwrap()]),
+                Dialog::Crafting(template_id, sid, iid) =>
+                    Response::OpenCrafting(template_id, sid, iid),
+            }
+        },
+
+        ClientResponse::MainInventory(iid) =>
+            Response::MainInventory(iid),
+
+        ClientResponse::AbilityInventory(iid) =>
+            Response::AbilityInventory(iid),
+
+        ClientResponse::ChatUpdate(msg) =>
+            Response::ChatUpdate(msg),
+
+        ClientResponse::KickReason(msg) =>
+            Response::KickReason(msg),
     }
 }
 
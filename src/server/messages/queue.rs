@@ -0,0 +1,212 @@
+//! Per-wire outbound queues.  Responses for logged-in clients wait here until `flush` hands them to
+//! the output thread.  Each wire gets a byte budget per tick, and within that budget high-priority
+//! messages go out first, so a client catching up on a pile of terrain still gets timely entity
+//! updates.  A wire whose queue grows past `MAX_QUEUED_BYTES` is reported as overflowed instead of
+//! being allowed to buffer without limit.
+//!
+//! Messages keep their order within a priority class, but not across classes.  Messages that
+//! refer to each other (such as `StructureAppear` and `StructureReplace`) must share a class.
+use std::cmp;
+use std::collections::{HashMap, VecDeque};
+use std::mem;
+
+use types::*;
+
+use msg::Response;
+
+
+/// Length of a send tick, in milliseconds.
+pub const TICK_MS: Time = 50;
+/// Bytes each wire may send per tick.  A wire always sends at least one message per tick, even if
+/// that message alone is over budget.
+pub const TICK_BUDGET: usize = 8 * 1024;
+/// A wire with more than this many bytes waiting is too far behind to ever catch up.
+pub const MAX_QUEUED_BYTES: usize = 2 * 1024 * 1024;
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Priority {
+    /// Anything the player is actively waiting on: entity motion, chat, dialogs, inventories.
+    High,
+    /// Bulk world data: terrain and structures.
+    Low,
+}
+
+/// Counters describing how far behind clients are.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct QueueStats {
+    /// Bytes currently waiting, across all wires.
+    pub queued_bytes: usize,
+    /// The most bytes that have ever been waiting for a single wire.
+    pub peak_queued_bytes: usize,
+    /// Total bytes handed to the output thread.
+    pub sent_bytes: u64,
+    /// Number of ticks in which a wire used up its budget with messages still waiting, summed over
+    /// all wires.
+    pub throttled_ticks: u64,
+    /// Number of wires that went over `MAX_QUEUED_BYTES`.
+    pub overflows: u64,
+}
+
+
+struct Queue {
+    high: VecDeque<(Response, usize)>,
+    low: VecDeque<(Response, usize)>,
+    queued_bytes: usize,
+    /// Start of the tick that `sent_bytes` counts toward.
+    tick: Time,
+    sent_bytes: usize,
+    /// Set once the wire has run out of budget during the current tick.
+    throttled: bool,
+    overflowed: bool,
+}
+
+impl Queue {
+    fn new() -> Queue {
+        Queue {
+            high: VecDeque::new(),
+            low: VecDeque::new(),
+            queued_bytes: 0,
+            tick: TIME_MIN,
+            sent_bytes: 0,
+            throttled: false,
+            overflowed: false,
+        }
+    }
+
+    fn next_size(&self) -> Option<usize> {
+        self.high.front().or(self.low.front()).map(|&(_, size)| size)
+    }
+
+    fn pop(&mut self) -> Option<(Response, usize)> {
+        let result = self.high.pop_front().or_else(|| self.low.pop_front());
+        if let Some(&(_, size)) = result.as_ref() {
+            self.queued_bytes -= size;
+        }
+        result
+    }
+
+    fn clear(&mut self) {
+        self.high.clear();
+        self.low.clear();
+        self.queued_bytes = 0;
+    }
+}
+
+
+pub struct SendQueues {
+    queues: HashMap<WireId, Queue>,
+    overflowed: Vec<WireId>,
+    stats: QueueStats,
+}
+
+impl SendQueues {
+    pub fn new() -> SendQueues {
+        SendQueues {
+            queues: HashMap::new(),
+            overflowed: Vec::new(),
+            stats: QueueStats::default(),
+        }
+    }
+
+    /// Queue `msg` for `wire_id`.  Messages for a wire that has already overflowed are dropped.
+    pub fn push(&mut self, wire_id: WireId, prio: Priority, msg: Response) {
+        let size = msg.wire_size();
+        let q = self.queues.entry(wire_id).or_insert_with(Queue::new);
+        if q.overflowed {
+            return;
+        }
+
+        match prio {
+            Priority::High => q.high.push_back((msg, size)),
+            Priority::Low => q.low.push_back((msg, size)),
+        }
+        q.queued_bytes += size;
+        self.stats.queued_bytes += size;
+        self.stats.peak_queued_bytes = cmp::max(self.stats.peak_queued_bytes, q.queued_bytes);
+
+        if q.queued_bytes > MAX_QUEUED_BYTES {
+            warn!("send queue for {:?} overflowed ({} bytes)", wire_id, q.queued_bytes);
+            self.stats.queued_bytes -= q.queued_bytes;
+            self.stats.overflows += 1;
+            q.clear();
+            q.overflowed = true;
+            self.overflowed.push(wire_id);
+        }
+    }
+
+    /// Pass queued messages to `send`, as far as each wire's budget for the tick containing `now`
+    /// allows.  If any messages are still waiting, returns the start of the next tick.
+    pub fn flush<F>(&mut self, now: Time, mut send: F) -> Option<Time>
+            where F: FnMut(WireId, Response) {
+        let tick = now - now % TICK_MS;
+        let mut waiting = false;
+
+        for (&wire_id, q) in self.queues.iter_mut() {
+            if q.tick != tick {
+                q.tick = tick;
+                q.sent_bytes = 0;
+                q.throttled = false;
+            }
+
+            while let Some(size) = q.next_size() {
+                if q.sent_bytes > 0 && q.sent_bytes + size > TICK_BUDGET {
+                    if !q.throttled {
+                        q.throttled = true;
+                        self.stats.throttled_ticks += 1;
+                    }
+                    waiting = true;
+                    break;
+                }
+                let (msg, _) = q.pop().unwrap();
+                q.sent_bytes += size;
+                self.stats.queued_bytes -= size;
+                self.stats.sent_bytes += size as u64;
+                send(wire_id, msg);
+            }
+        }
+
+        if waiting { Some(tick + TICK_MS) } else { None }
+    }
+
+    /// Pass every queued message to `send`, ignoring the budget.
+    pub fn flush_all<F>(&mut self, mut send: F)
+            where F: FnMut(WireId, Response) {
+        for (&wire_id, q) in self.queues.iter_mut() {
+            drain(wire_id, q, &mut self.stats, &mut send);
+        }
+    }
+
+    /// Pass every message queued for `wire_id` to `send`, ignoring the budget.
+    pub fn flush_wire<F>(&mut self, wire_id: WireId, mut send: F)
+            where F: FnMut(WireId, Response) {
+        if let Some(q) = self.queues.get_mut(&wire_id) {
+            drain(wire_id, q, &mut self.stats, &mut send);
+        }
+    }
+
+    /// Discard the queue for a wire that is going away.
+    pub fn remove(&mut self, wire_id: WireId) {
+        if let Some(q) = self.queues.remove(&wire_id) {
+            self.stats.queued_bytes -= q.queued_bytes;
+        }
+    }
+
+    /// Take the list of wires that have overflowed since the last call.
+    pub fn take_overflowed(&mut self) -> Vec<WireId> {
+        mem::replace(&mut self.overflowed, Vec::new())
+    }
+
+    pub fn stats(&self) -> QueueStats {
+        self.stats
+    }
+}
+
+fn drain<F>(wire_id: WireId, q: &mut Queue, stats: &mut QueueStats, send: &mut F)
+        where F: FnMut(WireId, Response) {
+    while let Some((msg, size)) = q.pop() {
+        stats.queued_bytes -= size;
+        stats.sent_bytes += size as u64;
+        send(wire_id, msg);
+    }
+}
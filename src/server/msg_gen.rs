@@ -0,0 +1,821 @@
+// Generated by src/gen/gen_protocol.py from src/gen/protocol.json.  Do not edit.
+//
+// Included by msg.rs.
+
+
+/// The protocol version spoken by this server.  Clients must send a matching version in their
+/// `Hello` before they can log in.  Bump this whenever an existing message changes format.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+// Optional protocol features.  A client lists the ones it understands in its `Hello`, and the
+// server only sends the corresponding messages to clients that asked for them.  New capabilities
+// can be added without bumping `PROTOCOL_VERSION`.
+bitflags! {
+    pub flags Capabilities: u32 {
+        // EntityHealth and EntityDamage
+        const CAP_ENTITY_HEALTH = 0x0001,
+        // GetUseActionArgs
+        const CAP_USE_ACTION = 0x0002,
+        // TerrainBlocksUpdate
+        const CAP_TERRAIN_DELTA = 0x0004,
+    }
+}
+
+
+#[allow(non_upper_case_globals, dead_code)]
+mod op {
+    use super::Opcode;
+
+    // Requests
+    pub const Ping: Opcode = Opcode(0x0003);
+    pub const Input: Opcode = Opcode(0x0004);
+    pub const Login: Opcode = Opcode(0x0005);
+    pub const UnsubscribeInventory: Opcode = Opcode(0x0007);
+    pub const CraftRecipe: Opcode = Opcode(0x0009);
+    pub const Chat: Opcode = Opcode(0x000a);
+    pub const Register: Opcode = Opcode(0x000b);
+    pub const Interact: Opcode = Opcode(0x000c);
+    pub const UseItem: Opcode = Opcode(0x000d);
+    pub const UseAbility: Opcode = Opcode(0x000e);
+    pub const InteractWithArgs: Opcode = Opcode(0x0010);
+    pub const UseItemWithArgs: Opcode = Opcode(0x0011);
+    pub const UseAbilityWithArgs: Opcode = Opcode(0x0012);
+    pub const MoveItem: Opcode = Opcode(0x0013);
+    pub const UseAction: Opcode = Opcode(0x0014);
+    pub const UseActionWithArgs: Opcode = Opcode(0x0015);
+    pub const Hello: Opcode = Opcode(0x0016);
+
+    // Deprecated requests
+    pub const GetTerrain: Opcode = Opcode(0x0001);
+    pub const UpdateMotion: Opcode = Opcode(0x0002);
+    pub const Action: Opcode = Opcode(0x0006);
+    pub const old_MoveItem: Opcode = Opcode(0x0008);
+    pub const OpenInventory: Opcode = Opcode(0x000f);
+
+    // Responses
+    pub const TerrainChunk: Opcode = Opcode(0x8001);
+    pub const Pong: Opcode = Opcode(0x8003);
+    pub const EntityUpdate: Opcode = Opcode(0x8004);
+    pub const Init: Opcode = Opcode(0x8005);
+    pub const KickReason: Opcode = Opcode(0x8006);
+    pub const UnloadChunk: Opcode = Opcode(0x8007);
+    pub const OpenDialog: Opcode = Opcode(0x8008);
+    pub const OpenCrafting: Opcode = Opcode(0x800a);
+    pub const ChatUpdate: Opcode = Opcode(0x800b);
+    pub const EntityAppear: Opcode = Opcode(0x800c);
+    pub const EntityGone: Opcode = Opcode(0x800d);
+    pub const RegisterResult: Opcode = Opcode(0x800e);
+    pub const StructureAppear: Opcode = Opcode(0x800f);
+    pub const StructureGone: Opcode = Opcode(0x8010);
+    pub const MainInventory: Opcode = Opcode(0x8011);
+    pub const AbilityInventory: Opcode = Opcode(0x8012);
+    pub const PlaneFlags: Opcode = Opcode(0x8013);
+    pub const GetInteractArgs: Opcode = Opcode(0x8014);
+    pub const GetUseItemArgs: Opcode = Opcode(0x8015);
+    pub const GetUseAbilityArgs: Opcode = Opcode(0x8016);
+    pub const SyncStatus: Opcode = Opcode(0x8017);
+    pub const StructureReplace: Opcode = Opcode(0x8018);
+    pub const InventoryUpdate: Opcode = Opcode(0x8019);
+    pub const InventoryAppear: Opcode = Opcode(0x801a);
+    pub const InventoryGone: Opcode = Opcode(0x801b);
+    pub const EntityHealth: Opcode = Opcode(0x801c);
+    pub const EntityDamage: Opcode = Opcode(0x801d);
+    pub const GetUseActionArgs: Opcode = Opcode(0x801e);
+    pub const HelloResult: Opcode = Opcode(0x801f);
+    pub const TerrainBlocksUpdate: Opcode = Opcode(0x8020);
+
+    // Deprecated responses
+    pub const PlayerMotion: Opcode = Opcode(0x8002);
+    pub const old_InventoryUpdate: Opcode = Opcode(0x8009);
+
+    // Control messages
+    pub const AddClient: Opcode = Opcode(0xff00);
+    pub const RemoveClient: Opcode = Opcode(0xff01);
+    pub const ClientRemoved: Opcode = Opcode(0xff02);
+    pub const ReplCommand: Opcode = Opcode(0xff03);
+    pub const ReplResult: Opcode = Opcode(0xff04);
+    pub const Shutdown: Opcode = Opcode(0xff05);
+    pub const RestartServer: Opcode = Opcode(0xff06);
+    pub const RestartClient: Opcode = Opcode(0xff07);
+    pub const RestartBoth: Opcode = Opcode(0xff08);
+}
+
+
+#[derive(Debug, Clone)]
+pub enum ExtraArg {
+    Int(i32),
+    Str(String),
+    List(Vec<ExtraArg>),
+    Map(HashMap<SimpleArg, ExtraArg>),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum ArgTag {
+    Int = 0,
+    Str = 1,
+    List = 2,
+    Map = 3,
+}
+
+impl wire::ReadFrom for ArgTag {
+    fn read_from<R: Read>(r: &mut WireReader<R>) -> io::Result<ArgTag> {
+        let tag = match try!(r.read::<u8>()) {
+            0 => ArgTag::Int,
+            1 => ArgTag::Str,
+            2 => ArgTag::List,
+            3 => ArgTag::Map,
+            x => return Err(io::Error::new(io::ErrorKind::Other,
+                                           format!("bad ArgTag variant: {}", x))),
+        };
+        Ok(tag)
+    }
+}
+
+impl wire::WriteTo for ArgTag {
+    fn write_to<W: Write>(&self, w: &mut WireWriter<W>) -> io::Result<()> {
+        w.write(*self as u8)
+    }
+
+    fn size(&self) -> usize { 1 }
+
+    fn size_is_fixed() -> bool { true }
+}
+
+impl wire::ReadFrom for ExtraArg {
+    fn read_from<R: Read>(r: &mut WireReader<R>) -> io::Result<ExtraArg> {
+        let x = match try!(r.read()) {
+            ArgTag::Int => ExtraArg::Int(try!(r.read())),
+            ArgTag::Str => ExtraArg::Str(try!(r.read())),
+            ArgTag::List => ExtraArg::List(try!(r.read())),
+            ArgTag::Map => ExtraArg::Map(try!(r.read())),
+        };
+        Ok(x)
+    }
+}
+
+impl wire::WriteTo for ExtraArg {
+    fn write_to<W: Write>(&self, w: &mut WireWriter<W>) -> io::Result<()> {
+        match *self {
+            ExtraArg::Int(ref x) => w.write((ArgTag::Int, x)),
+            ExtraArg::Str(ref x) => w.write((ArgTag::Str, x)),
+            ExtraArg::List(ref x) => w.write((ArgTag::List, x)),
+            ExtraArg::Map(ref x) => w.write((ArgTag::Map, x)),
+        }
+    }
+
+    fn size(&self) -> usize {
+        let inner_size = match *self {
+            ExtraArg::Int(ref x) => wire::WriteTo::size(x),
+            ExtraArg::Str(ref x) => wire::WriteTo::size(x),
+            ExtraArg::List(ref x) => wire::WriteTo::size(x),
+            ExtraArg::Map(ref x) => wire::WriteTo::size(x),
+        };
+        1 + inner_size
+    }
+
+    fn size_is_fixed() -> bool { false }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct Motion {
+    pub start_pos: (u16, u16, u16),
+    pub start_time: LocalTime,
+    pub end_pos: (u16, u16, u16),
+    pub end_time: LocalTime,
+}
+
+impl wire::ReadFrom for Motion {
+    fn read_from<R: Read>(r: &mut WireReader<R>) -> io::Result<Motion> {
+        Ok(Motion {
+            start_pos: try!(r.read()),
+            start_time: try!(r.read()),
+            end_pos: try!(r.read()),
+            end_time: try!(r.read()),
+        })
+    }
+}
+
+impl wire::WriteTo for Motion {
+    fn write_to<W: Write>(&self, w: &mut WireWriter<W>) -> io::Result<()> {
+        try!(w.write(&self.start_pos));
+        try!(w.write(&self.start_time));
+        try!(w.write(&self.end_pos));
+        try!(w.write(&self.end_time));
+        Ok(())
+    }
+
+    fn size(&self) -> usize {
+        wire::WriteTo::size(&self.start_pos) +
+        wire::WriteTo::size(&self.start_time) +
+        wire::WriteTo::size(&self.end_pos) +
+        wire::WriteTo::size(&self.end_time)
+    }
+
+    fn size_is_fixed() -> bool {
+        <(u16, u16, u16) as wire::WriteTo>::size_is_fixed() &&
+        <LocalTime as wire::WriteTo>::size_is_fixed() &&
+        <(u16, u16, u16) as wire::WriteTo>::size_is_fixed() &&
+        <LocalTime as wire::WriteTo>::size_is_fixed()
+    }
+}
+
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum Request {
+    // Ordinary requests
+    Ping(u16),
+    Input(LocalTime, u16),
+    Login(String, [u32; 4]),
+    UnsubscribeInventory(InventoryId),
+    CraftRecipe(StructureId, InventoryId, RecipeId, u16),
+    Chat(String),
+    Register(String, [u32; 4], u32),
+    Interact(LocalTime),
+    UseItem(LocalTime, ItemId),
+    UseAbility(LocalTime, ItemId),
+    InteractWithArgs(LocalTime, ExtraArg),
+    UseItemWithArgs(LocalTime, ItemId, ExtraArg),
+    UseAbilityWithArgs(LocalTime, ItemId, ExtraArg),
+    MoveItem(InventoryId, SlotId, InventoryId, SlotId, u8),
+    UseAction(LocalTime, ActionId),
+    UseActionWithArgs(LocalTime, ActionId, ExtraArg),
+    Hello(u32, u32),
+
+    // Control messages
+    AddClient(WireId),
+    RemoveClient(WireId),
+    ReplCommand(u16, String),
+    Shutdown,
+    Restart(bool, bool),
+
+    // Server-internal messages
+    BadMessage(Opcode),
+}
+
+impl Request {
+    pub fn read_from<R: Read>(wr: &mut WireReader<R>) -> io::Result<(WireId, Request)> {
+        let id = try!(wr.read_header());
+        let opcode = Opcode(try!(wr.read()));
+
+        let req = match opcode {
+            op::Ping => {
+                let cookie: u16 = try!(wr.read());
+                Ping(cookie)
+            },
+            op::Input => {
+                let (time, input): (LocalTime, u16) = try!(wr.read());
+                Input(time, input)
+            },
+            op::Login => {
+                let (secret, name): ([u32; 4], String) = try!(wr.read());
+                Login(name, secret)
+            },
+            op::UnsubscribeInventory => {
+                let inventory_id: InventoryId = try!(wr.read());
+                UnsubscribeInventory(inventory_id)
+            },
+            op::CraftRecipe => {
+                let (station_id, inventory_id, recipe_id, count): (StructureId, InventoryId, RecipeId, u16) = try!(wr.read());
+                CraftRecipe(station_id, inventory_id, recipe_id, count)
+            },
+            op::Chat => {
+                let text: String = try!(wr.read());
+                Chat(text)
+            },
+            op::Register => {
+                let (secret, appearance, name): ([u32; 4], u32, String) = try!(wr.read());
+                Register(name, secret, appearance)
+            },
+            op::Interact => {
+                let time: LocalTime = try!(wr.read());
+                Interact(time)
+            },
+            op::UseItem => {
+                let (time, item_id): (LocalTime, ItemId) = try!(wr.read());
+                UseItem(time, item_id)
+            },
+            op::UseAbility => {
+                let (time, item_id): (LocalTime, ItemId) = try!(wr.read());
+                UseAbility(time, item_id)
+            },
+            op::InteractWithArgs => {
+                let (time, args): (LocalTime, ExtraArg) = try!(wr.read());
+                InteractWithArgs(time, args)
+            },
+            op::UseItemWithArgs => {
+                let (time, item_id, args): (LocalTime, ItemId, ExtraArg) = try!(wr.read());
+                UseItemWithArgs(time, item_id, args)
+            },
+            op::UseAbilityWithArgs => {
+                let (time, item_id, args): (LocalTime, ItemId, ExtraArg) = try!(wr.read());
+                UseAbilityWithArgs(time, item_id, args)
+            },
+            op::MoveItem => {
+                let (from_inventory, from_slot, to_inventory, to_slot, count): (InventoryId, SlotId, InventoryId, SlotId, u8) = try!(wr.read());
+                MoveItem(from_inventory, from_slot, to_inventory, to_slot, count)
+            },
+            op::UseAction => {
+                let (time, action_id): (LocalTime, ActionId) = try!(wr.read());
+                UseAction(time, action_id)
+            },
+            op::UseActionWithArgs => {
+                let (time, action_id, args): (LocalTime, ActionId, ExtraArg) = try!(wr.read());
+                UseActionWithArgs(time, action_id, args)
+            },
+            op::Hello => {
+                let (version, caps): (u32, u32) = try!(wr.read());
+                Hello(version, caps)
+            },
+
+            op::AddClient => {
+                let wire_id: WireId = try!(wr.read());
+                AddClient(wire_id)
+            },
+            op::RemoveClient => {
+                let wire_id: WireId = try!(wr.read());
+                RemoveClient(wire_id)
+            },
+            op::ReplCommand => {
+                let (cookie, command): (u16, String) = try!(wr.read());
+                ReplCommand(cookie, command)
+            },
+            op::Shutdown => {
+                Shutdown
+            },
+            op::RestartServer => {
+                Restart(true, false)
+            },
+            op::RestartClient => {
+                Restart(false, true)
+            },
+            op::RestartBoth => {
+                Restart(true, true)
+            },
+            _ => BadMessage(opcode),
+        };
+
+        if !wr.done() {
+            Ok((id, BadMessage(opcode)))
+        } else {
+            Ok((id, req))
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn write_to<W: Write>(&self, id: WireId, ww: &mut WireWriter<W>) -> io::Result<()> {
+        try!(match *self {
+            Ping(ref cookie) =>
+                ww.write_msg(id, (op::Ping, cookie)),
+            Input(ref time, ref input) =>
+                ww.write_msg(id, (op::Input, time, input)),
+            Login(ref name, ref secret) =>
+                ww.write_msg(id, (op::Login, secret, name)),
+            UnsubscribeInventory(ref inventory_id) =>
+                ww.write_msg(id, (op::UnsubscribeInventory, inventory_id)),
+            CraftRecipe(ref station_id, ref inventory_id, ref recipe_id, ref count) =>
+                ww.write_msg(id, (op::CraftRecipe, station_id, inventory_id, recipe_id, count)),
+            Chat(ref text) =>
+                ww.write_msg(id, (op::Chat, text)),
+            Register(ref name, ref secret, ref appearance) =>
+                ww.write_msg(id, (op::Register, secret, appearance, name)),
+            Interact(ref time) =>
+                ww.write_msg(id, (op::Interact, time)),
+            UseItem(ref time, ref item_id) =>
+                ww.write_msg(id, (op::UseItem, time, item_id)),
+            UseAbility(ref time, ref item_id) =>
+                ww.write_msg(id, (op::UseAbility, time, item_id)),
+            InteractWithArgs(ref time, ref args) =>
+                ww.write_msg(id, (op::InteractWithArgs, time, args)),
+            UseItemWithArgs(ref time, ref item_id, ref args) =>
+                ww.write_msg(id, (op::UseItemWithArgs, time, item_id, args)),
+            UseAbilityWithArgs(ref time, ref item_id, ref args) =>
+                ww.write_msg(id, (op::UseAbilityWithArgs, time, item_id, args)),
+            MoveItem(ref from_inventory, ref from_slot, ref to_inventory, ref to_slot, ref count) =>
+                ww.write_msg(id, (op::MoveItem, from_inventory, from_slot, to_inventory, to_slot, count)),
+            UseAction(ref time, ref action_id) =>
+                ww.write_msg(id, (op::UseAction, time, action_id)),
+            UseActionWithArgs(ref time, ref action_id, ref args) =>
+                ww.write_msg(id, (op::UseActionWithArgs, time, action_id, args)),
+            Hello(ref version, ref caps) =>
+                ww.write_msg(id, (op::Hello, version, caps)),
+            AddClient(ref wire_id) =>
+                ww.write_msg(id, (op::AddClient, wire_id)),
+            RemoveClient(ref wire_id) =>
+                ww.write_msg(id, (op::RemoveClient, wire_id)),
+            ReplCommand(ref cookie, ref command) =>
+                ww.write_msg(id, (op::ReplCommand, cookie, command)),
+            Shutdown =>
+                ww.write_msg(id, op::Shutdown),
+            Restart(true, false) =>
+                ww.write_msg(id, op::RestartServer),
+            Restart(false, true) =>
+                ww.write_msg(id, op::RestartClient),
+            Restart(true, true) =>
+                ww.write_msg(id, op::RestartBoth),
+            _ => Err(io::Error::new(io::ErrorKind::Other,
+                                    format!("can't write {:?} to the wire", self))),
+        });
+        ww.flush()
+    }
+}
+
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum Response {
+    TerrainChunk(u16, Vec<u16>),
+    Pong(u16, LocalTime),
+    EntityUpdate(EntityId, Motion, u16),
+    Init(EntityId, LocalTime, u32, u32),
+    KickReason(String),
+    UnloadChunk(u16),
+    OpenDialog(u32, Vec<u32>),
+    OpenCrafting(TemplateId, StructureId, InventoryId),
+    ChatUpdate(String),
+    EntityAppear(EntityId, u32, String, u8),
+    EntityGone(EntityId, LocalTime),
+    RegisterResult(u32, String),
+    StructureAppear(StructureId, TemplateId, (u16, u16, u16)),
+    StructureGone(StructureId),
+    MainInventory(InventoryId),
+    AbilityInventory(InventoryId),
+    PlaneFlags(u32),
+    GetInteractArgs(u32, ExtraArg),
+    GetUseItemArgs(ItemId, u32, ExtraArg),
+    GetUseAbilityArgs(ItemId, u32, ExtraArg),
+    SyncStatus(u8),
+    StructureReplace(StructureId, TemplateId),
+    InventoryUpdate(InventoryId, u8, (u8, u8, ItemId)),
+    InventoryAppear(InventoryId, Vec<(u8, u8, ItemId)>),
+    InventoryGone(InventoryId),
+    EntityHealth(EntityId, u16, u16),
+    EntityDamage(EntityId, u16),
+    GetUseActionArgs(ActionId, u32, ExtraArg),
+    HelloResult(u32, u32),
+    TerrainBlocksUpdate(u16, Vec<(u16, BlockId)>),
+
+    ClientRemoved(WireId),
+    ReplResult(u16, String),
+}
+
+impl Response {
+    #[allow(dead_code)]
+    pub fn read_from<R: Read>(wr: &mut WireReader<R>) -> io::Result<(WireId, Response)> {
+        let id = try!(wr.read_header());
+        let opcode = Opcode(try!(wr.read()));
+
+        let resp = match opcode {
+            op::TerrainChunk => {
+                let (chunk_idx, data): (u16, Vec<u16>) = try!(wr.read());
+                TerrainChunk(chunk_idx, data)
+            },
+            op::Pong => {
+                let (cookie, server_time): (u16, LocalTime) = try!(wr.read());
+                Pong(cookie, server_time)
+            },
+            op::EntityUpdate => {
+                let (entity_id, motion, anim): (EntityId, Motion, u16) = try!(wr.read());
+                EntityUpdate(entity_id, motion, anim)
+            },
+            op::Init => {
+                let (entity_id, now, cycle_base, cycle_ms): (EntityId, LocalTime, u32, u32) = try!(wr.read());
+                Init(entity_id, now, cycle_base, cycle_ms)
+            },
+            op::KickReason => {
+                let msg: String = try!(wr.read());
+                KickReason(msg)
+            },
+            op::UnloadChunk => {
+                let chunk_idx: u16 = try!(wr.read());
+                UnloadChunk(chunk_idx)
+            },
+            op::OpenDialog => {
+                let (dialog_id, params): (u32, Vec<u32>) = try!(wr.read());
+                OpenDialog(dialog_id, params)
+            },
+            op::OpenCrafting => {
+                let (station_type, station_id, inventory_id): (TemplateId, StructureId, InventoryId) = try!(wr.read());
+                OpenCrafting(station_type, station_id, inventory_id)
+            },
+            op::ChatUpdate => {
+                let msg: String = try!(wr.read());
+                ChatUpdate(msg)
+            },
+            op::EntityAppear => {
+                let (entity_id, appearance, collision_groups, name): (EntityId, u32, u8, String) = try!(wr.read());
+                EntityAppear(entity_id, appearance, name, collision_groups)
+            },
+            op::EntityGone => {
+                let (entity_id, time): (EntityId, LocalTime) = try!(wr.read());
+                EntityGone(entity_id, time)
+            },
+            op::RegisterResult => {
+                let (code, msg): (u32, String) = try!(wr.read());
+                RegisterResult(code, msg)
+            },
+            op::StructureAppear => {
+                let (structure_id, template_id, pos): (StructureId, TemplateId, (u16, u16, u16)) = try!(wr.read());
+                StructureAppear(structure_id, template_id, pos)
+            },
+            op::StructureGone => {
+                let structure_id: StructureId = try!(wr.read());
+                StructureGone(structure_id)
+            },
+            op::MainInventory => {
+                let inventory_id: InventoryId = try!(wr.read());
+                MainInventory(inventory_id)
+            },
+            op::AbilityInventory => {
+                let inventory_id: InventoryId = try!(wr.read());
+                AbilityInventory(inventory_id)
+            },
+            op::PlaneFlags => {
+                let flags: u32 = try!(wr.read());
+                PlaneFlags(flags)
+            },
+            op::GetInteractArgs => {
+                let (dialog_id, args): (u32, ExtraArg) = try!(wr.read());
+                GetInteractArgs(dialog_id, args)
+            },
+            op::GetUseItemArgs => {
+                let (item_id, dialog_id, args): (ItemId, u32, ExtraArg) = try!(wr.read());
+                GetUseItemArgs(item_id, dialog_id, args)
+            },
+            op::GetUseAbilityArgs => {
+                let (item_id, dialog_id, args): (ItemId, u32, ExtraArg) = try!(wr.read());
+                GetUseAbilityArgs(item_id, dialog_id, args)
+            },
+            op::SyncStatus => {
+                let kind: u8 = try!(wr.read());
+                SyncStatus(kind)
+            },
+            op::StructureReplace => {
+                let (structure_id, template_id): (StructureId, TemplateId) = try!(wr.read());
+                StructureReplace(structure_id, template_id)
+            },
+            op::InventoryUpdate => {
+                let (inventory_id, slot_idx, slot): (InventoryId, u8, (u8, u8, ItemId)) = try!(wr.read());
+                InventoryUpdate(inventory_id, slot_idx, slot)
+            },
+            op::InventoryAppear => {
+                let (inventory_id, slots): (InventoryId, Vec<(u8, u8, ItemId)>) = try!(wr.read());
+                InventoryAppear(inventory_id, slots)
+            },
+            op::InventoryGone => {
+                let inventory_id: InventoryId = try!(wr.read());
+                InventoryGone(inventory_id)
+            },
+            op::EntityHealth => {
+                let (entity_id, hp, max): (EntityId, u16, u16) = try!(wr.read());
+                EntityHealth(entity_id, hp, max)
+            },
+            op::EntityDamage => {
+                let (entity_id, amount): (EntityId, u16) = try!(wr.read());
+                EntityDamage(entity_id, amount)
+            },
+            op::GetUseActionArgs => {
+                let (action_id, dialog_id, args): (ActionId, u32, ExtraArg) = try!(wr.read());
+                GetUseActionArgs(action_id, dialog_id, args)
+            },
+            op::HelloResult => {
+                let (version, caps): (u32, u32) = try!(wr.read());
+                HelloResult(version, caps)
+            },
+            op::TerrainBlocksUpdate => {
+                let (chunk_idx, blocks): (u16, Vec<(u16, BlockId)>) = try!(wr.read());
+                TerrainBlocksUpdate(chunk_idx, blocks)
+            },
+
+            op::ClientRemoved => {
+                let wire_id: WireId = try!(wr.read());
+                ClientRemoved(wire_id)
+            },
+            op::ReplResult => {
+                let (cookie, msg): (u16, String) = try!(wr.read());
+                ReplResult(cookie, msg)
+            },
+            _ => return Err(io::Error::new(io::ErrorKind::Other,
+                                           format!("bad opcode: {:?}", opcode))),
+        };
+
+        if !wr.done() {
+            Err(io::Error::new(io::ErrorKind::Other,
+                               format!("extra bytes after message: {:?}", opcode)))
+        } else {
+            Ok((id, resp))
+        }
+    }
+
+    pub fn write_to<W: Write>(&self, id: WireId, ww: &mut WireWriter<W>) -> io::Result<()> {
+        try!(match *self {
+            TerrainChunk(ref chunk_idx, ref data) =>
+                ww.write_msg(id, (op::TerrainChunk, chunk_idx, data)),
+            Pong(ref cookie, ref server_time) =>
+                ww.write_msg(id, (op::Pong, cookie, server_time)),
+            EntityUpdate(ref entity_id, ref motion, ref anim) =>
+                ww.write_msg(id, (op::EntityUpdate, entity_id, motion, anim)),
+            Init(ref entity_id, ref now, ref cycle_base, ref cycle_ms) =>
+                ww.write_msg(id, (op::Init, entity_id, now, cycle_base, cycle_ms)),
+            KickReason(ref msg) =>
+                ww.write_msg(id, (op::KickReason, msg)),
+            UnloadChunk(ref chunk_idx) =>
+                ww.write_msg(id, (op::UnloadChunk, chunk_idx)),
+            OpenDialog(ref dialog_id, ref params) =>
+                ww.write_msg(id, (op::OpenDialog, dialog_id, params)),
+            OpenCrafting(ref station_type, ref station_id, ref inventory_id) =>
+                ww.write_msg(id, (op::OpenCrafting, station_type, station_id, inventory_id)),
+            ChatUpdate(ref msg) =>
+                ww.write_msg(id, (op::ChatUpdate, msg)),
+            EntityAppear(ref entity_id, ref appearance, ref name, ref collision_groups) =>
+                ww.write_msg(id, (op::EntityAppear, entity_id, appearance, collision_groups, name)),
+            EntityGone(ref entity_id, ref time) =>
+                ww.write_msg(id, (op::EntityGone, entity_id, time)),
+            RegisterResult(ref code, ref msg) =>
+                ww.write_msg(id, (op::RegisterResult, code, msg)),
+            StructureAppear(ref structure_id, ref template_id, ref pos) =>
+                ww.write_msg(id, (op::StructureAppear, structure_id, template_id, pos)),
+            StructureGone(ref structure_id) =>
+                ww.write_msg(id, (op::StructureGone, structure_id)),
+            MainInventory(ref inventory_id) =>
+                ww.write_msg(id, (op::MainInventory, inventory_id)),
+            AbilityInventory(ref inventory_id) =>
+                ww.write_msg(id, (op::AbilityInventory, inventory_id)),
+            PlaneFlags(ref flags) =>
+                ww.write_msg(id, (op::PlaneFlags, flags)),
+            GetInteractArgs(ref dialog_id, ref args) =>
+                ww.write_msg(id, (op::GetInteractArgs, dialog_id, args)),
+            GetUseItemArgs(ref item_id, ref dialog_id, ref args) =>
+                ww.write_msg(id, (op::GetUseItemArgs, item_id, dialog_id, args)),
+            GetUseAbilityArgs(ref item_id, ref dialog_id, ref args) =>
+                ww.write_msg(id, (op::GetUseAbilityArgs, item_id, dialog_id, args)),
+            SyncStatus(ref kind) =>
+                ww.write_msg(id, (op::SyncStatus, kind)),
+            StructureReplace(ref structure_id, ref template_id) =>
+                ww.write_msg(id, (op::StructureReplace, structure_id, template_id)),
+            InventoryUpdate(ref inventory_id, ref slot_idx, ref slot) =>
+                ww.write_msg(id, (op::InventoryUpdate, inventory_id, slot_idx, slot)),
+            InventoryAppear(ref inventory_id, ref slots) =>
+                ww.write_msg(id, (op::InventoryAppear, inventory_id, slots)),
+            InventoryGone(ref inventory_id) =>
+                ww.write_msg(id, (op::InventoryGone, inventory_id)),
+            EntityHealth(ref entity_id, ref hp, ref max) =>
+                ww.write_msg(id, (op::EntityHealth, entity_id, hp, max)),
+            EntityDamage(ref entity_id, ref amount) =>
+                ww.write_msg(id, (op::EntityDamage, entity_id, amount)),
+            GetUseActionArgs(ref action_id, ref dialog_id, ref args) =>
+                ww.write_msg(id, (op::GetUseActionArgs, action_id, dialog_id, args)),
+            HelloResult(ref version, ref caps) =>
+                ww.write_msg(id, (op::HelloResult, version, caps)),
+            TerrainBlocksUpdate(ref chunk_idx, ref blocks) =>
+                ww.write_msg(id, (op::TerrainBlocksUpdate, chunk_idx, blocks)),
+            ClientRemoved(ref wire_id) =>
+                ww.write_msg(id, (op::ClientRemoved, wire_id)),
+            ReplResult(ref cookie, ref msg) =>
+                ww.write_msg(id, (op::ReplResult, cookie, msg)),
+        });
+        ww.flush()
+    }
+
+    /// The number of bytes `write_to` will produce, including the message header.
+    pub fn wire_size(&self) -> usize {
+        4 + match *self {
+            TerrainChunk(ref chunk_idx, ref data) =>
+                wire::WriteTo::size(&(op::TerrainChunk, chunk_idx, data)),
+            Pong(ref cookie, ref server_time) =>
+                wire::WriteTo::size(&(op::Pong, cookie, server_time)),
+            EntityUpdate(ref entity_id, ref motion, ref anim) =>
+                wire::WriteTo::size(&(op::EntityUpdate, entity_id, motion, anim)),
+            Init(ref entity_id, ref now, ref cycle_base, ref cycle_ms) =>
+                wire::WriteTo::size(&(op::Init, entity_id, now, cycle_base, cycle_ms)),
+            KickReason(ref msg) =>
+                wire::WriteTo::size(&(op::KickReason, msg)),
+            UnloadChunk(ref chunk_idx) =>
+                wire::WriteTo::size(&(op::UnloadChunk, chunk_idx)),
+            OpenDialog(ref dialog_id, ref params) =>
+                wire::WriteTo::size(&(op::OpenDialog, dialog_id, params)),
+            OpenCrafting(ref station_type, ref station_id, ref inventory_id) =>
+                wire::WriteTo::size(&(op::OpenCrafting, station_type, station_id, inventory_id)),
+            ChatUpdate(ref msg) =>
+                wire::WriteTo::size(&(op::ChatUpdate, msg)),
+            EntityAppear(ref entity_id, ref appearance, ref name, ref collision_groups) =>
+                wire::WriteTo::size(&(op::EntityAppear, entity_id, appearance, collision_groups, name)),
+            EntityGone(ref entity_id, ref time) =>
+                wire::WriteTo::size(&(op::EntityGone, entity_id, time)),
+            RegisterResult(ref code, ref msg) =>
+                wire::WriteTo::size(&(op::RegisterResult, code, msg)),
+            StructureAppear(ref structure_id, ref template_id, ref pos) =>
+                wire::WriteTo::size(&(op::StructureAppear, structure_id, template_id, pos)),
+            StructureGone(ref structure_id) =>
+                wire::WriteTo::size(&(op::StructureGone, structure_id)),
+            MainInventory(ref inventory_id) =>
+                wire::WriteTo::size(&(op::MainInventory, inventory_id)),
+            AbilityInventory(ref inventory_id) =>
+                wire::WriteTo::size(&(op::AbilityInventory, inventory_id)),
+            PlaneFlags(ref flags) =>
+                wire::WriteTo::size(&(op::PlaneFlags, flags)),
+            GetInteractArgs(ref dialog_id, ref args) =>
+                wire::WriteTo::size(&(op::GetInteractArgs, dialog_id, args)),
+            GetUseItemArgs(ref item_id, ref dialog_id, ref args) =>
+                wire::WriteTo::size(&(op::GetUseItemArgs, item_id, dialog_id, args)),
+            GetUseAbilityArgs(ref item_id, ref dialog_id, ref args) =>
+                wire::WriteTo::size(&(op::GetUseAbilityArgs, item_id, dialog_id, args)),
+            SyncStatus(ref kind) =>
+                wire::WriteTo::size(&(op::SyncStatus, kind)),
+            StructureReplace(ref structure_id, ref template_id) =>
+                wire::WriteTo::size(&(op::StructureReplace, structure_id, template_id)),
+            InventoryUpdate(ref inventory_id, ref slot_idx, ref slot) =>
+                wire::WriteTo::size(&(op::InventoryUpdate, inventory_id, slot_idx, slot)),
+            InventoryAppear(ref inventory_id, ref slots) =>
+                wire::WriteTo::size(&(op::InventoryAppear, inventory_id, slots)),
+            InventoryGone(ref inventory_id) =>
+                wire::WriteTo::size(&(op::InventoryGone, inventory_id)),
+            EntityHealth(ref entity_id, ref hp, ref max) =>
+                wire::WriteTo::size(&(op::EntityHealth, entity_id, hp, max)),
+            EntityDamage(ref entity_id, ref amount) =>
+                wire::WriteTo::size(&(op::EntityDamage, entity_id, amount)),
+            GetUseActionArgs(ref action_id, ref dialog_id, ref args) =>
+                wire::WriteTo::size(&(op::GetUseActionArgs, action_id, dialog_id, args)),
+            HelloResult(ref version, ref caps) =>
+                wire::WriteTo::size(&(op::HelloResult, version, caps)),
+            TerrainBlocksUpdate(ref chunk_idx, ref blocks) =>
+                wire::WriteTo::size(&(op::TerrainBlocksUpdate, chunk_idx, blocks)),
+            ClientRemoved(ref wire_id) =>
+                wire::WriteTo::size(&(op::ClientRemoved, wire_id)),
+            ReplResult(ref cookie, ref msg) =>
+                wire::WriteTo::size(&(op::ReplResult, cookie, msg)),
+        }
+    }
+}
+
+
+/// One value of each request, for round-trip tests.
+#[cfg(test)]
+pub fn sample_requests() -> Vec<Request> {
+    vec![
+        Request::Ping(1),
+        Request::Input(1, 2),
+        Request::Login("str1".to_owned(), [2, 3, 4, 5]),
+        Request::UnsubscribeInventory(InventoryId(1)),
+        Request::CraftRecipe(StructureId(1), InventoryId(2), 3, 4),
+        Request::Chat("str1".to_owned()),
+        Request::Register("str1".to_owned(), [2, 3, 4, 5], 6),
+        Request::Interact(1),
+        Request::UseItem(1, 2),
+        Request::UseAbility(1, 2),
+        Request::InteractWithArgs(1, ExtraArg::List(vec![ExtraArg::Int(-2), ExtraArg::Str("str3".to_owned()), ExtraArg::Map({ let mut m = HashMap::new(); m.insert(SimpleArg::Str("key".to_owned()), ExtraArg::Int(-4)); m })])),
+        Request::UseItemWithArgs(1, 2, ExtraArg::List(vec![ExtraArg::Int(-3), ExtraArg::Str("str4".to_owned()), ExtraArg::Map({ let mut m = HashMap::new(); m.insert(SimpleArg::Str("key".to_owned()), ExtraArg::Int(-5)); m })])),
+        Request::UseAbilityWithArgs(1, 2, ExtraArg::List(vec![ExtraArg::Int(-3), ExtraArg::Str("str4".to_owned()), ExtraArg::Map({ let mut m = HashMap::new(); m.insert(SimpleArg::Str("key".to_owned()), ExtraArg::Int(-5)); m })])),
+        Request::MoveItem(InventoryId(1), 2, InventoryId(3), 4, 5),
+        Request::UseAction(1, 2),
+        Request::UseActionWithArgs(1, 2, ExtraArg::List(vec![ExtraArg::Int(-3), ExtraArg::Str("str4".to_owned()), ExtraArg::Map({ let mut m = HashMap::new(); m.insert(SimpleArg::Str("key".to_owned()), ExtraArg::Int(-5)); m })])),
+        Request::Hello(1, 2),
+        Request::AddClient(WireId(1)),
+        Request::RemoveClient(WireId(1)),
+        Request::ReplCommand(1, "str2".to_owned()),
+        Request::Shutdown,
+        Request::Restart(true, false),
+        Request::Restart(false, true),
+        Request::Restart(true, true),
+    ]
+}
+
+/// One value of each response, for round-trip tests.
+#[cfg(test)]
+pub fn sample_responses() -> Vec<Response> {
+    vec![
+        Response::TerrainChunk(1, vec![2, 3]),
+        Response::Pong(1, 2),
+        Response::EntityUpdate(EntityId(1), Motion { start_pos: (2, 3, 4), start_time: 5, end_pos: (6, 7, 8), end_time: 9 }, 10),
+        Response::Init(EntityId(1), 2, 3, 4),
+        Response::KickReason("str1".to_owned()),
+        Response::UnloadChunk(1),
+        Response::OpenDialog(1, vec![2, 3]),
+        Response::OpenCrafting(1, StructureId(2), InventoryId(3)),
+        Response::ChatUpdate("str1".to_owned()),
+        Response::EntityAppear(EntityId(1), 2, "str3".to_owned(), 4),
+        Response::EntityGone(EntityId(1), 2),
+        Response::RegisterResult(1, "str2".to_owned()),
+        Response::StructureAppear(StructureId(1), 2, (3, 4, 5)),
+        Response::StructureGone(StructureId(1)),
+        Response::MainInventory(InventoryId(1)),
+        Response::AbilityInventory(InventoryId(1)),
+        Response::PlaneFlags(1),
+        Response::GetInteractArgs(1, ExtraArg::List(vec![ExtraArg::Int(-2), ExtraArg::Str("str3".to_owned()), ExtraArg::Map({ let mut m = HashMap::new(); m.insert(SimpleArg::Str("key".to_owned()), ExtraArg::Int(-4)); m })])),
+        Response::GetUseItemArgs(1, 2, ExtraArg::List(vec![ExtraArg::Int(-3), ExtraArg::Str("str4".to_owned()), ExtraArg::Map({ let mut m = HashMap::new(); m.insert(SimpleArg::Str("key".to_owned()), ExtraArg::Int(-5)); m })])),
+        Response::GetUseAbilityArgs(1, 2, ExtraArg::List(vec![ExtraArg::Int(-3), ExtraArg::Str("str4".to_owned()), ExtraArg::Map({ let mut m = HashMap::new(); m.insert(SimpleArg::Str("key".to_owned()), ExtraArg::Int(-5)); m })])),
+        Response::SyncStatus(1),
+        Response::StructureReplace(StructureId(1), 2),
+        Response::InventoryUpdate(InventoryId(1), 2, (3, 4, 5)),
+        Response::InventoryAppear(InventoryId(1), vec![(2, 3, 4), (5, 6, 7)]),
+        Response::InventoryGone(InventoryId(1)),
+        Response::EntityHealth(EntityId(1), 2, 3),
+        Response::EntityDamage(EntityId(1), 2),
+        Response::GetUseActionArgs(1, 2, ExtraArg::List(vec![ExtraArg::Int(-3), ExtraArg::Str("str4".to_owned()), ExtraArg::Map({ let mut m = HashMap::new(); m.insert(SimpleArg::Str("key".to_owned()), ExtraArg::Int(-5)); m })])),
+        Response::HelloResult(1, 2),
+        Response::TerrainBlocksUpdate(1, vec![(2, 3), (4, 5)]),
+        Response::ClientRemoved(WireId(1)),
+        Response::ReplResult(1, "str2".to_owned()),
+    ]
+}
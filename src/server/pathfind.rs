@@ -0,0 +1,212 @@
+//! Tile-level A* pathfinding.  Paths are planned for an entity that is one tile wide and two tiles
+//! tall (currently the size of every entity), moving only in the four cardinal directions.  A path
+//! node is the tile containing the bottom of the entity, which must be a floor or a ramp.  Ramps
+//! connect a floor at one level to a floor one level higher, in the ramp's uphill direction.
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use libphysics::ShapeSource;
+use libphysics::TILE_SIZE;
+
+use types::*;
+
+use cache::TerrainCache;
+use physics::ChunksSource;
+
+
+/// Give up after visiting this many nodes.  Keeps a search for an unreachable goal from scanning
+/// every loaded chunk.
+const MAX_VISITED: usize = 4096;
+
+const DIRS: [V2; 4] = [V2 { x: 1, y: 0 },
+                       V2 { x: -1, y: 0 },
+                       V2 { x: 0, y: 1 },
+                       V2 { x: 0, y: -1 }];
+
+
+/// Get the tile where an entity at pixel position `pos` is standing, for use as a path node.
+pub fn entity_tile(pos: V3) -> V3 {
+    // TODO: hardcoded constant based on entity size
+    (pos + V3::new(TILE_SIZE / 2, TILE_SIZE / 2, 0)).div_floor(scalar(TILE_SIZE))
+}
+
+/// Find a path between two tiles on plane `pid`.  Unloaded chunks are treated as empty, so the
+/// path never leaves the loaded area.
+pub fn find_path_in_cache(cache: &TerrainCache,
+                          pid: PlaneId,
+                          start: V3,
+                          goal: V3) -> Option<Vec<V3>> {
+    let source = ChunksSource::new(cache, scalar(0), pid);
+    find_path(&source, start, goal)
+}
+
+/// Find a shortest path from tile `start` to tile `goal`.  The result includes both endpoints.
+pub fn find_path<S: ShapeSource>(s: &S, start: V3, goal: V3) -> Option<Vec<V3>> {
+    if !standable(s, start) || !standable(s, goal) {
+        return None;
+    }
+
+    let mut queue = BinaryHeap::new();
+    // Maps each visited node to its predecessor and its distance from `start`.
+    let mut visited: HashMap<V3, (V3, i32)> = HashMap::new();
+
+    queue.push(Entry { est: estimate(start, goal), cost: 0, pos: start });
+    visited.insert(start, (start, 0));
+
+    while let Some(Entry { cost, pos, .. }) = queue.pop() {
+        if pos == goal {
+            return Some(build_path(&visited, start, goal));
+        }
+        if cost > visited[&pos].1 {
+            // Stale entry.  `pos` was reached by a shorter path after this was enqueued.
+            continue;
+        }
+        if visited.len() >= MAX_VISITED {
+            break;
+        }
+
+        for &dir in &DIRS {
+            let next = unwrap_or!(step(s, pos, dir), continue);
+            let next_cost = cost + 1;
+            if let Some(&(_, old_cost)) = visited.get(&next) {
+                if old_cost <= next_cost {
+                    continue;
+                }
+            }
+            visited.insert(next, (pos, next_cost));
+            queue.push(Entry {
+                est: next_cost + estimate(next, goal),
+                cost: next_cost,
+                pos: next,
+            });
+        }
+    }
+
+    None
+}
+
+fn build_path(visited: &HashMap<V3, (V3, i32)>, start: V3, goal: V3) -> Vec<V3> {
+    let mut path = vec![goal];
+    let mut pos = goal;
+    while pos != start {
+        pos = visited[&pos].0;
+        path.push(pos);
+    }
+    path.reverse();
+    path
+}
+
+/// Lower bound on the cost from `pos` to `goal`.  Only x and y count, since climbing a ramp
+/// changes z without costing an extra step.
+fn estimate(pos: V3, goal: V3) -> i32 {
+    let d = (goal - pos).abs();
+    d.x + d.y
+}
+
+
+#[derive(PartialEq, Eq)]
+struct Entry {
+    est: i32,
+    cost: i32,
+    pos: V3,
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Entry) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Entry) -> Ordering {
+        // Reversed, so that the BinaryHeap acts as a min-heap.  Break ties by position to keep the
+        // search deterministic.
+        match other.est.cmp(&self.est) {
+            Ordering::Equal => other.pos.cmp(&self.pos),
+            o => o,
+        }
+    }
+}
+
+
+fn uphill(shape: Shape) -> Option<V2> {
+    match shape {
+        Shape::RampE => Some(V2::new(1, 0)),
+        Shape::RampW => Some(V2::new(-1, 0)),
+        Shape::RampS => Some(V2::new(0, 1)),
+        Shape::RampN => Some(V2::new(0, -1)),
+        _ => None,
+    }
+}
+
+/// Check if `dir` is perpendicular to the slope of ramp `shape`.
+fn across(shape: Shape, dir: V2) -> bool {
+    match uphill(shape) {
+        Some(u) => u.x * dir.x + u.y * dir.y == 0,
+        None => false,
+    }
+}
+
+/// Check if an entity can stand with its bottom in tile `pos`.
+pub fn standable<S: ShapeSource>(s: &S, pos: V3) -> bool {
+    let shape = s.get_shape(pos);
+    // An entity partway up a ramp sticks up into a third tile.
+    let height =
+        if shape == Shape::Floor { 1 }
+        else if shape.is_ramp() { 2 }
+        else { return false };
+
+    (1 .. height + 1).all(|dz| {
+        match s.get_shape(pos + V3::new(0, 0, dz)) {
+            Shape::Empty | Shape::Floor => true,
+            _ => false,
+        }
+    })
+}
+
+/// Find the node reached by moving one tile from `pos` in direction `dir`, if that move is
+/// possible.  This mirrors the continuity rules in `libphysics::walk`: floors join floors at the
+/// same level and the low end of a ramp, and the high end of a ramp joins a floor one level up.
+/// Ramps can't be entered or left through their sides, except into an identical ramp.
+fn step<S: ShapeSource>(s: &S, pos: V3, dir: V2) -> Option<V3> {
+    let here = s.get_shape(pos);
+    let next = pos + dir.extend(0);
+
+    let dest =
+        if uphill(here) == Some(dir) {
+            // Leaving the high end of a ramp.
+            let up = next + V3::new(0, 0, 1);
+            let shape = s.get_shape(up);
+            if shape == Shape::Floor || uphill(shape) == Some(dir) {
+                up
+            } else {
+                return None;
+            }
+        } else {
+            let level = here == Shape::Floor || uphill(here) == Some(-dir);
+            let shape = s.get_shape(next);
+            match shape {
+                Shape::Floor if level => next,
+                // Entering the low end of a ramp.
+                _ if shape.is_ramp() && here == Shape::Floor && uphill(shape) == Some(dir) => next,
+                // Walking along a ramp, perpendicular to its slope.
+                _ if shape.is_ramp() && shape == here && across(shape, dir) => next,
+                Shape::Empty if level => {
+                    // Stepping down onto the high end of a ramp.
+                    let down = next - V3::new(0, 0, 1);
+                    if uphill(s.get_shape(down)) == Some(-dir) {
+                        down
+                    } else {
+                        return None;
+                    }
+                },
+                _ => return None,
+            }
+        };
+
+    if standable(s, dest) {
+        Some(dest)
+    } else {
+        None
+    }
+}
@@ -1,13 +1,17 @@
 //! Interface to the physics engine.  The physics engine itself lives in a separate library,
 //! `libphysics`, so that it can be compiled to asm.js for use on the client.  This system just
 //! provides the glue to connect the physics engine to entities and the rest of the `World`.
-use libphysics::{self, ShapeSource};
+use std::collections::HashSet;
+use std::f64;
+
+use libphysics::{self, ShapeSource, EntityBox, Momentum, Surface, ENTITY_LOOKAHEAD};
+use libphysics::collision_groups::{self, PAWN, NPC};
 use libphysics::{CHUNK_SIZE, CHUNK_BITS, CHUNK_MASK, TILE_SIZE};
 
 use types::*;
 use util::StrResult;
 
-use cache::TerrainCache;
+use cache::{TerrainCache, CacheEntry};
 use data::Data;
 use world::{self, World};
 use world::{Motion, Activity};
@@ -27,16 +31,30 @@ impl<'d> Physics<'d> {
 }
 
 
-struct ChunksSource<'a> {
+/// Adapter for using the `TerrainCache` as a `ShapeSource`.  Tile coordinates passed to
+/// `get_shape` are offset by `base_tile`.
+pub struct ChunksSource<'a> {
     cache: &'a TerrainCache,
     base_tile: V3,
     plane: PlaneId,
 }
 
-impl<'a> ShapeSource for ChunksSource<'a> {
-    fn get_shape(&self, pos: V3) -> Shape {
+impl<'a> ChunksSource<'a> {
+    pub fn new(cache: &'a TerrainCache, base_tile: V3, plane: PlaneId) -> ChunksSource<'a> {
+        ChunksSource {
+            cache: cache,
+            base_tile: base_tile,
+            plane: plane,
+        }
+    }
+}
+
+impl<'a> ChunksSource<'a> {
+    /// Find the cache entry and index for tile `pos`.  Returns `None` if `pos` is outside the
+    /// world or in an unloaded chunk.
+    fn lookup(&self, pos: V3) -> Option<(&'a CacheEntry, usize)> {
         if pos.z < 0 || pos.z >= CHUNK_SIZE {
-            return Shape::Empty;
+            return None;
         }
 
         let pos = pos + self.base_tile;
@@ -44,13 +62,179 @@ impl<'a> ShapeSource for ChunksSource<'a> {
         let offset = pos & scalar(CHUNK_MASK);
         let cpos = (pos >> CHUNK_BITS).reduce();
 
-        if let Some(entry) = self.cache.get(self.plane, cpos) {
-            let idx = Region::new(scalar(0), scalar(CHUNK_SIZE)).index(offset);
-            entry.shape[idx]
-        } else {
-            return Shape::Empty;
+        let entry = unwrap_or!(self.cache.get(self.plane, cpos), return None);
+        let idx = Region::new(scalar(0), scalar(CHUNK_SIZE)).index(offset);
+        Some((entry, idx))
+    }
+}
+
+impl<'a> ShapeSource for ChunksSource<'a> {
+    fn get_shape(&self, pos: V3) -> Shape {
+        match self.lookup(pos) {
+            Some((entry, idx)) => entry.shape[idx],
+            None => Shape::Empty,
         }
     }
+
+    fn get_surface(&self, pos: V3) -> Surface {
+        match self.lookup(pos) {
+            Some((entry, idx)) => entry.surface[idx],
+            None => Surface::normal(),
+        }
+    }
+}
+
+
+/// Get the direction suffix of the pony animation to use for an entity facing `facing`.
+pub fn anim_dir(facing: V3) -> u8 {
+    let idx = (3 * (facing.x + 1) + (facing.y + 1)) as usize;
+    [2, 2, 2, 3, 0, 1, 0, 0, 0][idx]
+}
+
+/// Get the size of an entity's bounding box.
+// TODO: hardcoded constant based on entity size
+pub fn entity_size() -> V3 {
+    V3::new(32, 32, 64)
+}
+
+/// Get the collision groups of entity `e`.  Clients get these in `EntityAppear`, so they can
+/// apply the same rule during prediction.
+pub fn entity_groups(e: &world::Entity) -> collision_groups::Groups {
+    match e.attachment() {
+        world::EntityAttachment::Client(_) => PAWN,
+        _ => NPC,
+    }
+}
+
+/// Find the entities on `plane` whose current motion passes through a chunk overlapping `area`.
+/// The result can include entities that never actually come near `area`.
+pub fn entities_near(world: &World, plane: PlaneId, area: Region) -> Vec<EntityId> {
+    let chunk_px = CHUNK_SIZE * TILE_SIZE;
+    let mut seen = HashSet::new();
+    let mut result = Vec::new();
+    for cpos in area.reduce().div_round_signed(chunk_px).points() {
+        for e in world.chunk_entities(plane, cpos) {
+            if seen.insert(e.id()) {
+                result.push(e.id());
+            }
+        }
+    }
+    result
+}
+
+/// Collect the bounding boxes of all entities `mover` might run into while moving, relative to
+/// `base_px`.  Each box covers where the entity will be over the next `ENTITY_LOOKAHEAD` ms.  Only
+/// entities within `libphysics::entity_reach` of `start_pos` are included.
+// NB: keep this in sync with libclient/client.rs  entity_boxes()
+fn entity_boxes(world: &World,
+                mover: EntityId,
+                plane: PlaneId,
+                now: Time,
+                start_pos: V3,
+                size: V3,
+                base_px: V3) -> Vec<EntityBox> {
+    let reach = libphysics::entity_reach(start_pos, size);
+    let mut boxes = Vec::new();
+    for id in entities_near(world, plane, reach) {
+        if id == mover {
+            continue;
+        }
+        let other = world.entity(id);
+        let pos = other.pos(now);
+        let ahead = other.pos(now + ENTITY_LOOKAHEAD as Time);
+        let b = EntityBox::swept(pos, ahead, size, entity_groups(&*other));
+        if !reach.overlaps(b.bounds) {
+            continue;
+        }
+        boxes.push(EntityBox::swept(pos - base_px, ahead - base_px, size, b.groups));
+    }
+    boxes
+}
+
+
+/// Find the first period, starting no earlier than `now`, during which two entities following
+/// motions `a` and `b` are within `margin` pixels of touching.  Returns the start and end of that
+/// period.  The end is `None` if the entities stay close once their motions are over.
+///
+/// Collision checks only look `ENTITY_LOOKAHEAD` ms into the other entities' motions, so this is
+/// used to decide when motions computed at different times need to be checked again.
+pub fn approach(a: &Motion,
+                b: &Motion,
+                size: V3,
+                margin: i32,
+                now: Time) -> Option<(Time, Option<Time>)> {
+    // Between these times, both entities move in straight lines (or stand still).
+    let mut times = vec![now, a.end_time(), b.end_time()];
+    times.retain(|&t| t >= now);
+    times.sort();
+    times.dedup();
+
+    let limit = size + scalar(margin);
+    let rel = |t: Time| b.pos(t) - a.pos(t);
+    let mut period_start = None;
+    for i in 0 .. times.len() {
+        let t0 = times[i];
+        let next = times.get(i + 1).cloned();
+        let span = match next {
+            Some(t1) => span_overlap(rel(t0), rel(t1), t0, t1, limit),
+            // Both motions are over, so nothing changes from here on.
+            None if close(rel(t0), limit) => Some((t0 as f64, f64::INFINITY)),
+            None => None,
+        };
+
+        let (s, e) = match span {
+            Some(x) => x,
+            None => {
+                if let Some(start) = period_start {
+                    return Some((start, Some(t0)));
+                }
+                continue;
+            },
+        };
+        let start = match period_start {
+            None => s.floor() as Time,
+            // The entities separated right at the end of the previous span.
+            Some(start) if s > t0 as f64 => return Some((start, Some(t0))),
+            Some(start) => start,
+        };
+        match next {
+            Some(t1) if e < t1 as f64 => return Some((start, Some(e.ceil() as Time))),
+            None => return Some((start, None)),
+            // Still close at the end of this span.
+            Some(_) => period_start = Some(start),
+        }
+    }
+    None
+}
+
+fn close(rel: V3, limit: V3) -> bool {
+    rel.abs().zip(limit, |r, l| (r < l) as i32) == scalar(1)
+}
+
+/// Find the part of the time span `t0 .. t1` during which the offset between two entities, which
+/// changes linearly from `r0` to `r1`, is less than `limit` on every axis.
+fn span_overlap(r0: V3, r1: V3, t0: Time, t1: Time, limit: V3) -> Option<(f64, f64)> {
+    let mut lo = t0 as f64;
+    let mut hi = t1 as f64;
+    let dt = (t1 - t0) as f64;
+    for &(a, b, l) in &[(r0.x, r1.x, limit.x), (r0.y, r1.y, limit.y), (r0.z, r1.z, limit.z)] {
+        if a == b {
+            if a.abs() >= l {
+                return None;
+            }
+            continue;
+        }
+        let (a, b, l) = (a as f64, b as f64, l as f64);
+        let enter = t0 as f64 + (-l - a) / (b - a) * dt;
+        let exit = t0 as f64 + (l - a) / (b - a) * dt;
+        lo = lo.max(enter.min(exit));
+        hi = hi.min(enter.max(exit));
+    }
+    if lo < hi {
+        Some((lo, hi))
+    } else {
+        None
+    }
 }
 
 
@@ -83,41 +267,46 @@ pub trait Fragment<'d> {
     fn update(&mut self, now: Time, eid: EntityId) -> StrResult<()> {
         use world::Fragment;
 
-        let motion = try!(self.with_cache(|_sys, cache, world| -> StrResult<_> {
+        let (motion, swimming) = try!(self.with_cache(|_sys, cache, world| -> StrResult<_> {
             let e = unwrap!(world.get_entity(eid));
 
             match e.activity() {
                 Activity::Move => {},   // Fall through to physics calculation
                 Activity::Special(_, _) => {
                     let pos = e.pos(now);
-                    return Ok(Motion {
+                    return Ok((Motion {
                         start_time: now,
                         duration: DURATION_MAX,
                         start_pos: pos,
                         end_pos: pos,
-                    });
+                    }, false));
                 },
             }
 
             // Run the physics calculation
 
-            // TODO: hardcoded constant based on entity size
             let start_pos = e.pos(now);
             let velocity = e.target_velocity();
-            let size = V3::new(32, 32, 64);
+            let size = entity_size();
 
             let chunk_px = CHUNK_SIZE * TILE_SIZE;
             let base_chunk = start_pos.div_floor(scalar(chunk_px)) - scalar::<V2>(3).extend(0);
             let base_tile = base_chunk * scalar(CHUNK_SIZE);
             let base_px = base_tile * scalar(TILE_SIZE);
 
-            let source = ChunksSource {
-                cache: cache,
-                base_tile: base_tile,
-                plane: e.plane_id(),
+            let source = ChunksSource::new(cache, base_tile, e.plane_id());
+            let entities = entity_boxes(world, eid, e.plane_id(), now,
+                                        start_pos, size, base_px);
+            let mask = collision_groups::mask(entity_groups(&*e));
+            let momentum = {
+                let m = e.motion();
+                Momentum::from_motion(m.start_pos - base_px, m.end_pos - base_px,
+                                      m.duration as i32)
             };
+            let swimming = libphysics::surface_at(&source, start_pos - base_px, size).is_liquid();
             let (mut end_pos, mut dur) =
-                libphysics::collide(&source, start_pos - base_px, size, velocity);
+                libphysics::collide_with_entities(&source, &entities, mask,
+                                                  start_pos - base_px, size, velocity, momentum);
             end_pos = end_pos + base_px;
 
             // NB: keep this in sync with libclient/predict.rs  predict()
@@ -129,12 +318,12 @@ pub trait Fragment<'d> {
                 dur = DURATION_MAX as i32;
             }
 
-            Ok(Motion {
+            Ok((Motion {
                 start_time: now,
                 duration: dur as Duration,
                 start_pos: start_pos,
                 end_pos: end_pos,
-            })
+            }, swimming))
         }));
 
         self.with_world(|wf| {
@@ -142,8 +331,9 @@ pub trait Fragment<'d> {
             let mut e = wf.entity_mut(eid);
 
             // Compute extra information for the entity.
-            let velocity = e.target_velocity();
-            let dir = velocity.signum();
+            // Animations only depend on horizontal movement.
+            let velocity = e.target_velocity().reduce();
+            let dir = velocity.signum().extend(0);
             // TODO: player speed handling shouldn't be here
             let speed = velocity.abs().max() / 50;
 
@@ -156,13 +346,13 @@ pub trait Fragment<'d> {
 
             let anim = match e.activity() {
                 Activity::Move => {
-                    const ANIM_DIR_COUNT: AnimId = 8;
                     static SPEED_NAME_MAP: [&'static str; 4] = ["stand", "walk", "", "run"];
-                    let idx = (3 * (facing.x + 1) + (facing.y + 1)) as usize;
-                    let anim_dir = [2, 2, 2, 3, 0, 1, 0, 0, 0][idx];
-                    let anim_name = format!("pony//{}-{}",
-                                            SPEED_NAME_MAP[speed as usize],
-                                            anim_dir);
+                    let anim_dir = anim_dir(facing);
+                    // Swimming uses the same animation at every speed.
+                    let speed_name =
+                        if swimming { "swim" }
+                        else { SPEED_NAME_MAP[speed as usize] };
+                    let anim_name = format!("pony//{}-{}", speed_name, anim_dir);
                     data.animations.get_id(&anim_name)
                 },
                 Activity::Special(anim, _) => anim,
@@ -181,6 +181,26 @@ define_python_class! {
                               runtime_error, "no sprite layer with that ID");
             Pack::pack(l)
         }
+
+
+        fn action_count(&this) -> usize {
+            this.actions.len()
+        }
+
+        fn action_by_name(&this, name: String) -> PyResult<ActionId> {
+            Ok(pyunwrap!(this.actions.find_id(&name),
+                         key_error, "no such action: {:?}", name))
+        }
+
+        fn get_action_by_name(&this, name: String) -> Option<ActionId> {
+            this.actions.find_id(&name)
+        }
+
+        fn action_name(&this, id: ActionId) -> PyResult<PyBox> {
+            let name = pyunwrap!(this.actions.get_name(id),
+                                 runtime_error, "no action with that ID");
+            Pack::pack(name)
+        }
     }
 }
 
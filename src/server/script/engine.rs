@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::mem;
 use std::ptr;
 use python3_sys::*;
@@ -6,6 +7,7 @@ use libphysics::CHUNK_SIZE;
 
 use types::*;
 
+use auth::Role;
 use engine::Engine;
 use engine::glue;
 use engine::split::{EngineRef, Part, PartFlags};
@@ -22,6 +24,7 @@ use world::object::*;
 
 use super::{Pack, Unpack};
 use super::{types, v3};
+use super::hooks::ChatFilter;
 
 
 pub fn init(module: PyRef) {
@@ -192,6 +195,9 @@ pub fn with_engine_ref<E, F, R>(e: E, f: F) -> R
 engine_part_typedef!(OnlyWorld(world));
 engine_part_typedef!(OnlyMessages(messages));
 engine_part_typedef!(OnlyTimer(timer));
+engine_part_typedef!(OnlyAuth(auth));
+engine_part_typedef!(WorldAuth(world, auth));
+engine_part_typedef!(OnlyChat(chat));
 engine_part_typedef!(EmptyPart());
 
 
@@ -215,6 +221,17 @@ define_python_class! {
             eng.messages().clients_len()
         }
 
+        fn messages_queue_stats(eng: OnlyMessages,) -> HashMap<&'static str, u64> {
+            let stats = eng.messages().queue_stats();
+            let mut h = HashMap::new();
+            h.insert("queued_bytes", stats.queued_bytes as u64);
+            h.insert("peak_queued_bytes", stats.peak_queued_bytes as u64);
+            h.insert("sent_bytes", stats.sent_bytes);
+            h.insert("throttled_ticks", stats.throttled_ticks);
+            h.insert("overflows", stats.overflows);
+            h
+        }
+
         fn messages_client_by_name(eng: OnlyMessages, name: String) -> Option<ClientId> {
             eng.messages().name_to_client(&name)
         }
@@ -254,6 +271,16 @@ define_python_class! {
             eng.messages().send_client(cid, resp);
         }
 
+        fn messages_send_get_use_action_args(eng: OnlyMessages,
+                                             cid: ClientId,
+                                             action: ActionId,
+                                             dialog_id: u32,
+                                             args: ExtraArg) {
+            use messages::ClientResponse;
+            let resp = ClientResponse::GetUseActionArgs(action, dialog_id, args);
+            eng.messages().send_client(cid, resp);
+        }
+
 
         fn logic_set_main_inventories(eng: EngineRef,
                                       cid: ClientId,
@@ -307,6 +334,103 @@ define_python_class! {
         }
 
 
+        fn auth_ban(eng: EngineRef,
+                    name: String,
+                    reason: String,
+                    duration: Option<Time>) -> PyResult<bool> {
+            match logic::client::ban(eng, &name, &reason, duration) {
+                Ok(x) => Ok(x),
+                Err(e) => pyraise!(runtime_error, "auth error: {}", e),
+            }
+        }
+
+        fn auth_unban(eng: OnlyAuth, name: String) -> PyResult<bool> {
+            let mut eng = eng;
+            match eng.auth_mut().unban(&name) {
+                Ok(x) => Ok(x),
+                Err(e) => pyraise!(runtime_error, "auth error: {}", e),
+            }
+        }
+
+        fn auth_ban_info(eng: OnlyAuth,
+                         name: String) -> PyResult<Option<(String, Option<Time>)>> {
+            let mut eng = eng;
+            match eng.auth_mut().ban_info(&name, ::util::now()) {
+                Ok(x) => Ok(x.map(|b| (b.reason, b.expires))),
+                Err(e) => pyraise!(runtime_error, "auth error: {}", e),
+            }
+        }
+
+        fn auth_set_role(eng: EngineRef, name: String, role: u8) -> PyResult<bool> {
+            let role = pyunwrap!(Role::from_primitive(role), value_error, "invalid role");
+            match logic::client::set_role(eng, &name, role) {
+                Ok(x) => Ok(x),
+                Err(e) => pyraise!(runtime_error, "auth error: {}", e),
+            }
+        }
+
+        fn auth_delete_account(eng: EngineRef, name: String) -> PyResult<bool> {
+            match logic::client::delete_account(eng, &name) {
+                Ok(x) => Ok(x),
+                Err(e) => pyraise!(runtime_error, "auth error: {}", e),
+            }
+        }
+
+        fn auth_change_secret(eng: EngineRef,
+                              cid: ClientId,
+                              old_secret: (u32, u32, u32, u32),
+                              new_secret: (u32, u32, u32, u32)) -> PyResult<bool> {
+            let (a, b, c, d) = old_secret;
+            let (e, f, g, h) = new_secret;
+            match logic::client::change_secret(eng, cid, [a, b, c, d], [e, f, g, h]) {
+                Ok(x) => Ok(x),
+                Err(e) => pyraise!(runtime_error, "auth error: {}", e),
+            }
+        }
+
+
+        fn chat_mute(eng: OnlyChat, name: String, duration: Option<Time>) {
+            let mut eng = eng;
+            let until = duration.map(|d| eng.now() + d);
+            eng.chat_mut().mute(&name, until);
+        }
+
+        fn chat_unmute(eng: OnlyChat, name: String) -> bool {
+            let mut eng = eng;
+            eng.chat_mut().unmute(&name)
+        }
+
+        fn chat_mute_status(eng: OnlyChat, name: String) -> Option<Option<Time>> {
+            let mut eng = eng;
+            let now = eng.now();
+            eng.chat_mut().mute_status(now, &name)
+        }
+
+        fn chat_add_filter(eng: OnlyChat, f: PyBox) {
+            let mut eng = eng;
+            eng.chat_mut().add_filter(Box::new(ChatFilter::new(f)));
+        }
+
+
+        fn snapshot_create(eng: EngineRef,) -> PyResult<String> {
+            match logic::snapshot::create(eng) {
+                Ok(x) => Ok(x),
+                Err(e) => pyraise!(runtime_error, "snapshot error: {}", e),
+            }
+        }
+
+        fn snapshot_list(eng: EmptyPart,) -> Vec<String> {
+            logic::snapshot::list(eng.storage())
+        }
+
+        fn snapshot_restore(eng: EngineRef, name: String) -> PyResult<()> {
+            match logic::snapshot::request_restore(eng, &name) {
+                Ok(()) => Ok(()),
+                Err(e) => pyraise!(runtime_error, "snapshot error: {}", e),
+            }
+        }
+
+
         fn timer_schedule(eng: OnlyTimer,
                           when: Time,
                           userdata: PyBox) -> u32 {
@@ -350,6 +474,19 @@ define_python_class! {
             Ok(c.name().to_owned())
         }
 
+        fn world_client_role(eng: WorldAuth, cid: ClientId) -> PyResult<u8> {
+            let mut eng = eng;
+            let name = {
+                let c = pyunwrap!(eng.world().get_client(cid),
+                                  runtime_error, "no client with that ID");
+                c.name().to_owned()
+            };
+            match eng.auth_mut().role(&name) {
+                Ok(r) => Ok(r as u8),
+                Err(e) => pyraise!(runtime_error, "auth error: {}", e),
+            }
+        }
+
         fn world_client_pawn_id(eng: OnlyWorld, cid: ClientId) -> PyResult<Option<EntityId>> {
             let c = pyunwrap!(eng.world().get_client(cid),
                               runtime_error, "no client with that ID");
@@ -414,6 +551,35 @@ define_python_class! {
             Ok(())
         }
 
+        fn world_entity_health(eng: OnlyWorld, eid: EntityId) -> PyResult<(u16, u16)> {
+            let e = pyunwrap!(eng.world().get_entity(eid),
+                              runtime_error, "no entity with that ID");
+            let h = e.health();
+            Ok((h.hp, h.max))
+        }
+
+        fn world_entity_damage(eng: EngineRef,
+                               eid: EntityId,
+                               amount: u16,
+                               attacker: Option<EntityId>) -> PyResult<()> {
+            try!(logic::combat::damage(eng, eid, amount, attacker));
+            Ok(())
+        }
+
+        fn world_entity_heal(eng: EngineRef,
+                             eid: EntityId,
+                             amount: u16) -> PyResult<()> {
+            try!(logic::combat::heal(eng, eid, amount));
+            Ok(())
+        }
+
+        fn world_entity_set_max_health(eng: EngineRef,
+                                       eid: EntityId,
+                                       max: u16) -> PyResult<()> {
+            try!(logic::combat::set_max_health(eng, eid, max));
+            Ok(())
+        }
+
         fn world_entity_controller(eng: OnlyWorld, eid: EntityId) -> PyResult<Option<ClientId>> {
             let e = pyunwrap!(eng.world().get_entity(eid),
                               runtime_error, "no entity with that ID");
@@ -469,6 +635,66 @@ define_python_class! {
             Ok(())
         }
 
+        fn world_entity_walk_to(eng: EngineRef,
+                                eid: EntityId,
+                                pos: V3,
+                                run: bool) -> PyResult<bool> {
+            Ok(try!(logic::walk::start(eng, eid, pos, run)))
+        }
+
+        fn world_entity_walk_cancel(eng: EngineRef,
+                                    eid: EntityId) {
+            logic::walk::cancel(eng, eid)
+        }
+
+
+        fn world_entity_create(eng: glue::WorldFragment,
+                               stable_pid: Stable<PlaneId>,
+                               pos: V3,
+                               appearance: u32) -> PyResult<EntityId> {
+            let mut eng = eng;
+            let anim = eng.world().data().animations.get_id("pony//stand-0");
+            let e = try!(eng.create_entity(stable_pid, pos, anim, appearance));
+            Ok(e.id())
+        }
+
+        fn world_entity_destroy(eng: glue::WorldFragment,
+                                eid: EntityId) -> PyResult<()> {
+            let mut eng = eng;
+            try!(eng.destroy_entity(eid));
+            Ok(())
+        }
+
+        fn world_entity_npc_add(eng: EngineRef,
+                                eid: EntityId,
+                                temperament: u8,
+                                spawned: bool) -> PyResult<()> {
+            let temperament = pyunwrap!(logic::npc::Temperament::from_primitive(temperament),
+                                        value_error, "invalid temperament");
+            try!(logic::npc::add(eng, eid, temperament, spawned));
+            Ok(())
+        }
+
+        fn world_entity_npc_remove(eng: EngineRef,
+                                   eid: EntityId) {
+            logic::npc::remove(eng, eid)
+        }
+
+        fn world_entity_npc_behavior(eng: EngineRef,
+                                     eid: EntityId) -> Option<String> {
+            logic::npc::behavior(eng, eid).map(|b| b.name().to_owned())
+        }
+
+        fn world_entity_npc_set_behavior(eng: EngineRef,
+                                         eid: EntityId,
+                                         name: String,
+                                         target: Option<EntityId>) -> PyResult<()> {
+            let behavior = pyunwrap!(logic::npc::Behavior::from_name(&name, target),
+                                     value_error, "behavior requires a target");
+            try!(logic::npc::set_behavior(eng, eid, behavior));
+            Ok(())
+        }
+
 
         fn world_inventory_create(eng: glue::WorldFragment,
                                   size: u8) -> PyResult<InventoryId> {
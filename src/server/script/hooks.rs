@@ -1,5 +1,9 @@
+use std::collections::HashMap;
+
 use types::*;
 
+use auth::Role;
+use chat;
 use engine::split;
 use msg::ExtraArg;
 use python as py;
@@ -19,12 +23,17 @@ macro_rules! define_script_hooks {
     ($($name:ident,)*) => {
         pub struct ScriptHooks {
             $($name: Option<PyBox>,)*
+
+            /// Minimum role required to run each chat command.  Commands not listed here are
+            /// available to everyone.
+            chat_command_roles: HashMap<String, Role>,
         }
 
         impl ScriptHooks {
             pub fn new() -> ScriptHooks {
                 ScriptHooks {
                     $($name: None,)*
+                    chat_command_roles: HashMap::new(),
                 }
             }
         }
@@ -41,6 +50,17 @@ macro_rules! define_script_hooks {
                         this.$name = Some(f);
                     }
                 )*
+
+                fn set_chat_command_role(&mut this, cmd: String, role: u8) -> PyResult<()> {
+                    let role = pyunwrap!(Role::from_primitive(role),
+                                         value_error, "invalid role");
+                    if role == Role::Player {
+                        this.chat_command_roles.remove(&cmd);
+                    } else {
+                        this.chat_command_roles.insert(cmd, role);
+                    }
+                    Ok(())
+                }
             }
         }
     };
@@ -52,11 +72,20 @@ define_script_hooks!(
 
     timer_fired,
 
+    entity_walk_done,
+    entity_death,
+    entity_respawn,
+
+    npc_spawn,
+    npc_think,
+    npc_attack,
+
     client_login,
     client_chat_command,
     client_interact,
     client_use_item,
     client_use_ability,
+    client_use_action,
 
     structure_import_hook,
     // structure_export_hook,   // unimplemented
@@ -65,6 +94,11 @@ define_script_hooks!(
 );
 
 impl ScriptHooks {
+    /// Get the minimum role required to run chat command `cmd` (without the leading `/`).
+    pub fn chat_command_role(&self, cmd: &str) -> Role {
+        self.chat_command_roles.get(cmd).map_or(Role::Player, |&r| r)
+    }
+
     pub fn call_server_startup(&self, eng: split::EngineRef) -> PyResult<()> {
         call_with_engine0(&self.server_startup, eng)
     }
@@ -89,6 +123,47 @@ impl ScriptHooks {
         call_with_engine1(&self.timer_fired, eng, userdata)
     }
 
+    pub fn call_entity_walk_done(&self,
+                                 eng: split::EngineRef,
+                                 eid: EntityId,
+                                 arrived: bool) -> PyResult<()> {
+        call_with_engine2(&self.entity_walk_done, eng, eid, arrived)
+    }
+
+    pub fn call_entity_death(&self,
+                             eng: split::EngineRef,
+                             eid: EntityId,
+                             attacker: Option<EntityId>) -> PyResult<()> {
+        call_with_engine2(&self.entity_death, eng, eid, attacker)
+    }
+
+    pub fn call_entity_respawn(&self,
+                               eng: split::EngineRef,
+                               eid: EntityId) -> PyResult<()> {
+        call_with_engine1(&self.entity_respawn, eng, eid)
+    }
+
+    pub fn call_npc_spawn(&self,
+                          eng: split::EngineRef,
+                          pid: PlaneId,
+                          pos: V3) -> PyResult<()> {
+        call_with_engine2(&self.npc_spawn, eng, pid, pos)
+    }
+
+    pub fn call_npc_think(&self,
+                          eng: split::EngineRef,
+                          eid: EntityId,
+                          behavior: &str) -> PyResult<()> {
+        call_with_engine2(&self.npc_think, eng, eid, behavior)
+    }
+
+    pub fn call_npc_attack(&self,
+                           eng: split::EngineRef,
+                           eid: EntityId,
+                           target: EntityId) -> PyResult<()> {
+        call_with_engine2(&self.npc_attack, eng, eid, target)
+    }
+
     pub fn call_client_login(&self,
                              eng: split::EngineRef,
                              cid: ClientId) -> PyResult<()> {
@@ -125,6 +200,14 @@ impl ScriptHooks {
         call_with_engine3(&self.client_use_ability, eng, cid, ability, args)
     }
 
+    pub fn call_client_use_action(&self,
+                                  eng: split::EngineRef,
+                                  cid: ClientId,
+                                  action: ActionId,
+                                  args: Option<ExtraArg>) -> PyResult<()> {
+        call_with_engine3(&self.client_use_action, eng, cid, action, args)
+    }
+
 
     pub fn call_structure_import_hook<'d, F>(&self,
                                              f: F,
@@ -150,6 +233,37 @@ unsafe impl RustRefType for ScriptHooks {
 }
 
 
+/// A chat filter registered by a script.  The function is called with the sender's name, the
+/// channel name, and the message text, and returns `(True, new_text)` to let the message through
+/// or `(False, reason)` to drop it.  If the function raises an exception, the message is let
+/// through unchanged.
+pub struct ChatFilter(PyBox);
+
+impl ChatFilter {
+    pub fn new(f: PyBox) -> ChatFilter {
+        ChatFilter(f)
+    }
+}
+
+impl chat::Filter for ChatFilter {
+    fn filter(&mut self,
+              sender: &str,
+              channel: chat::Channel,
+              text: String) -> Result<String, String> {
+        let result = call::<_, (bool, String)>(self.0.borrow(),
+                                               (sender, channel.name(), &text as &str));
+        match result {
+            Ok((true, new_text)) => Ok(new_text),
+            Ok((false, reason)) => Err(reason),
+            Err(e) => {
+                warn!("error in script chat filter: {}", e);
+                Ok(text)
+            },
+        }
+    }
+}
+
+
 // Helper functions
 
 pub fn call<A: Pack, R: for <'a> Unpack<'a>>(f: PyRef, args: A) -> PyResult<R> {
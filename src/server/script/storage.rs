@@ -5,7 +5,7 @@ use python::{PyBox, PyResult};
 use super::rust_ref::{RustRef, RustRefType};
 
 macro_rules! storage_ref_func {
-    ( $($all:tt)* ) => ( rust_ref_func!(Storage, $($all)*); );
+    ( $($all:tt)* ) => ( rust_ref_func!(Box<Storage>, $($all)*); );
 }
 
 define_python_class! {
@@ -21,7 +21,8 @@ define_python_class! {
     }
 }
 
-unsafe impl RustRefType for Storage {
+// `RustRef` stores a thin pointer, so the ref wraps the box rather than the trait object.
+unsafe impl RustRefType for Box<Storage> {
     fn get_type_object() -> PyBox {
         get_type().to_box()
     }
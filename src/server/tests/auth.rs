@@ -0,0 +1,116 @@
+//! Account management in `Auth`: bans and their expiry, roles, deleting accounts, and upgrading
+//! databases written by older servers.
+
+use rusqlite::Connection;
+
+use auth::{Auth, BanInfo, Role};
+use auth::schema::CURRENT_VERSION;
+use logic;
+use msg::{Request, Response};
+
+use super::harness::{self, ScratchDir};
+
+
+#[test]
+fn banned_login_is_kicked_with_reason() {
+    harness::run(|h| {
+        let wire = h.login("Alice");
+        h.take_responses(wire);
+
+        // Banning kicks the client if it's online.
+        assert!(logic::client::ban(h.engine().as_ref(), "Alice", "spamming", None).unwrap());
+        let resps = h.take_responses(wire);
+        assert!(resps.iter().any(|r| match *r { Response::KickReason(_) => true, _ => false }));
+
+        let expect = BanInfo { reason: "spamming".to_owned(), expires: None }.describe();
+        let wire = h.connect();
+        h.send(wire, Request::Login("Alice".to_owned(), [1, 2, 3, 4]));
+        let resps = h.take_responses(wire);
+        assert!(resps.iter().any(|r| match *r {
+            Response::KickReason(ref msg) => *msg == expect,
+            _ => false,
+        }));
+        assert!(h.engine().messages.wire_to_client(wire).is_none());
+
+        // Once the ban is lifted, the same login works.
+        assert!(h.engine().auth.unban("Alice").unwrap());
+        let wire = h.connect();
+        h.send(wire, Request::Login("Alice".to_owned(), [1, 2, 3, 4]));
+        assert!(h.engine().messages.wire_to_client(wire).is_some());
+    });
+}
+
+#[test]
+fn expired_ban_is_cleared() {
+    harness::run(|h| {
+        h.login("Alice");
+        let auth = &mut h.engine().auth;
+        assert!(auth.ban("Alice", "spamming", Some(10_000)).unwrap());
+        assert!(!auth.ban("Nobody", "spamming", None).unwrap());
+
+        let info = auth.ban_info("Alice", 5_000).unwrap().expect("ban should still be active");
+        assert_eq!(info.reason, "spamming");
+        assert_eq!(info.expires, Some(10_000));
+
+        assert!(auth.ban_info("Alice", 10_000).unwrap().is_none());
+        // The expired ban was removed, not just ignored.
+        assert!(auth.ban_info("Alice", 0).unwrap().is_none());
+        assert!(!auth.unban("Alice").unwrap());
+    });
+}
+
+#[test]
+fn roles_and_delete() {
+    harness::run(|h| {
+        h.login("Alice");
+        let auth = &mut h.engine().auth;
+        assert_eq!(auth.role("Alice").unwrap(), Role::Player);
+        assert!(auth.set_role("Alice", Role::Moderator).unwrap());
+        assert_eq!(auth.role("Alice").unwrap(), Role::Moderator);
+        assert!(!auth.set_role("Nobody", Role::Admin).unwrap());
+        assert_eq!(auth.role("Nobody").unwrap(), Role::Player);
+
+        assert!(auth.ban("Alice", "spamming", None).unwrap());
+        assert!(auth.delete("Alice").unwrap());
+        assert!(!auth.exists("Alice").unwrap());
+        assert!(auth.ban_info("Alice", 0).unwrap().is_none());
+        assert_eq!(auth.role("Alice").unwrap(), Role::Player);
+        assert!(!auth.delete("Alice").unwrap());
+    });
+}
+
+#[test]
+fn unversioned_database_is_upgraded() {
+    let dir = ScratchDir::new();
+    let path = dir.0.join("auth.sqlite");
+
+    // Before versioning, the database had only the `auth` table.
+    {
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch("CREATE TABLE auth (
+                                name      TEXT NOT NULL UNIQUE,
+                                secret    TEXT NOT NULL
+                            );
+                            INSERT INTO auth (name, secret) VALUES ('Alice', '0;1;2;3');")
+            .unwrap();
+    }
+
+    {
+        let mut auth = Auth::new(&path).unwrap();
+        assert!(auth.exists("Alice").unwrap());
+        assert!(auth.set_role("Alice", Role::Admin).unwrap());
+        assert!(auth.ban("Alice", "testing", None).unwrap());
+    }
+
+    let conn = Connection::open(&path).unwrap();
+    let mut stmt = conn.prepare("SELECT version FROM schema_version").unwrap();
+    let versions = stmt.query(&[]).unwrap()
+                       .map(|row| row.unwrap().get(0))
+                       .collect::<Vec<i64>>();
+    assert_eq!(versions, vec![CURRENT_VERSION]);
+
+    // Opening it again leaves an up-to-date database as it is.
+    let mut auth = Auth::new(&path).unwrap();
+    assert_eq!(auth.role("Alice").unwrap(), Role::Admin);
+    assert!(auth.ban_info("Alice", 0).unwrap().is_some());
+}
@@ -0,0 +1,223 @@
+//! Rate limiting, mutes, and history in the chat system, including reloading history and mutes
+//! from disk.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use types::*;
+
+use chat::{Chat, Channel, Filter, Message, Rejection};
+use chat::{HISTORY_LEN, RATE_BURST, RATE_REFILL_MS};
+
+use super::harness::ScratchDir;
+
+
+fn paths(dir: &ScratchDir) -> (PathBuf, PathBuf) {
+    (dir.0.join("chat.log"), dir.0.join("chat_mutes.txt"))
+}
+
+fn write_file(path: &Path, contents: &str) {
+    File::create(path).unwrap().write_all(contents.as_bytes()).unwrap();
+}
+
+fn new_chat(dir: &ScratchDir) -> Chat {
+    let (log, mutes) = paths(dir);
+    Chat::new(log, mutes)
+}
+
+fn send(chat: &mut Chat, now: Time, text: &str) -> Result<String, Rejection> {
+    chat.check(now, ClientId(0), "Alice", Channel::Global, text.to_owned())
+}
+
+fn message(time: Time, text: String) -> Message {
+    Message {
+        time: time,
+        channel: Channel::Global,
+        sender: "Alice".to_owned(),
+        target: None,
+        text: text,
+    }
+}
+
+fn history_text(chat: &Chat) -> Vec<String> {
+    chat.history(Channel::Global, HISTORY_LEN).into_iter()
+        .map(|m| m.text.clone())
+        .collect()
+}
+
+
+#[test]
+fn rate_limit_allows_a_burst_then_refills() {
+    let dir = ScratchDir::new();
+    let mut chat = new_chat(&dir);
+
+    for _ in 0 .. RATE_BURST {
+        assert!(send(&mut chat, 0, "hi").is_ok());
+    }
+    match send(&mut chat, 0, "hi") {
+        Err(Rejection::RateLimited) => {},
+        r => panic!("expected RateLimited, got {:?}", r),
+    }
+
+    // One token comes back per refill period.
+    assert!(send(&mut chat, RATE_REFILL_MS - 1, "hi").is_err());
+    assert!(send(&mut chat, RATE_REFILL_MS, "hi").is_ok());
+    assert!(send(&mut chat, RATE_REFILL_MS, "hi").is_err());
+
+    // The bucket never holds more than `RATE_BURST` tokens, however long the client waits.
+    let later = 100 * RATE_REFILL_MS;
+    for _ in 0 .. RATE_BURST {
+        assert!(send(&mut chat, later, "hi").is_ok());
+    }
+    assert!(send(&mut chat, later, "hi").is_err());
+
+    // Each client has its own bucket.
+    assert!(chat.check(later, ClientId(1), "Bob", Channel::Global, "hi".to_owned()).is_ok());
+}
+
+#[test]
+fn rejected_messages_do_not_use_tokens() {
+    let dir = ScratchDir::new();
+    let mut chat = new_chat(&dir);
+
+    for _ in 0 .. 2 * RATE_BURST {
+        assert!(send(&mut chat, 0, "   ").is_err());
+    }
+    for _ in 0 .. RATE_BURST {
+        assert!(send(&mut chat, 0, "hi").is_ok());
+    }
+}
+
+#[test]
+fn mutes_expire() {
+    let dir = ScratchDir::new();
+    let mut chat = new_chat(&dir);
+
+    chat.mute("Alice", Some(1000));
+    match send(&mut chat, 500, "hi") {
+        Err(Rejection::Muted(Some(1000))) => {},
+        r => panic!("expected Muted, got {:?}", r),
+    }
+    assert!(send(&mut chat, 1000, "hi").is_ok());
+    assert_eq!(chat.mute_status(1000, "Alice"), None);
+    assert!(!chat.unmute("Alice"));
+}
+
+#[test]
+fn indefinite_mutes_last_until_unmuted() {
+    let dir = ScratchDir::new();
+    let mut chat = new_chat(&dir);
+
+    chat.mute("Alice", None);
+    assert_eq!(chat.mute_status(1_000_000, "Alice"), Some(None));
+    assert!(send(&mut chat, 1_000_000, "hi").is_err());
+    assert_eq!(chat.mute_status(0, "Bob"), None);
+
+    assert!(chat.unmute("Alice"));
+    assert!(send(&mut chat, 1_000_000, "hi").is_ok());
+}
+
+#[test]
+fn mutes_are_reloaded() {
+    let dir = ScratchDir::new();
+    {
+        let mut chat = new_chat(&dir);
+        chat.mute("Alice", None);
+        chat.mute("Bob", Some(5000));
+        chat.mute("Carol", None);
+        chat.unmute("Carol");
+    }
+
+    let mut chat = new_chat(&dir);
+    assert_eq!(chat.mute_status(0, "Alice"), Some(None));
+    assert_eq!(chat.mute_status(0, "Bob"), Some(Some(5000)));
+    assert_eq!(chat.mute_status(0, "Carol"), None);
+}
+
+#[test]
+fn history_is_bounded() {
+    let dir = ScratchDir::new();
+    let mut chat = new_chat(&dir);
+
+    for i in 0 .. HISTORY_LEN + 10 {
+        chat.record(message(i as Time, i.to_string()));
+    }
+    let text = history_text(&chat);
+    assert_eq!(text.len(), HISTORY_LEN);
+    assert_eq!(text[0], "10");
+    assert_eq!(text[HISTORY_LEN - 1], (HISTORY_LEN + 9).to_string());
+
+    // `count` limits the result to the most recent messages, and other channels are excluded.
+    let recent = chat.history(Channel::Global, 3);
+    assert_eq!(recent.len(), 3);
+    assert_eq!(recent[2].text, (HISTORY_LEN + 9).to_string());
+    assert!(chat.history(Channel::Local, HISTORY_LEN).is_empty());
+}
+
+#[test]
+fn history_is_reloaded_from_log() {
+    let dir = ScratchDir::new();
+    {
+        let mut chat = new_chat(&dir);
+        for i in 0 .. HISTORY_LEN + 10 {
+            chat.record(message(i as Time, format!("message {}", i)));
+        }
+        chat.record(Message {
+            time: 1000,
+            channel: Channel::Whisper,
+            sender: "Alice".to_owned(),
+            target: Some("Bob".to_owned()),
+            text: "psst".to_owned(),
+        });
+    }
+
+    let chat = new_chat(&dir);
+    let text = history_text(&chat);
+    assert_eq!(text.len(), HISTORY_LEN - 1);
+    assert_eq!(text[0], "message 11");
+    assert_eq!(text[HISTORY_LEN - 2], format!("message {}", HISTORY_LEN + 9));
+
+    let whisper = chat.history(Channel::Whisper, 1);
+    assert_eq!(whisper.len(), 1);
+    assert_eq!(whisper[0].time, 1000);
+    assert_eq!(whisper[0].target, Some("Bob".to_owned()));
+    assert_eq!(whisper[0].text, "psst");
+}
+
+#[test]
+fn history_reload_includes_rotated_log_and_skips_bad_lines() {
+    let dir = ScratchDir::new();
+    let (log, _) = paths(&dir);
+    write_file(&dir.0.join("chat.log.1"), "1\tglobal\tAlice\t\tolder\n");
+    write_file(&log, "garbage\n2\tglobal\tBob\t\tnewer\twith a tab\n");
+
+    let chat = new_chat(&dir);
+    assert_eq!(history_text(&chat), vec!["older".to_owned(), "newer\twith a tab".to_owned()]);
+}
+
+
+struct ShoutFilter;
+
+impl Filter for ShoutFilter {
+    fn filter(&mut self, _: &str, _: Channel, text: String) -> Result<String, String> {
+        if text.contains("secret") {
+            Err("no secrets".to_owned())
+        } else {
+            Ok(text.to_uppercase())
+        }
+    }
+}
+
+#[test]
+fn added_filters_run_after_builtin_ones() {
+    let dir = ScratchDir::new();
+    let mut chat = new_chat(&dir);
+    chat.add_filter(Box::new(ShoutFilter));
+
+    assert_eq!(send(&mut chat, 0, "hi\tthere").unwrap(), "HITHERE");
+    match send(&mut chat, 0, "a secret") {
+        Err(Rejection::Filtered(ref r)) if r == "no secrets" => {},
+        r => panic!("expected Filtered, got {:?}", r),
+    }
+}
@@ -0,0 +1,482 @@
+use std::collections::HashSet;
+
+use types::*;
+use libphysics::TILE_SIZE;
+
+use input::INPUT_RIGHT;
+use logic;
+use logic::combat;
+use msg::{Request, Response, PROTOCOL_VERSION, Capabilities, CAP_TERRAIN_DELTA};
+use vision::vision_region;
+use wire::WireWriter;
+use world::Health;
+use world::object::*;
+
+use super::harness;
+
+
+#[test]
+fn register_and_login() {
+    harness::run(|h| {
+        let wire = h.login("Alice");
+
+        let resps = h.take_responses(wire);
+        match resps[0] {
+            Response::RegisterResult(0, _) => {},
+            _ => panic!("expected successful RegisterResult first"),
+        }
+        assert!(resps.iter().any(|r| match *r { Response::Init(_) => true, _ => false }));
+        assert!(resps.iter().any(|r| match *r { Response::SyncStatus(_) => true, _ => false }));
+    });
+}
+
+#[test]
+fn bad_login_is_kicked() {
+    harness::run(|h| {
+        let wire = h.connect();
+        h.send(wire, Request::Login("Nobody".to_owned(), [1, 2, 3, 4]));
+
+        let resps = h.take_responses(wire);
+        assert!(resps.iter().any(|r| match *r { Response::KickReason(_) => true, _ => false }));
+        let closed = h.take_responses(CONTROL_WIRE_ID);
+        assert!(closed.iter().any(|r| match *r {
+            Response::ClientRemoved(w) => w == wire,
+            _ => false,
+        }));
+    });
+}
+
+#[test]
+fn login_without_hello_is_kicked() {
+    harness::run(|h| {
+        let wire = h.connect_raw();
+        h.send(wire, Request::Login("Alice".to_owned(), [1, 2, 3, 4]));
+
+        let resps = h.take_responses(wire);
+        assert!(resps.iter().any(|r| match *r { Response::KickReason(_) => true, _ => false }));
+        assert!(h.engine().messages.wire_to_client(wire).is_none());
+    });
+}
+
+#[test]
+fn login_is_dropped_if_wire_closes_while_hashing() {
+    harness::run(|h| {
+        let wire = h.login("Alice");
+        h.disconnect(wire);
+
+        // The wire closes and a new connection gets the same ID before the worker replies.
+        let wire = h.connect();
+        h.send_no_wait(wire, Request::Login("Alice".to_owned(), [1, 2, 3, 4]));
+        h.send_no_wait(CONTROL_WIRE_ID, Request::RemoveClient(wire));
+        h.send_no_wait(CONTROL_WIRE_ID, Request::AddClient(wire));
+        h.send_no_wait(wire, Request::Hello(PROTOCOL_VERSION, Capabilities::all().bits()));
+        h.wait_for_auth();
+
+        assert!(h.engine().messages.wire_to_client(wire).is_none());
+        assert!(h.engine().world.clients().next().is_none());
+    });
+}
+
+#[test]
+fn second_login_while_hashing_is_kicked() {
+    harness::run(|h| {
+        let wire = h.connect();
+        h.send_no_wait(wire, Request::Login("Alice".to_owned(), [1, 2, 3, 4]));
+        h.send_no_wait(wire, Request::Login("Alice".to_owned(), [1, 2, 3, 4]));
+
+        let resps = h.take_responses(wire);
+        assert!(resps.iter().any(|r| match *r { Response::KickReason(_) => true, _ => false }));
+        assert_eq!(h.engine().auth.pending_len(), 0);
+    });
+}
+
+#[test]
+fn change_secret() {
+    harness::run(|h| {
+        let wire = h.login("Alice");
+        let cid = h.client_id(wire);
+        h.take_responses(wire);
+
+        // A wrong old secret leaves the account alone.
+        assert!(logic::client::change_secret(h.engine().as_ref(), cid,
+                                             [9, 9, 9, 9], [5, 6, 7, 8]).unwrap());
+        h.pump();
+        let resps = h.take_responses(wire);
+        assert!(resps.iter().any(|r| match *r {
+            Response::ChatUpdate(ref msg) => msg.contains("incorrect"),
+            _ => false,
+        }));
+
+        assert!(logic::client::change_secret(h.engine().as_ref(), cid,
+                                             [1, 2, 3, 4], [5, 6, 7, 8]).unwrap());
+        h.pump();
+        let resps = h.take_responses(wire);
+        assert!(resps.iter().any(|r| match *r {
+            Response::ChatUpdate(ref msg) => msg.contains("has been changed"),
+            _ => false,
+        }));
+        h.disconnect(wire);
+
+        let old = h.connect();
+        h.send(old, Request::Login("Alice".to_owned(), [1, 2, 3, 4]));
+        let resps = h.take_responses(old);
+        assert!(resps.iter().any(|r| match *r { Response::KickReason(_) => true, _ => false }));
+        assert!(h.engine().messages.wire_to_client(old).is_none());
+
+        let new = h.connect();
+        h.send(new, Request::Login("Alice".to_owned(), [5, 6, 7, 8]));
+        let resps = h.take_responses(new);
+        assert!(resps.iter().any(|r| match *r { Response::Init(_) => true, _ => false }));
+        assert!(h.engine().messages.wire_to_client(new).is_some());
+    });
+}
+
+#[test]
+fn hello_negotiates_version() {
+    harness::run(|h| {
+        let wire = h.connect_raw();
+        h.send(wire, Request::Hello(PROTOCOL_VERSION, 0xffff_ffff));
+        let resps = h.take_responses(wire);
+        match resps[0] {
+            Response::HelloResult(version, caps) => {
+                assert_eq!(version, PROTOCOL_VERSION);
+                // Unknown capability bits are dropped.
+                assert_eq!(caps, Capabilities::all().bits());
+            },
+            _ => panic!("expected HelloResult"),
+        }
+
+        let old = h.connect_raw();
+        h.send(old, Request::Hello(PROTOCOL_VERSION + 1, 0));
+        let resps = h.take_responses(old);
+        assert!(resps.iter().any(|r| match *r { Response::KickReason(_) => true, _ => false }));
+    });
+}
+
+#[test]
+fn login_loads_visible_chunks() {
+    harness::run(|h| {
+        let wire = h.login("Alice");
+        let region = vision_region(h.pawn_pos(wire));
+
+        let loaded = h.take_responses(wire).into_iter().filter_map(|r| match r {
+            Response::TerrainChunk(idx, _) => Some(idx),
+            _ => None,
+        }).collect::<HashSet<_>>();
+        assert_eq!(loaded.len(), region.points().count());
+    });
+}
+
+#[test]
+fn input_moves_pawn() {
+    harness::run(|h| {
+        let wire = h.login("Alice");
+        let start = h.pawn_pos(wire);
+
+        let now = h.now();
+        h.send(wire, Request::Input(now.to_local(), INPUT_RIGHT.bits()));
+        h.advance(1000);
+        let moved = h.pawn_pos(wire);
+        assert!(moved.x > start.x);
+        assert_eq!(moved.y, start.y);
+
+        let now = h.now();
+        h.send(wire, Request::Input(now.to_local(), 0));
+        h.advance(10);
+        let stopped = h.pawn_pos(wire);
+        h.advance(1000);
+        assert_eq!(h.pawn_pos(wire), stopped);
+    });
+}
+
+#[test]
+fn chat_reaches_other_clients() {
+    harness::run(|h| {
+        let alice = h.login("Alice");
+        let bob = h.login("Bob");
+        h.take_responses(bob);
+
+        h.send(alice, Request::Chat("hello".to_owned()));
+        let resps = h.take_responses(bob);
+        assert!(resps.iter().any(|r| match *r {
+            Response::ChatUpdate(ref msg) => msg.contains("hello"),
+            _ => false,
+        }));
+    });
+}
+
+#[test]
+fn unknown_action_is_not_a_protocol_error() {
+    harness::run(|h| {
+        let wire = h.login("Alice");
+        h.take_responses(wire);
+
+        // Action 1 is declared in the test data; action 99 is not.  Neither should get the client
+        // kicked, since clients built against different data may send stale action IDs.
+        let now = h.now();
+        h.send(wire, Request::UseAction(now.to_local(), 1));
+        h.send(wire, Request::UseAction(now.to_local(), 99));
+        h.advance(100);
+
+        let resps = h.take_responses(wire);
+        assert!(!resps.iter().any(|r| match *r { Response::KickReason(_) => true, _ => false }));
+    });
+}
+
+#[test]
+fn move_items_between_inventories() {
+    harness::run(|h| {
+        let wire = h.login("Alice");
+        let main = h.pawn_inventory(wire, "main");
+        let ability = h.pawn_inventory(wire, "ability");
+        h.give_items(main, "wood", 10);
+
+        h.send(wire, Request::MoveItem(main, 0, ability, 0, 4));
+        assert_eq!(h.count_items(main, "wood"), 6);
+        assert_eq!(h.count_items(ability, "wood"), 4);
+    });
+}
+
+#[test]
+fn craft_recipe() {
+    harness::run(|h| {
+        let wire = h.login("Alice");
+        let main = h.pawn_inventory(wire, "main");
+        h.give_items(main, "wood", 5);
+        let station = h.engine().world.structures().next().expect("no anvil at spawn").id();
+
+        // Recipe 0 turns 2 wood into 1 stone.  Only 2 of the 3 requested can be made.
+        h.send(wire, Request::CraftRecipe(station, main, 0, 3));
+        assert_eq!(h.count_items(main, "wood"), 1);
+        assert_eq!(h.count_items(main, "stone"), 2);
+    });
+}
+
+#[test]
+fn logout_saves_client() {
+    harness::run(|h| {
+        let wire = h.login("Alice");
+        let main = h.pawn_inventory(wire, "main");
+        h.give_items(main, "wood", 3);
+        h.disconnect(wire);
+        assert!(h.engine().world.clients().next().is_none());
+
+        let wire = h.connect();
+        h.send(wire, Request::Login("Alice".to_owned(), [1, 2, 3, 4]));
+        let main = h.pawn_inventory(wire, "main");
+        assert_eq!(h.count_items(main, "wood"), 3);
+    });
+}
+
+#[test]
+fn damage_kills_and_respawns_pawn() {
+    harness::run(|h| {
+        let alice = h.login("Alice");
+        let bob = h.login("Bob");
+        let eid = h.pawn_id(alice);
+        h.take_responses(bob);
+
+        combat::damage(h.engine().as_ref(), eid, 30, None).unwrap();
+        assert_eq!(h.engine().world.entity(eid).health(), Health { hp: 70, max: 100 });
+        let resps = h.take_responses(bob);
+        assert!(resps.iter().any(|r| match *r {
+            Response::EntityDamage(e, 30) => e == eid,
+            _ => false,
+        }));
+        assert!(resps.iter().any(|r| match *r {
+            Response::EntityHealth(e, 70, 100) => e == eid,
+            _ => false,
+        }));
+
+        combat::damage(h.engine().as_ref(), eid, 500, None).unwrap();
+        assert!(h.engine().world.entity(eid).health().is_dead());
+        // Dead pawns can't be hurt any further.
+        combat::damage(h.engine().as_ref(), eid, 10, None).unwrap();
+        assert!(h.take_responses(bob).iter().all(|r| match *r {
+            Response::EntityDamage(_, amount) => amount != 10,
+            _ => true,
+        }));
+
+        h.advance(combat::RESPAWN_DELAY);
+        assert_eq!(h.engine().world.entity(eid).health(), Health::new(100));
+    });
+}
+
+#[test]
+fn dead_pawn_cannot_craft() {
+    harness::run(|h| {
+        let wire = h.login("Alice");
+        let eid = h.pawn_id(wire);
+        let main = h.pawn_inventory(wire, "main");
+        h.give_items(main, "wood", 2);
+        let station = h.engine().world.structures().next().expect("no anvil at spawn").id();
+
+        combat::damage(h.engine().as_ref(), eid, 500, None).unwrap();
+        h.send(wire, Request::CraftRecipe(station, main, 0, 1));
+        assert_eq!(h.count_items(main, "wood"), 2);
+
+        h.advance(combat::RESPAWN_DELAY);
+        h.send(wire, Request::CraftRecipe(station, main, 0, 1));
+        assert_eq!(h.count_items(main, "stone"), 1);
+    });
+}
+
+#[test]
+fn snapshots_get_unique_names() {
+    harness::run(|h| {
+        h.login("Alice");
+        let a = logic::snapshot::create(h.engine().as_ref()).unwrap();
+        let b = {
+            let mut eng = h.engine().as_ref();
+            logic::snapshot::wait(eng.borrow());
+            logic::snapshot::create(eng).unwrap()
+        };
+        logic::snapshot::wait(h.engine().as_ref());
+
+        assert!(a != b);
+        assert_eq!(logic::snapshot::list(h.engine().storage), vec![a, b]);
+    });
+}
+
+#[test]
+fn health_updates_require_capability() {
+    harness::run(|h| {
+        let alice = h.login("Alice");
+        let bob = h.login_with("Bob", Capabilities::empty());
+        let eid = h.pawn_id(alice);
+        h.take_responses(bob);
+
+        combat::damage(h.engine().as_ref(), eid, 30, None).unwrap();
+        assert!(h.take_responses(bob).iter().all(|r| match *r {
+            Response::EntityHealth(..) | Response::EntityDamage(..) => false,
+            _ => true,
+        }));
+    });
+}
+
+#[test]
+fn block_changes_are_sent_as_deltas() {
+    harness::run(|h| {
+        let alice = h.login("Alice");
+        let bob = h.login_with("Bob", Capabilities::all() - CAP_TERRAIN_DELTA);
+        h.wait_for_terrain_gen();
+        let pawn = h.pawn_id(alice);
+        let pid = h.engine().world.entity(pawn).plane_id();
+        let pos = h.pawn_pos(alice).div_floor(scalar(TILE_SIZE));
+        h.take_responses(alice);
+        h.take_responses(bob);
+
+        // Block 2 is a wall; generated terrain is all floor.
+        h.set_block(pid, pos, 2);
+
+        let resps = h.take_responses(alice);
+        assert!(resps.iter().any(|r| match *r {
+            Response::TerrainBlocksUpdate(_, ref blocks) => blocks.len() == 1 && blocks[0].1 == 2,
+            _ => false,
+        }));
+        assert!(resps.iter().all(|r| match *r {
+            Response::TerrainChunk(..) => false,
+            _ => true,
+        }));
+
+        let resps = h.take_responses(bob);
+        assert!(resps.iter().any(|r| match *r { Response::TerrainChunk(..) => true, _ => false }));
+        assert!(resps.iter().all(|r| match *r {
+            Response::TerrainBlocksUpdate(..) => false,
+            _ => true,
+        }));
+    });
+}
+
+#[test]
+fn restart_restores_clients_on_their_wires() {
+    harness::run(|h| {
+        let wire = h.login("Alice");
+        h.send(CONTROL_WIRE_ID, Request::Restart(true, false));
+        assert!(!h.running());
+
+        // Under the wrapper, the websocket stays open and keeps its wire ID.
+        h.restart(true);
+        let cid = h.client_id(wire);
+        assert_eq!(h.engine().world.client(cid).name(), "Alice");
+    });
+}
+
+#[test]
+fn restart_without_surviving_wires_is_a_shutdown() {
+    harness::run(|h| {
+        h.engine().extra.wires_survive_restart = false;
+        let wire = h.login("Alice");
+        h.send(CONTROL_WIRE_ID, Request::Restart(true, false));
+        assert!(!h.running());
+        assert!(h.engine().storage.open_restart_file().is_none());
+
+        h.restart(false);
+        assert_eq!(h.engine().world.clients().count(), 0);
+
+        // The next connection gets the same wire ID, but none of Alice's session.
+        let wire2 = h.connect();
+        assert_eq!(wire2, wire);
+        assert!(h.engine().messages.wire_to_client(wire2).is_none());
+    });
+}
+
+#[test]
+fn stale_restart_file_is_discarded_without_surviving_wires() {
+    harness::run(|h| {
+        let wire = h.login("Alice");
+        h.send(CONTROL_WIRE_ID, Request::Restart(true, false));
+        assert!(h.engine().storage.open_restart_file().is_some());
+
+        // The server comes back up accepting its own connections, so the wire IDs recorded in
+        // the restart file now belong to whoever connects next.
+        h.restart(false);
+        assert!(h.engine().storage.open_restart_file().is_none());
+        assert_eq!(h.engine().world.clients().count(), 0);
+
+        let wire2 = h.connect();
+        assert_eq!(wire2, wire);
+        assert!(h.engine().messages.wire_to_client(wire2).is_none());
+    });
+}
+
+#[test]
+fn restart_file_without_version_header_is_read() {
+    harness::run(|h| {
+        let wire = h.login("Alice");
+        h.send(CONTROL_WIRE_ID, Request::Shutdown);
+        assert!(!h.running());
+
+        // Older servers wrote just the name for each client, with no header.
+        {
+            let mut file = h.engine().storage.create_restart_file();
+            WireWriter::new(&mut file).write_msg(wire, "Alice").unwrap();
+            file.commit().unwrap();
+        }
+
+        h.restart(true);
+        let cid = h.client_id(wire);
+        assert_eq!(h.engine().world.client(cid).name(), "Alice");
+        assert_eq!(h.engine().messages.client_capabilities(cid), Some(Capabilities::empty()));
+    });
+}
+
+#[test]
+fn restart_notice_arrives_before_reset() {
+    harness::run(|h| {
+        let wire = h.login("Alice");
+        h.take_responses(wire);
+        h.send(CONTROL_WIRE_ID, Request::Restart(true, false));
+
+        let resps = h.take_responses(wire);
+        let notice = resps.iter().position(|r| match *r {
+            Response::ChatUpdate(ref msg) => msg.contains("Server restarting"),
+            _ => false,
+        }).expect("no restart notice");
+        let reset = resps.iter().position(|r| match *r {
+            Response::SyncStatus(2) => true,
+            _ => false,
+        }).expect("no reset");
+        assert!(notice < reset);
+    });
+}
@@ -0,0 +1,400 @@
+//! Headless harness for driving the `Engine` from tests.  The engine gets an in-memory `Storage`,
+//! a small synthetic `Data`, no Python hooks, and a manual `Timer`, so time only passes when the
+//! test calls `advance`.  Requests go in over the same channel `tasks::run_input` feeds, and
+//! responses are collected from the channel `tasks::run_output` would drain.
+//!
+//! Terrain generation still runs on its worker thread.  Call `wait_for_terrain_gen` to block until
+//! every pending chunk has been generated.  Secrets are also hashed on a worker thread, but `send`
+//! waits for those results itself, so logins finish before it returns.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Sender, Receiver};
+use rand::random;
+use rustc_serialize::json::Json;
+
+use types::*;
+use libphysics::CHUNK_SIZE;
+
+use data::Data;
+use engine::{Engine, LoopEvent};
+use msg::{Request, Response, PROTOCOL_VERSION, Capabilities};
+use script::ScriptHooks;
+use storage::{Storage, MemStorage};
+use timer::Timer;
+use world;
+use world::extra::{View, Value};
+use world::flags;
+use world::object::*;
+
+
+const BLOCK_JSON: &'static str = r#"[
+    {"name": "floor", "shape": "floor"},
+    {"name": "empty", "shape": "empty"},
+    {"name": "wall", "shape": "solid"}
+]"#;
+
+const ITEM_JSON: &'static str = r#"[
+    {"name": "none"},
+    {"name": "wood"},
+    {"name": "stone"},
+    {"name": "ability/light"}
+]"#;
+
+const RECIPE_JSON: &'static str = r#"[
+    {"name": "stone", "station": 0, "inputs": [[1, 2]], "outputs": [[2, 1]]}
+]"#;
+
+const TEMPLATE_JSON: &'static str = r#"[
+    {"name": "anvil", "size": [1, 1, 1], "shape": [2], "layer": 1},
+    {"name": "chest", "size": [1, 1, 1], "shape": [2], "layer": 1}
+]"#;
+
+const SPRITE_LAYER_JSON: &'static str = r#"[]"#;
+
+const LOOT_TABLE_JSON: &'static str = r#"{
+    "items": [
+        {"name": "cave/chest", "type": "multi", "parts": []}
+    ],
+    "structures": [
+        {"name": "cave/floor", "type": "choose", "variants": []},
+        {"name": "forest/floor", "type": "choose", "variants": []}
+    ]
+}"#;
+
+const ACTION_JSON: &'static str = r#"[
+    {"name": "none"},
+    {"name": "wave"}
+]"#;
+
+/// Build a minimal `Data`.  Block 0 is a floor, so chunks generated from the forest provider
+/// (which falls back to block 0 for every name it doesn't recognize) are flat and walkable.  Item,
+/// recipe, template, and action IDs match the order of the lists above.
+pub fn test_data() -> Data {
+    // Physics picks animations by name based on speed and direction.  Dead pawns sit.
+    let mut anims = Vec::new();
+    for speed in &["stand", "walk", "run", "sit"] {
+        for dir in 0 .. 4 {
+            anims.push(format!(r#"{{"name": "pony//{}-{}", "framerate": 1, "length": 1}}"#,
+                               speed, dir));
+        }
+    }
+    let animation_json = format!("[{}]", anims.join(", "));
+
+    let parse = |s: &str| Json::from_str(s).unwrap();
+    Data::from_json(parse(BLOCK_JSON),
+                    parse(ITEM_JSON),
+                    parse(RECIPE_JSON),
+                    parse(TEMPLATE_JSON),
+                    parse(&animation_json),
+                    parse(SPRITE_LAYER_JSON),
+                    parse(LOOT_TABLE_JSON),
+                    parse(ACTION_JSON)).unwrap()
+}
+
+
+/// A scratch directory for the plain files `Storage` keeps on disk (auth database, chat log,
+/// terrain summaries).  Removed on drop, even if the test panics.
+pub struct ScratchDir(pub PathBuf);
+
+impl ScratchDir {
+    pub fn new() -> ScratchDir {
+        let path = env::temp_dir().join(format!("outpost-test-{:016x}", random::<u64>()));
+        fs::create_dir_all(&path).unwrap();
+        ScratchDir(path)
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+
+pub struct Harness<'d> {
+    engine: Engine<'d>,
+    send: Sender<(WireId, Request)>,
+    recv: Receiver<(WireId, Response)>,
+    responses: Vec<(WireId, Response)>,
+    next_wire: u16,
+    running: bool,
+}
+
+/// Create an engine with a manual timer and run its startup.
+fn start_engine<'d>(data: &'d Data,
+                    storage: &'d Storage,
+                    hooks: &'d ScriptHooks,
+                    wires_survive_restart: bool)
+                    -> (Engine<'d>, Sender<(WireId, Request)>, Receiver<(WireId, Response)>) {
+    let (req_send, req_recv) = channel();
+    let (resp_send, resp_recv) = channel();
+
+    let mut engine = Engine::new(data, storage, hooks, req_recv, resp_send);
+    engine.timer = Timer::new_manual();
+    engine.extra.wires_survive_restart = wires_survive_restart;
+    engine.start_up();
+    let now = engine.now;
+    engine.messages.set_manual_now(Some(now));
+    (engine, req_send, resp_recv)
+}
+
+/// Start up a fresh engine, pass it to `f`, then shut it down.
+pub fn run<F>(f: F)
+        where F: FnOnce(&mut Harness) {
+    let dir = ScratchDir::new();
+    let data = test_data();
+    let storage = MemStorage::new(&dir.0);
+    let hooks = ScriptHooks::new();
+    let (engine, req_send, resp_recv) = start_engine(&data, &storage, &hooks, true);
+
+    let mut h = Harness {
+        engine: engine,
+        send: req_send,
+        recv: resp_recv,
+        responses: Vec::new(),
+        next_wire: 1,
+        running: true,
+    };
+    f(&mut h);
+    if h.running {
+        h.engine.shut_down();
+    }
+}
+
+impl<'d> Harness<'d> {
+    pub fn engine(&mut self) -> &mut Engine<'d> {
+        &mut self.engine
+    }
+
+    pub fn now(&self) -> Time {
+        self.engine.now
+    }
+
+    pub fn running(&self) -> bool {
+        self.running
+    }
+
+    /// Start a new engine on the same storage, as if the server process had exited and been
+    /// started again.  The old engine is shut down first if it's still running.  If
+    /// `wires_survive_restart` is false, connections from before the restart are gone, and wire
+    /// IDs are handed out from the start again, as a fresh transport would.
+    pub fn restart(&mut self, wires_survive_restart: bool) {
+        if self.running {
+            self.engine.shut_down();
+        }
+        let (engine, req_send, resp_recv) = start_engine(self.engine.data,
+                                                         self.engine.storage,
+                                                         self.engine.script_hooks,
+                                                         wires_survive_restart);
+        self.engine = engine;
+        self.send = req_send;
+        self.recv = resp_recv;
+        self.responses.clear();
+        self.running = true;
+        if !wires_survive_restart {
+            self.next_wire = 1;
+        }
+    }
+
+
+    // Events
+
+    fn process(&mut self, evt: LoopEvent) {
+        assert!(self.running, "engine has already stopped");
+        if !self.engine.process(evt) {
+            self.running = false;
+            self.engine.shut_down();
+        }
+    }
+
+    /// Handle every request that has been sent so far, without waiting for the auth worker.
+    fn pump_messages(&mut self) {
+        while self.running {
+            let result = self.engine.messages.receiver().try_recv();
+            match result {
+                Ok(evt) => self.process(LoopEvent::FromMessage(evt)),
+                Err(_) => break,
+            }
+        }
+    }
+
+    /// Handle every request that has been sent so far, and wait for any logins or registrations
+    /// they started to finish.
+    pub fn pump(&mut self) {
+        self.pump_messages();
+        while self.running && self.engine.auth.pending_len() > 0 {
+            let evt = self.engine.auth.receiver().recv().unwrap();
+            self.process(LoopEvent::FromAuth(evt));
+            self.pump_messages();
+        }
+    }
+
+    /// Wait for the next reply from the auth worker and handle it, even if it's for a request
+    /// that was since cancelled.
+    pub fn wait_for_auth(&mut self) {
+        let evt = self.engine.auth.receiver().recv().unwrap();
+        self.process(LoopEvent::FromAuth(evt));
+    }
+
+    /// Advance the clock by `ms` milliseconds, running timers in order as they come due.
+    pub fn advance(&mut self, ms: Time) {
+        let target = self.engine.now + ms;
+        while self.running {
+            let evt = unwrap_or!(self.engine.timer.next_due(target), break);
+            self.process(LoopEvent::FromTimer(evt));
+            let now = self.engine.now;
+            self.engine.messages.set_manual_now(Some(now));
+        }
+        self.engine.now = target;
+        self.engine.messages.set_manual_now(Some(target));
+    }
+
+    fn generation_pending(&self) -> bool {
+        self.engine.world.terrain_chunks()
+            .any(|tc| tc.flags().contains(flags::TC_GENERATION_PENDING))
+    }
+
+    /// Block until all loaded chunks have finished generating.
+    pub fn wait_for_terrain_gen(&mut self) {
+        while self.running && self.generation_pending() {
+            let evt = self.engine.terrain_gen.receiver().recv().unwrap();
+            self.process(LoopEvent::FromTerrainGen(evt));
+        }
+    }
+
+
+    // Requests and responses
+
+    /// Open a new connection and complete the `Hello` handshake, claiming every capability.
+    pub fn connect(&mut self) -> WireId {
+        self.connect_with(Capabilities::all())
+    }
+
+    /// Open a new connection and complete the `Hello` handshake with the given capabilities.  The
+    /// `HelloResult` is discarded.
+    pub fn connect_with(&mut self, caps: Capabilities) -> WireId {
+        let wire_id = self.connect_raw();
+        self.send(wire_id, Request::Hello(PROTOCOL_VERSION, caps.bits()));
+        self.take_responses(wire_id);
+        wire_id
+    }
+
+    /// Open a new connection without sending `Hello`.
+    pub fn connect_raw(&mut self) -> WireId {
+        let wire_id = WireId(self.next_wire);
+        self.next_wire += 1;
+        self.send(CONTROL_WIRE_ID, Request::AddClient(wire_id));
+        wire_id
+    }
+
+    pub fn disconnect(&mut self, wire_id: WireId) {
+        self.send(CONTROL_WIRE_ID, Request::RemoveClient(wire_id));
+    }
+
+    /// Send `req` on `wire_id` and handle it immediately.
+    pub fn send(&mut self, wire_id: WireId, req: Request) {
+        self.send.send((wire_id, req)).unwrap();
+        self.pump();
+    }
+
+    /// Like `send`, but leave any login or registration it starts waiting on the auth worker.
+    pub fn send_no_wait(&mut self, wire_id: WireId, req: Request) {
+        self.send.send((wire_id, req)).unwrap();
+        self.pump_messages();
+    }
+
+    fn collect(&mut self) {
+        // Tests see every response right away, regardless of the send budget.
+        self.engine.messages.flush_all();
+        while let Ok(x) = self.recv.try_recv() {
+            self.responses.push(x);
+        }
+    }
+
+    /// Remove and return all responses sent to `wire_id` so far.
+    pub fn take_responses(&mut self, wire_id: WireId) -> Vec<Response> {
+        self.collect();
+        let (mine, others): (Vec<_>, Vec<_>) =
+            self.responses.drain(..).partition(|&(w, _)| w == wire_id);
+        self.responses = others;
+        mine.into_iter().map(|(_, r)| r).collect()
+    }
+
+    /// Register account `name` and log in on a new connection.  Waits for the chunks around the
+    /// new pawn to finish generating.
+    pub fn login(&mut self, name: &str) -> WireId {
+        self.login_with(name, Capabilities::all())
+    }
+
+    /// Like `login`, but the connection claims only the capabilities in `caps`.
+    pub fn login_with(&mut self, name: &str, caps: Capabilities) -> WireId {
+        let secret = [1, 2, 3, 4];
+        let wire_id = self.connect_with(caps);
+        self.send(wire_id, Request::Register(name.to_owned(), secret, 0));
+        self.send(wire_id, Request::Login(name.to_owned(), secret));
+        self.wait_for_terrain_gen();
+        wire_id
+    }
+
+    pub fn client_id(&self, wire_id: WireId) -> ClientId {
+        self.engine.messages.wire_to_client(wire_id).expect("wire is not logged in")
+    }
+
+    pub fn pawn_id(&self, wire_id: WireId) -> EntityId {
+        let cid = self.client_id(wire_id);
+        self.engine.world.client(cid).pawn_id().expect("client has no pawn")
+    }
+
+    pub fn pawn_pos(&self, wire_id: WireId) -> V3 {
+        let cid = self.client_id(wire_id);
+        let c = self.engine.world.client(cid);
+        c.pawn().expect("client has no pawn").pos(self.engine.now)
+    }
+
+    /// Get one of the pawn's inventories, as recorded in its `inv` extra (`"main"` or
+    /// `"ability"`).
+    pub fn pawn_inventory(&self, wire_id: WireId, key: &str) -> InventoryId {
+        let cid = self.client_id(wire_id);
+        let c = self.engine.world.client(cid);
+        let e = c.pawn().expect("client has no pawn");
+        let inv = match e.extra().get("inv") {
+            Some(View::Hash(h)) => h.get(key),
+            _ => None,
+        };
+        match inv {
+            Some(View::Value(Value::InventoryId(iid))) => iid,
+            _ => panic!("pawn has no {} inventory", key),
+        }
+    }
+
+    pub fn count_items(&self, iid: InventoryId, item: &str) -> u16 {
+        let item_id = self.engine.data.item_data.get_id(item);
+        self.engine.world.inventory(iid).count(item_id)
+    }
+
+    /// Add items directly to an inventory, bypassing the usual game logic.
+    pub fn give_items(&mut self, iid: InventoryId, item: &str, count: u16) {
+        let mut eng = self.engine.as_ref();
+        let mut wf = eng.as_world_fragment();
+        let mut i = world::Fragment::inventory_mut(&mut wf, iid);
+        i.bulk_add_by_name(item, count).unwrap();
+    }
+
+    /// Replace the terrain block at `pos` (in blocks, not pixels) and run the terrain update
+    /// hooks, as block-changing game logic does.
+    pub fn set_block(&mut self, pid: PlaneId, pos: V3, block: BlockId) {
+        let mut eng = self.engine.as_ref();
+        let mut wf = eng.as_world_fragment();
+        let tcid = {
+            let mut p = world::Fragment::plane_mut(&mut wf, pid);
+            let cpos = pos.reduce().div_floor(scalar(CHUNK_SIZE));
+            let mut tc = p.terrain_chunk_mut(cpos);
+            let idx = tc.bounds().index(pos);
+            tc.blocks_mut()[idx] = block;
+            tc.id()
+        };
+        world::Fragment::with_hooks(&mut wf, |h| world::Hooks::on_terrain_chunk_update(h, tcid));
+    }
+}
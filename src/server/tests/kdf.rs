@@ -0,0 +1,60 @@
+//! Test vectors for the password hashing primitives.  The scrypt and PBKDF2 vectors are from RFC
+//! 7914, sections 11 and 12; the SHA-256 ones are from FIPS 180-2.
+
+use auth::kdf::{self, Params};
+
+
+fn check_scrypt(password: &str, salt: &str, log_n: u8, r: u32, p: u32, expect: &str) {
+    let params = Params { log_n: log_n, r: r, p: p };
+    let mut out = [0; 64];
+    kdf::scrypt(password.as_bytes(), salt.as_bytes(), params, &mut out);
+    assert_eq!(kdf::to_hex(&out), expect);
+}
+
+
+#[test]
+fn sha256_vectors() {
+    assert_eq!(kdf::to_hex(&kdf::sha256(b"")),
+               "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+    assert_eq!(kdf::to_hex(&kdf::sha256(b"abc")),
+               "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    assert_eq!(kdf::to_hex(&kdf::sha256(
+                   b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+               "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+}
+
+#[test]
+fn pbkdf2_sha256_vectors() {
+    let mut out = [0; 64];
+    kdf::pbkdf2_sha256(b"passwd", b"salt", 1, &mut out);
+    assert_eq!(kdf::to_hex(&out),
+               "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc\
+                49ca9cccf179b645991664b39d77ef317c71b845b1e30bd509112041d3a19783");
+
+    kdf::pbkdf2_sha256(b"Password", b"NaCl", 80000, &mut out);
+    assert_eq!(kdf::to_hex(&out),
+               "4ddcd8f60b98be21830cee5ef22701f9641a4418d04c0414aeff08876b34ab56\
+                a1d425a1225833549adb841b51c9b3176a272bdebba1d078478f62b397f33c8d");
+}
+
+#[test]
+fn scrypt_vectors() {
+    check_scrypt("", "", 4, 1, 1,
+                 "77d6576238657b203b19ca42c18a0497f16b4844e3074ae8dfdffa3fede21442\
+                  fcd0069ded0948f8326a753a0fc81f17e8d3e0fb2e0d3628cf35e20c38d18906");
+    check_scrypt("password", "NaCl", 10, 8, 16,
+                 "fdbabe1c9d3472007856e7190d01e9fe7c6ad7cbc8237830e77376634b373162\
+                  2eaf30d92e22a3886ff109279d9830dac727afb94a83ee6d8360cbdfa2cc0640");
+    check_scrypt("pleaseletmein", "SodiumChloride", 14, 8, 1,
+                 "7023bdcb3afd7348461c06cd81fd38ebfda8fbba904f8e3ea9b543f6545da1f2\
+                  d5432955613f0fcf62d49705242a9af9e61e85dc0d651e40dfcf017b45575887");
+}
+
+#[test]
+fn hex_round_trip() {
+    let bytes = [0x00, 0x7f, 0x80, 0xff, 0x12];
+    assert_eq!(kdf::to_hex(&bytes), "007f80ff12");
+    assert_eq!(kdf::from_hex("007f80ff12"), Some(bytes.to_vec()));
+    assert_eq!(kdf::from_hex("007"), None);
+    assert_eq!(kdf::from_hex("zz"), None);
+}
@@ -0,0 +1,72 @@
+//! Upgrading bundles saved at older save versions.
+
+use world::bundle::{self, Builder, Bundle, CURRENT_SAVE_VERSION};
+use world::bundle::migrate;
+
+use super::harness;
+
+
+fn sample_bundle() -> Bundle {
+    let data = harness::test_data();
+    let mut b = Builder::new(&data);
+    b.anim("pony//walk-0");
+    b.item("wood");
+    b.item("stone");
+    b.block("wall");
+    b.template("chest");
+    b.finish()
+}
+
+fn names(table: &[Box<str>]) -> Vec<&str> {
+    table.iter().map(|s| &**s).collect()
+}
+
+
+#[test]
+fn unversioned_bundle_is_upgraded() {
+    let mut b = sample_bundle();
+    b.version = 0;
+
+    let mut buf = Vec::new();
+    bundle::write_bundle(&mut buf, &b).unwrap();
+    let b2 = bundle::read_bundle(&mut &buf[..]).unwrap();
+
+    assert_eq!(b2.version, CURRENT_SAVE_VERSION);
+    assert_eq!(names(&b2.anims), names(&b.anims));
+    assert_eq!(names(&b2.items), names(&b.items));
+    assert_eq!(names(&b2.blocks), names(&b.blocks));
+    assert_eq!(names(&b2.templates), names(&b.templates));
+}
+
+#[test]
+fn current_bundle_is_unchanged() {
+    let mut b = sample_bundle();
+    assert_eq!(b.version, CURRENT_SAVE_VERSION);
+    migrate::upgrade(&mut b).unwrap();
+    assert_eq!(b.version, CURRENT_SAVE_VERSION);
+}
+
+#[test]
+fn newer_bundle_is_rejected() {
+    let mut b = sample_bundle();
+    b.version = CURRENT_SAVE_VERSION + 1;
+    assert!(migrate::upgrade(&mut b).is_err());
+}
+
+#[test]
+fn rename_helpers() {
+    let mut b = sample_bundle();
+    // Names that aren't in the bundle are ignored.
+    migrate::rename_items(&mut b, &[("wood", "log"), ("iron", "steel")]);
+    migrate::rename_blocks(&mut b, &[("wall", "stone_wall")]);
+    migrate::rename_templates(&mut b, &[("chest", "chest/wood")]);
+    migrate::rename_anims(&mut b, &[("pony//walk-0", "pony//walk-e")]);
+
+    assert!(names(&b.items).contains(&"log"));
+    assert!(names(&b.items).contains(&"stone"));
+    assert!(!names(&b.items).contains(&"wood"));
+    assert!(!names(&b.items).contains(&"steel"));
+    assert_eq!(names(&b.blocks), vec!["stone_wall"]);
+    assert_eq!(names(&b.templates), vec!["chest/wood"]);
+    assert_eq!(names(&b.anims), vec!["pony//walk-e"]);
+}
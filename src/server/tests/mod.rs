@@ -10,6 +10,23 @@ use pubsub::{PubSub, Name, ZOrdered};
 use vision::{Vision, NoHooks, vision_region};
 
 
+mod auth;
+mod bundle_json;
+mod chat;
+mod flows;
+mod kdf;
+mod migrate;
+mod npc;
+mod physics;
+mod protocol;
+mod send_queue;
+mod storage;
+mod walk;
+mod websocket;
+mod pathfind;
+pub mod harness;
+
+
 struct BlackBoxHooks;
 
 impl ::vision::Hooks for BlackBoxHooks {
@@ -0,0 +1,164 @@
+//! NPC behaviour: the think loop's state transitions, path planning while chasing, despawning,
+//! and restoring NPC state after a restart.
+
+use types::*;
+use libphysics::TILE_SIZE;
+
+use logic;
+use logic::npc::{self, Behavior, Temperament};
+use logic::walk;
+use msg::Request;
+use pathfind;
+use world;
+use world::object::*;
+
+use super::harness::{self, Harness};
+
+
+/// Create an NPC standing on the tile `offset` away from `wire`'s pawn.
+fn add_npc(h: &mut Harness,
+           wire: WireId,
+           offset: V3,
+           temperament: Temperament,
+           spawned: bool) -> EntityId {
+    let tile = pathfind::entity_tile(h.pawn_pos(wire)) + offset;
+    let pawn = h.pawn_id(wire);
+    let stable_pid = h.engine().world.entity(pawn).stable_plane_id();
+
+    let mut eng = h.engine().as_ref();
+    let eid = {
+        let mut wf = eng.as_world_fragment();
+        let e = world::Fragment::create_entity(&mut wf, stable_pid, tile * scalar(TILE_SIZE), 0, 0);
+        e.unwrap().id()
+    };
+    npc::add(eng, eid, temperament, spawned).unwrap();
+    eid
+}
+
+fn behavior(h: &mut Harness, eid: EntityId) -> Option<Behavior> {
+    npc::behavior(h.engine().as_ref(), eid)
+}
+
+fn is_walking(h: &mut Harness, eid: EntityId) -> bool {
+    walk::is_walking(h.engine().as_ref(), eid)
+}
+
+
+#[test]
+fn hostile_npc_chases_and_attacks() {
+    harness::run(|h| {
+        let alice = h.login("Alice");
+        let pawn = h.pawn_id(alice);
+        let eid = add_npc(h, alice, V3::new(3, 0, 0), Temperament::Hostile, false);
+        assert_eq!(behavior(h, eid), Some(Behavior::Idle));
+
+        // The first think step runs right away.  Alice is within sensing range.
+        h.advance(0);
+        assert_eq!(behavior(h, eid), Some(Behavior::Attack(pawn)));
+        assert!(is_walking(h, eid));
+
+        // Once it's close enough, it stops and attacks.
+        let start = h.now();
+        h.advance(5000);
+        assert!(!is_walking(h, eid));
+        assert!(h.engine().extra.npcs.get(&eid).unwrap().next_attack() > start);
+    });
+}
+
+#[test]
+fn chase_replans_only_when_target_moves() {
+    harness::run(|h| {
+        let alice = h.login("Alice");
+        let pawn = h.pawn_id(alice);
+        let eid = add_npc(h, alice, V3::new(5, 0, 0), Temperament::Hostile, false);
+
+        h.advance(0);
+        let goal = pathfind::entity_tile(h.pawn_pos(alice));
+        assert_eq!(walk::goal(h.engine().as_ref(), eid), Some(goal));
+        let started = h.engine().world.entity(eid).motion().start_time;
+
+        // Alice hasn't moved, so the next step keeps following the same path.
+        h.advance(1000);
+        assert_eq!(behavior(h, eid), Some(Behavior::Attack(pawn)));
+        assert_eq!(h.engine().world.entity(eid).motion().start_time, started);
+
+        let goal = goal + V3::new(0, 3, 0);
+        {
+            let mut eng = h.engine().as_ref();
+            logic::world::teleport_entity(eng.as_world_fragment(),
+                                          pawn,
+                                          goal * scalar(TILE_SIZE)).unwrap();
+        }
+        h.advance(1000);
+        assert_eq!(walk::goal(h.engine().as_ref(), eid), Some(goal));
+    });
+}
+
+#[test]
+fn spawned_npc_despawns_when_its_chunk_unloads() {
+    harness::run(|h| {
+        let alice = h.login("Alice");
+        let eid = add_npc(h, alice, V3::new(3, 0, 0), Temperament::Passive, true);
+        h.advance(0);
+        assert!(h.engine().world.get_entity(eid).is_some());
+
+        // Logging out unloads the chunks around Alice.  The NPC goes away on its next step,
+        // without waiting out the full despawn delay.
+        h.disconnect(alice);
+        h.advance(1000);
+        assert!(h.engine().world.get_entity(eid).is_none());
+        assert!(h.engine().extra.npcs.is_empty());
+    });
+}
+
+#[test]
+fn placed_npc_is_not_despawned() {
+    harness::run(|h| {
+        let alice = h.login("Alice");
+        let eid = add_npc(h, alice, V3::new(3, 0, 0), Temperament::Passive, false);
+        h.disconnect(alice);
+
+        h.advance(70_000);
+        assert!(h.engine().world.get_entity(eid).is_some());
+        assert!(behavior(h, eid).is_some());
+    });
+}
+
+#[test]
+fn removed_npc_is_an_ordinary_entity() {
+    harness::run(|h| {
+        let alice = h.login("Alice");
+        let eid = add_npc(h, alice, V3::new(3, 0, 0), Temperament::Hostile, false);
+        h.advance(0);
+        assert!(is_walking(h, eid));
+
+        npc::remove(h.engine().as_ref(), eid);
+        assert_eq!(behavior(h, eid), None);
+        assert!(!is_walking(h, eid));
+        assert!(!h.engine().world.entity(eid).extra().contains(npc::EXTRA_KEY));
+    });
+}
+
+#[test]
+fn npc_state_is_restored_after_restart() {
+    harness::run(|h| {
+        let alice = h.login("Alice");
+        let eid = add_npc(h, alice, V3::new(3, 0, 0), Temperament::Timid, false);
+        let dance = Behavior::Script("dance".to_owned());
+        npc::set_behavior(h.engine().as_ref(), eid, dance.clone()).unwrap();
+
+        h.send(CONTROL_WIRE_ID, Request::Restart(true, false));
+        h.restart(true);
+        h.advance(0);
+
+        let eids = h.engine().world.entities()
+                    .filter(|e| e.extra().contains(npc::EXTRA_KEY))
+                    .map(|e| e.id())
+                    .collect::<Vec<_>>();
+        assert_eq!(eids.len(), 1);
+        // Script behaviours are saved.  A timid NPC only reacts to players while idle or
+        // wandering, so Alice being nearby doesn't change it.
+        assert_eq!(behavior(h, eids[0]), Some(dance));
+        assert!(h.engine().extra.npcs.get(&eids[0]).unwrap().think_timer().is_some());
+    });
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+use libphysics::ShapeSource;
+
+use types::*;
+
+use pathfind::find_path;
+
+
+/// A small hand-built terrain.  Tiles not listed are `Empty`.
+struct Scene {
+    shapes: HashMap<V3, Shape>,
+}
+
+impl Scene {
+    /// A flat 8x8 floor at z = 0.
+    fn flat() -> Scene {
+        let mut shapes = HashMap::new();
+        for p in Region::new(scalar(0), V3::new(8, 8, 1)).points() {
+            shapes.insert(p, Shape::Floor);
+        }
+        Scene { shapes: shapes }
+    }
+
+    fn set(&mut self, pos: V3, shape: Shape) {
+        self.shapes.insert(pos, shape);
+    }
+}
+
+impl ShapeSource for Scene {
+    fn get_shape(&self, pos: V3) -> Shape {
+        self.shapes.get(&pos).cloned().unwrap_or(Shape::Empty)
+    }
+}
+
+fn assert_connected(path: &[V3]) {
+    for w in path.windows(2) {
+        let d = (w[1] - w[0]).abs();
+        assert_eq!(d.x + d.y, 1);
+    }
+}
+
+
+#[test]
+fn straight_line() {
+    let s = Scene::flat();
+    let path = find_path(&s, V3::new(1, 1, 0), V3::new(5, 1, 0)).unwrap();
+    assert_eq!(path.len(), 5);
+    assert_eq!(path[0], V3::new(1, 1, 0));
+    assert_eq!(path[4], V3::new(5, 1, 0));
+    assert_connected(&path);
+}
+
+#[test]
+fn around_wall() {
+    let mut s = Scene::flat();
+    for y in 0 .. 6 {
+        s.set(V3::new(3, y, 1), Shape::Solid);
+    }
+    let path = find_path(&s, V3::new(1, 1, 0), V3::new(5, 1, 0)).unwrap();
+    assert_connected(&path);
+    assert!(path.iter().any(|p| p.y >= 6));
+    assert!(path.iter().all(|p| p.x != 3 || p.y >= 6));
+}
+
+#[test]
+fn no_path_through_wall() {
+    let mut s = Scene::flat();
+    for y in 0 .. 8 {
+        s.set(V3::new(3, y, 1), Shape::Solid);
+    }
+    assert!(find_path(&s, V3::new(1, 1, 0), V3::new(5, 1, 0)).is_none());
+}
+
+#[test]
+fn up_and_down_ramp() {
+    // A raised platform at z = 1 covering x >= 4, reached by an east-facing ramp at x = 3.
+    let mut s = Scene::flat();
+    for y in 0 .. 8 {
+        s.set(V3::new(3, y, 0), Shape::RampE);
+        for x in 4 .. 8 {
+            s.set(V3::new(x, y, 0), Shape::Solid);
+            s.set(V3::new(x, y, 1), Shape::Floor);
+        }
+    }
+
+    let path = find_path(&s, V3::new(1, 2, 0), V3::new(6, 2, 1)).unwrap();
+    assert_eq!(path, vec![V3::new(1, 2, 0),
+                          V3::new(2, 2, 0),
+                          V3::new(3, 2, 0),
+                          V3::new(4, 2, 1),
+                          V3::new(5, 2, 1),
+                          V3::new(6, 2, 1)]);
+
+    let back = find_path(&s, V3::new(6, 2, 1), V3::new(1, 2, 0)).unwrap();
+    let mut rev = path.clone();
+    rev.reverse();
+    assert_eq!(back, rev);
+}
+
+#[test]
+fn no_ramp_side_entry() {
+    // A single ramp leading up to a one-tile ledge.  The ramp can only be entered from the west.
+    let mut s = Scene::flat();
+    s.set(V3::new(3, 3, 0), Shape::RampE);
+    s.set(V3::new(4, 3, 0), Shape::Solid);
+    s.set(V3::new(4, 3, 1), Shape::Floor);
+
+    let path = find_path(&s, V3::new(3, 1, 0), V3::new(4, 3, 1)).unwrap();
+    assert_connected(&path);
+    let i = path.iter().position(|&p| p == V3::new(3, 3, 0)).unwrap();
+    assert_eq!(path[i - 1], V3::new(2, 3, 0));
+}
@@ -0,0 +1,122 @@
+//! Collisions between entities: when motions get rechecked, and what happens when entities get in
+//! each other's way.
+
+use types::*;
+
+use input::INPUT_RIGHT;
+use msg::Request;
+use physics;
+use world::{self, Motion};
+use world::object::*;
+
+use super::harness::{self, Harness};
+
+
+/// Create an entity (with no behavior of its own) `offset` pixels away from `wire`'s pawn.
+fn add_entity(h: &mut Harness, wire: WireId, offset: V3) -> EntityId {
+    let pos = h.pawn_pos(wire) + offset;
+    let pawn = h.pawn_id(wire);
+    let stable_pid = h.engine().world.entity(pawn).stable_plane_id();
+
+    let mut eng = h.engine().as_ref();
+    let mut wf = eng.as_world_fragment();
+    let e = world::Fragment::create_entity(&mut wf, stable_pid, pos, 0, 0);
+    e.unwrap().id()
+}
+
+fn entity_pos(h: &mut Harness, eid: EntityId) -> V3 {
+    let now = h.now();
+    h.engine().world.entity(eid).pos(now)
+}
+
+fn set_velocity(h: &mut Harness, eid: EntityId, velocity: V3) {
+    let now = h.now();
+    let mut eng = h.engine().as_ref();
+    physics::Fragment::set_velocity(&mut eng.as_physics_fragment(), now, eid, velocity).unwrap();
+}
+
+fn overlapping(a: V3, b: V3) -> bool {
+    let d = (b - a).abs();
+    d.x < 32 && d.y < 32
+}
+
+
+#[test]
+fn approach_finds_contact_period() {
+    let size = V3::new(32, 32, 64);
+    let a = Motion {
+        start_time: 0,
+        duration: 4096,
+        start_pos: scalar(0),
+        end_pos: V3::new(256, 0, 0),
+    };
+    let b = Motion::stationary(V3::new(128, 0, 0), 0);
+
+    // Within 4px from x = 128 - 36 until x = 128 + 36.
+    assert_eq!(physics::approach(&a, &b, size, 4, 0), Some((1472, Some(2624))));
+    assert_eq!(physics::approach(&b, &a, size, 4, 0), Some((1472, Some(2624))));
+    // Already close.
+    assert_eq!(physics::approach(&a, &b, size, 4, 2000), Some((2000, Some(2624))));
+    // Already past.
+    assert_eq!(physics::approach(&a, &b, size, 4, 3000), None);
+
+    // Passing by on a different row never comes close.
+    let c = Motion::stationary(V3::new(128, 64, 0), 0);
+    assert_eq!(physics::approach(&a, &c, size, 4, 0), None);
+
+    // Stopping next to each other stays close forever.
+    let d = Motion::stationary(V3::new(256 + 32, 0, 0), 0);
+    assert_eq!(physics::approach(&a, &d, size, 4, 0), Some((4032, None)));
+}
+
+#[test]
+fn blocked_pawn_resumes_when_way_clears() {
+    harness::run(|h| {
+        let wire = h.login("Alice");
+        let start = h.pawn_pos(wire);
+        let npc = add_entity(h, wire, V3::new(96, 0, 0));
+
+        let now = h.now();
+        h.send(wire, Request::Input(now.to_local(), INPUT_RIGHT.bits()));
+        h.advance(3000);
+        assert_eq!(h.pawn_pos(wire), start + V3::new(64, 0, 0));
+
+        // Still blocked a while later, even though the pawn keeps trying.
+        h.advance(2000);
+        assert_eq!(h.pawn_pos(wire), start + V3::new(64, 0, 0));
+
+        // Once the NPC walks out of the way, the pawn continues on its own.
+        set_velocity(h, npc, V3::new(0, 50, 0));
+        h.advance(2000);
+        let pos = h.pawn_pos(wire);
+        assert!(pos.x > start.x + 64);
+        assert_eq!(pos.y, start.y);
+    });
+}
+
+#[test]
+fn crossing_entities_never_overlap() {
+    harness::run(|h| {
+        let wire = h.login("Alice");
+        let start = h.pawn_pos(wire);
+        let pawn = h.pawn_id(wire);
+        // Walking at the same speed, the two would meet where their paths cross.
+        let npc = add_entity(h, wire, V3::new(128, -128, 0));
+        let npc_start = entity_pos(h, npc);
+
+        let now = h.now();
+        h.send(wire, Request::Input(now.to_local(), INPUT_RIGHT.bits()));
+        set_velocity(h, npc, V3::new(0, 50, 0));
+
+        for _ in 0 .. 160 {
+            h.advance(50);
+            let a = entity_pos(h, pawn);
+            let b = entity_pos(h, npc);
+            assert!(!overlapping(a, b), "pawn at {:?} overlaps NPC at {:?}", a, b);
+        }
+
+        // One of them waited for the other, but both got past the crossing.
+        assert!(h.pawn_pos(wire).x > start.x + 160);
+        assert!(entity_pos(h, npc).y > npc_start.y + 160);
+    });
+}
@@ -0,0 +1,110 @@
+//! Ordering, budgeting, and overflow behavior of the per-wire send queues.
+
+use types::*;
+
+use messages::queue::{SendQueues, Priority, TICK_MS, MAX_QUEUED_BYTES};
+use msg::Response;
+
+
+fn chunk() -> Response {
+    Response::TerrainChunk(0, vec![0; 3000])
+}
+
+fn chat(s: &str) -> Response {
+    Response::ChatUpdate(s.to_owned())
+}
+
+fn flush(q: &mut SendQueues, now: Time) -> (Vec<(WireId, Response)>, Option<Time>) {
+    let mut sent = Vec::new();
+    let next = q.flush(now, |wire_id, msg| sent.push((wire_id, msg)));
+    (sent, next)
+}
+
+
+#[test]
+fn high_priority_goes_first() {
+    let mut q = SendQueues::new();
+    q.push(WireId(1), Priority::Low, chunk());
+    q.push(WireId(1), Priority::High, chat("a"));
+    q.push(WireId(1), Priority::High, chat("b"));
+
+    let (sent, next) = flush(&mut q, 0);
+    assert_eq!(next, None);
+    let sent = sent.into_iter().map(|(_, msg)| format!("{:?}", msg)).collect::<Vec<_>>();
+    assert_eq!(sent, vec![format!("{:?}", chat("a")),
+                          format!("{:?}", chat("b")),
+                          format!("{:?}", chunk())]);
+}
+
+#[test]
+fn budget_defers_to_next_tick() {
+    let mut q = SendQueues::new();
+    for _ in 0 .. 3 {
+        q.push(WireId(1), Priority::Low, chunk());
+    }
+    // Another wire's budget is independent.
+    q.push(WireId(2), Priority::Low, chunk());
+
+    let (sent, next) = flush(&mut q, 0);
+    assert_eq!(sent.len(), 2);
+    assert_eq!(next, Some(TICK_MS));
+
+    let (sent, next) = flush(&mut q, TICK_MS / 2);
+    assert_eq!(sent.len(), 0);
+    assert_eq!(next, Some(TICK_MS));
+
+    let (sent, next) = flush(&mut q, TICK_MS);
+    assert_eq!(sent.len(), 1);
+    assert_eq!(next, Some(2 * TICK_MS));
+
+    let (sent, next) = flush(&mut q, 2 * TICK_MS);
+    assert_eq!(sent.len(), 1);
+    assert_eq!(next, None);
+
+    let stats = q.stats();
+    assert_eq!(stats.queued_bytes, 0);
+    assert_eq!(stats.sent_bytes, 4 * chunk().wire_size() as u64);
+    // Wire 1 ran out of budget in the first two ticks.  The extra flush partway through the
+    // first tick doesn't count again.
+    assert_eq!(stats.throttled_ticks, 2);
+}
+
+#[test]
+fn flush_wire_ignores_budget() {
+    let mut q = SendQueues::new();
+    for _ in 0 .. 3 {
+        q.push(WireId(1), Priority::Low, chunk());
+    }
+    q.push(WireId(2), Priority::Low, chunk());
+
+    let mut sent = Vec::new();
+    q.flush_wire(WireId(1), |wire_id, msg| sent.push((wire_id, msg)));
+    assert_eq!(sent.len(), 3);
+    assert!(sent.iter().all(|&(w, _)| w == WireId(1)));
+
+    // Wire 2's message is still waiting.
+    let (sent, next) = flush(&mut q, 0);
+    assert_eq!(sent.len(), 1);
+    assert_eq!(next, None);
+}
+
+#[test]
+fn overflow_reports_wire() {
+    let mut q = SendQueues::new();
+    let n = MAX_QUEUED_BYTES / chunk().wire_size() + 1;
+    for _ in 0 .. n {
+        q.push(WireId(1), Priority::Low, chunk());
+    }
+    q.push(WireId(2), Priority::High, chat("hi"));
+
+    assert_eq!(q.take_overflowed(), vec![WireId(1)]);
+    assert_eq!(q.take_overflowed(), vec![]);
+    assert_eq!(q.stats().overflows, 1);
+
+    // Everything queued for the overflowed wire is discarded, including later messages.
+    q.push(WireId(1), Priority::High, chat("dropped"));
+    let (sent, _) = flush(&mut q, 0);
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].0, WireId(2));
+    assert_eq!(q.stats().queued_bytes, 0);
+}
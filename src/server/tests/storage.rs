@@ -0,0 +1,110 @@
+//! Round trips through each `Storage` backend.  The bundles are opaque to storage, so plain byte
+//! strings stand in for them.
+
+use std::io::{Read, Write};
+
+use types::*;
+
+use storage::{self, Storage, FileStorage, SqliteStorage, MemStorage};
+
+use super::harness::ScratchDir;
+
+
+fn put(f: Box<storage::SaveFile>, data: &[u8]) {
+    let mut f = f;
+    f.write_all(data).unwrap();
+    f.commit().unwrap();
+}
+
+fn get(f: Option<Box<Read>>) -> Option<Vec<u8>> {
+    f.map(|mut f| {
+        let mut buf = Vec::new();
+        f.read_to_end(&mut buf).unwrap();
+        buf
+    })
+}
+
+fn fill(s: &Storage) {
+    put(s.create_world_file(), b"world");
+    put(s.create_client_file("Alice"), b"alice");
+    put(s.create_client_file("Bob"), b"bob");
+    put(s.create_plane_file(Stable::new(2)), b"plane 2");
+    // Stable IDs with the high bit set must survive backends that store them as signed integers.
+    put(s.create_terrain_chunk_file(Stable::new(0xffff_0000_0000_0001)), b"chunk");
+}
+
+fn check(s: &Storage) {
+    assert_eq!(get(s.open_world_file()), Some(b"world".to_vec()));
+    assert_eq!(get(s.open_client_file("Alice")), Some(b"alice".to_vec()));
+    assert_eq!(get(s.open_client_file("Carol")), None);
+    assert_eq!(get(s.open_plane_file(Stable::new(2))), Some(b"plane 2".to_vec()));
+    assert_eq!(get(s.open_terrain_chunk_file(Stable::new(0xffff_0000_0000_0001))),
+               Some(b"chunk".to_vec()));
+
+    let mut clients = s.list_clients();
+    clients.sort();
+    assert_eq!(clients, vec!["Alice".to_owned(), "Bob".to_owned()]);
+    assert_eq!(s.list_planes(), vec![Stable::new(2)]);
+    assert_eq!(s.list_terrain_chunks(), vec![Stable::new(0xffff_0000_0000_0001)]);
+}
+
+fn overwrite_and_remove(s: &Storage) {
+    put(s.create_client_file("Alice"), b"alice 2");
+    assert_eq!(get(s.open_client_file("Alice")), Some(b"alice 2".to_vec()));
+
+    // An uncommitted write leaves the old contents in place.
+    {
+        let mut f = s.create_client_file("Alice");
+        f.write_all(b"discarded").unwrap();
+    }
+    assert_eq!(get(s.open_client_file("Alice")), Some(b"alice 2".to_vec()));
+
+    s.remove_client_file("Bob");
+    s.remove_plane_file(Stable::new(2));
+    s.remove_terrain_chunk_file(Stable::new(0xffff_0000_0000_0001));
+    assert_eq!(s.list_clients(), vec!["Alice".to_owned()]);
+    assert!(s.list_planes().is_empty());
+    assert!(s.list_terrain_chunks().is_empty());
+    assert_eq!(get(s.open_client_file("Bob")), None);
+}
+
+
+#[test]
+fn file_storage_round_trip() {
+    let dir = ScratchDir::new();
+    fill(&FileStorage::new(&dir.0));
+    // Everything must still be there when the save directory is opened again.
+    let s = FileStorage::new(&dir.0);
+    check(&s);
+    overwrite_and_remove(&s);
+}
+
+#[test]
+fn sqlite_storage_round_trip() {
+    let dir = ScratchDir::new();
+    fill(&SqliteStorage::new(&dir.0));
+    let s = SqliteStorage::new(&dir.0);
+    check(&s);
+    overwrite_and_remove(&s);
+}
+
+#[test]
+fn mem_storage_round_trip() {
+    let dir = ScratchDir::new();
+    let s = MemStorage::new(&dir.0);
+    fill(&s);
+    check(&s);
+    overwrite_and_remove(&s);
+}
+
+#[test]
+fn open_picks_backend() {
+    let dir = ScratchDir::new();
+    fill(&FileStorage::new(&dir.0));
+    let sqlite_dir = ScratchDir::new();
+    storage::copy_world(&FileStorage::new(&dir.0), &SqliteStorage::new(&sqlite_dir.0)).unwrap();
+
+    // The sqlite save has only the database, so `open` must pick the sqlite backend to see it.
+    check(&*storage::open(&sqlite_dir.0));
+    check(&*storage::open(&dir.0));
+}
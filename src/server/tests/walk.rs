@@ -0,0 +1,150 @@
+//! The walk-to-point activity: arriving, re-planning after getting stuck, and giving up.  The
+//! harness runs without scripts, so the `entity_walk_done` hook itself is a no-op here; these tests
+//! check the state `finish` leaves behind instead.
+
+use types::*;
+use libphysics::TILE_SIZE;
+
+use logic::walk;
+use pathfind;
+use world;
+use world::object::*;
+
+use super::harness::{self, Harness};
+
+
+/// Create an entity (with no behavior of its own) standing on the tile `offset` away from
+/// `wire`'s pawn.
+fn add_entity(h: &mut Harness, wire: WireId, offset: V3) -> EntityId {
+    let tile = pathfind::entity_tile(h.pawn_pos(wire)) + offset;
+    let pawn = h.pawn_id(wire);
+    let stable_pid = h.engine().world.entity(pawn).stable_plane_id();
+
+    let mut eng = h.engine().as_ref();
+    let mut wf = eng.as_world_fragment();
+    let e = world::Fragment::create_entity(&mut wf, stable_pid, tile * scalar(TILE_SIZE), 0, 0);
+    e.unwrap().id()
+}
+
+fn entity_tile(h: &mut Harness, eid: EntityId) -> V3 {
+    let now = h.now();
+    pathfind::entity_tile(h.engine().world.entity(eid).pos(now))
+}
+
+fn entity_pos(h: &mut Harness, eid: EntityId) -> V3 {
+    let now = h.now();
+    h.engine().world.entity(eid).pos(now)
+}
+
+fn start_walk(h: &mut Harness, eid: EntityId, tile: V3) -> bool {
+    walk::start(h.engine().as_ref(), eid, tile * scalar(TILE_SIZE), false).unwrap()
+}
+
+fn is_walking(h: &mut Harness, eid: EntityId) -> bool {
+    walk::is_walking(h.engine().as_ref(), eid)
+}
+
+fn is_stopped(h: &mut Harness, eid: EntityId) -> bool {
+    let m = h.engine().world.entity(eid).motion().clone();
+    m.start_pos == m.end_pos
+}
+
+fn is_waiting_to_retry(h: &mut Harness, eid: EntityId) -> bool {
+    h.engine().extra.entity_walk.get(&eid).map_or(false, |w| w.retry_timer().is_some())
+}
+
+/// Put a wall on top of the floor at `tile`, as in the `pathfind` tests.
+fn add_wall(h: &mut Harness, wire: WireId, tile: V3) {
+    let pawn = h.pawn_id(wire);
+    let pid = h.engine().world.entity(pawn).plane_id();
+    h.set_block(pid, tile + V3::new(0, 0, 1), 2);
+}
+
+
+#[test]
+fn walk_arrives_at_goal() {
+    harness::run(|h| {
+        let alice = h.login("Alice");
+        h.wait_for_terrain_gen();
+        let eid = add_entity(h, alice, V3::new(0, 3, 0));
+        let goal = entity_tile(h, eid) + V3::new(4, 2, 0);
+
+        assert!(start_walk(h, eid, goal));
+        assert_eq!(walk::goal(h.engine().as_ref(), eid), Some(goal));
+
+        // Six tiles at walking speed take a little under four seconds.
+        h.advance(5000);
+        assert!(!is_walking(h, eid));
+        assert_eq!(entity_pos(h, eid), goal * scalar(TILE_SIZE));
+        assert!(is_stopped(h, eid));
+    });
+}
+
+#[test]
+fn blocked_walk_replans_around_wall() {
+    harness::run(|h| {
+        let alice = h.login("Alice");
+        h.wait_for_terrain_gen();
+        let eid = add_entity(h, alice, V3::new(0, 3, 0));
+        let start = entity_tile(h, eid);
+        add_entity(h, alice, V3::new(3, 3, 0));
+        let goal = start + V3::new(6, 0, 0);
+
+        // Path planning ignores entities, so the walker heads straight for the other entity and
+        // gets stuck right in front of it.
+        assert!(start_walk(h, eid, goal));
+        h.advance(2000);
+        assert_eq!(entity_tile(h, eid), start + V3::new(2, 0, 0));
+        assert!(is_walking(h, eid));
+        assert!(is_waiting_to_retry(h, eid));
+
+        // With a wall under the blocker, the next attempt finds a detour.
+        add_wall(h, alice, start + V3::new(3, 0, 0));
+        h.advance(1000);
+        assert!(is_walking(h, eid));
+        assert!(!is_waiting_to_retry(h, eid));
+        assert!(entity_tile(h, eid).y != start.y);
+
+        h.advance(5000);
+        assert!(!is_walking(h, eid));
+        assert_eq!(entity_pos(h, eid), goal * scalar(TILE_SIZE));
+    });
+}
+
+#[test]
+fn stuck_walk_gives_up() {
+    harness::run(|h| {
+        let alice = h.login("Alice");
+        h.wait_for_terrain_gen();
+        let eid = add_entity(h, alice, V3::new(0, 3, 0));
+        let start = entity_tile(h, eid);
+        add_entity(h, alice, V3::new(3, 3, 0));
+
+        assert!(start_walk(h, eid, start + V3::new(6, 0, 0)));
+        h.advance(2000);
+        assert!(is_walking(h, eid));
+
+        // Every retry plans the same path straight into the blocker.  Eventually the walk ends
+        // where the entity got stuck.
+        h.advance(5000);
+        assert!(!is_walking(h, eid));
+        assert_eq!(entity_tile(h, eid), start + V3::new(2, 0, 0));
+        assert!(is_stopped(h, eid));
+    });
+}
+
+#[test]
+fn walk_to_unreachable_goal_is_refused() {
+    harness::run(|h| {
+        let alice = h.login("Alice");
+        h.wait_for_terrain_gen();
+        let eid = add_entity(h, alice, V3::new(0, 3, 0));
+        let goal = entity_tile(h, eid) + V3::new(4, 0, 0);
+        for &d in &[V3::new(1, 0, 0), V3::new(-1, 0, 0), V3::new(0, 1, 0), V3::new(0, -1, 0)] {
+            add_wall(h, alice, goal + d);
+        }
+
+        assert!(!start_walk(h, eid, goal));
+        assert!(!is_walking(h, eid));
+    });
+}
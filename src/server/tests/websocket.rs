@@ -0,0 +1,108 @@
+//! The handshake and framing halves of the native websocket transport.
+
+use std::io::{BufReader, Cursor};
+
+use websocket::frame::{self, OP_BINARY, OP_PING};
+use websocket::handshake;
+
+
+#[test]
+fn accept_key_matches_rfc_example() {
+    // The example from RFC 6455, section 1.3.
+    assert_eq!(handshake::accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+               "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+}
+
+#[test]
+fn handshake_accepts_upgrade() {
+    let req = "GET /ws HTTP/1.1\r\n\
+               Host: localhost:8888\r\n\
+               Upgrade: WebSocket\r\n\
+               Connection: keep-alive, Upgrade\r\n\
+               Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+               Sec-WebSocket-Version: 13\r\n\r\n";
+    let mut r = BufReader::new(req.as_bytes());
+    let mut out = Vec::new();
+    handshake::accept(&mut r, &mut out).unwrap();
+
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with("HTTP/1.1 101 "));
+    assert!(out.contains("\r\nSec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+}
+
+#[test]
+fn handshake_rejects_plain_http() {
+    let req = "GET /ws HTTP/1.1\r\n\
+               Host: localhost:8888\r\n\r\n";
+    let mut r = BufReader::new(req.as_bytes());
+    let mut out = Vec::new();
+    assert!(handshake::accept(&mut r, &mut out).is_err());
+    assert!(String::from_utf8(out).unwrap().starts_with("HTTP/1.1 400 "));
+}
+
+/// Build a masked frame, as a client would send it.
+fn client_frame(fin: bool, opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mask = [0x12, 0x34, 0x56, 0x78];
+    let mut buf = vec![(if fin { 0x80 } else { 0 }) | opcode];
+    if payload.len() < 126 {
+        buf.push(0x80 | payload.len() as u8);
+    } else {
+        buf.push(0x80 | 126);
+        buf.push((payload.len() >> 8) as u8);
+        buf.push(payload.len() as u8);
+    }
+    buf.extend_from_slice(&mask);
+    for (i, &b) in payload.iter().enumerate() {
+        buf.push(b ^ mask[i % 4]);
+    }
+    buf
+}
+
+#[test]
+fn read_masked_frames() {
+    let long = (0 .. 1000).map(|i| i as u8).collect::<Vec<_>>();
+    let mut buf = client_frame(true, OP_BINARY, b"hello");
+    buf.extend(client_frame(false, OP_BINARY, &long));
+    buf.extend(client_frame(true, OP_PING, b""));
+    let mut r = Cursor::new(buf);
+
+    let f = frame::read_frame(&mut r, 2000).unwrap();
+    assert!(f.fin);
+    assert_eq!(f.opcode, OP_BINARY);
+    assert_eq!(f.payload, b"hello");
+
+    let f = frame::read_frame(&mut r, 2000).unwrap();
+    assert!(!f.fin);
+    assert_eq!(f.payload, long);
+
+    let f = frame::read_frame(&mut r, 2000).unwrap();
+    assert_eq!(f.opcode, OP_PING);
+    assert!(f.payload.is_empty());
+}
+
+#[test]
+fn reject_bad_frames() {
+    // Too long.
+    let buf = client_frame(true, OP_BINARY, &[0; 200]);
+    assert!(frame::read_frame(&mut Cursor::new(buf), 100).is_err());
+
+    // Not masked.
+    let buf = vec![0x80 | OP_BINARY, 1, 0];
+    assert!(frame::read_frame(&mut Cursor::new(buf), 100).is_err());
+
+    // Fragmented control frame.
+    let buf = client_frame(false, OP_PING, b"x");
+    assert!(frame::read_frame(&mut Cursor::new(buf), 100).is_err());
+}
+
+#[test]
+fn write_frame_lengths() {
+    for &(len, header_len) in &[(5, 2), (200, 4), (70000, 10)] {
+        let mut buf = Vec::new();
+        frame::write_frame(&mut buf, OP_BINARY, &vec![7; len]).unwrap();
+        assert_eq!(buf.len(), header_len + len);
+        assert_eq!(buf[0], 0x80 | OP_BINARY);
+        // Server frames are never masked.
+        assert_eq!(buf[1] & 0x80, 0);
+    }
+}
@@ -31,6 +31,15 @@ impl Timer {
         }
     }
 
+    /// Create a timer that only fires when polled with `next_due`, for driving the `Engine`
+    /// deterministically in tests.
+    pub fn new_manual() -> Timer {
+        Timer {
+            queue: WakeQueue::new_manual(),
+            time_base: 0,
+        }
+    }
+
 
     // Keep track of the delta between world time and UTC.  The WakeQueue operates on UTC
     // exclusively, while the rest of the system uses world time, so we have to convent back and
@@ -66,6 +75,12 @@ impl Timer {
         cast_receiver(self.queue.receiver())
     }
 
+    /// For manual timers: get the earliest event scheduled at or before world time `until`.  Pass
+    /// the result to `process` to run it.
+    pub fn next_due(&self, until: Time) -> Option<TimerEvent> {
+        self.queue.next_due(self.from_world_time(until)).map(TimerEvent)
+    }
+
     pub fn process(&mut self, evt: TimerEvent) -> Option<(Box<FnBox(EngineRef)+'static>, Time)> {
         self.queue.retrieve(evt.0)
             .map(|(unix_when, cb)| (cb, self.world_time(unix_when)))
@@ -269,14 +269,17 @@ impl Cookie {
 #[derive(Debug)]
 struct WakeItem<T> {
     time: Time,
+    /// Order of scheduling, used to break ties between items with the same `time` in manual mode.
+    seq: u64,
     cancelled: bool,
     reason: T,
 }
 
 impl<T> WakeItem<T> {
-    fn new(time: Time, reason: T) -> WakeItem<T> {
+    fn new(time: Time, seq: u64, reason: T) -> WakeItem<T> {
         WakeItem {
             time: time,
+            seq: seq,
             cancelled: false,
             reason: reason,
         }
@@ -285,9 +288,11 @@ impl<T> WakeItem<T> {
 
 
 pub struct WakeQueue<T> {
-    send: Sender<Command>,
+    /// Command channel to the worker thread.  `None` for a manual queue, which has no worker.
+    send: Option<Sender<Command>>,
     recv: Receiver<Cookie>,
     items: IdMap<WakeItem<T>>,
+    next_seq: u64,
 }
 
 impl<T> WakeQueue<T> {
@@ -300,28 +305,65 @@ impl<T> WakeQueue<T> {
         });
 
         WakeQueue {
-            send: send_cmd,
+            send: Some(send_cmd),
             recv: recv_wake,
             items: IdMap::new(),
+            next_seq: 0,
+        }
+    }
+
+    /// Create a queue that doesn't follow the system clock.  Nothing is ever sent on its
+    /// `receiver()`; instead, the owner polls for items with `next_due`.  Used by tests that need
+    /// to control the passage of time.
+    pub fn new_manual() -> WakeQueue<T> {
+        let (_, recv_wake) = channel();
+
+        WakeQueue {
+            send: None,
+            recv: recv_wake,
+            items: IdMap::new(),
+            next_seq: 0,
         }
     }
 
     pub fn schedule(&mut self, when: Time, reason: T) -> Cookie {
-        let raw_cookie = self.items.insert(WakeItem::new(when, reason));
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let raw_cookie = self.items.insert(WakeItem::new(when, seq, reason));
         assert!(raw_cookie < (1 << COOKIE_BITS));
-        self.send.send(Command::Schedule(Wake::new(when, raw_cookie as u32))).unwrap();
+        if let Some(ref send) = self.send {
+            send.send(Command::Schedule(Wake::new(when, raw_cookie as u32))).unwrap();
+        }
         Cookie(raw_cookie as u32)
     }
 
     pub fn cancel(&mut self, cookie: Cookie) {
+        if self.send.is_none() {
+            // No worker holds a copy of the cookie, so the item can be dropped right away.
+            self.items.remove(cookie.0 as usize);
+            return;
+        }
+
         // Might have already been retrieved, since it's possible to get two duplicate Cookie
         // values.
         if let Some(item) = self.items.get_mut(cookie.0 as usize) {
-            self.send.send(Command::Cancel(Wake::new(item.time, cookie.0))).unwrap();
+            self.send.as_ref().unwrap()
+                .send(Command::Cancel(Wake::new(item.time, cookie.0))).unwrap();
             item.cancelled = true;
         }
     }
 
+    /// Manual mode only: get the cookie of the earliest item scheduled at or before `until`.
+    /// Items with the same time come out in the order they were scheduled.  The item stays in the
+    /// queue until it is `retrieve`d.
+    pub fn next_due(&self, until: Time) -> Option<Cookie> {
+        assert!(self.send.is_none(), "next_due is only supported on manual queues");
+        self.items.iter()
+            .filter(|&(_, item)| item.time <= until)
+            .min_by_key(|&(_, item)| (item.time, item.seq))
+            .map(|(raw, _)| Cookie(raw as u32))
+    }
+
     pub fn receiver(&self) -> &Receiver<Cookie> {
         &self.recv
     }
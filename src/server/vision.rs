@@ -78,6 +78,8 @@ pub trait Hooks {
     fn on_entity_disappear(&mut self, cid: ClientId, eid: EntityId) {}
     fn on_entity_motion_update(&mut self, cid: ClientId, eid: EntityId) {}
     fn on_entity_appearance_update(&mut self, cid: ClientId, eid: EntityId) {}
+    fn on_entity_health_update(&mut self, cid: ClientId, eid: EntityId) {}
+    fn on_entity_damage(&mut self, cid: ClientId, eid: EntityId, amount: u16) {}
 
     fn on_plane_change(&mut self,
                        cid: ClientId,
@@ -272,6 +274,13 @@ impl Vision {
         self.ps.message(&vid, |_, &cid| h.on_entity_motion_update(cid, eid));
     }
 
+    /// Check if any client can currently see entity `eid`.
+    pub fn entity_is_visible(&self, eid: EntityId) -> bool {
+        let mut visible = false;
+        self.ps.message(&ViewableId::Entity(eid), |_, _| visible = true);
+        visible
+    }
+
     pub fn update_entity_appearance<H>(&mut self,
                                        eid: EntityId,
                                        h: &mut H)
@@ -280,6 +289,25 @@ impl Vision {
                         |_, &cid| h.on_entity_appearance_update(cid, eid));
     }
 
+    pub fn update_entity_health<H>(&mut self,
+                                   eid: EntityId,
+                                   h: &mut H)
+            where H: Hooks {
+        self.ps.message(&ViewableId::Entity(eid),
+                        |_, &cid| h.on_entity_health_update(cid, eid));
+    }
+
+    /// Tell every client that can see entity `eid` that it took `amount` damage.  Unlike the other
+    /// entity updates, this is a one-off event, not a change in the entity's state.
+    pub fn show_entity_damage<H>(&mut self,
+                                 eid: EntityId,
+                                 amount: u16,
+                                 h: &mut H)
+            where H: Hooks {
+        self.ps.message(&ViewableId::Entity(eid),
+                        |_, &cid| h.on_entity_damage(cid, eid, amount));
+    }
+
 
     pub fn add_terrain_chunk<H>(&mut self,
                                 tcid: TerrainChunkId,
@@ -431,6 +459,8 @@ gen_Fragment! {
     fn remove_entity(eid: EntityId);
     fn set_entity_area(eid: EntityId, plane: PlaneId, area: SmallSet<V2>);
     fn update_entity_appearance(eid: EntityId);
+    fn update_entity_health(eid: EntityId);
+    fn show_entity_damage(eid: EntityId, amount: u16);
 
     fn add_terrain_chunk(tcid: TerrainChunkId, plane: PlaneId, cpos: V2);
     fn remove_terrain_chunk(tcid: TerrainChunkId);
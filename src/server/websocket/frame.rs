@@ -0,0 +1,116 @@
+//! Reading and writing individual WebSocket frames (RFC 6455, section 5).
+use std::io::{self, Read, Write};
+
+
+pub const OP_CONTINUATION: u8 = 0x0;
+pub const OP_TEXT: u8 = 0x1;
+pub const OP_BINARY: u8 = 0x2;
+pub const OP_CLOSE: u8 = 0x8;
+pub const OP_PING: u8 = 0x9;
+pub const OP_PONG: u8 = 0xa;
+
+
+pub struct Frame {
+    pub fin: bool,
+    pub opcode: u8,
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    pub fn is_control(&self) -> bool {
+        self.opcode & 0x8 != 0
+    }
+}
+
+
+fn bad_frame(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn read_exact<R: Read>(r: &mut R, buf: &mut [u8]) -> io::Result<()> {
+    let mut base = 0;
+    while base < buf.len() {
+        let n = try!(r.read(&mut buf[base..]));
+        if n == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "EOF while reading frame"));
+        }
+        base += n;
+    }
+    Ok(())
+}
+
+/// Read one frame sent by a client.  Client frames are always masked; the returned payload has
+/// the mask already removed.  Frames with payloads longer than `max_len` are rejected without
+/// reading the payload.
+pub fn read_frame<R: Read>(r: &mut R, max_len: usize) -> io::Result<Frame> {
+    let mut header = [0; 2];
+    try!(read_exact(r, &mut header));
+
+    let fin = header[0] & 0x80 != 0;
+    if header[0] & 0x70 != 0 {
+        return Err(bad_frame("reserved bits set, but no extensions were negotiated"));
+    }
+    let opcode = header[0] & 0x0f;
+    if header[1] & 0x80 == 0 {
+        return Err(bad_frame("client frame is not masked"));
+    }
+
+    let len = match header[1] & 0x7f {
+        126 => {
+            let mut buf = [0; 2];
+            try!(read_exact(r, &mut buf));
+            (buf[0] as u64) << 8 | buf[1] as u64
+        },
+        127 => {
+            let mut buf = [0; 8];
+            try!(read_exact(r, &mut buf));
+            buf.iter().fold(0, |acc, &b| acc << 8 | b as u64)
+        },
+        x => x as u64,
+    };
+    if len > max_len as u64 {
+        return Err(bad_frame("frame is too long"));
+    }
+
+    let mut mask = [0; 4];
+    try!(read_exact(r, &mut mask));
+
+    let mut payload = vec![0; len as usize];
+    try!(read_exact(r, &mut payload));
+    for (i, b) in payload.iter_mut().enumerate() {
+        *b ^= mask[i % 4];
+    }
+
+    let frame = Frame {
+        fin: fin,
+        opcode: opcode,
+        payload: payload,
+    };
+    if frame.is_control() && (!frame.fin || frame.payload.len() > 125) {
+        return Err(bad_frame("control frames must be short and unfragmented"));
+    }
+    Ok(frame)
+}
+
+/// Write a complete, unmasked message in a single frame, as the server side of the connection.
+pub fn write_frame<W: Write>(w: &mut W, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut header = Vec::with_capacity(10);
+    header.push(0x80 | opcode);
+    if payload.len() < 126 {
+        header.push(payload.len() as u8);
+    } else if payload.len() <= 0xffff {
+        header.push(126);
+        header.push((payload.len() >> 8) as u8);
+        header.push(payload.len() as u8);
+    } else {
+        header.push(127);
+        let len = payload.len() as u64;
+        for i in 0 .. 8 {
+            header.push((len >> (56 - 8 * i)) as u8);
+        }
+    }
+
+    try!(w.write_all(&header));
+    try!(w.write_all(payload));
+    w.flush()
+}
@@ -0,0 +1,154 @@
+//! The HTTP side of the WebSocket protocol: reading the client's upgrade request and producing the
+//! `101 Switching Protocols` response.
+use std::ascii::AsciiExt;
+use std::io::{self, BufRead, Read, Write};
+use std::num::Wrapping;
+use rustc_serialize::base64::{ToBase64, STANDARD};
+
+
+/// Upper bound on the size of the upgrade request, including all headers.
+const MAX_REQUEST_SIZE: u64 = 8 * 1024;
+
+const ACCEPT_GUID: &'static str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+
+fn bad_request(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Read the client's upgrade request and reply to it.  On success, the connection is ready for
+/// WebSocket frames.  On failure, the client has been sent an error response, and the caller
+/// should drop the connection.
+pub fn accept<R: BufRead, W: Write>(r: &mut R, w: &mut W) -> io::Result<()> {
+    let key = match read_request(r) {
+        Ok(key) => key,
+        Err(e) => {
+            let _ = w.write_all(b"HTTP/1.1 400 Bad Request\r\n\
+                                  Connection: close\r\n\
+                                  Content-Length: 0\r\n\r\n");
+            return Err(e);
+        },
+    };
+
+    try!(write!(w, "HTTP/1.1 101 Switching Protocols\r\n\
+                    Upgrade: websocket\r\n\
+                    Connection: Upgrade\r\n\
+                    Sec-WebSocket-Accept: {}\r\n\r\n",
+                accept_key(&key)));
+    w.flush()
+}
+
+/// Read the request line and headers, and return the `Sec-WebSocket-Key`.
+fn read_request<R: BufRead>(r: &mut R) -> io::Result<String> {
+    let mut r = r.take(MAX_REQUEST_SIZE);
+    let mut line = String::new();
+
+    try!(r.read_line(&mut line));
+    if !line.starts_with("GET ") || !line.trim_right().ends_with(" HTTP/1.1") {
+        return Err(bad_request("expected a GET request"));
+    }
+
+    let mut upgrade = false;
+    let mut version_ok = false;
+    let mut key = None;
+    loop {
+        line.clear();
+        if try!(r.read_line(&mut line)) == 0 {
+            return Err(bad_request("request ended before end of headers"));
+        }
+        let line = line.trim_right();
+        if line.len() == 0 {
+            break;
+        }
+
+        let colon = unwrap_or!(line.find(':'), return Err(bad_request("malformed header")));
+        let name = line[.. colon].trim().to_lowercase();
+        let value = line[colon + 1 ..].trim();
+        match &name as &str {
+            "upgrade" => upgrade = value.eq_ignore_ascii_case("websocket"),
+            "sec-websocket-version" => version_ok = value == "13",
+            "sec-websocket-key" => key = Some(value.to_owned()),
+            _ => {},
+        }
+    }
+
+    if !upgrade {
+        return Err(bad_request("not a websocket upgrade request"));
+    }
+    if !version_ok {
+        return Err(bad_request("unsupported websocket version"));
+    }
+    key.ok_or_else(|| bad_request("missing Sec-WebSocket-Key"))
+}
+
+/// Compute the `Sec-WebSocket-Accept` value for a client's key.
+pub fn accept_key(key: &str) -> String {
+    let mut input = key.as_bytes().to_owned();
+    input.extend_from_slice(ACCEPT_GUID.as_bytes());
+    sha1(&input).to_base64(STANDARD)
+}
+
+
+/// SHA-1, which the handshake requires.  It's only used on the short accept key, so this makes no
+/// attempt to be fast.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h = [Wrapping(0x67452301_u32),
+                 Wrapping(0xefcdab89),
+                 Wrapping(0x98badcfe),
+                 Wrapping(0x10325476),
+                 Wrapping(0xc3d2e1f0)];
+
+    let mut msg = data.to_owned();
+    msg.push(0x80);
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+    let bit_len = (data.len() as u64) * 8;
+    for i in 0 .. 8 {
+        msg.push((bit_len >> (56 - 8 * i)) as u8);
+    }
+
+    for block in msg.chunks(64) {
+        let mut w = [Wrapping(0_u32); 80];
+        for i in 0 .. 16 {
+            w[i] = Wrapping((block[4 * i] as u32) << 24 |
+                            (block[4 * i + 1] as u32) << 16 |
+                            (block[4 * i + 2] as u32) << 8 |
+                            (block[4 * i + 3] as u32));
+        }
+        for i in 16 .. 80 {
+            let x = w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16];
+            w[i] = Wrapping(x.0.rotate_left(1));
+        }
+
+        let (mut a, mut b, mut c, mut d, mut e) = (h[0], h[1], h[2], h[3], h[4]);
+        for i in 0 .. 80 {
+            let (f, k) = match i {
+                0 ... 19 => ((b & c) | (!b & d), 0x5a827999),
+                20 ... 39 => (b ^ c ^ d, 0x6ed9eba1),
+                40 ... 59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let tmp = Wrapping(a.0.rotate_left(5)) + f + e + Wrapping(k) + w[i];
+            e = d;
+            d = c;
+            c = Wrapping(b.0.rotate_left(30));
+            b = a;
+            a = tmp;
+        }
+
+        h[0] = h[0] + a;
+        h[1] = h[1] + b;
+        h[2] = h[2] + c;
+        h[3] = h[3] + d;
+        h[4] = h[4] + e;
+    }
+
+    let mut result = [0; 20];
+    for i in 0 .. 5 {
+        for j in 0 .. 4 {
+            result[4 * i + j] = (h[i].0 >> (24 - 8 * j)) as u8;
+        }
+    }
+    result
+}
@@ -0,0 +1,303 @@
+//! An in-process WebSocket transport, for running the server without the wrapper.  It accepts
+//! connections itself, assigns each one a `WireId`, and feeds the same request and response
+//! channels that `tasks::run_input` and `tasks::run_output` would otherwise connect to
+//! stdin/stdout.  From the engine's point of view the two are indistinguishable: new connections
+//! show up as `AddClient` on the control wire, and disconnects as `RemoveClient`.
+//!
+//! This is meant for development and tests.  Each connection gets a thread for reading and one
+//! for writing, so a client that stops reading only stalls its own output.  Such a client is
+//! disconnected once `OUTPUT_QUEUE_LEN` messages back up for it, or a single write blocks for
+//! `WRITE_TIMEOUT_SECS`.  There is no REPL or control socket, so the server can only be stopped by
+//! killing the process.
+use std::collections::HashMap;
+use std::io::{self, BufReader, Read};
+use std::net::{TcpListener, TcpStream, Shutdown, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Sender, Receiver, SyncSender, TrySendError};
+use std::thread;
+use std::time::Duration;
+use std::u16;
+
+use types::*;
+
+use msg::{Request, Response};
+use wire::{WireReader, WireWriter};
+
+use self::frame::{OP_CONTINUATION, OP_TEXT, OP_BINARY, OP_CLOSE, OP_PING, OP_PONG};
+
+pub mod frame;
+pub mod handshake;
+
+
+/// Largest message a client may send.  The body of a message, including its opcode, must fit in
+/// the 16-bit length field of the wire header.
+const MAX_MESSAGE_SIZE: usize = u16::MAX as usize;
+
+/// How long a write to a single client may block before the client is disconnected.
+const WRITE_TIMEOUT_SECS: u64 = 10;
+
+/// Number of messages that may wait for a connection's writer thread before the client is
+/// disconnected.
+const OUTPUT_QUEUE_LEN: usize = 1024;
+
+
+enum Output {
+    Frame(u8, Vec<u8>),
+    /// Send a close frame, then shut down the connection.
+    Close,
+}
+
+struct Conn {
+    /// Messages for the connection's writer thread.
+    output: SyncSender<Output>,
+    /// Used only to shut down the connection, which wakes up both of its threads.
+    stream: TcpStream,
+    /// Cleared when the client closes its end of the connection.
+    client_connected: bool,
+    /// Cleared when the engine reports the wire closed with `ClientRemoved`.
+    backend_connected: bool,
+}
+
+/// The set of open connections.  Like the wrapper, this keeps a connection's `WireId` reserved
+/// until both the client and the engine are done with it, so a new connection can never receive
+/// messages meant for an old one.
+struct Conns {
+    next_id: u16,
+    conns: HashMap<WireId, Conn>,
+}
+
+impl Conns {
+    fn new() -> Conns {
+        Conns {
+            next_id: 1,
+            conns: HashMap::new(),
+        }
+    }
+
+    fn add(&mut self, output: SyncSender<Output>, stream: TcpStream) -> WireId {
+        while self.next_id == CONTROL_WIRE_ID.unwrap() ||
+              self.conns.contains_key(&WireId(self.next_id)) {
+            self.next_id = self.next_id.wrapping_add(1);
+        }
+        let wire_id = WireId(self.next_id);
+        self.next_id = self.next_id.wrapping_add(1);
+
+        self.conns.insert(wire_id, Conn {
+            output: output,
+            stream: stream,
+            client_connected: true,
+            backend_connected: true,
+        });
+        wire_id
+    }
+
+    /// Queue a message for `wire_id`'s writer thread.  This never blocks.
+    fn send(&self, wire_id: WireId, output: Output) {
+        let conn = unwrap_or!(self.conns.get(&wire_id), return);
+        if !conn.client_connected {
+            return;
+        }
+
+        match conn.output.try_send(output) {
+            Ok(()) => {},
+            Err(TrySendError::Full(_)) => {
+                warn!("{:?} is not keeping up with its output; disconnecting", wire_id);
+                // The reading thread will see the shutdown and report the client as gone.
+                let _ = conn.stream.shutdown(Shutdown::Both);
+            },
+            // The writer thread already failed and shut down the connection.
+            Err(TrySendError::Disconnected(_)) => {},
+        }
+    }
+
+    /// Record that the client side of `wire_id` has closed.  Returns `true` if the engine still
+    /// needs to be told.
+    fn client_closed(&mut self, wire_id: WireId) -> bool {
+        let backend_connected = {
+            let conn = unwrap_or!(self.conns.get_mut(&wire_id), return false);
+            conn.client_connected = false;
+            conn.backend_connected
+        };
+        if !backend_connected {
+            self.conns.remove(&wire_id);
+        }
+        backend_connected
+    }
+
+    /// Record that the engine is done with `wire_id`, and close the client side if it's still
+    /// open.
+    fn backend_closed(&mut self, wire_id: WireId) {
+        let client_connected = {
+            let conn = unwrap_or!(self.conns.get_mut(&wire_id), return);
+            conn.backend_connected = false;
+            conn.client_connected
+        };
+        if client_connected {
+            self.send(wire_id, Output::Close);
+        } else {
+            self.conns.remove(&wire_id);
+        }
+    }
+}
+
+
+/// Start accepting WebSocket connections on `addr`.  Requests from clients go to `send`, and
+/// responses from `recv` go back out to the clients.  Returns once the listening socket is set
+/// up; everything else happens on background threads.
+pub fn listen<A: ToSocketAddrs>(addr: A,
+                                send: Sender<(WireId, Request)>,
+                                recv: Receiver<(WireId, Response)>) -> io::Result<()> {
+    let listener = try!(TcpListener::bind(addr));
+    info!("listening for websocket connections on {}", try!(listener.local_addr()));
+    let conns = Arc::new(Mutex::new(Conns::new()));
+
+    {
+        let conns = conns.clone();
+        thread::spawn(move || run_output(conns, recv));
+    }
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(x) => x,
+                Err(e) => {
+                    warn!("error accepting connection: {}", e);
+                    continue;
+                },
+            };
+            let conns = conns.clone();
+            let send = send.clone();
+            thread::spawn(move || warn_on_err!(run_conn(stream, conns, send)));
+        }
+    });
+
+    Ok(())
+}
+
+fn run_conn(stream: TcpStream,
+            conns: Arc<Mutex<Conns>>,
+            send: Sender<(WireId, Request)>) -> io::Result<()> {
+    let mut r = BufReader::new(try!(stream.try_clone()));
+    let mut w = stream;
+    try!(handshake::accept(&mut r, &mut w));
+    try!(w.set_write_timeout(Some(Duration::from_secs(WRITE_TIMEOUT_SECS))));
+
+    let peer = try!(w.peer_addr());
+    let (output_send, output_recv) = mpsc::sync_channel(OUTPUT_QUEUE_LEN);
+    let wire_id = conns.lock().unwrap().add(output_send, try!(w.try_clone()));
+    info!("websocket connection from {} is {:?}", peer, wire_id);
+    thread::spawn(move || run_writer(wire_id, w, output_recv));
+    send.send((CONTROL_WIRE_ID, Request::AddClient(wire_id))).unwrap();
+
+    let result = read_messages(&mut r, wire_id, &conns, &send);
+
+    if conns.lock().unwrap().client_closed(wire_id) {
+        send.send((CONTROL_WIRE_ID, Request::RemoveClient(wire_id))).unwrap();
+    }
+    result
+}
+
+fn read_messages<R: Read>(r: &mut R,
+                          wire_id: WireId,
+                          conns: &Mutex<Conns>,
+                          send: &Sender<(WireId, Request)>) -> io::Result<()> {
+    let mut msg = Vec::new();
+    loop {
+        let frame = try!(frame::read_frame(r, MAX_MESSAGE_SIZE));
+        match frame.opcode {
+            OP_CONTINUATION | OP_TEXT | OP_BINARY => {
+                if msg.len() + frame.payload.len() > MAX_MESSAGE_SIZE {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "message is too long"));
+                }
+                msg.extend_from_slice(&frame.payload);
+                if frame.fin {
+                    match decode_request(wire_id, &msg) {
+                        Ok(req) => send.send((wire_id, req)).unwrap(),
+                        Err(e) => warn!("error reading message from {:?}: {}", wire_id, e),
+                    }
+                    msg.clear();
+                }
+            },
+
+            OP_PING => conns.lock().unwrap().send(wire_id, Output::Frame(OP_PONG, frame.payload)),
+
+            OP_PONG => {},
+
+            OP_CLOSE => {
+                // Echo the status code to complete the closing handshake.
+                let mut payload = frame.payload;
+                payload.truncate(2);
+                if payload.len() < 2 {
+                    payload.clear();
+                }
+                conns.lock().unwrap().send(wire_id, Output::Frame(OP_CLOSE, payload));
+                return Ok(());
+            },
+
+            op => {
+                let msg = format!("unknown frame opcode {:x}", op);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+            },
+        }
+    }
+}
+
+/// Decode the body of a client message, which is everything after the wire header.
+fn decode_request(wire_id: WireId, body: &[u8]) -> io::Result<Request> {
+    let mut buf = Vec::with_capacity(4 + body.len());
+    buf.push(wire_id.unwrap() as u8);
+    buf.push((wire_id.unwrap() >> 8) as u8);
+    buf.push(body.len() as u8);
+    buf.push((body.len() >> 8) as u8);
+    buf.extend_from_slice(body);
+
+    let (_, req) = try!(Request::read_from(&mut WireReader::new(&buf[..])));
+    Ok(req)
+}
+
+/// Encode a response, and strip off the wire header to leave only the message body.
+fn encode_response(wire_id: WireId, resp: &Response) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(resp.wire_size());
+    try!(resp.write_to(wire_id, &mut WireWriter::new(&mut buf)));
+    Ok(buf.split_off(4))
+}
+
+fn run_output(conns: Arc<Mutex<Conns>>, recv: Receiver<(WireId, Response)>) {
+    // The engine drops its end of the channel when it shuts down.
+    while let Ok((wire_id, resp)) = recv.recv() {
+        if wire_id == CONTROL_WIRE_ID {
+            match resp {
+                Response::ClientRemoved(wire_id) => conns.lock().unwrap().backend_closed(wire_id),
+                _ => warn!("unexpected control response: {:?}", resp),
+            }
+            continue;
+        }
+
+        match encode_response(wire_id, &resp) {
+            Ok(body) => conns.lock().unwrap().send(wire_id, Output::Frame(OP_BINARY, body)),
+            Err(e) => warn!("error encoding response for {:?}: {}", wire_id, e),
+        }
+    }
+}
+
+/// Write queued messages to one connection.  Runs until the connection is removed from `Conns`
+/// (which drops the sending side of `recv`) or a write fails.
+fn run_writer(wire_id: WireId, mut stream: TcpStream, recv: Receiver<Output>) {
+    for output in recv.iter() {
+        let result = match output {
+            Output::Frame(opcode, payload) => frame::write_frame(&mut stream, opcode, &payload),
+            Output::Close => {
+                // Status code 1000, "normal closure".
+                let _ = frame::write_frame(&mut stream, OP_CLOSE, &[0x03, 0xe8]);
+                break;
+            },
+        };
+        if let Err(e) = result {
+            warn!("error sending to {:?}: {}", wire_id, e);
+            break;
+        }
+    }
+    // If the client is still connected, the reading thread will see the shutdown and report it
+    // as gone.
+    let _ = stream.shutdown(Shutdown::Both);
+}
@@ -88,6 +88,8 @@ impl<'d> Exporter<'d> {
 
     pub fn finish(&self) -> b::Bundle {
         b::Bundle {
+            version: b::CURRENT_SAVE_VERSION,
+
             anims: convert_str_vec(&self.anims.vals),
             items: convert_str_vec(&self.items.vals),
             blocks: convert_str_vec(&self.blocks.vals),
@@ -271,6 +273,7 @@ impl<'d> Exporter<'d> {
             facing: e.facing,
             target_velocity: e.target_velocity,
             appearance: e.appearance,
+            health: e.health,
 
             extra: self.export(&e.extra),
             stable_id: e.stable_id,
@@ -150,6 +150,7 @@ impl<'d> Importer<'d> {
         e.facing = b.facing;
         e.target_velocity = b.target_velocity;
         e.appearance = b.appearance;
+        e.health = b.health;
 
         e.extra = self.import(&b.extra);
         e.stable_id = b.stable_id;
@@ -0,0 +1,85 @@
+//! Upgrades for bundles saved by older versions of the server.  Each `Migration` takes a bundle
+//! from one save version to the next.  `read_bundle` runs the chain automatically, so the rest of
+//! the server only ever sees bundles at `CURRENT_SAVE_VERSION`.
+//!
+//! To change the meaning of saved data: bump `CURRENT_SAVE_VERSION` in `libserver_bundle::types`,
+//! then add a `Migration` to `MIGRATIONS` whose `from` is the old version.  Renaming a block, item,
+//! animation, or template only needs a call to one of the `rename_*` helpers, since bundles refer
+//! to those by name through their string tables.
+
+use world::bundle::types::*;
+use world::bundle::Result;
+
+
+pub struct Migration {
+    /// The version this migration applies to.  The result has version `from + 1`.
+    pub from: u32,
+    /// Short description, for the log.
+    pub desc: &'static str,
+    pub apply: fn(&mut Bundle),
+}
+
+static MIGRATIONS: [Migration; 1] = [
+    Migration {
+        from: 0,
+        desc: "add save version",
+        apply: v0_add_save_version,
+    },
+];
+
+
+/// Upgrade `b` to `CURRENT_SAVE_VERSION` by applying each registered migration in turn.
+pub fn upgrade(b: &mut Bundle) -> Result<()> {
+    if b.version > CURRENT_SAVE_VERSION {
+        fail!("bundle was saved by a newer version of the server");
+    }
+
+    while b.version < CURRENT_SAVE_VERSION {
+        let m = unwrap!(MIGRATIONS.iter().find(|m| m.from == b.version),
+                        "no migration registered for bundle's save version");
+        info!("upgrading bundle from save version {} to {}: {}",
+              b.version, b.version + 1, m.desc);
+        (m.apply)(b);
+        b.version += 1;
+    }
+
+    Ok(())
+}
+
+
+// Helpers for common migrations
+
+fn rename(table: &mut [Box<str>], renames: &[(&str, &str)]) {
+    for s in table.iter_mut() {
+        if let Some(&(_, new)) = renames.iter().find(|&&(old, _)| old == &**s) {
+            *s = new.to_owned().into_boxed_str();
+        }
+    }
+}
+
+/// Rename animations.  Each entry in `renames` is an `(old, new)` pair.
+pub fn rename_anims(b: &mut Bundle, renames: &[(&str, &str)]) {
+    rename(&mut b.anims, renames);
+}
+
+/// Rename items.  Each entry in `renames` is an `(old, new)` pair.
+pub fn rename_items(b: &mut Bundle, renames: &[(&str, &str)]) {
+    rename(&mut b.items, renames);
+}
+
+/// Rename blocks.  Each entry in `renames` is an `(old, new)` pair.
+pub fn rename_blocks(b: &mut Bundle, renames: &[(&str, &str)]) {
+    rename(&mut b.blocks, renames);
+}
+
+/// Rename structure templates.  Each entry in `renames` is an `(old, new)` pair.
+pub fn rename_templates(b: &mut Bundle, renames: &[(&str, &str)]) {
+    rename(&mut b.templates, renames);
+}
+
+
+// Migrations
+
+/// Version 1 introduced the save version itself.  The contents are otherwise unchanged.
+fn v0_add_save_version(_b: &mut Bundle) {
+}
@@ -11,13 +11,16 @@ pub use libserver_bundle::*;
 
 pub mod export;
 pub mod import;
+pub mod migrate;
 
 
 pub fn read_bundle<R: ::std::io::Read>(r: &mut R) -> Result<Bundle> {
     let mut v = Vec::new();
     try!(r.read_to_end(&mut v));
     let f = try!(flat::FlatView::from_bytes(&v));
-    Ok(f.unflatten_bundle())
+    let mut b = f.unflatten_bundle();
+    try!(migrate::upgrade(&mut b));
+    Ok(b)
 }
 
 pub fn write_bundle<W: ::std::io::Write>(w: &mut W, b: &Bundle) -> Result<()> {
@@ -17,6 +17,7 @@ pub trait Hooks {
     fn on_entity_activity_change(&mut self, eid: EntityId) {}
     fn on_entity_motion_change(&mut self, eid: EntityId) {}
     fn on_entity_appearance_change(&mut self, eid: EntityId) {}
+    fn on_entity_health_change(&mut self, eid: EntityId) {}
     fn on_entity_plane_change(&mut self, eid: EntityId) {}
 
     fn on_inventory_create(&mut self, iid: InventoryId) {}
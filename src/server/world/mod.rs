@@ -15,6 +15,7 @@ pub use self::types::{
     InventoryAttachment,
     Activity,
     Motion,
+    Health,
 };
 pub use self::world::{EntitiesById, StructuresById, InventoriesById};
 pub use libserver_extra as extra;
@@ -68,6 +69,10 @@ pub struct World<'d> {
     /// Entities indexed by their containing plane.  Entities in PLANE_LIMBO are not included here.
     entities_by_plane: HashMap<PlaneId, HashSet<EntityId>>,
 
+    /// Entities indexed by the chunks their current motion passes through.  Like
+    /// `entities_by_plane`, this doesn't include entities in PLANE_LIMBO.
+    entities_by_chunk: HashMap<(PlaneId, V2), HashSet<EntityId>>,
+
     /// Entities in PLANE_LIMBO, indexed by the stable ID of their containing plane.  When a plane
     /// is loaded, its entities will automatically be moved out of limbo.
     limbo_entities: HashMap<Stable<PlaneId>, HashSet<EntityId>>,
@@ -104,6 +109,7 @@ pub struct Entity {
     facing: V3,
     target_velocity: V3,
     appearance: u32,
+    health: Health,
 
     extra: Extra,
     stable_id: StableId,
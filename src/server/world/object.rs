@@ -11,7 +11,7 @@ use world::{Client, Entity, Inventory, Plane, TerrainChunk, Structure};
 use world::{EntitiesById, StructuresById, InventoriesById};
 use world::{EntityAttachment, StructureAttachment, InventoryAttachment};
 use world::{TerrainChunkFlags, StructureFlags};
-use world::{Activity, Motion};
+use world::{Activity, Motion, Health};
 use world::Item;
 use world::fragment::Fragment;
 use world::hooks::Hooks;
@@ -297,9 +297,7 @@ pub trait EntityRefMut<'d, F: Fragment<'d>>: ObjectRefMutBase<'d, Entity, F> {
 
     fn set_motion(&mut self, motion: Motion) {
         let eid = self.id();
-        // TODO: update entity-by-chunk cache
-        self.obj_mut().motion = motion;
-        self.fragment_mut().with_hooks(|h| h.on_entity_motion_change(eid));
+        ops::entity::set_motion(self.fragment_mut(), eid, motion).unwrap();
     }
 
     fn set_appearance(&mut self, appearance: u32) {
@@ -308,6 +306,12 @@ pub trait EntityRefMut<'d, F: Fragment<'d>>: ObjectRefMutBase<'d, Entity, F> {
         self.fragment_mut().with_hooks(|h| h.on_entity_appearance_change(eid));
     }
 
+    fn set_health(&mut self, health: Health) {
+        let eid = self.id();
+        self.obj_mut().health = health;
+        self.fragment_mut().with_hooks(|h| h.on_entity_health_change(eid));
+    }
+
     fn set_attachment(&mut self, attach: EntityAttachment) -> OpResult<EntityAttachment> {
         let eid = self.id();
         ops::entity::attach(self.fragment_mut(), eid, attach)
@@ -1,10 +1,12 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::mem::replace;
 
+use libphysics::{CHUNK_SIZE, TILE_SIZE};
 use types::*;
 use util::{multimap_insert, multimap_remove};
 
-use world::{Entity, EntityAttachment, Motion, Activity};
+use world::{Entity, EntityAttachment, Motion, Activity, Health};
+use world::types::DEFAULT_MAX_HEALTH;
 use world::{Fragment, Hooks};
 use world::extra::Extra;
 use world::ops::{self, OpResult};
@@ -27,6 +29,7 @@ pub fn create<'d, F>(f: &mut F,
         facing: V3::new(1, 0, 0),
         target_velocity: scalar(0),
         appearance: appearance,
+        health: Health::new(DEFAULT_MAX_HEALTH),
 
         extra: Extra::new(),
         stable_id: NO_STABLE_ID,
@@ -52,6 +55,7 @@ pub fn create_unchecked<'d, F>(f: &mut F) -> EntityId
         facing: scalar(0),
         target_velocity: scalar(0),
         appearance: 0,
+        health: Health::new(DEFAULT_MAX_HEALTH),
 
         extra: Extra::new(),
         stable_id: NO_STABLE_ID,
@@ -73,6 +77,7 @@ pub fn post_init<'d, F>(f: &mut F,
         multimap_insert(&mut w.limbo_entities, e.stable_plane, eid);
     } else {
         multimap_insert(&mut w.entities_by_plane, e.plane, eid);
+        add_to_lookup(&mut w.entities_by_chunk, eid, e.plane, &e.motion);
     }
 }
 
@@ -86,6 +91,7 @@ pub fn pre_fini<'d, F>(f: &mut F,
         multimap_remove(&mut w.limbo_entities, e.stable_plane, eid);
     } else {
         multimap_remove(&mut w.entities_by_plane, e.plane, eid);
+        remove_from_lookup(&mut w.entities_by_chunk, eid, e.plane, &e.motion);
     }
 }
 
@@ -196,12 +202,14 @@ pub fn set_stable_plane<'d, F>(f: &mut F,
             multimap_remove(&mut w.limbo_entities, old_stable_pid, eid);
         } else {
             multimap_remove(&mut w.entities_by_plane, old_pid, eid);
+            remove_from_lookup(&mut w.entities_by_chunk, eid, old_pid, &e.motion);
         }
 
         if new_pid == PLANE_LIMBO {
             multimap_insert(&mut w.limbo_entities, new_stable_pid, eid);
         } else {
             multimap_insert(&mut w.entities_by_plane, new_pid, eid);
+            add_to_lookup(&mut w.entities_by_chunk, eid, new_pid, &e.motion);
         }
 
         e.plane = new_pid;
@@ -211,3 +219,48 @@ pub fn set_stable_plane<'d, F>(f: &mut F,
     f.with_hooks(|h| h.on_entity_plane_change(eid));
     Ok(())
 }
+
+pub fn set_motion<'d, F>(f: &mut F,
+                         eid: EntityId,
+                         motion: Motion) -> OpResult<()>
+        where F: Fragment<'d> {
+    {
+        let w = f.world_mut();
+        let e = unwrap!(w.entities.get_mut(eid));
+        if e.plane != PLANE_LIMBO {
+            remove_from_lookup(&mut w.entities_by_chunk, eid, e.plane, &e.motion);
+            add_to_lookup(&mut w.entities_by_chunk, eid, e.plane, &motion);
+        }
+        e.motion = motion;
+    }
+
+    f.with_hooks(|h| h.on_entity_motion_change(eid));
+    Ok(())
+}
+
+
+/// Get the range of chunks containing every point along `motion`.
+fn motion_chunks(motion: &Motion) -> Region<V2> {
+    let chunk_px = CHUNK_SIZE * TILE_SIZE;
+    let a = motion.start_pos.reduce().div_floor(scalar(chunk_px));
+    let b = motion.end_pos.reduce().div_floor(scalar(chunk_px));
+    Region::new(a, a + scalar(1)).join(Region::new(b, b + scalar(1)))
+}
+
+pub fn add_to_lookup(lookup: &mut HashMap<(PlaneId, V2), HashSet<EntityId>>,
+                     eid: EntityId,
+                     pid: PlaneId,
+                     motion: &Motion) {
+    for cpos in motion_chunks(motion).points() {
+        multimap_insert(lookup, (pid, cpos), eid);
+    }
+}
+
+pub fn remove_from_lookup(lookup: &mut HashMap<(PlaneId, V2), HashSet<EntityId>>,
+                          eid: EntityId,
+                          pid: PlaneId,
+                          motion: &Motion) {
+    for cpos in motion_chunks(motion).points() {
+        multimap_remove(lookup, (pid, cpos), eid);
+    }
+}
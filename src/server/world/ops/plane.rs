@@ -51,7 +51,9 @@ pub fn post_init<'d, F>(f: &mut F,
     if let Some(eids) = f.world_mut().limbo_entities.remove(&stable_pid) {
         let mut eids_vec = Vec::with_capacity(eids.len());
         for &eid in eids.iter() {
-            f.world_mut().entities[eid].plane = pid;
+            let w = f.world_mut();
+            w.entities[eid].plane = pid;
+            ops::entity::add_to_lookup(&mut w.entities_by_chunk, eid, pid, &w.entities[eid].motion);
             eids_vec.push(eid);
         }
         f.world_mut().entities_by_plane.insert(pid, eids);
@@ -76,7 +78,10 @@ pub fn pre_fini<'d, F>(f: &mut F,
     if let Some(eids) = f.world_mut().entities_by_plane.remove(&pid) {
         let mut eids_vec = Vec::with_capacity(eids.len());
         for &eid in eids.iter() {
-            f.world_mut().entities[eid].plane = PLANE_LIMBO;
+            let w = f.world_mut();
+            w.entities[eid].plane = PLANE_LIMBO;
+            ops::entity::remove_from_lookup(&mut w.entities_by_chunk, eid, pid,
+                                            &w.entities[eid].motion);
             eids_vec.push(eid);
         }
         f.world_mut().limbo_entities.insert(stable_pid, eids);
@@ -8,7 +8,7 @@ use world::extra::Extra;
 pub use super::World;
 pub use super::{Client, Entity, Inventory, Plane, TerrainChunk, Structure};
 
-pub use libserver_world_types::{Item, Motion};
+pub use libserver_world_types::{Item, Motion, Health, DEFAULT_MAX_HEALTH};
 pub use libserver_world_types::{
     EntityAttachment,
     StructureAttachment,
@@ -108,6 +108,10 @@ impl super::Entity {
         self.appearance
     }
 
+    pub fn health(&self) -> Health {
+        self.health
+    }
+
     pub fn pos(&self, now: Time) -> V3 {
         self.motion.pos(now)
     }
@@ -25,6 +25,7 @@ impl<'d> super::World<'d> {
 
             structures_by_chunk: HashMap::new(),
             entities_by_plane: HashMap::new(),
+            entities_by_chunk: HashMap::new(),
             limbo_entities: HashMap::new(),
         }
     }
@@ -65,6 +66,14 @@ impl<'d> super::World<'d> {
         }
     }
 
+    /// Iterate over the entities whose current motion passes through chunk `cpos`.
+    pub fn chunk_entities<'a>(&'a self, pid: PlaneId, cpos: V2) -> ChunkEntities<'a, 'd> {
+        ChunkEntities {
+            world: self,
+            iter: self.entities_by_chunk.get(&(pid, cpos)).map(|xs| xs.iter()),
+        }
+    }
+
     pub fn clients<'a>(&'a self) -> Clients<'a, 'd> {
         Clients {
             world: self,
@@ -272,6 +281,31 @@ impl<'a, 'd> Iterator for ChunkStructures<'a, 'd> {
     }
 }
 
+pub struct ChunkEntities<'a, 'd: 'a> {
+    world: &'a World<'d>,
+    iter: Option<hash_set::Iter<'a, EntityId>>,
+}
+
+impl<'a, 'd> Iterator for ChunkEntities<'a, 'd> {
+    type Item = ObjectRef<'a, 'd, Entity>;
+    fn next(&mut self) -> Option<ObjectRef<'a, 'd, Entity>> {
+        let iter = match self.iter {
+            Some(ref mut x) => x,
+            None => return None,
+        };
+
+        let world = self.world;
+        iter.next().map(|&eid| {
+            let e = &world.entities[eid];
+            ObjectRef {
+                world: world,
+                id: eid,
+                obj: e,
+            }
+        })
+    }
+}
+
 
 macro_rules! object_iter {
     ($name:ident, $obj_ty:ty, $id_ty:ty) => {
@@ -21,6 +21,7 @@ use rand::{Rng, XorShiftRng, SeedableRng};
 use rustc_serialize::json;
 
 use libserver_config::{Data, Storage};
+use libserver_config::storage::FileStorage;
 use libserver_types::*;
 use libterrain_gen::{GenChunk, GenStructure};
 
@@ -43,7 +44,7 @@ fn read_json(mut file: File) -> json::Json {
 
 impl TerrainGen {
     fn new(path: &str) -> TerrainGen {
-        let storage = Box::new(Storage::new(&path.to_owned()));
+        let storage: Box<Storage> = Box::new(FileStorage::new(&path.to_owned()));
 
         let block_json = read_json(storage.open_block_data());
         let item_json = read_json(storage.open_item_data());
@@ -52,13 +53,15 @@ impl TerrainGen {
         let animation_json = read_json(storage.open_animation_data());
         let sprite_part_json = read_json(storage.open_sprite_part_data());
         let loot_table_json = read_json(storage.open_loot_table_data());
+        let action_json = read_json(storage.open_action_data());
         let data = Box::new(Data::from_json(block_json,
                                             item_json,
                                             recipe_json,
                                             template_json,
                                             animation_json,
                                             sprite_part_json,
-                                            loot_table_json).unwrap());
+                                            loot_table_json,
+                                            action_json).unwrap());
 
         let mut rng: XorShiftRng = SeedableRng::from_seed([0xe0e0e0e0,
                                                            0x00012345,
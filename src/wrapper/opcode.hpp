@@ -1,6 +1,8 @@
 #ifndef OUTPOST_WRAPPER_OPCODES_HPP
 #define OUTPOST_WRAPPER_OPCODES_HPP
 
+// Generated by src/gen/gen_protocol.py from src/gen/protocol.json.  Do not edit.
+
 enum opcode {
     OP_ADD_CLIENT =         0xff00,
     OP_REMOVE_CLIENT =      0xff01,